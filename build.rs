@@ -0,0 +1,84 @@
+//! Renders man pages from the command definitions and help topics, embedded in
+//! the binary for `forest man`.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use clap::CommandFactory;
+
+#[allow(dead_code)]
+#[path = "src/cli.rs"]
+mod cli;
+#[allow(dead_code)]
+#[path = "src/topics.rs"]
+mod topics;
+
+fn main() -> std::io::Result<()> {
+    println!("cargo:rerun-if-changed=src/cli.rs");
+    println!("cargo:rerun-if-changed=src/topics.rs");
+
+    let mut pages = Vec::new();
+    let mut cmd = cli::Cli::command();
+    cmd.build();
+    command_pages(&cmd, &mut pages)?;
+    for topic in topics::TOPICS {
+        pages.push((format!("man7/forest-{}.7", topic.name), topic_page(topic)));
+    }
+
+    let mut out = String::from("pub const PAGES: &[(&str, &str)] = &[\n");
+    for (path, page) in &pages {
+        writeln!(out, "    ({:?}, {:?}),", path, page).unwrap();
+    }
+    out.push_str("];\n");
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("man_pages.rs"), out)
+}
+
+/// A section 1 page for `cmd` and one for each of its subcommands, which
+/// `build` names like git's `forest-trash-restore`.
+fn command_pages(cmd: &clap::Command, pages: &mut Vec<(String, String)>) -> std::io::Result<()> {
+    let name = cmd.get_display_name().unwrap_or(cmd.get_name());
+    let mut page = Vec::new();
+    clap_mangen::Man::new(cmd.clone()).render(&mut page)?;
+    pages.push((
+        format!("man1/{}.1", name),
+        String::from_utf8_lossy(&page).into_owned(),
+    ));
+    for sub in cmd.get_subcommands() {
+        command_pages(sub, pages)?;
+    }
+    Ok(())
+}
+
+fn topic_page(topic: &topics::Topic) -> String {
+    let mut page = format!(
+        ".TH forest-{} 7 \"\" \"forest {}\"\n.SH NAME\nforest\\-{} \\- {}\n.SH DESCRIPTION\n",
+        topic.name,
+        std::env::var("CARGO_PKG_VERSION").unwrap_or_default(),
+        topic.name,
+        topic.summary
+    );
+    let mut preformatted = false;
+    for line in topic.body.lines() {
+        // indented lines are tables and examples, everything else is prose
+        let indented = line.starts_with("  ");
+        if indented != preformatted {
+            page.push_str(if indented { ".nf\n" } else { ".fi\n" });
+            preformatted = indented;
+        }
+        if line.is_empty() {
+            page.push_str(".PP\n");
+            continue;
+        }
+        let line = line.replace('\\', "\\e").replace('-', "\\-");
+        if line.starts_with('.') || line.starts_with('\'') {
+            page.push_str("\\&");
+        }
+        page.push_str(&line);
+        page.push('\n');
+    }
+    if preformatted {
+        page.push_str(".fi\n");
+    }
+    page
+}
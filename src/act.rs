@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use crate::backend;
+
+/// Docker API socket act reaches podman through, `None` with docker or when
+/// `DOCKER_HOST` already says where to go.
+pub fn podman_socket() -> Option<String> {
+    if backend::kind() != backend::Kind::Podman || std::env::var_os("DOCKER_HOST").is_some() {
+        return None;
+    }
+    let rootless = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("podman").join("podman.sock"));
+    rootless
+        .into_iter()
+        .chain([PathBuf::from("/run/podman/podman.sock")])
+        .find(|path| path.exists())
+        .map(|path| format!("unix://{}", path.display()))
+}
+
+/// Job and message of a line of act's `--json` log.
+pub fn parse_line(line: &str) -> Option<(String, String)> {
+    let entry: Value = serde_json::from_str(line).ok()?;
+    let job = entry
+        .get("jobID")
+        .or_else(|| entry.get("job"))
+        .and_then(Value::as_str)?;
+    let message = entry.get("msg").and_then(Value::as_str)?;
+    Some((job.to_string(), message.to_string()))
+}
+
+/// Runs the GitHub Actions workflows of `worktree` with act, only job `job`
+/// when given, printing each line prefixed with its job and writing each
+/// job's log to `<logs>/<job>.log`. Returns whether act succeeded and the
+/// logs it wrote.
+pub fn run(
+    worktree: &Path,
+    job: Option<&str>,
+    logs: &Path,
+    verbose: bool,
+) -> anyhow::Result<(bool, Vec<PathBuf>)> {
+    let mut cmd = Command::new("act");
+    cmd.arg("--directory").arg(worktree).arg("--json");
+    if let Some(job) = job {
+        cmd.arg("--job").arg(job);
+    }
+    if let Some(socket) = podman_socket() {
+        cmd.env("DOCKER_HOST", socket);
+    }
+    if verbose {
+        println!("Running: {}", crate::shell::display(&cmd));
+    }
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow::anyhow!("act not found; install it from https://github.com/nektos/act")
+            } else {
+                e.into()
+            }
+        })?;
+    // logs of an earlier run would mix with this one's
+    if logs.exists() {
+        fs::remove_dir_all(logs)?;
+    }
+    fs::create_dir_all(logs)?;
+    let mut files: BTreeMap<String, (PathBuf, File)> = BTreeMap::new();
+    let stdout = child.stdout.take().expect("stdout is piped");
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        let Some((job, message)) = parse_line(&line) else {
+            println!("{}", line);
+            continue;
+        };
+        if !files.contains_key(&job) {
+            let path = logs.join(format!("{}.log", job.replace('/', "-")));
+            let file = File::create(&path)?;
+            files.insert(job.clone(), (path, file));
+        }
+        if let Some((_, file)) = files.get_mut(&job) {
+            writeln!(file, "{}", message)?;
+        }
+        println!("[{}] {}", job, message);
+    }
+    let status = child.wait()?;
+    Ok((
+        status.success(),
+        files.into_values().map(|(path, _)| path).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_act_json_logs() {
+        assert_eq!(
+            parse_line(
+                r#"{"job":"CI/test","jobID":"test","level":"info","msg":"  ✅  Success - Main cargo test"}"#
+            ),
+            Some((
+                "test".to_string(),
+                "  ✅  Success - Main cargo test".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_line(r#"{"job":"lint","msg":"ok"}"#),
+            Some(("lint".to_string(), "ok".to_string()))
+        );
+        assert_eq!(
+            parse_line(r#"{"level":"info","msg":"Using docker host"}"#),
+            None
+        );
+        assert_eq!(parse_line("[CI/test] plain"), None);
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Container engine running sessions, set with `backend` in forest.toml or
+/// `--backend` on `open`, `kill` and `ls`.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Podman,
+    Docker,
+}
+
+impl From<crate::cli::Backend> for Kind {
+    fn from(backend: crate::cli::Backend) -> Self {
+        match backend {
+            crate::cli::Backend::Podman => Kind::Podman,
+            crate::cli::Backend::Docker => Kind::Docker,
+        }
+    }
+}
+
+/// A container listed by `ps`, with its labels.
+pub struct PsEntry {
+    pub id: String,
+    pub labels: BTreeMap<String, String>,
+}
+
+/// What differs between the engines forest can drive: the program invoked,
+/// the shape of their output and how they attach networks, mounts and labels.
+pub trait ContainerBackend: Sync {
+    /// Program run for container commands, and by `devcontainer`
+    fn program(&self) -> &'static str;
+
+    /// Network containers are attached to when nothing else is asked for.
+    fn default_network(&self) -> &'static str;
+
+    /// Whether `run --network a,b` attaches a container to several networks
+    /// at once; otherwise the others are connected after it starts.
+    fn joins_networks_at_run(&self) -> bool;
+
+    /// Go template `inspect` formats to the image a container was created from.
+    fn image_template(&self) -> &'static str;
+
+    /// Containers in the output of `ps --format json`.
+    fn parse_ps(&self, output: &str) -> anyhow::Result<Vec<PsEntry>>;
+
+    /// Whether the engine already holds credentials for registry `host`.
+    fn logged_in(&self, host: &str) -> bool;
+
+    /// `--mount` value binding host directory `source` at `target`.
+    fn bind_mount(&self, source: &Path, target: &str) -> String {
+        format!("type=bind,source={},target={}", source.display(), target)
+    }
+
+    /// Like `bind_mount`, but the container can't write to it.
+    fn readonly_bind_mount(&self, source: &Path, target: &str) -> String {
+        format!("{},readonly", self.bind_mount(source, target))
+    }
+
+    /// Arguments making `devcontainer` drive this engine.
+    fn devcontainer_args(&self) -> [&'static str; 2] {
+        ["--docker-path", self.program()]
+    }
+}
+
+pub struct Podman;
+
+impl ContainerBackend for Podman {
+    fn program(&self) -> &'static str {
+        "podman"
+    }
+
+    fn default_network(&self) -> &'static str {
+        "podman"
+    }
+
+    fn joins_networks_at_run(&self) -> bool {
+        // rootless podman can't `network connect` a container using pasta
+        true
+    }
+
+    fn image_template(&self) -> &'static str {
+        "{{.ImageName}}"
+    }
+
+    /// One JSON array, with labels as an object.
+    fn parse_ps(&self, output: &str) -> anyhow::Result<Vec<PsEntry>> {
+        if output.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let value: Value = serde_json::from_str(output)?;
+        Ok(value
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| {
+                Some(PsEntry {
+                    id: entry.get("Id")?.as_str()?.to_string(),
+                    labels: entry
+                        .get("Labels")
+                        .and_then(Value::as_object)
+                        .map(|labels| {
+                            labels
+                                .iter()
+                                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    fn logged_in(&self, host: &str) -> bool {
+        Command::new(self.program())
+            .args(["login", "--get-login", host])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+}
+
+pub struct Docker;
+
+impl Docker {
+    fn config_path() -> Option<PathBuf> {
+        match std::env::var_os("DOCKER_CONFIG") {
+            Some(dir) => Some(PathBuf::from(dir).join("config.json")),
+            None => directories::BaseDirs::new()
+                .map(|dirs| dirs.home_dir().join(".docker").join("config.json")),
+        }
+    }
+}
+
+impl ContainerBackend for Docker {
+    fn program(&self) -> &'static str {
+        "docker"
+    }
+
+    fn default_network(&self) -> &'static str {
+        "bridge"
+    }
+
+    fn joins_networks_at_run(&self) -> bool {
+        false
+    }
+
+    fn image_template(&self) -> &'static str {
+        "{{.Config.Image}}"
+    }
+
+    /// One JSON object per line, with labels as `key=value,key=value`.
+    fn parse_ps(&self, output: &str) -> anyhow::Result<Vec<PsEntry>> {
+        let mut entries = Vec::new();
+        for line in output.lines().filter(|l| !l.trim().is_empty()) {
+            let entry: Value = serde_json::from_str(line)?;
+            let Some(id) = entry.get("ID").and_then(Value::as_str) else {
+                continue;
+            };
+            let labels = entry
+                .get("Labels")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            entries.push(PsEntry {
+                id: id.to_string(),
+                labels,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// docker has no `login --get-login`; its config lists the registries it
+    /// has credentials for, whether stored inline or in a helper.
+    fn logged_in(&self, host: &str) -> bool {
+        let Some(config) = Self::config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+        else {
+            return false;
+        };
+        ["auths", "credHelpers"].iter().any(|key| {
+            config
+                .get(key)
+                .and_then(Value::as_object)
+                .is_some_and(|hosts| {
+                    hosts
+                        .keys()
+                        .any(|h| h == host || h.trim_start_matches("https://").starts_with(host))
+                })
+        })
+    }
+}
+
+static KIND: OnceLock<Kind> = OnceLock::new();
+
+pub fn init(kind: Kind) {
+    let _ = KIND.set(kind);
+}
+
+pub fn kind() -> Kind {
+    KIND.get().copied().unwrap_or_default()
+}
+
+/// The backend picked at startup, podman unless configured otherwise.
+pub fn current() -> &'static dyn ContainerBackend {
+    match kind() {
+        Kind::Podman => &Podman,
+        Kind::Docker => &Docker,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_docker_ps_lines() {
+        let output = concat!(
+            r#"{"ID":"abc","Labels":"devcontainer.local_folder=/h/worktrees/r/x,name=x"}"#,
+            "\n",
+            r#"{"ID":"def","Labels":""}"#,
+            "\n"
+        );
+        let entries = Docker.parse_ps(output).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].labels["name"], "x");
+        assert_eq!(
+            entries[0].labels["devcontainer.local_folder"],
+            "/h/worktrees/r/x"
+        );
+        assert!(entries[1].labels.is_empty());
+    }
+
+    #[test]
+    fn drives_devcontainer_with_the_backend() {
+        assert_eq!(Podman.devcontainer_args(), ["--docker-path", "podman"]);
+        assert_eq!(Docker.devcontainer_args(), ["--docker-path", "docker"]);
+        assert_eq!(
+            Docker.bind_mount(Path::new("/src/widget"), "/repo"),
+            "type=bind,source=/src/widget,target=/repo"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::git::glob_match;
+use crate::style::{self, Role};
+
+/// Whether `spec` is a pattern like `feat/*` rather than a session name;
+/// branch names can't hold `*` or `?`.
+pub fn is_pattern(spec: &str) -> bool {
+    spec.contains(['*', '?'])
+}
+
+/// Sessions `specs` stand for: names as they are and, for each pattern,
+/// the sessions of `known` it matches, `*` and `?` staying within a `/`
+/// component. In order, without repeats; a pattern matching nothing fails
+/// rather than doing nothing quietly.
+pub fn expand(specs: &[String], known: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    for spec in specs {
+        let matched: Vec<&String> = if is_pattern(spec) {
+            known
+                .iter()
+                .filter(|name| glob_match(spec.as_bytes(), name.as_bytes()))
+                .collect()
+        } else {
+            vec![spec]
+        };
+        if matched.is_empty() {
+            anyhow::bail!("no session matches {}", spec);
+        }
+        for name in matched {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Runs `task` for each of `names`, at most `jobs` at a time, returning the
+/// results in the order of `names`.
+pub fn run<T: Send>(
+    names: &[String],
+    jobs: usize,
+    task: impl Fn(&str) -> anyhow::Result<T> + Sync,
+) -> Vec<anyhow::Result<T>> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<anyhow::Result<T>>>> =
+        Mutex::new(names.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, names.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(name) = names.get(i) else {
+                    break;
+                };
+                let result = task(name);
+                results.lock().unwrap_or_else(PoisonError::into_inner)[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("didn't run"))))
+        .collect()
+}
+
+/// Prints how `action` went for each of `names`, failing when it failed for
+/// any.
+pub fn summarize(
+    action: &str,
+    names: &[String],
+    results: Vec<anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let mut table = style::Table::new(&["session", "result"]);
+    let mut failed = 0;
+    for (name, result) in names.iter().zip(results) {
+        let outcome = match result {
+            Ok(()) => ("ok".to_string(), Some(Role::Success)),
+            Err(e) => {
+                failed += 1;
+                (e.to_string(), Some(Role::Error))
+            }
+        };
+        table.row(vec![(name.clone(), None), outcome]);
+    }
+    table.print();
+    if failed > 0 {
+        anyhow::bail!(
+            "{} failed for {} of {} sessions",
+            action,
+            failed,
+            names.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_patterns_over_known_sessions() {
+        let known: Vec<String> = ["feat/a", "feat/b", "feat/b/c", "fix"]
+            .map(String::from)
+            .to_vec();
+        let specs = |specs: &[&str]| specs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            expand(&specs(&["feat/*", "fix", "feat/a"]), &known).unwrap(),
+            ["feat/a", "feat/b", "fix"]
+        );
+        assert_eq!(
+            expand(&specs(&["new", "f?x"]), &known).unwrap(),
+            ["new", "fix"]
+        );
+        assert!(expand(&specs(&["docs/*"]), &known).is_err());
+    }
+
+    #[test]
+    fn runs_every_task_in_order_of_names() {
+        let names: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let results = run(&names, 3, |name| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            running.fetch_sub(1, Ordering::SeqCst);
+            match name {
+                "4" => anyhow::bail!("no"),
+                _ => Ok(name.parse::<usize>()?),
+            }
+        });
+        assert!(most.load(Ordering::SeqCst) <= 3);
+        assert_eq!(results[7].as_ref().unwrap(), &7);
+        assert!(results[4].is_err());
+        assert!(summarize("kill", &names[..1], vec![Ok(())]).is_ok());
+        assert!(summarize(
+            "kill",
+            &names[..2],
+            vec![Ok(()), Err(anyhow::anyhow!("no"))]
+        )
+        .is_err());
+    }
+}
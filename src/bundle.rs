@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::backend;
+use crate::names::sanitize_podman_name;
+
+/// Devcontainer environment holding the bundle's offline configuration once it
+/// has been unpacked into the repository.
+pub const BUNDLE_ENV: &str = "forest-bundle";
+
+const MANIFEST: &str = "forest-bundle.json";
+const IMAGE: &str = "image.tar";
+const REPO: &str = "repo.bundle";
+const CONFIG: &str = "devcontainer.json";
+
+/// Describes the session a bundle opens, stored as `forest-bundle.json`.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub session: String,
+    pub repo: String,
+    /// Image saved in `image.tar`, already built with all devcontainer features
+    pub image: String,
+    pub forest_version: String,
+}
+
+/// A bundle unpacked into a repository, ready for `open`.
+pub struct Unpacked {
+    pub repo_dir: PathBuf,
+    pub session: String,
+}
+
+/// Builds the session image and packs it, the repository and an offline
+/// devcontainer configuration into a single tar archive at `output`.
+pub fn create(
+    repo_root: &Path,
+    repo_name: &str,
+    session: &str,
+    devcontainer_path: &Path,
+    output: &Path,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let contents = fs::read_to_string(devcontainer_path)?;
+    let value: Value = serde_json::from_str(&contents)?;
+    let image = format!(
+        "localhost/forest-bundle-{}:{}",
+        sanitize_podman_name(repo_name).to_lowercase(),
+        sanitize_podman_name(session).to_lowercase()
+    );
+
+    let staging = std::env::temp_dir().join(format!("forest-bundle-{}", std::process::id()));
+    fs::create_dir_all(&staging)?;
+    let result = (|| {
+        println!("Building image {}", image);
+        run(
+            crate::devcontainer::command("build")
+                .arg("--workspace-folder")
+                .arg(repo_root)
+                .arg("--config")
+                .arg(devcontainer_path)
+                .arg("--image-name")
+                .arg(&image),
+            verbose,
+        )?;
+        println!("Saving image");
+        run(
+            Command::new(backend::current().program())
+                .args(["save", "--output"])
+                .arg(staging.join(IMAGE))
+                .arg(&image),
+            verbose,
+        )?;
+        run(
+            Command::new("git")
+                .args(["bundle", "create"])
+                .arg(staging.join(REPO))
+                .arg("--all")
+                .current_dir(repo_root),
+            verbose,
+        )?;
+        fs::write(
+            staging.join(CONFIG),
+            serde_json::to_string_pretty(&offline_config(&value, &image))?,
+        )?;
+        let manifest = Manifest {
+            session: session.to_string(),
+            repo: repo_name.to_string(),
+            image: image.clone(),
+            forest_version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        fs::write(
+            staging.join(MANIFEST),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+        run(
+            Command::new("tar")
+                .arg("-cf")
+                .arg(output)
+                .arg("-C")
+                .arg(&staging)
+                .args([MANIFEST, CONFIG, IMAGE, REPO]),
+            verbose,
+        )
+    })();
+    let _ = fs::remove_dir_all(&staging);
+    result?;
+    println!("Wrote bundle {}", output.display());
+    Ok(())
+}
+
+/// Loads a bundle's image and checks out its repository under `dir`, without
+/// touching the network. The git bundle stays in `.git` as `origin`, so forest
+/// doesn't try to create a GitHub repository for it.
+pub fn unpack(archive: &Path, dir: &Path, verbose: bool) -> anyhow::Result<Unpacked> {
+    let staging = std::env::temp_dir().join(format!("forest-unbundle-{}", std::process::id()));
+    fs::create_dir_all(&staging)?;
+    let result = (|| {
+        run(
+            Command::new("tar")
+                .arg("-xf")
+                .arg(archive)
+                .arg("-C")
+                .arg(&staging),
+            verbose,
+        )?;
+        let manifest: Manifest = serde_json::from_str(
+            &fs::read_to_string(staging.join(MANIFEST))
+                .map_err(|_| anyhow::anyhow!("{} is not a forest bundle", archive.display()))?,
+        )?;
+
+        println!("Loading image {}", manifest.image);
+        run(
+            Command::new(backend::current().program())
+                .args(["load", "--input"])
+                .arg(staging.join(IMAGE)),
+            verbose,
+        )?;
+
+        let repo_dir = dir.join(&manifest.repo);
+        let origin = repo_dir.join(".git").join("forest-origin.bundle");
+        if repo_dir.exists() {
+            println!("Using existing checkout {}", repo_dir.display());
+        } else {
+            run(
+                Command::new("git")
+                    .arg("clone")
+                    .arg(staging.join(REPO))
+                    .arg(&repo_dir),
+                verbose,
+            )?;
+            fs::copy(staging.join(REPO), &origin)?;
+            run(
+                Command::new("git")
+                    .args(["remote", "set-url", "origin"])
+                    .arg(&origin)
+                    .current_dir(&repo_dir),
+                verbose,
+            )?;
+            // clone only creates the default branch locally
+            let remote = format!("refs/remotes/origin/{}", manifest.session);
+            let has_remote = Command::new("git")
+                .args(["show-ref", "--verify", "--quiet", &remote])
+                .current_dir(&repo_dir)
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            let has_local = Command::new("git")
+                .args(["show-ref", "--verify", "--quiet"])
+                .arg(format!("refs/heads/{}", manifest.session))
+                .current_dir(&repo_dir)
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            if has_remote && !has_local {
+                run(
+                    Command::new("git")
+                        .args(["branch", "--track", &manifest.session, &remote])
+                        .current_dir(&repo_dir),
+                    verbose,
+                )?;
+            }
+        }
+
+        let env_dir = repo_dir.join(".devcontainer").join(BUNDLE_ENV);
+        fs::create_dir_all(&env_dir)?;
+        fs::copy(staging.join(CONFIG), env_dir.join(CONFIG))?;
+        exclude(&repo_dir, &format!("/.devcontainer/{}/", BUNDLE_ENV))?;
+        Ok(Unpacked {
+            repo_dir,
+            session: manifest.session,
+        })
+    })();
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// The devcontainer configuration with everything that would need the network
+/// at `up` time replaced by the prebuilt image.
+fn offline_config(value: &Value, image: &str) -> Value {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+        for key in ["build", "dockerFile", "context", "features"] {
+            object.remove(key);
+        }
+        object.insert("image".to_string(), Value::String(image.to_string()));
+    }
+    value
+}
+
+fn exclude(repo_dir: &Path, pattern: &str) -> anyhow::Result<()> {
+    let path = repo_dir.join(".git").join("info").join("exclude");
+    let excludes = fs::read_to_string(&path).unwrap_or_default();
+    if excludes.lines().any(|l| l.trim() == pattern) {
+        return Ok(());
+    }
+    fs::create_dir_all(repo_dir.join(".git").join("info"))?;
+    fs::write(&path, format!("{}{}\n", excludes, pattern))?;
+    Ok(())
+}
+
+fn run(cmd: &mut Command, verbose: bool) -> anyhow::Result<()> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let status = crate::run_command_verbose(cmd, verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("{} command not found", program)
+        } else {
+            e.into()
+        }
+    })?;
+    if !status.success() {
+        anyhow::bail!("{} failed", program);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_config_uses_prebuilt_image() {
+        let value: Value = serde_json::from_str(
+            r#"{"build": {"dockerfile": "Dockerfile"},
+                "features": {"ghcr.io/devcontainers/features/node:1": {}},
+                "runArgs": ["--cpus=2"]}"#,
+        )
+        .unwrap();
+        let offline = offline_config(&value, "localhost/forest-bundle-r:s");
+        assert_eq!(offline["image"], "localhost/forest-bundle-r:s");
+        assert!(offline.get("build").is_none());
+        assert!(offline.get("features").is_none());
+        assert_eq!(offline["runArgs"][0], "--cpus=2");
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{self, Kind};
+use crate::store::{self, Schema};
+
+/// A session container frozen by `forest checkpoint`, kept in `checkpoints.json`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Checkpoint {
+    pub container_id: String,
+    pub created_at: u64,
+}
+
+/// `checkpoints.json`: the checkpoint of each session, by label.
+pub const CHECKPOINTS: Schema = Schema {
+    file: "checkpoints.json",
+    migrations: &[],
+};
+
+pub fn load(data_dir: &Path) -> anyhow::Result<BTreeMap<String, Checkpoint>> {
+    store::load(data_dir, &CHECKPOINTS)
+}
+
+fn save(data_dir: &Path, checkpoints: &BTreeMap<String, Checkpoint>) -> anyhow::Result<()> {
+    store::save(data_dir, &CHECKPOINTS, checkpoints)
+}
+
+fn runtime(action: &str, id: &str, verbose: bool) -> anyhow::Result<()> {
+    if backend::kind() != Kind::Podman {
+        anyhow::bail!("checkpoints need the podman backend");
+    }
+    if !crate::command_exists("criu") {
+        anyhow::bail!("checkpoints need CRIU; install the criu package");
+    }
+    let mut cmd = Command::new(backend::current().program());
+    // `--keep` leaves the checkpoint in the container's storage, so a restore
+    // that fails can be retried; `--tcp-established` keeps open connections
+    cmd.args(["container", action, "--keep", "--tcp-established", id]);
+    let status = crate::run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!(
+            "podman container {} failed; CRIU needs a rootful runtime (try `sudo podman`)",
+            action
+        );
+    }
+    Ok(())
+}
+
+/// Freezes the processes of session `label`'s container `id` to disk, which
+/// stops the container until `restore`.
+pub fn create(data_dir: &Path, label: &str, id: &str, verbose: bool) -> anyhow::Result<()> {
+    runtime("checkpoint", id, verbose)?;
+    let mut checkpoints = load(data_dir)?;
+    checkpoints.insert(
+        label.to_string(),
+        Checkpoint {
+            container_id: id.to_string(),
+            created_at: crate::timesheet::now(),
+        },
+    );
+    save(data_dir, &checkpoints)
+}
+
+/// Restores the processes of session `label` from its checkpoint.
+pub fn restore(data_dir: &Path, label: &str, verbose: bool) -> anyhow::Result<Checkpoint> {
+    let mut checkpoints = load(data_dir)?;
+    let Some(checkpoint) = checkpoints.get(label).cloned() else {
+        anyhow::bail!("no checkpoint for session {}", label);
+    };
+    runtime("restore", &checkpoint.container_id, verbose)?;
+    checkpoints.remove(label);
+    save(data_dir, &checkpoints)?;
+    Ok(checkpoint)
+}
+
+/// Drops the record of a checkpoint whose container is gone.
+pub fn forget(data_dir: &Path, label: &str) -> anyhow::Result<()> {
+    if !load(data_dir)?.contains_key(label) {
+        return Ok(());
+    }
+    store::update(
+        data_dir,
+        &CHECKPOINTS,
+        |checkpoints: &mut BTreeMap<String, Checkpoint>| {
+            checkpoints.remove(label);
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn forgets_checkpoints() {
+        let dir = tempdir().unwrap();
+        let checkpoint = Checkpoint {
+            container_id: "abc".to_string(),
+            created_at: 1,
+        };
+        save(
+            dir.path(),
+            &BTreeMap::from([("feat".to_string(), checkpoint)]),
+        )
+        .unwrap();
+        assert_eq!(load(dir.path()).unwrap()["feat"].container_id, "abc");
+        forget(dir.path(), "feat").unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+        assert!(restore(dir.path(), "feat", false).is_err());
+    }
+}
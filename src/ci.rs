@@ -0,0 +1,260 @@
+use std::fs;
+use std::os::fd::AsFd;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::git::{add_readonly_worktree, worktree_admin_dir};
+use crate::session::{enforce_policy, locate_devcontainer_in};
+use crate::{backend, devcontainer, locks, runtime, shell};
+
+/// What `forest ci-exec` prints on stdout once it's done.
+#[derive(Serialize, Debug)]
+pub struct CiResult {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    /// Commit the reference resolved to, empty when it didn't
+    pub sha: String,
+    pub command: Vec<String>,
+    /// The command's exit code, `None` when it didn't run to the end
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Why the run stopped short of the command's exit code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u128,
+}
+
+impl CiResult {
+    /// Exit status for `ci-exec`: the command's, 124 on timeout like
+    /// `timeout(1)`, and 1 when something else went wrong.
+    pub fn exit_code(&self) -> u8 {
+        if self.timed_out {
+            return 124;
+        }
+        match self.exit_code {
+            Some(code) => u8::try_from(code).unwrap_or(1),
+            None => 1,
+        }
+    }
+}
+
+/// Runs `cmd` until it exits or `deadline` passes, when it's killed; `None`
+/// means it timed out.
+pub fn run_until(cmd: &mut Command, deadline: Instant) -> std::io::Result<Option<ExitStatus>> {
+    let mut child = cmd.spawn()?;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Output going to stderr, keeping stdout for the JSON result.
+fn to_stderr() -> anyhow::Result<Stdio> {
+    Ok(Stdio::from(std::io::stderr().as_fd().try_clone_to_owned()?))
+}
+
+/// Runs `command` in a throwaway container of commit `reference` of the
+/// repository in the current directory, checked out into a throwaway
+/// worktree, within `timeout` all told. Nothing is recorded in forest's data
+/// directory, and the container and worktree are removed however it ends.
+pub fn exec(
+    reference: &str,
+    command: &[String],
+    dev_env: Option<&str>,
+    timeout: Duration,
+    config: &Config,
+    verbose: bool,
+) -> CiResult {
+    let start = Instant::now();
+    let mut result = CiResult {
+        reference: reference.to_string(),
+        sha: String::new(),
+        command: command.to_vec(),
+        exit_code: None,
+        timed_out: false,
+        error: None,
+        duration_ms: 0,
+    };
+    if let Err(e) = run(&mut result, dev_env, start + timeout, config, verbose) {
+        result.error = Some(e.to_string());
+    }
+    result.duration_ms = start.elapsed().as_millis();
+    result
+}
+
+fn run(
+    result: &mut CiResult,
+    dev_env: Option<&str>,
+    deadline: Instant,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let Some(repo_root) = crate::git::repo_toplevel() else {
+        anyhow::bail!("run `forest ci-exec` inside a git repository");
+    };
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", result.reference))
+        .current_dir(&repo_root)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("no commit {}", result.reference);
+    }
+    result.sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let id = format!("{}-{}", &result.sha[..12], std::process::id());
+    let label = format!("forest-ci-{}", id);
+    let worktree = std::env::temp_dir().join(&label);
+    {
+        let _lock =
+            locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+        add_readonly_worktree(&repo_root, &worktree, &result.sha, verbose)?;
+    }
+    let outcome = up_and_exec(
+        result, &repo_root, &worktree, &label, dev_env, deadline, verbose,
+    );
+    if let Err(e) = teardown(&repo_root, &worktree, &label, verbose) {
+        crate::style::warn(format!("cleaning up after ci-exec failed: {}", e));
+    }
+    outcome
+}
+
+fn up_and_exec(
+    result: &mut CiResult,
+    repo_root: &Path,
+    worktree: &Path,
+    label: &str,
+    dev_env: Option<&str>,
+    deadline: Instant,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let Some(config_path) = locate_devcontainer_in(worktree, dev_env)? else {
+        anyhow::bail!("{} has no devcontainer.json", result.sha);
+    };
+    // CI runners are held to the administrator's policy like any session
+    let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&config_path)?)?;
+    enforce_policy(&value, &config_path)?;
+    let mount = |source: &Path, target: &str| backend::current().bind_mount(source, target);
+    let mut up = devcontainer::command("up");
+    up.arg("--workspace-folder")
+        .arg(worktree)
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--id-label")
+        .arg(format!("name={}", label))
+        .arg("--mount")
+        .arg(mount(repo_root, "/repo"))
+        .arg("--mount")
+        .arg(mount(worktree, "/code"))
+        .arg("--skip-post-attach")
+        .stdin(Stdio::null())
+        .stdout(to_stderr()?);
+    if verbose {
+        eprintln!("Running: {}", shell::display(&up));
+    }
+    match run_until(&mut up, deadline)? {
+        None => {
+            result.timed_out = true;
+            anyhow::bail!("timed out starting the container");
+        }
+        Some(status) if !status.success() => anyhow::bail!("devcontainer up failed"),
+        Some(_) => {}
+    }
+    let mut exec = devcontainer::command("exec");
+    exec.arg("--workspace-folder")
+        .arg(worktree)
+        .args(devcontainer::target_args(label, None))
+        .args(shell::in_dir("/code", &result.command))
+        .stdin(Stdio::null())
+        .stdout(to_stderr()?);
+    if verbose {
+        eprintln!("Running: {}", shell::display(&exec));
+    }
+    match run_until(&mut exec, deadline)? {
+        None => {
+            result.timed_out = true;
+            anyhow::bail!("timed out running the command");
+        }
+        Some(status) => result.exit_code = status.code(),
+    }
+    Ok(())
+}
+
+/// Removes the run's container and worktree, with git's record of it.
+fn teardown(repo_root: &Path, worktree: &Path, label: &str, verbose: bool) -> anyhow::Result<()> {
+    let ids = runtime::containers_with_name(label, true).unwrap_or_default();
+    if !ids.is_empty() {
+        let status = Command::new(runtime::program())
+            .args(["rm", "--force"])
+            .args(&ids)
+            .stdout(Stdio::null())
+            .status()?;
+        if !status.success() {
+            anyhow::bail!("{} rm failed", runtime::program());
+        }
+    }
+    if let Some(admin) = worktree_admin_dir(repo_root, worktree) {
+        fs::remove_dir_all(admin)?;
+    }
+    if worktree.exists() {
+        fs::remove_dir_all(worktree)?;
+    }
+    if verbose {
+        eprintln!("Removed {} and {}", label, worktree.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_results_as_json() {
+        let mut result = CiResult {
+            reference: "main".to_string(),
+            sha: "abc".to_string(),
+            command: vec!["cargo".to_string(), "test".to_string()],
+            exit_code: Some(101),
+            timed_out: false,
+            error: None,
+            duration_ms: 1500,
+        };
+        assert_eq!(result.exit_code(), 101);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["ref"], "main");
+        assert_eq!(json["exit_code"], 101);
+        assert!(json.get("error").is_none());
+        result.exit_code = None;
+        result.timed_out = true;
+        assert_eq!(result.exit_code(), 124);
+    }
+
+    #[test]
+    fn kills_commands_past_their_deadline() {
+        let start = Instant::now();
+        let status = run_until(
+            Command::new("sleep").arg("5"),
+            start + Duration::from_millis(100),
+        )
+        .unwrap();
+        assert!(status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(2));
+        let status = run_until(
+            &mut Command::new("true"),
+            Instant::now() + Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(status.unwrap().success());
+    }
+}
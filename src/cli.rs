@@ -0,0 +1,889 @@
+//! Command-line definitions, shared with `build.rs` to generate man pages, so
+//! they can't depend on the rest of the crate.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+/// When to color output, set with `--color`.
+#[derive(clap::ValueEnum, Clone, Copy, Default)]
+pub enum ColorChoice {
+    /// Color terminals unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Network a session may reach, set with `open --network`.
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum NetworkMode {
+    /// The runtime's default network
+    Open,
+    /// Only the session's own internal network, with no route out
+    Internal,
+    /// Internal network plus HTTP(S) to `--allow-host` hosts through a proxy
+    Allowlist,
+    /// No network at all
+    None,
+}
+
+/// Container engine, set with `--backend` on `open`, `kill` and `ls`.
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Backend {
+    Podman,
+    Docker,
+}
+
+/// Shell `forest completions` writes a script for.
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+#[derive(Parser)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    disable_help_subcommand = true,
+    after_long_help = "See `forest help <topic>` for worktrees, configuration, policy and features."
+)]
+pub struct Cli {
+    /// Print debugging information
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// Turn on an experimental feature for this run (see `forest features`)
+    #[arg(long = "enable-feature", value_name = "FEATURE", global = true)]
+    pub enable_features: Vec<String>,
+    /// Pass a raw argument on to `devcontainer build`, `up` and `exec`
+    #[arg(
+        long = "devcontainer-arg",
+        value_name = "ARG",
+        global = true,
+        allow_hyphen_values = true
+    )]
+    pub devcontainer_args: Vec<String>,
+    /// When to color output
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub color: ColorChoice,
+    /// Line-by-line output without colors, aligned columns or progress
+    /// redrawn in place, for screen readers and dumb terminals
+    #[arg(long, global = true)]
+    pub plain: bool,
+    /// Never ask questions; they take their default answer, which is no
+    #[arg(long, global = true)]
+    pub non_interactive: bool,
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+// parsed once per run, so `Open`'s many flags cost nothing worth boxing
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Open a session, creating it if it doesn't exist
+    #[command(after_long_help = "Examples:
+  forest open feat/login
+  forest open https://github.com/acme/widget/tree/feat/login
+  forest open https://github.com/acme/widget/pull/42
+  forest open api --devcontainer-env rust
+  forest open hotfix --from release/1.4
+  forest open try-nightly --container-image rustlang/rust:nightly
+  forest open spike --feature ghcr.io/devcontainers/features/go:1
+  forest open untrusted --network none
+  forest open api -e RUST_LOG=debug --env-file .env
+  forest open sandbox --allow-host crates.io --allow-host '*.crates.io'
+  forest open feat/a feat/b 'fix/*'   # several at once, detached
+  forest --enable-feature jj open --jj @-")]
+    Open {
+        /// Session name, or a GitHub repository, branch (`/tree/`), pull
+        /// request, issue or commit URL to open a session for; defaults to the
+        /// jj change's bookmark with `--jj`. Several, or patterns like `feat/*`
+        /// matching the repository's sessions, open at once, detached
+        #[arg(required_unless_present = "jj")]
+        names: Vec<String>,
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+        /// Open a session on a jj change (any revision resolving to one change)
+        #[arg(long, value_name = "CHANGE")]
+        jj: Option<String>,
+        /// Use this image instead of the devcontainer's for this session; later
+        /// opens and rebuilds of the session keep using it
+        #[arg(long, value_name = "IMAGE")]
+        container_image: Option<String>,
+        /// Add a devcontainer feature to this session only, e.g.
+        /// `ghcr.io/devcontainers/features/go:1`; later opens keep it
+        #[arg(long = "feature", value_name = "FEATURE")]
+        features: Vec<String>,
+        /// Restrict the session's network, e.g. to run untrusted code; later
+        /// opens keep the restriction
+        #[arg(long, value_enum, value_name = "MODE")]
+        network: Option<NetworkMode>,
+        /// Host the session may reach over HTTP(S) with `--network allowlist`
+        /// (implied); `*.example.com` allows subdomains
+        #[arg(long = "allow-host", value_name = "HOST")]
+        allow_hosts: Vec<String>,
+        /// Go back to the devcontainer.json as committed, dropping earlier
+        /// `--container-image`, `--feature`, `--network` and `--allow-host` overrides
+        #[arg(long)]
+        reset_overrides: bool,
+        /// Undo the WIP commit or stash `wip_on_detach` made when the session
+        /// was last left, putting the work back into the working tree
+        #[arg(long)]
+        restore_wip: bool,
+        /// Mount the worktree and repository read-only and check out the
+        /// existing branch `name` detached instead of creating one; later
+        /// opens keep it read-only
+        #[arg(long, conflicts_with = "jj")]
+        readonly: bool,
+        /// Start a new session's branch from this branch, tag or commit,
+        /// preferring the remote's fresh copy of a branch, instead of
+        /// `default_base_branch` or HEAD
+        #[arg(long, value_name = "REF", conflicts_with_all = ["jj", "readonly"])]
+        from: Option<String>,
+        /// Don't fetch before starting from `--from` or `default_base_branch`
+        #[arg(long)]
+        no_fetch: bool,
+        /// Bind-mount another repository or directory into the session, at
+        /// `/mounts/<directory name>` unless a target is given; later opens
+        /// keep it
+        #[arg(long = "also-mount", value_name = "PATH[:TARGET]")]
+        also_mount: Vec<String>,
+        /// Run the session at this priority, a nice value from -20 (first)
+        /// to 19 (last); later opens keep it
+        #[arg(long, value_name = "NICE", allow_negative_numbers = true,
+              value_parser = clap::value_parser!(i32).range(-20..=19))]
+        priority: Option<i32>,
+        /// Cap the session's container at this many CPUs, such as 2 or 1.5,
+        /// instead of `cpus` under [resources]; later opens keep it
+        #[arg(long)]
+        cpus: Option<f64>,
+        /// Cap the session's container's memory, such as `8g`, instead of
+        /// `memory` under [resources]; later opens keep it
+        #[arg(long)]
+        memory: Option<String>,
+        /// Set an environment variable for this open's lifecycle commands
+        /// and shell, as `KEY=VALUE`, or `KEY` for the host's value; lasting
+        /// ones belong under [env] in forest.toml
+        #[arg(long, short, value_name = "KEY=VALUE")]
+        env: Vec<String>,
+        /// Read variables like `--env` from a file of `KEY=VALUE` lines
+        #[arg(long, value_name = "PATH")]
+        env_file: Vec<PathBuf>,
+        /// Set the session up and exit, printing its name, instead of
+        /// starting a shell in it
+        #[arg(long, short)]
+        detach: bool,
+        /// Write a default devcontainer.json when the repository has none,
+        /// without asking, where `scaffold` in forest.toml says
+        #[arg(long)]
+        scaffold: bool,
+        /// Take over a container that carries the session's label, or holds
+        /// its egress proxy's name, though forest didn't create it for this
+        /// session
+        #[arg(long)]
+        adopt: bool,
+        /// Sessions opened at once when there are several
+        #[arg(long, short, value_name = "N", default_value_t = 4)]
+        jobs: usize,
+        /// Container engine instead of `backend` from forest.toml
+        #[arg(long, value_enum)]
+        backend: Option<Backend>,
+    },
+    /// Open a shell in a running session's container, skipping everything
+    /// else `open` does
+    Attach { name: String },
+    /// Put the host's clipboard into a running session, where `pbpaste`,
+    /// `xclip -o` and the like of `shell_helpers` print it
+    #[command(
+        after_long_help = "The text lands in /tmp/forest-clipboard in the container, readable by its
+user only. Nothing reads the host's clipboard unless you run this.
+
+Examples:
+  forest paste feat/login"
+    )]
+    Paste { name: String },
+    /// Build a session's image again and recreate its container, keeping the
+    /// worktree
+    #[command(
+        after_long_help = "For when the Dockerfile or features changed: `open` only rebuilds when
+devcontainer.json does. The overrides earlier opens recorded are kept, and the
+session's shell opens once the new container is up.
+
+Examples:
+  forest rebuild feat/login
+  forest rebuild feat/login --no-cache --detach"
+    )]
+    Rebuild {
+        name: String,
+        /// Build without the engine's layer cache, running every step again
+        #[arg(long)]
+        no_cache: bool,
+        /// Return once the container is up instead of opening its shell
+        #[arg(long)]
+        detach: bool,
+    },
+    /// List how a session's container differs from what its devcontainer.json
+    /// asks for now
+    Drift { name: String },
+    /// Push a session's branch and open a GitHub pull request for it
+    #[command(
+        after_long_help = "Pushes from inside the session's container when it's running, from the
+host otherwise, then runs `gh pr create --fill` on the host. With `[remotes]`
+pushing to a fork, the pull request goes from the fork to the base remote's
+repository.
+
+Examples:
+  forest pr feat/login
+  forest pr feat/login --draft --base release"
+    )]
+    Pr {
+        name: String,
+        /// Open the pull request as a draft
+        #[arg(long)]
+        draft: bool,
+        /// Branch to merge into instead of the repository's default
+        #[arg(long)]
+        base: Option<String>,
+    },
+    /// Run the repository's GitHub Actions workflows on a session's worktree
+    /// with act
+    #[command(
+        after_long_help = "act runs next to the session, on the host, and starts the jobs' containers
+with the session's engine; with podman it talks to podman's Docker API socket
+unless DOCKER_HOST is set. Each job's log is kept under forest's data
+directory until the next run.
+
+Examples:
+  forest act feat/ci
+  forest act feat/ci -j test"
+    )]
+    Act {
+        name: String,
+        /// Run only this job
+        #[arg(long, short)]
+        job: Option<String>,
+    },
+    /// Run a command in a throwaway container of one commit, for CI runners
+    #[command(
+        after_long_help = "Checks the commit out into a temporary worktree, brings its devcontainer up,
+runs the command in /code and removes both again, without recording anything
+in forest's data directory. It never prompts. Everything but the result goes
+to stderr; stdout gets one JSON object with the ref, the commit, the command,
+its exit code, whether it timed out and how long it all took. forest exits
+with the command's code, 124 on timeout and 1 when the run failed otherwise.
+
+Examples:
+  forest ci-exec --ref HEAD -- cargo test
+  forest ci-exec --ref origin/main --timeout 600 -- make check"
+    )]
+    CiExec {
+        /// Commit to run on, anything `git rev-parse` understands
+        #[arg(long = "ref", value_name = "REF")]
+        reference: String,
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+        /// Seconds the whole run may take, container start included
+        #[arg(long, value_name = "SECS", default_value_t = 3600)]
+        timeout: u64,
+        /// Command and arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Fetch the base remote and rebase a session's branch onto its default
+    /// branch
+    #[command(
+        after_long_help = "The base remote is `origin` unless `base` under [remotes] in forest.toml
+names another, such as `upstream` in a fork. git runs inside the session's
+container when it's running, on the host otherwise. A rebase that stops on
+conflicts is left for you to finish in the session.
+
+Examples:
+  forest sync feat/login"
+    )]
+    Sync { name: String },
+    /// Change a session's CPU and IO priority, at once if it's running
+    #[command(after_long_help = "Examples:
+  forest renice bisect 19
+  forest renice feat/login -5")]
+    Renice {
+        name: String,
+        /// Nice value from -20 (first) to 19 (last); 0 is the default
+        #[arg(allow_negative_numbers = true,
+              value_parser = clap::value_parser!(i32).range(-20..=19))]
+        priority: i32,
+    },
+    /// Open the default branch read-only, to run or inspect it without risk
+    /// of edits
+    Main {
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+    },
+    /// Kill running sessions
+    #[command(
+        after_long_help = "Several sessions are killed at once, `--jobs` at a time, without asking
+about uncommitted changes, and a table shows how each went.
+
+Examples:
+  forest kill feat/login            # stop the container
+  forest kill feat/login --down     # remove the container
+  forest kill feat/login --purge    # also trash the worktree
+  forest kill 'feat/*' --all --down # every feat/ session, stopped ones too"
+    )]
+    Kill {
+        /// Sessions, or patterns like `feat/*` matching the repository's
+        /// running sessions
+        #[arg(required = true, num_args = 1..)]
+        names: Vec<String>,
+        /// Let patterns match stopped sessions too
+        #[arg(long)]
+        all: bool,
+        /// Sessions killed at once when there are several
+        #[arg(long, short, value_name = "N", default_value_t = 4)]
+        jobs: usize,
+        /// Remove the container instead of stopping it
+        #[arg(long, conflicts_with = "purge")]
+        down: bool,
+        /// Remove the container and move the session's worktree into the forest trash
+        #[arg(long)]
+        purge: bool,
+        /// Push the session branch's unpushed commits first, as
+        /// `push_on_kill` does
+        #[arg(long)]
+        push: bool,
+        /// Go ahead with `--down`, `--purge` or `--push` even though the
+        /// worktree has uncommitted changes, leaving them in place
+        #[arg(long)]
+        force: bool,
+        /// Stash the worktree's uncommitted changes first
+        #[arg(long)]
+        stash: bool,
+        /// Container engine instead of `backend` from forest.toml
+        #[arg(long, value_enum)]
+        backend: Option<Backend>,
+    },
+    /// Delete a session: its container, worktree and branch
+    #[command(after_long_help = "Examples:
+  forest rm feat/login                  # remove everything
+  forest rm feat/login --keep-branch    # keep the branch to push or merge later")]
+    Rm {
+        name: String,
+        /// Leave the local branch in place
+        #[arg(long)]
+        keep_branch: bool,
+        /// Leave the worktree directory in place
+        #[arg(long)]
+        keep_worktree: bool,
+    },
+    /// Rename a session: its branch, worktree, records and container
+    #[command(
+        after_long_help = "The container is removed, since its label and mounts are fixed when it's
+created, and started again as the new session when it was running. When a
+step fails, the ones before it are undone.
+
+Examples:
+  forest rename feat/lgoin feat/login"
+    )]
+    Rename {
+        name: String,
+        /// The session's new name, which its branch takes too
+        new_name: String,
+    },
+    /// Find and remove what sessions left behind: records and containers of
+    /// deleted worktrees, worktrees no session knows and stale git worktrees
+    #[command(
+        after_long_help = "Cross-references forest's session records, the engine's session containers,
+the worktrees directory and the `.git/worktrees` of the sessions' repositories
+and the current one. Without --yes it only reports. Orphaned worktrees go to
+the trash, so `forest trash restore` brings them back.
+
+Examples:
+  forest prune          # report what's orphaned
+  forest prune --yes    # and remove it"
+    )]
+    Prune {
+        /// Remove what's found instead of only reporting it
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// List sessions, running and stopped
+    Ls {
+        /// Include stopped containers, worktrees without a session and
+        /// sessions whose worktree is gone
+        #[arg(long, short)]
+        all: bool,
+        /// Container engine instead of `backend` from forest.toml
+        #[arg(long, value_enum)]
+        backend: Option<Backend>,
+    },
+    /// Show a session's container, git state, uptime, resource usage and the
+    /// forest version that created it
+    Status { name: String },
+    /// Resolve a session whose worktree has another branch checked out than
+    /// the session's
+    #[command(after_long_help = "Examples:
+  forest adopt-branch feat/login            # the session follows the checked-out branch
+  forest adopt-branch feat/login --restore  # switch the worktree back")]
+    AdoptBranch {
+        name: String,
+        /// Check the session's branch out again instead of adopting the other
+        #[arg(long)]
+        restore: bool,
+    },
+    /// Dump everything forest knows about a session along with the container
+    /// engine's own view of its container, for debugging
+    Inspect {
+        name: String,
+        /// Print one JSON document, including the full `inspect` output
+        #[arg(long)]
+        json: bool,
+    },
+    /// Upgrade session records written by older forest versions
+    Migrate,
+    /// Verify prerequisites are installed and config is valid
+    Precheck,
+    /// Summarise time spent attached to sessions
+    #[command(after_long_help = "Examples:
+  forest time
+  forest time --since 1w")]
+    Time {
+        /// Only count time within this span, e.g. `8h`, `3d` or `1w`
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Stop every other running session so one session gets the whole machine
+    #[command(after_long_help = "Examples:
+  forest focus feat/login
+  forest unfocus")]
+    Focus { name: String },
+    /// Restart the sessions stopped by `focus`
+    Unfocus,
+    /// Open a scratch branch and worktree inside an existing session's container
+    #[command(after_long_help = "Examples:
+  forest subtask feat/login fix-typo    # branch feat/login-fix-typo")]
+    Subtask {
+        /// Running session whose container is reused
+        session: String,
+        /// Subtask name; the branch is `<session>-<name>`
+        name: String,
+    },
+    /// Run one command in running sessions' worktrees and exit with its status
+    #[command(after_long_help = "Examples:
+  forest exec feat/login -- cargo test
+  forest exec feat/login -- git log --oneline -5
+  forest exec feat/login feat/signup --group-output -- cargo test")]
+    Exec {
+        /// Running sessions whose containers run the command; with several,
+        /// it runs in all of them at once and each output line is prefixed
+        /// with its session
+        #[arg(required = true, num_args = 1..)]
+        names: Vec<String>,
+        /// With several sessions, show each session's output in one block
+        /// once its command ends instead of line by line
+        #[arg(long)]
+        group_output: bool,
+        /// Command and arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Exercise open, exec and kill end-to-end against a throwaway repository
+    SelfTest {
+        /// Use a bundled devcontainer stand-in instead of the real CLI and runtime
+        #[arg(long)]
+        stub: bool,
+    },
+    /// Freeze a running session's processes to disk (experimental feature `checkpoint`)
+    #[command(
+        after_long_help = "Uses CRIU through the container runtime, which has to run as root.
+
+Examples:
+  forest --enable-feature checkpoint checkpoint feat/login
+  forest --enable-feature checkpoint resume feat/login"
+    )]
+    Checkpoint { name: String },
+    /// Restore a session's processes from its checkpoint (experimental feature `checkpoint`)
+    Resume { name: String },
+    /// List experimental features and whether they're enabled
+    Features,
+    /// Write a devcontainer.json for a repository without one, from a template
+    #[command(
+        after_long_help = "Templates are the built-in ubuntu, rust, node and python, directories of
+your own under `templates` next to forest.toml (one per template, holding its
+devcontainer.json and any files it needs; a directory named like a built-in
+replaces it), or a path to such a directory or a devcontainer.json. Without
+--template, `scaffold_template` from forest.toml is used, else one guessed
+from the repository's files. `scaffold` in forest.toml picks where it goes.
+
+Examples:
+  forest init
+  forest init --template rust
+  forest init --template ~/src/dotfiles/devcontainer"
+    )]
+    Init {
+        /// Template name or path
+        #[arg(long)]
+        template: Option<String>,
+    },
+    /// Check the devcontainer.json a session would use
+    #[command(after_long_help = "Examples:
+  forest validate
+  forest validate --devcontainer-env gpu")]
+    Validate {
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+    },
+    /// Scan a session's image, or any image, for vulnerabilities with trivy or grype
+    #[command(
+        after_long_help = "Fails when the image has critical vulnerabilities, so it can gate CI.
+
+Examples:
+  forest scan feat/login
+  forest scan docker.io/library/ubuntu:24.04"
+    )]
+    Scan {
+        /// Session name, or an image reference when no session has that name
+        target: String,
+    },
+    /// Open a session on a GitHub pull request, optionally sandboxed
+    #[command(
+        after_long_help = "The pull request's head is fetched from origin into the branch
+`review/<number>`. Without --sandbox that branch is opened like any session.
+
+With --sandbox the code runs in `sandbox/<number>`, built from your checkout's
+devcontainer.json rather than the pull request's. Host commands, extra mounts,
+forwarded environment variables, privileges and ports are stripped from it, the
+repository is mounted read-only, the code is an exported copy without git
+metadata that is thrown away with `forest kill --purge`, and the container has
+no network unless hosts are allowed with --allow-host.
+
+Examples:
+  forest review 42
+  forest review --sandbox https://github.com/acme/widget/pull/42
+  forest review --sandbox 42 --allow-host crates.io --allow-host static.crates.io"
+    )]
+    Review {
+        /// Pull request number or URL
+        pr: String,
+        /// Run the pull request's code in a hardened session
+        #[arg(long)]
+        sandbox: bool,
+        /// Host the sandbox may reach over HTTP(S)
+        #[arg(long = "allow-host", value_name = "HOST", requires = "sandbox")]
+        allow_hosts: Vec<String>,
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+    },
+    /// Open sibling sessions of a branch per platform and run commands across
+    /// them, to check cross-architecture behaviour before a release
+    #[command(
+        after_long_help = "Each platform gets a session and branch `<name>-<arch>` created from
+branch `name`, whose container runs as that platform, emulated where the host
+can't run it natively.
+
+Examples:
+  forest matrix open release/1.4 --platforms amd64,arm64
+  forest matrix exec release/1.4 -- cargo test"
+    )]
+    Matrix {
+        #[command(subcommand)]
+        action: MatrixAction,
+    },
+    /// Open a preset's sessions in a tmux window each, split into its panes
+    #[command(after_long_help = "Presets are defined in forest.toml:
+
+  [layouts.review]
+  sessions = [\"feat/login\", \"feat/signup\"]
+  panes = [\"\", \"cargo watch -x test\", \"tail -F /tmp/app.log\"]
+  arrange = \"tiled\"
+
+An empty pane is the session's shell; the others run their command in the
+session's container.
+
+Examples:
+  forest layout apply review
+  forest layout list")]
+    Layout {
+        #[command(subcommand)]
+        action: LayoutAction,
+    },
+    /// Show or edit the repository's devcontainer overlay, personal settings
+    /// merged over its devcontainer.json without changing it
+    #[command(
+        after_long_help = "The overlay is JSON in forest's data directory, one per repository.
+Objects such as `containerEnv` and `features` are merged key by key, arrays such
+as `mounts` and `runArgs` are appended to, and other settings replace the
+repository's.
+
+Examples:
+  forest overlay
+  forest overlay --edit"
+    )]
+    Overlay {
+        /// Open the overlay in $VISUAL or $EDITOR, creating it if needed
+        #[arg(long)]
+        edit: bool,
+    },
+    /// Show the container label forest derives from session names
+    #[command(after_long_help = "Examples:
+  forest sanitize feat/cool feat.cool")]
+    Sanitize {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Inspect and restore worktrees removed by `kill --purge`
+    #[command(after_long_help = "Examples:
+  forest trash list
+  forest trash restore 1760000000-feat-login
+  forest trash empty --all")]
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Browse and restore snapshots of sessions' uncommitted changes
+    #[command(after_long_help = "Examples:
+  forest snapshots take --every 10      # snapshot all sessions every 10 minutes
+  forest snapshots list feat/login
+  forest snapshots restore feat/login 4")]
+    Snapshots {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Create and open air-gapped session bundles
+    #[command(after_long_help = "Examples:
+  forest bundle create lab -o lab.forest.tar
+  forest bundle open lab.forest.tar --dir ~/src")]
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+    /// Share devcontainer configurations as devcontainer templates in an OCI registry
+    #[command(after_long_help = "Examples:
+  forest template publish --version 1.1.0
+  forest template pull rust-service
+  forest new widget --template rust-service")]
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+    /// Show help for a command or a topic
+    #[command(after_long_help = "Examples:
+  forest help open
+  forest help trash restore
+  forest help worktrees")]
+    Help {
+        /// Command, e.g. `open` or `trash restore`, or topic, e.g. `worktrees`
+        topic: Vec<String>,
+    },
+    /// Write forest's man pages
+    #[command(after_long_help = "Examples:
+  forest man -o ~/.local/share/man && man forest-open")]
+    Man {
+        /// Directory to write `man1` and `man7` into
+        #[arg(long, short, default_value = "man")]
+        output: PathBuf,
+    },
+    /// Install or remove `gh forest`, a GitHub CLI extension running forest
+    /// with gh's repository context
+    #[command(
+        after_long_help = "`gh forest <command>` runs `forest <command>`. When gh has a repository from
+GH_REPO, such as `gh -R acme/widget forest ...` sets, `open <number>` and
+`review <number>` open that repository's pull request and `open <branch>` its
+branch, wherever forest finds or clones the repository.
+
+Examples:
+  forest gh-extension install
+  gh forest review 123
+  GH_REPO=acme/widget gh forest open feat/login"
+    )]
+    GhExtension {
+        #[command(subcommand)]
+        action: GhExtensionAction,
+    },
+    /// Print a shell completion script; session names are completed from
+    /// the sessions forest knows
+    #[command(after_long_help = "Examples:
+  forest completions bash > ~/.local/share/bash-completion/completions/forest
+  source <(forest completions zsh)      # in ~/.zshrc
+  forest completions fish > ~/.config/fish/completions/forest.fish")]
+    Completions {
+        #[arg(value_enum, required_unless_present = "sessions")]
+        shell: Option<CompletionShell>,
+        /// Print the names of known sessions instead, one per line, for the
+        /// scripts to offer
+        #[arg(long, conflicts_with = "shell")]
+        sessions: bool,
+    },
+    /// Write a diagnostic report to attach to a bug report
+    #[command(
+        after_long_help = "The report holds forest's version, tool versions, selected environment
+variables, the configuration with credentials redacted and the last few
+failures. Nothing is sent anywhere; review the file before sharing it.
+
+Examples:
+  forest report-bug
+  forest report-bug -o /tmp/forest-report.txt"
+    )]
+    ReportBug {
+        /// File to write, `forest-report-<time>.txt` by default
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Create a new project repository and open its first session
+    #[command(after_long_help = "Examples:
+  forest new widget
+  forest new widget --org acme --template ~/templates/rust --session main")]
+    New {
+        /// Directory (and GitHub repository) name for the project
+        name: String,
+        /// GitHub organisation to create the repository under instead of `githuborg`
+        #[arg(long)]
+        org: Option<String>,
+        /// Directory whose contents seed the initial commit, or a published
+        /// devcontainer template (see `forest template`)
+        #[arg(long)]
+        template: Option<String>,
+        /// Name of the first session
+        #[arg(long, default_value = "dev")]
+        session: String,
+    },
+}
+
+impl Commands {
+    /// The `--backend` given to the command, if it takes one.
+    pub fn backend(&self) -> Option<Backend> {
+        match self {
+            Commands::Open { backend, .. }
+            | Commands::Kill { backend, .. }
+            | Commands::Ls { backend, .. } => *backend,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BundleAction {
+    /// Pack a session's image, repository and configuration into one archive
+    Create {
+        /// Session the bundle opens
+        name: String,
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+        /// Archive to write, `<repo>-<name>.forest.tar` by default
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Load a bundle and open its session without network access
+    Open {
+        file: PathBuf,
+        /// Directory to check the repository out under
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TemplateAction {
+    /// Publish the repository's .devcontainer as a template
+    Publish {
+        /// Template id, the repository name by default
+        #[arg(long)]
+        id: Option<String>,
+        /// Semantic version of this release of the template
+        #[arg(long, default_value = "1.0.0")]
+        version: String,
+    },
+    /// Add a published template's .devcontainer to a directory
+    Pull {
+        /// Template id in the organisation's namespace, or a full OCI reference
+        template: String,
+        /// Directory to apply the template to
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotAction {
+    /// Snapshot the uncommitted changes of every session's worktree, e.g.
+    /// from cron
+    Take {
+        /// Keep running, taking snapshots every this many minutes
+        #[arg(long, value_name = "MINUTES")]
+        every: Option<u64>,
+    },
+    /// List a session's snapshots
+    List { name: String },
+    /// Apply a snapshot's changes to the session's worktree
+    Restore { name: String, id: u32 },
+}
+
+#[derive(Subcommand)]
+pub enum LayoutAction {
+    /// Open the preset's sessions detached and show them in tmux session
+    /// `forest-<preset>`, which is created unless tmux already has it
+    Apply { preset: String },
+    /// List the presets of forest.toml
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum MatrixAction {
+    /// Open a session per platform off branch `name`, set up and detached
+    Open {
+        /// Existing branch the platform sessions start from
+        name: String,
+        /// Platforms, such as `amd64,arm64` or `linux/arm/v7`
+        #[arg(long, value_delimiter = ',', required = true)]
+        platforms: Vec<String>,
+        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
+        #[arg(long)]
+        devcontainer_env: Option<String>,
+    },
+    /// Run a command in every platform session of `name` at once and sum up
+    /// how it went on each
+    Exec {
+        name: String,
+        /// Command and arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GhExtensionAction {
+    /// Register `gh forest` with gh, pointing at this forest binary
+    Install,
+    /// Unregister `gh forest`
+    Uninstall,
+    /// What `gh forest` runs
+    #[command(hide = true)]
+    Run {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List trashed worktrees
+    List,
+    /// Move a trashed worktree back into place
+    Restore { id: String },
+    /// Delete trashed worktrees past the retention period
+    Empty {
+        /// Delete everything regardless of age
+        #[arg(long)]
+        all: bool,
+    },
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Worktree-aware tools managing a repository alongside plain git.
+#[derive(Clone, Copy, Default)]
+pub struct Tools {
+    /// A colocated jj repository: `.jj` next to `.git`. jj owns `HEAD` in the
+    /// main checkout and imports branches as bookmarks, so forest must not move
+    /// existing branches.
+    pub jj: bool,
+    /// git-branchless keeps its event log in `.git/branchless` and installs
+    /// hooks that call `git branchless`, which fail in containers without it.
+    pub branchless: bool,
+}
+
+impl Tools {
+    pub fn any(&self) -> bool {
+        self.jj || self.branchless
+    }
+}
+
+pub fn detect(repo_root: &Path) -> Tools {
+    Tools {
+        jj: repo_root.join(".jj").is_dir() && repo_root.join(".git").exists(),
+        branchless: repo_root.join(".git").join("branchless").is_dir(),
+    }
+}
+
+/// A jj change resolved to the git commit currently holding it.
+#[derive(Debug, PartialEq)]
+pub struct JjChange {
+    pub commit: String,
+    pub change_id: String,
+    /// Local bookmarks pointing at the change
+    pub bookmarks: Vec<String>,
+}
+
+impl JjChange {
+    /// Session for the change: its first bookmark, or `jj-<change id>`.
+    pub fn session(&self) -> String {
+        self.bookmarks
+            .first()
+            .cloned()
+            .unwrap_or_else(|| format!("jj-{}", self.change_id))
+    }
+}
+
+const JJ_TEMPLATE: &str = r#"commit_id ++ "\t" ++ change_id.short(12) ++ "\t" ++ local_bookmarks.map(|b| b.name()).join(",") ++ "\n""#;
+
+pub fn resolve_jj_change(repo_root: &Path, revision: &str) -> anyhow::Result<JjChange> {
+    let output = Command::new("jj")
+        .args(["log", "--no-graph", "--ignore-working-copy", "-r", revision])
+        .args(["-T", JJ_TEMPLATE])
+        .current_dir(repo_root)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow::anyhow!("jj command not found")
+            } else {
+                e.into()
+            }
+        })?;
+    if !output.status.success() {
+        anyhow::bail!("jj log -r {} failed", revision);
+    }
+    parse_jj_log(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| anyhow::anyhow!("{} must resolve to exactly one jj change", revision))
+}
+
+fn parse_jj_log(output: &str) -> Option<JjChange> {
+    let mut lines = output.lines().filter(|l| !l.trim().is_empty());
+    let line = lines.next()?;
+    if lines.next().is_some() {
+        return None;
+    }
+    let mut fields = line.split('\t');
+    let commit = fields.next()?.trim().to_string();
+    let change_id = fields.next()?.trim().to_string();
+    let bookmarks = fields
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(str::to_string)
+        .collect();
+    if commit.is_empty() || change_id.is_empty() {
+        return None;
+    }
+    Some(JjChange {
+        commit,
+        change_id,
+        bookmarks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn detects_colocated_jj_and_branchless() {
+        let repo = tempdir().unwrap();
+        assert!(!detect(repo.path()).any());
+
+        fs::create_dir_all(repo.path().join(".jj")).unwrap();
+        // a jj repository without a git checkout next to it isn't colocated
+        assert!(!detect(repo.path()).jj);
+        fs::create_dir_all(repo.path().join(".git").join("branchless")).unwrap();
+        let tools = detect(repo.path());
+        assert!(tools.jj);
+        assert!(tools.branchless);
+    }
+
+    #[test]
+    fn parses_jj_log() {
+        let change = parse_jj_log("0123abcd\tkxqvtmzy\tfeat-x,wip\n").unwrap();
+        assert_eq!(change.commit, "0123abcd");
+        assert_eq!(change.bookmarks, ["feat-x", "wip"]);
+        assert_eq!(change.session(), "feat-x");
+
+        let change = parse_jj_log("0123abcd\tkxqvtmzy\t\n").unwrap();
+        assert_eq!(change.session(), "jj-kxqvtmzy");
+
+        assert!(parse_jj_log("").is_none());
+        assert!(parse_jj_log("a\tb\t\nc\td\t\n").is_none());
+    }
+}
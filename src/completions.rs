@@ -0,0 +1,225 @@
+use std::io::Write;
+use std::path::Path;
+
+use clap::Command;
+use clap_complete::Shell;
+
+use crate::config::WorktreeLayout;
+use crate::sessions;
+
+/// Commands whose first argument is a session, completed from the registry.
+pub const SESSION_COMMANDS: [&str; 20] = [
+    "open",
+    "attach",
+    "rebuild",
+    "paste",
+    "drift",
+    "pr",
+    "sync",
+    "act",
+    "renice",
+    "kill",
+    "rm",
+    "rename",
+    "status",
+    "adopt-branch",
+    "inspect",
+    "focus",
+    "subtask",
+    "exec",
+    "checkpoint",
+    "resume",
+];
+
+impl From<crate::cli::CompletionShell> for Shell {
+    fn from(shell: crate::cli::CompletionShell) -> Self {
+        match shell {
+            crate::cli::CompletionShell::Bash => Shell::Bash,
+            crate::cli::CompletionShell::Zsh => Shell::Zsh,
+            crate::cli::CompletionShell::Fish => Shell::Fish,
+            crate::cli::CompletionShell::Powershell => Shell::PowerShell,
+        }
+    }
+}
+
+/// Writes the completion script for `shell`. clap's scripts only know the
+/// command line's shape, so bash, zsh and fish get a wrapper asking
+/// `forest completions --sessions` for session names where one is expected.
+pub fn generate(shell: Shell, cmd: &mut Command, out: &mut dyn Write) -> std::io::Result<()> {
+    clap_complete::generate(shell, cmd, "forest", out);
+    let commands = SESSION_COMMANDS.join("|");
+    // global options taking a value, whose value isn't the subcommand
+    let valued: Vec<String> = cmd
+        .get_arguments()
+        .filter(|arg| arg.is_global_set() && arg.get_action().takes_values())
+        .filter_map(|arg| arg.get_long().map(|long| format!("--{}", long)))
+        .collect();
+    match shell {
+        Shell::Bash => write!(
+            out,
+            r#"
+_forest_sessions() {{
+    local i cmd=""
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        case "${{COMP_WORDS[i]}}" in
+            {valued}) (( i++ )) ;;
+            -*) ;;
+            *) cmd="${{COMP_WORDS[i]}}"; break ;;
+        esac
+    done
+    case "$cmd" in
+        {commands})
+            if (( i == COMP_CWORD - 1 )) && [[ "${{COMP_WORDS[COMP_CWORD]}}" != -* ]]; then
+                COMPREPLY=( $(compgen -W "$(forest completions --sessions 2>/dev/null)" -- "${{COMP_WORDS[COMP_CWORD]}}") )
+                return 0
+            fi
+            ;;
+    esac
+    _forest "$@"
+}}
+complete -F _forest_sessions -o bashdefault -o default forest
+"#,
+            valued = valued.join("|"),
+        ),
+        Shell::Zsh => write!(
+            out,
+            r#"
+_forest_sessions() {{
+    local i cmd
+    for (( i = 2; i < CURRENT; i++ )); do
+        case ${{words[i]}} in
+            ({valued}) (( i++ )) ;;
+            (-*) ;;
+            (*) cmd=${{words[i]}}; break ;;
+        esac
+    done
+    if (( i == CURRENT - 1 )) && [[ ${{words[CURRENT]}} != -* ]]; then
+        case $cmd in
+            ({commands})
+                compadd -- ${{(f)"$(forest completions --sessions 2>/dev/null)"}}
+                return
+                ;;
+        esac
+    fi
+    _forest "$@"
+}}
+compdef _forest_sessions forest
+"#,
+            valued = valued.join("|"),
+        ),
+        Shell::Fish => writeln!(
+            out,
+            "complete -c forest -n '__fish_seen_subcommand_from {}' -f -a '(forest completions --sessions 2>/dev/null)'",
+            SESSION_COMMANDS.join(" ")
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Names of the sessions in the registry, as `open` was given them: the
+/// worktree's path under `worktrees` as `layout` reads it, else the branch.
+pub fn session_names(
+    data_dir: &Path,
+    worktrees: &Path,
+    layout: WorktreeLayout,
+) -> anyhow::Result<Vec<String>> {
+    let mut names: Vec<String> = sessions::load(data_dir)?
+        .values()
+        .map(|session| session_name(session, worktrees, layout))
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// The name `session` is opened with: its worktree's path below the
+/// repository's directory, or its branch for a worktree elsewhere.
+pub fn session_name(
+    session: &sessions::Session,
+    worktrees: &Path,
+    layout: WorktreeLayout,
+) -> String {
+    let repo_name = session.repo.file_name().unwrap_or_default();
+    session
+        .worktree
+        .strip_prefix(worktrees.join(repo_name))
+        .map(|dir| layout.name(dir))
+        .unwrap_or_else(|_| session.branch.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn session_commands_take_a_session_first() {
+        let cmd = crate::cli::Cli::command();
+        for name in SESSION_COMMANDS {
+            let sub = cmd
+                .find_subcommand(name)
+                .unwrap_or_else(|| panic!("no command {}", name));
+            let first = sub
+                .get_positionals()
+                .next()
+                .map(|arg| arg.get_id().as_str());
+            assert!(
+                matches!(first, Some("name" | "names" | "session")),
+                "{} starts with {:?}",
+                name,
+                first
+            );
+        }
+    }
+
+    #[test]
+    fn scripts_complete_session_names() {
+        let mut cmd = crate::cli::Cli::command();
+        let mut bash = Vec::new();
+        generate(Shell::Bash, &mut cmd, &mut bash).unwrap();
+        let bash = String::from_utf8(bash).unwrap();
+        assert!(bash.contains("complete -F _forest_sessions"));
+        assert!(bash.contains("--enable-feature|--devcontainer-arg|--color) (( i++ ))"));
+        let mut fish = Vec::new();
+        generate(Shell::Fish, &mut cmd, &mut fish).unwrap();
+        assert!(String::from_utf8(fish)
+            .unwrap()
+            .contains("__fish_seen_subcommand_from open attach"));
+    }
+
+    #[test]
+    fn names_sessions_by_worktree() {
+        let dir = tempdir().unwrap();
+        let session = |branch: &str, worktree: &str| sessions::Session {
+            branch: branch.to_string(),
+            repo: PathBuf::from("/src/widget"),
+            worktree: PathBuf::from(worktree),
+            devcontainer_env: None,
+            created_at: 0,
+            readonly: false,
+        };
+        sessions::record(
+            dir.path(),
+            "feat-login",
+            session("feat/login", "/w/widget/feat/login"),
+        )
+        .unwrap();
+        sessions::record(dir.path(), "old", session("old", "/elsewhere/old")).unwrap();
+        assert_eq!(
+            session_names(dir.path(), Path::new("/w"), WorktreeLayout::Nested).unwrap(),
+            ["feat/login", "old"]
+        );
+        sessions::record(
+            dir.path(),
+            "fix-deep-bug",
+            session("fix/deep/bug", "/w/widget/fix%2Fdeep%2Fbug"),
+        )
+        .unwrap();
+        assert_eq!(
+            session_names(dir.path(), Path::new("/w"), WorktreeLayout::Flat).unwrap(),
+            ["feat/login", "fix/deep/bug", "old"]
+        );
+    }
+}
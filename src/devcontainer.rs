@@ -0,0 +1,432 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Subcommands that get the raw arguments of `devcontainer_args` and
+/// `--devcontainer-arg`.
+const EXTRA_ARGS_SUBCOMMANDS: [&str; 3] = ["build", "up", "exec"];
+
+static EXTRA_ARGS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Sets raw arguments forest passes on without interpreting them, for
+/// devcontainer CLI features it has no option for yet.
+pub fn init(extra_args: Vec<String>) {
+    let _ = EXTRA_ARGS.set(extra_args);
+}
+
+/// `devcontainer <subcommand>`, driving the configured container backend.
+pub fn command(subcommand: &str) -> Command {
+    let mut cmd = Command::new("devcontainer");
+    cmd.arg(subcommand)
+        .args(crate::backend::current().devcontainer_args());
+    if EXTRA_ARGS_SUBCOMMANDS.contains(&subcommand) {
+        cmd.args(EXTRA_ARGS.get().into_iter().flatten());
+    }
+    cmd
+}
+
+/// What `devcontainer up` reports on stdout once the container is running.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpResult {
+    pub container_id: String,
+    pub remote_user: Option<String>,
+    /// Where the workspace is mounted inside the container
+    pub remote_workspace_folder: Option<String>,
+}
+
+/// Finds the result line in `devcontainer up` output, which may be preceded by
+/// log lines depending on the CLI version and `--log-format`.
+pub fn parse_up(stdout: &str) -> Option<UpResult> {
+    stdout.lines().rev().find_map(|line| {
+        let value: Value = serde_json::from_str(line.trim()).ok()?;
+        if value.get("outcome").and_then(Value::as_str) != Some("success") {
+            return None;
+        }
+        serde_json::from_value(value).ok()
+    })
+}
+
+/// Failure the CLI reports as JSON on stdout, e.g. when `devcontainer.json`
+/// doesn't parse.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct CliError {
+    pub message: String,
+    pub description: Option<String>,
+}
+
+pub fn parse_error(stdout: &str) -> Option<CliError> {
+    stdout.lines().rev().find_map(|line| {
+        let value: Value = serde_json::from_str(line.trim()).ok()?;
+        if value.get("outcome").and_then(Value::as_str) != Some("error") {
+            return None;
+        }
+        serde_json::from_value(value).ok()
+    })
+}
+
+/// Error for a failed `devcontainer <command>`, carrying the CLI's own message
+/// when it printed one.
+pub fn failure(command: &str, stdout: &str, config: &Path) -> anyhow::Error {
+    let Some(error) = parse_error(stdout) else {
+        return anyhow::anyhow!("devcontainer {} failed", command);
+    };
+    let mut message = format!("devcontainer {} failed: {}", command, error.message);
+    if let Some(description) = error.description.filter(|d| *d != error.message) {
+        message.push_str(&format!("\n{}", description));
+    }
+    message.push_str(&format!(
+        "\nconfiguration: {}\nrun `forest validate` to check it",
+        config.display()
+    ));
+    anyhow::anyhow!(message)
+}
+
+/// Arguments selecting a session's container for `devcontainer exec`: the
+/// container id when known, which skips the CLI's label lookup, otherwise the
+/// session's id label.
+pub fn target_args(label: &str, container_id: Option<&str>) -> [String; 2] {
+    match container_id {
+        Some(id) => ["--container-id".to_string(), id.to_string()],
+        None => ["--id-label".to_string(), format!("name={}", label)],
+    }
+}
+
+/// Compose files of a configuration built from Docker Compose: the
+/// `dockerComposeFile` string or list, empty for image and build ones.
+pub fn compose_files(value: &Value) -> Vec<String> {
+    match value.get("dockerComposeFile") {
+        Some(Value::String(file)) => vec![file.clone()],
+        Some(Value::Array(files)) => files
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Files an anchored configuration's container is built from: its
+/// Dockerfile or compose files.
+pub fn build_files(value: &Value) -> Vec<String> {
+    let dockerfile = value
+        .get("build")
+        .and_then(|build| build.get("dockerfile"))
+        .or_else(|| value.get("dockerFile"))
+        .and_then(Value::as_str);
+    dockerfile
+        .map(str::to_string)
+        .into_iter()
+        .chain(compose_files(value))
+        .collect()
+}
+
+/// Whether the configuration runs a Docker Compose `service` rather than an
+/// image or build of its own.
+pub fn is_compose(value: &Value) -> bool {
+    value.get("dockerComposeFile").is_some()
+}
+
+/// Whether the configuration says what to run: an `image`, a `build`, or a
+/// Docker Compose `service`.
+pub fn has_container(value: &Value) -> bool {
+    value.get("image").is_some() || value.get("build").is_some() || is_compose(value)
+}
+
+/// The error for a configuration without `image`, `build` or compose file.
+pub const NO_CONTAINER: &str = "devcontainer.json sets none of image, build or dockerComposeFile";
+
+/// The configuration running `image` instead of its own image or build.
+pub fn with_image(value: &Value, image: &str) -> Value {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+        for key in ["build", "dockerFile", "context"] {
+            object.remove(key);
+        }
+        object.insert("image".to_string(), Value::String(image.to_string()));
+    }
+    value
+}
+
+/// The configuration with `overlay` merged over it: objects such as
+/// `containerEnv` and `features` key by key, arrays such as `mounts` and
+/// `runArgs` appended to, anything else replaced.
+pub fn merge_overlay(value: &Value, overlay: &Value) -> Value {
+    match (value, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            let mut merged = base.clone();
+            for (key, over) in overlay {
+                let value = match merged.get(key) {
+                    Some(existing) => merge_overlay(existing, over),
+                    None => over.clone(),
+                };
+                merged.insert(key.clone(), value);
+            }
+            Value::Object(merged)
+        }
+        (Value::Array(base), Value::Array(overlay)) => {
+            Value::Array(base.iter().chain(overlay).cloned().collect())
+        }
+        (_, overlay) => overlay.clone(),
+    }
+}
+
+/// The configuration with its container run for `platform`, such as
+/// `linux/arm64`, replacing a `--platform` it sets itself.
+pub fn with_platform(value: &Value, platform: &str) -> Value {
+    let mut value = value.clone();
+    let Some(object) = value.as_object_mut() else {
+        return value;
+    };
+    let mut run_args: Vec<Value> = Vec::new();
+    let mut skip_value = false;
+    for arg in object
+        .get("runArgs")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+    {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        match arg.as_str() {
+            Some("--platform") => skip_value = true,
+            Some(text) if text.starts_with("--platform=") => {}
+            _ => run_args.push(arg),
+        }
+    }
+    run_args.push(Value::String(format!("--platform={}", platform)));
+    object.insert("runArgs".to_string(), Value::Array(run_args));
+    value
+}
+
+/// The configuration with `features` added, using their default options. A
+/// feature the configuration already has keeps its options.
+pub fn with_features(value: &Value, features: &[String]) -> Value {
+    let mut value = value.clone();
+    if features.is_empty() {
+        return value;
+    }
+    if let Some(object) = value.as_object_mut() {
+        let entry = object
+            .entry("features")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if let Some(existing) = entry.as_object_mut() {
+            for feature in features {
+                existing
+                    .entry(feature.clone())
+                    .or_insert_with(|| Value::Object(Default::default()));
+            }
+        }
+    }
+    value
+}
+
+/// The configuration with the container labelled `key=value`, replacing a
+/// label of the same key it sets itself.
+pub fn with_label(value: &Value, key: &str, label: &str) -> Value {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+        let prefix = format!("--label={}=", key);
+        let mut run_args: Vec<Value> = object
+            .get("runArgs")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        run_args.retain(|a| !a.as_str().unwrap_or_default().starts_with(&prefix));
+        run_args.push(Value::String(format!("{}{}", prefix, label)));
+        object.insert("runArgs".to_string(), Value::Array(run_args));
+    }
+    value
+}
+
+/// The configuration with its Dockerfile, build context and compose files made
+/// absolute, for a copy of it stored outside `dir`, the directory it was read
+/// from.
+pub fn anchor_build_paths(value: &Value, dir: &Path) -> Value {
+    let mut value = value.clone();
+    let anchor = |object: &mut serde_json::Map<String, Value>, file: &str, context: &str| {
+        if let Some(path) = object.get(file).and_then(Value::as_str) {
+            let path = dir.join(path).to_string_lossy().into_owned();
+            object.insert(file.to_string(), Value::String(path));
+            // the context defaults to the configuration's directory
+            let context_path = object.get(context).and_then(Value::as_str).unwrap_or(".");
+            let context_path = dir.join(context_path).to_string_lossy().into_owned();
+            object.insert(context.to_string(), Value::String(context_path));
+        }
+    };
+    if let Some(object) = value.as_object_mut() {
+        anchor(object, "dockerFile", "context");
+        if let Some(build) = object.get_mut("build").and_then(Value::as_object_mut) {
+            anchor(build, "dockerfile", "context");
+        }
+        // compose resolves paths inside the files against their own directory
+        let anchored = |file: &str| dir.join(file).to_string_lossy().into_owned();
+        match object.get_mut("dockerComposeFile") {
+            Some(Value::String(file)) => *file = anchored(file),
+            Some(Value::Array(files)) => {
+                for file in files {
+                    if let Value::String(path) = file {
+                        *path = anchored(path);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_platforms() {
+        let value: Value =
+            serde_json::from_str(r#"{"runArgs": ["--platform", "linux/amd64", "--cpus=2"]}"#)
+                .unwrap();
+        assert_eq!(
+            with_platform(&value, "linux/arm64")["runArgs"],
+            serde_json::json!(["--cpus=2", "--platform=linux/arm64"])
+        );
+    }
+
+    #[test]
+    fn merges_overlays() {
+        let value: Value = serde_json::from_str(
+            r#"{"image": "x", "containerEnv": {"A": "1", "B": "2"}, "mounts": ["m1"],
+                "features": {"go": {"version": "1.22"}}}"#,
+        )
+        .unwrap();
+        let overlay: Value = serde_json::from_str(
+            r#"{"containerEnv": {"B": "3"}, "mounts": ["m2"], "features": {"rust": {}},
+                "remoteUser": "me"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            merge_overlay(&value, &overlay),
+            serde_json::json!({
+                "image": "x",
+                "containerEnv": {"A": "1", "B": "3"},
+                "mounts": ["m1", "m2"],
+                "features": {"go": {"version": "1.22"}, "rust": {}},
+                "remoteUser": "me",
+            })
+        );
+    }
+
+    #[test]
+    fn parses_up_result() {
+        let stdout = "[2 ms] Dev Containers CLI 0.60.0\n\
+            {\"outcome\":\"success\",\"containerId\":\"abc123\",\"remoteUser\":\"vscode\",\
+            \"remoteWorkspaceFolder\":\"/workspaces/feat\"}\n";
+        let result = parse_up(stdout).unwrap();
+        assert_eq!(result.container_id, "abc123");
+        assert_eq!(result.remote_user.as_deref(), Some("vscode"));
+        assert_eq!(
+            result.remote_workspace_folder.as_deref(),
+            Some("/workspaces/feat")
+        );
+
+        assert!(parse_up("").is_none());
+        assert!(parse_up("{\"outcome\":\"error\",\"message\":\"boom\"}").is_none());
+    }
+
+    #[test]
+    fn reports_cli_errors() {
+        let stdout = "{\"outcome\":\"error\",\"message\":\"Dev container config (x) not found.\",\
+            \"description\":\"Dev container config (x) not found.\"}\n";
+        let error = parse_error(stdout).unwrap();
+        assert_eq!(error.message, "Dev container config (x) not found.");
+
+        let message =
+            failure("up", stdout, Path::new(".devcontainer/devcontainer.json")).to_string();
+        assert!(message.contains("not found."));
+        assert!(message.contains(".devcontainer/devcontainer.json"));
+        assert!(message.contains("forest validate"));
+        // the description repeats the message here, so it's printed once
+        assert_eq!(message.matches("not found.").count(), 1);
+
+        assert_eq!(
+            failure("up", "", Path::new("x")).to_string(),
+            "devcontainer up failed"
+        );
+    }
+
+    #[test]
+    fn prefers_container_id() {
+        assert_eq!(
+            target_args("feat", Some("abc")),
+            ["--container-id", "abc"].map(String::from)
+        );
+        assert_eq!(
+            target_args("feat", None),
+            ["--id-label", "name=feat"].map(String::from)
+        );
+    }
+
+    #[test]
+    fn adds_session_features() {
+        let value: Value = serde_json::from_str(
+            r#"{"image": "x", "features": {"ghcr.io/devcontainers/features/go:1": {"version": "1.22"}}}"#,
+        )
+        .unwrap();
+        let features = [
+            "ghcr.io/devcontainers/features/go:1".to_string(),
+            "ghcr.io/devcontainers/features/node:1".to_string(),
+        ];
+        let value = with_features(&value, &features);
+        assert_eq!(
+            value["features"]["ghcr.io/devcontainers/features/go:1"]["version"],
+            "1.22"
+        );
+        assert!(value["features"]["ghcr.io/devcontainers/features/node:1"].is_object());
+    }
+
+    #[test]
+    fn replaces_labels() {
+        let value: Value =
+            serde_json::from_str(r#"{"runArgs": ["--cpus=2", "--label=forest.version=0.0.1"]}"#)
+                .unwrap();
+        let value = with_label(&value, "forest.version", "0.1.0");
+        assert_eq!(
+            value["runArgs"],
+            serde_json::json!(["--cpus=2", "--label=forest.version=0.1.0"])
+        );
+    }
+
+    #[test]
+    fn anchors_build_paths() {
+        let value: Value =
+            serde_json::from_str(r#"{"build": {"dockerfile": "Dockerfile", "context": ".."}}"#)
+                .unwrap();
+        let value = anchor_build_paths(&value, Path::new("/repo/.devcontainer"));
+        assert_eq!(
+            value["build"]["dockerfile"],
+            "/repo/.devcontainer/Dockerfile"
+        );
+        assert_eq!(value["build"]["context"], "/repo/.devcontainer/..");
+
+        let compose: Value = serde_json::from_str(
+            r#"{"dockerComposeFile": ["../compose.yml", "compose.dev.yml"], "service": "app"}"#,
+        )
+        .unwrap();
+        assert!(has_container(&compose));
+        let compose = anchor_build_paths(&compose, Path::new("/repo/.devcontainer"));
+        assert_eq!(
+            compose_files(&compose),
+            [
+                "/repo/.devcontainer/../compose.yml",
+                "/repo/.devcontainer/compose.dev.yml"
+            ]
+        );
+        assert!(!has_container(&serde_json::json!({"service": "app"})));
+        assert_eq!(build_files(&value), ["/repo/.devcontainer/Dockerfile"]);
+        assert_eq!(build_files(&compose).len(), 2);
+        assert!(build_files(&serde_json::json!({"image": "x"})).is_empty());
+    }
+}
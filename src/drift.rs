@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{Map, Value};
+
+/// Settings compared on their own rather than as a whole.
+const COMPARED_APART: [&str; 4] = ["image", "build", "features", "containerEnv"];
+
+/// How a session's container differs from what its devcontainer.json asks
+/// for now. `recorded` is the configuration the container was created from,
+/// when forest kept it; `container` is the engine's `inspect` document and
+/// `image_id` the id the configured image resolves to locally.
+pub fn differences(
+    recorded: Option<&Value>,
+    desired: &Value,
+    container: &Value,
+    image_id: Option<&str>,
+) -> Vec<String> {
+    let mut found = Vec::new();
+    if let Some(want) = desired.get("image").and_then(Value::as_str) {
+        let have = container
+            .pointer("/Config/Image")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let running = container
+            .get("Image")
+            .and_then(Value::as_str)
+            .map(strip_digest_algorithm);
+        if normalize_image(want) != normalize_image(have) {
+            found.push(format!("image: the container runs {}, not {}", have, want));
+        } else if image_id.is_some_and(|id| Some(strip_digest_algorithm(id)) != running) {
+            found.push(format!(
+                "image: {} was updated since the container was created",
+                want
+            ));
+        }
+    }
+    if let Some(recorded) = recorded {
+        if recorded.get("build") != desired.get("build") {
+            found.push("build: the build configuration changed".to_string());
+        }
+        let features = |value: &Value| object(value, "features");
+        let (before, after) = (features(recorded), features(desired));
+        for (id, options) in &after {
+            match before.get(id) {
+                None => found.push(format!("feature added: {}", id)),
+                Some(old) if old != options => {
+                    found.push(format!("feature options changed: {}", id))
+                }
+                Some(_) => {}
+            }
+        }
+        for id in before.keys().filter(|id| !after.contains_key(*id)) {
+            found.push(format!("feature removed: {}", id));
+        }
+        let (before, after) = (as_map(recorded), as_map(desired));
+        let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        for key in keys {
+            if COMPARED_APART.contains(&key.as_str()) || before.get(key) == after.get(key) {
+                continue;
+            }
+            let change = match (before.contains_key(key), after.contains_key(key)) {
+                (false, _) => "added",
+                (_, false) => "removed",
+                _ => "changed",
+            };
+            found.push(format!("{}: {}", key, change));
+        }
+    }
+    let env = container_env(container);
+    let wanted = object(desired, "containerEnv");
+    for (key, value) in &wanted {
+        let Some(value) = value.as_str() else {
+            continue;
+        };
+        // `${localEnv:...}` and the like are only resolved by the CLI
+        if value.contains("${") {
+            continue;
+        }
+        match env.get(key.as_str()) {
+            None => found.push(format!("env {} added", key)),
+            Some(have) if *have != value => found.push(format!("env {} changed", key)),
+            Some(_) => {}
+        }
+    }
+    if let Some(recorded) = recorded {
+        for key in object(recorded, "containerEnv").keys() {
+            if !wanted.contains_key(key) && env.contains_key(key.as_str()) {
+                found.push(format!("env {} removed", key));
+            }
+        }
+    }
+    found
+}
+
+fn as_map(value: &Value) -> Map<String, Value> {
+    value.as_object().cloned().unwrap_or_default()
+}
+
+fn object(value: &Value, key: &str) -> Map<String, Value> {
+    value
+        .get(key)
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The container's `KEY=value` environment.
+fn container_env(container: &Value) -> BTreeMap<&str, &str> {
+    container
+        .pointer("/Config/Env")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.as_str()?.split_once('='))
+        .collect()
+}
+
+/// An image reference as engines report it: `rust:1` is
+/// `docker.io/library/rust:1`, and no tag means `latest`.
+fn normalize_image(reference: &str) -> String {
+    let mut reference = reference.to_string();
+    let name_start = reference.rfind('/').map_or(0, |i| i + 1);
+    if !reference.contains('@') && !reference[name_start..].contains(':') {
+        reference.push_str(":latest");
+    }
+    let first = reference.split('/').next().unwrap_or_default();
+    let has_registry = reference.contains('/')
+        && (first.contains('.') || first.contains(':') || first == "localhost");
+    if has_registry {
+        reference
+    } else if reference.contains('/') {
+        format!("docker.io/{}", reference)
+    } else {
+        format!("docker.io/library/{}", reference)
+    }
+}
+
+/// Docker reports image ids as `sha256:<hex>`, podman as just the hex.
+fn strip_digest_algorithm(id: &str) -> &str {
+    id.strip_prefix("sha256:").unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalizes_image_references() {
+        assert_eq!(normalize_image("rust"), "docker.io/library/rust:latest");
+        assert_eq!(normalize_image("rust:1"), "docker.io/library/rust:1");
+        assert_eq!(normalize_image("me/tool:2"), "docker.io/me/tool:2");
+        assert_eq!(
+            normalize_image("localhost:5000/tool"),
+            "localhost:5000/tool:latest"
+        );
+        assert_eq!(
+            normalize_image("ghcr.io/o/i@sha256:ab"),
+            "ghcr.io/o/i@sha256:ab"
+        );
+    }
+
+    #[test]
+    fn lists_configuration_drift() {
+        let recorded = json!({
+            "image": "rust:1.79",
+            "features": {"ghcr.io/devcontainers/features/go:1": {}},
+            "containerEnv": {"MODE": "dev", "OLD": "1"},
+            "remoteUser": "dev",
+        });
+        let desired = json!({
+            "image": "rust:1.80",
+            "features": {
+                "ghcr.io/devcontainers/features/go:1": {"version": "1.22"},
+                "ghcr.io/devcontainers/features/node:1": {},
+            },
+            "containerEnv": {"MODE": "ci", "HOME_DIR": "${localEnv:HOME}"},
+            "remoteUser": "dev",
+            "postCreateCommand": "make",
+        });
+        let container = json!({
+            "Image": "sha256:aaa",
+            "Config": {
+                "Image": "docker.io/library/rust:1.79",
+                "Env": ["PATH=/usr/bin", "MODE=dev", "OLD=1"],
+            },
+        });
+        assert_eq!(
+            differences(Some(&recorded), &desired, &container, None),
+            [
+                "image: the container runs docker.io/library/rust:1.79, not rust:1.80",
+                "feature options changed: ghcr.io/devcontainers/features/go:1",
+                "feature added: ghcr.io/devcontainers/features/node:1",
+                "postCreateCommand: added",
+                "env MODE changed",
+                "env OLD removed",
+            ]
+        );
+
+        assert!(differences(Some(&recorded), &recorded, &container, Some("aaa")).is_empty());
+        assert_eq!(
+            differences(None, &recorded, &container, Some("sha256:bbb")),
+            ["image: rust:1.79 was updated since the container was created"]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::backend;
+
+const PROXY_PORT: u16 = 8888;
+/// Image the allowlist proxy installs tinyproxy into.
+pub const DEFAULT_PROXY_IMAGE: &str = "docker.io/library/alpine:3";
+
+/// How much of the network a session's container can reach.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// The runtime's default network
+    Open,
+    /// Only the session's own internal network, with no route out
+    Internal,
+    /// The internal network plus HTTP(S) to allowed hosts through a proxy
+    Allowlist,
+    /// No network at all
+    None,
+}
+
+impl From<crate::cli::NetworkMode> for Mode {
+    fn from(mode: crate::cli::NetworkMode) -> Self {
+        match mode {
+            crate::cli::NetworkMode::Open => Mode::Open,
+            crate::cli::NetworkMode::Internal => Mode::Internal,
+            crate::cli::NetworkMode::Allowlist => Mode::Allowlist,
+            crate::cli::NetworkMode::None => Mode::None,
+        }
+    }
+}
+
+/// Label marking a container as the proxy of the session it names.
+const PROXY_LABEL: &str = "forest.egress";
+
+/// Network with no route out of the host that session `label`'s container
+/// and proxy share. Each session has its own, so a restricted session can't
+/// reach other sessions or use their proxies.
+pub fn network_name(label: &str) -> String {
+    format!("forest-internal-{}", label)
+}
+
+/// Name of the proxy container serving an allowlisted session.
+pub fn proxy_name(label: &str) -> String {
+    format!("forest-egress-{}", label)
+}
+
+/// The configuration with the session's container attached to the network
+/// `mode` allows, replacing any network it sets itself.
+pub fn apply(value: &Value, mode: Mode, label: &str) -> Value {
+    let mut value = value.clone();
+    if mode == Mode::Open {
+        return value;
+    }
+    let Some(object) = value.as_object_mut() else {
+        return value;
+    };
+    let mut run_args: Vec<Value> = Vec::new();
+    let mut skip_value = false;
+    for arg in object
+        .get("runArgs")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+    {
+        let text = arg.as_str().unwrap_or_default();
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        if text == "--network" || text == "--net" {
+            skip_value = true;
+            continue;
+        }
+        if text.starts_with("--network=") || text.starts_with("--net=") {
+            continue;
+        }
+        run_args.push(arg);
+    }
+    let network = match mode {
+        Mode::None => "none".to_string(),
+        _ => network_name(label),
+    };
+    run_args.push(Value::String(format!("--network={}", network)));
+    object.insert("runArgs".to_string(), Value::Array(run_args));
+
+    if mode == Mode::Allowlist {
+        let proxy = format!("http://{}:{}", proxy_name(label), PROXY_PORT);
+        let env = object
+            .entry("containerEnv")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if let Some(env) = env.as_object_mut() {
+            for key in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+                env.insert(key.to_string(), Value::String(proxy.clone()));
+            }
+            for key in ["NO_PROXY", "no_proxy"] {
+                env.insert(key.to_string(), Value::String("localhost,127.0.0.1".into()));
+            }
+        }
+    }
+    value
+}
+
+/// tinyproxy's configuration and host filter, denying every host that isn't
+/// in `hosts`. A leading `*.` allows subdomains.
+fn proxy_config(hosts: &[String]) -> (String, String) {
+    let config = format!(
+        "Port {}\nListen 0.0.0.0\nTimeout 600\nFilter \"/etc/tinyproxy/filter\"\n\
+         FilterType ere\nFilterURLs Off\nFilterDefaultDeny Yes\nConnectPort 443\nConnectPort 80\n",
+        PROXY_PORT
+    );
+    let filter = hosts
+        .iter()
+        .map(|host| match host.strip_prefix("*.") {
+            Some(domain) => format!("(^|\\.){}$", domain.replace('.', "\\.")),
+            None => format!("^{}$", host.replace('.', "\\.")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    (config, filter + "\n")
+}
+
+fn runtime(args: &[&str], verbose: bool) -> anyhow::Result<()> {
+    let program = backend::current().program();
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if !verbose {
+        cmd.stdout(Stdio::null());
+    }
+    let status = crate::run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("{} {} failed", program, args[0]);
+    }
+    Ok(())
+}
+
+fn exists(args: &[&str]) -> bool {
+    Command::new(backend::current().program())
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Creates the session's internal network unless it exists already.
+pub fn ensure_network(label: &str, verbose: bool) -> anyhow::Result<()> {
+    let network = network_name(label);
+    if exists(&["network", "inspect", &network]) {
+        return Ok(());
+    }
+    runtime(&["network", "create", "--internal", &network], verbose)
+}
+
+/// (Re)creates the session's proxy: a container on both the backend's default
+/// network and the session's internal network that forwards only to `hosts`.
+pub fn start_proxy(
+    label: &str,
+    hosts: &[String],
+    image: &str,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let name = proxy_name(label);
+    remove_proxy(label, verbose)?;
+    let (config, filter) = proxy_config(hosts);
+    let script = "apk add --no-cache tinyproxy >/dev/null \
+         && printf '%s' \"$PROXY_CONFIG\" > /etc/tinyproxy/tinyproxy.conf \
+         && printf '%s' \"$PROXY_FILTER\" > /etc/tinyproxy/filter \
+         && exec tinyproxy -d -c /etc/tinyproxy/tinyproxy.conf";
+    let backend = backend::current();
+    let network = network_name(label);
+    let networks = if backend.joins_networks_at_run() {
+        format!("{},{}", backend.default_network(), network)
+    } else {
+        backend.default_network().to_string()
+    };
+    let config_env = format!("PROXY_CONFIG={}", config);
+    let filter_env = format!("PROXY_FILTER={}", filter);
+    println!("Starting egress proxy {} for {}", name, hosts.join(", "));
+    runtime(
+        &[
+            "run",
+            "--detach",
+            "--name",
+            &name,
+            "--label",
+            &format!("{}={}", PROXY_LABEL, label),
+            "--network",
+            &networks,
+            "--env",
+            &config_env,
+            "--env",
+            &filter_env,
+            image,
+            "sh",
+            "-c",
+            script,
+        ],
+        verbose,
+    )?;
+    if !backend.joins_networks_at_run() {
+        runtime(&["network", "connect", &network, &name], verbose)?;
+    }
+    Ok(())
+}
+
+/// Starts a stopped proxy again, for a session whose container is reused.
+pub fn resume_proxy(label: &str, verbose: bool) -> anyhow::Result<()> {
+    runtime(&["start", &proxy_name(label)], verbose)
+}
+
+pub fn stop_proxy(label: &str, verbose: bool) -> anyhow::Result<()> {
+    let name = proxy_name(label);
+    if !exists(&["container", "inspect", &name]) {
+        return Ok(());
+    }
+    runtime(&["stop", &name], verbose)
+}
+
+/// Name of a container that holds the session's proxy name without being its
+/// proxy, which `open` would otherwise remove; `None` when there's none.
+pub fn foreign_proxy(label: &str) -> anyhow::Result<Option<String>> {
+    let name = proxy_name(label);
+    if !exists(&["container", "inspect", &name]) {
+        return Ok(None);
+    }
+    let inspected = crate::runtime::inspect(&name)?;
+    Ok((!is_proxy(&inspected, label)).then_some(name))
+}
+
+/// Whether inspected container `inspected` is session `label`'s proxy.
+fn is_proxy(inspected: &Value, label: &str) -> bool {
+    let config = &inspected["Config"];
+    // proxies started before the label are known by the filter they're given
+    config["Labels"][PROXY_LABEL] == label
+        || config["Env"].as_array().is_some_and(|env| {
+            env.iter()
+                .any(|e| e.as_str().is_some_and(|e| e.starts_with("PROXY_FILTER=")))
+        })
+}
+
+pub fn remove_proxy(label: &str, verbose: bool) -> anyhow::Result<()> {
+    let name = proxy_name(label);
+    if !exists(&["container", "inspect", &name]) {
+        return Ok(());
+    }
+    runtime(&["rm", "--force", &name], verbose)
+}
+
+/// Removes the session's proxy and internal network, once its container is
+/// gone.
+pub fn remove(label: &str, verbose: bool) -> anyhow::Result<()> {
+    remove_proxy(label, verbose)?;
+    let network = network_name(label);
+    if !exists(&["network", "inspect", &network]) {
+        return Ok(());
+    }
+    runtime(&["network", "rm", &network], verbose)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_network_run_args() {
+        let value: Value = serde_json::from_str(
+            r#"{"image": "x", "runArgs": ["--network", "host", "--cpus=2", "--net=bridge"]}"#,
+        )
+        .unwrap();
+        let none = apply(&value, Mode::None, "s");
+        assert_eq!(
+            none["runArgs"],
+            serde_json::json!(["--cpus=2", "--network=none"])
+        );
+        assert!(apply(&value, Mode::Open, "s") == value);
+
+        let allowlist = apply(&value, Mode::Allowlist, "s");
+        assert_eq!(allowlist["runArgs"][1], "--network=forest-internal-s");
+        let other = apply(&value, Mode::Internal, "t");
+        assert_eq!(other["runArgs"][1], "--network=forest-internal-t");
+        assert_eq!(
+            allowlist["containerEnv"]["HTTPS_PROXY"],
+            "http://forest-egress-s:8888"
+        );
+    }
+
+    #[test]
+    fn recognizes_session_proxies() {
+        let labelled = serde_json::json!({"Config": {"Labels": {"forest.egress": "s"}}});
+        assert!(is_proxy(&labelled, "s"));
+        assert!(!is_proxy(&labelled, "t"));
+        let legacy = serde_json::json!({"Config": {"Labels": null, "Env": ["PROXY_FILTER=^x$"]}});
+        assert!(is_proxy(&legacy, "s"));
+        let other = serde_json::json!({"Config": {"Labels": {}, "Env": ["PATH=/bin"]}});
+        assert!(!is_proxy(&other, "s"));
+    }
+
+    #[test]
+    fn filters_allowed_hosts() {
+        let (config, filter) = proxy_config(&["crates.io".to_string(), "*.github.com".to_string()]);
+        assert!(config.contains("FilterDefaultDeny Yes"));
+        assert_eq!(filter, "^crates\\.io$\n(^|\\.)github\\.com$\n");
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Variable `KEY=VALUE` of `-e`. A bare `KEY` takes the host's value, as
+/// `docker run -e` does.
+pub fn parse(spec: &str) -> anyhow::Result<(String, String)> {
+    let (key, value) = match spec.split_once('=') {
+        Some((key, value)) => (key, value.to_string()),
+        None => {
+            let Ok(value) = std::env::var(spec) else {
+                anyhow::bail!("{} isn't KEY=VALUE, nor set on the host", spec);
+            };
+            (spec, value)
+        }
+    };
+    check_key(key)?;
+    Ok((key.to_string(), value))
+}
+
+/// Fails unless `key` is an environment variable name.
+pub fn check_key(key: &str) -> anyhow::Result<()> {
+    let valid = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        anyhow::bail!("{:?} isn't an environment variable name", key);
+    }
+    Ok(())
+}
+
+/// Variables of an env file: `KEY=VALUE` lines, optionally after `export`,
+/// with blank lines and `#` comments skipped and a value's surrounding quotes
+/// dropped.
+pub fn read_file(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("can't read {}: {}", path.display(), e))?;
+    let mut vars = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let Some((key, value)) = line.split_once('=') else {
+            anyhow::bail!("{}:{}: expected KEY=VALUE", path.display(), number + 1);
+        };
+        let key = key.trim();
+        check_key(key).map_err(|e| anyhow::anyhow!("{}:{}: {}", path.display(), number + 1, e))?;
+        let value = value.trim();
+        let unquoted = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+            .unwrap_or(value);
+        vars.push((key.to_string(), unquoted.to_string()));
+    }
+    Ok(vars)
+}
+
+/// Variables of `open`'s `--env-file` files and then its `-e` flags, later
+/// ones winning.
+pub fn from_flags(specs: &[String], files: &[std::path::PathBuf]) -> anyhow::Result<Vec<String>> {
+    let mut vars = BTreeMap::new();
+    for file in files {
+        vars.extend(read_file(file)?);
+    }
+    for spec in specs {
+        let (key, value) = parse(spec)?;
+        vars.insert(key, value);
+    }
+    Ok(entries(&vars))
+}
+
+/// `KEY=VALUE` entries of `vars`.
+pub fn entries(vars: &BTreeMap<String, String>) -> Vec<String> {
+    vars.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect()
+}
+
+/// `--remote-env` arguments of devcontainer `up` and `exec` for `entries`.
+pub fn remote_env_args<'a>(entries: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    entries
+        .into_iter()
+        .flat_map(|entry| ["--remote-env".to_string(), entry.clone()])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reads_variables_from_flags_and_files() {
+        assert_eq!(
+            parse("URL=http://x?a=b").unwrap(),
+            ("URL".to_string(), "http://x?a=b".to_string())
+        );
+        assert!(parse("1X=y").is_err());
+        assert!(parse("=y").is_err());
+        std::env::set_var("FOREST_TEST_ENV", "from host");
+        assert_eq!(parse("FOREST_TEST_ENV").unwrap().1, "from host");
+        assert!(parse("FOREST_TEST_UNSET_ENV").is_err());
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join(".env");
+        fs::write(
+            &file,
+            "# keys\nexport API_KEY=\"s3cret\"\n\nHTTPS_PROXY='http://proxy:3128'\nMODE = dev\n",
+        )
+        .unwrap();
+        let vars = from_flags(&["MODE=ci".to_string()], std::slice::from_ref(&file)).unwrap();
+        assert_eq!(
+            vars,
+            ["API_KEY=s3cret", "HTTPS_PROXY=http://proxy:3128", "MODE=ci"]
+        );
+        assert_eq!(
+            remote_env_args(&vars[..1]),
+            ["--remote-env", "API_KEY=s3cret"]
+        );
+        fs::write(&file, "API_KEY\n").unwrap();
+        assert!(read_file(&file).unwrap_err().to_string().contains(":1:"));
+    }
+}
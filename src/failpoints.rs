@@ -0,0 +1,86 @@
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus};
+
+/// Environment variable naming the commands to fail, e.g.
+/// `FOREST_TEST_FAILPOINTS=build,up=timeout,exec=die`. A point is a
+/// `devcontainer` subcommand, or the program name for anything else.
+pub const ENV: &str = "FOREST_TEST_FAILPOINTS";
+
+/// How a command at a failpoint fails instead of running.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Failure {
+    /// Exits with status 1, the default
+    Fail,
+    /// Never gets a result, reported as an I/O timeout
+    Timeout,
+    /// Killed by SIGKILL partway through
+    Die,
+}
+
+/// Parses the failpoint list, skipping entries it doesn't understand.
+fn parse(spec: &str) -> Vec<(String, Failure)> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let (point, mode) = entry.split_once('=').unwrap_or((entry, "fail"));
+            let failure = match mode {
+                "fail" => Failure::Fail,
+                "timeout" => Failure::Timeout,
+                "die" => Failure::Die,
+                _ => return None,
+            };
+            Some((point.to_string(), failure))
+        })
+        .collect()
+}
+
+/// The failpoint `cmd` would hit.
+fn point(cmd: &Command) -> String {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    if program == "devcontainer" {
+        if let Some(sub) = cmd.get_args().next() {
+            return sub.to_string_lossy().to_string();
+        }
+    }
+    program
+}
+
+/// What `cmd` should do instead of running, if a failpoint is set for it:
+/// an exit status to report, or an error.
+pub fn check(cmd: &Command) -> Option<std::io::Result<ExitStatus>> {
+    let spec = std::env::var(ENV).ok()?;
+    let point = point(cmd);
+    let (_, failure) = parse(&spec).into_iter().find(|(p, _)| *p == point)?;
+    crate::style::note(format!("failpoint {}: simulating {:?}", point, failure));
+    Some(match failure {
+        Failure::Fail => Ok(ExitStatus::from_raw(1 << 8)),
+        Failure::Timeout => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("{} timed out (failpoint)", point),
+        )),
+        // SIGKILL
+        Failure::Die => Ok(ExitStatus::from_raw(9)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_failpoints() {
+        assert_eq!(
+            parse("build, up=timeout,exec=die,git=explode,,"),
+            [
+                ("build".to_string(), Failure::Fail),
+                ("up".to_string(), Failure::Timeout),
+                ("exec".to_string(), Failure::Die),
+            ]
+        );
+        let mut cmd = Command::new("devcontainer");
+        cmd.args(["up", "--workspace-folder", "/w"]);
+        assert_eq!(point(&cmd), "up");
+        assert_eq!(point(Command::new("podman").arg("ps")), "podman");
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+/// An experimental capability that stays off unless enabled in the `[features]`
+/// table of forest.toml or with `--enable-feature`.
+pub struct Feature {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const FEATURES: &[Feature] = &[
+    Feature {
+        name: "jj",
+        description:
+            "`forest open --jj <change>` sessions on jj changes; relies on jj's template language",
+    },
+    Feature {
+        name: "checkpoint",
+        description: "`forest checkpoint` and `forest resume` of session processes with CRIU; \
+                      needs a rootful runtime",
+    },
+];
+
+fn known(name: &str) -> bool {
+    FEATURES.iter().any(|f| f.name == name)
+}
+
+/// Merges `--enable-feature` flags into the configured `[features]` table.
+/// Unknown names on the command line are errors; in the config file they only
+/// warn, so a config shared across forest versions keeps working.
+pub fn resolve(
+    mut features: BTreeMap<String, bool>,
+    cli: &[String],
+) -> anyhow::Result<BTreeMap<String, bool>> {
+    for name in features.keys().filter(|name| !known(name)) {
+        crate::style::warn(format!("unknown feature {} in config, ignoring it", name));
+    }
+    for name in cli {
+        if !known(name) {
+            anyhow::bail!("unknown feature {}; see `forest features`", name);
+        }
+        features.insert(name.clone(), true);
+    }
+    Ok(features)
+}
+
+/// Fails unless `name` is enabled in `features`.
+pub fn require(features: &BTreeMap<String, bool>, name: &str) -> anyhow::Result<()> {
+    if features.get(name).copied().unwrap_or(false) {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "{} is experimental; enable it with --enable-feature {} or `{} = true` under \
+         [features] in forest.toml",
+        name,
+        name,
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_enables_known_features() {
+        let configured = BTreeMap::from([("jj".to_string(), false)]);
+        let features = resolve(configured, &["jj".to_string()]).unwrap();
+        assert!(require(&features, "jj").is_ok());
+
+        assert!(resolve(BTreeMap::new(), &["warp-drive".to_string()]).is_err());
+        let err = require(&BTreeMap::new(), "jj").unwrap_err();
+        assert!(err.to_string().contains("--enable-feature jj"));
+    }
+
+    #[test]
+    fn config_tolerates_unknown_features() {
+        let configured = BTreeMap::from([("from-the-future".to_string(), true)]);
+        assert!(resolve(configured, &[]).is_ok());
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Name gh runs the extension under, `gh forest ...`.
+pub const NAME: &str = "forest";
+
+/// Directory holding the extension's executable, which gh links to. gh wants
+/// both named `gh-<name>`.
+pub fn dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(format!("gh-{}", NAME))
+}
+
+/// Writes the extension's executable, which hands its arguments to
+/// `forest gh-extension run` of the binary at `forest`.
+pub fn write(data_dir: &Path, forest: &Path) -> anyhow::Result<PathBuf> {
+    let dir = dir(data_dir);
+    fs::create_dir_all(&dir)?;
+    let script = dir.join(format!("gh-{}", NAME));
+    fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n# written by `forest gh-extension install`\nexec {} gh-extension run -- \"$@\"\n",
+            crate::shell::quote(&forest.to_string_lossy())
+        ),
+    )?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    Ok(dir)
+}
+
+/// `owner/repo` of gh's repository override `GH_REPO`, `[HOST/]OWNER/REPO`.
+/// forest only opens github.com repositories, so other hosts are an error.
+pub fn repository(gh_repo: &str, gh_host: Option<&str>) -> anyhow::Result<(String, String)> {
+    let parts: Vec<&str> = gh_repo.trim().split('/').collect();
+    let (host, owner, repo) = match parts[..] {
+        [owner, repo] => (gh_host.unwrap_or("github.com"), owner, repo),
+        [host, owner, repo] => (host, owner, repo),
+        _ => anyhow::bail!("GH_REPO {:?} isn't [HOST/]OWNER/REPO", gh_repo),
+    };
+    if host != "github.com" {
+        anyhow::bail!("forest only opens github.com repositories, not {}", host);
+    }
+    if owner.is_empty() || repo.is_empty() {
+        anyhow::bail!("GH_REPO {:?} isn't [HOST/]OWNER/REPO", gh_repo);
+    }
+    Ok((owner.to_string(), repo.to_string()))
+}
+
+/// Pull request number of `#123` or `123`.
+fn number(arg: &str) -> Option<u64> {
+    arg.strip_prefix('#').unwrap_or(arg).parse().ok()
+}
+
+/// The forest command line for `gh forest <args>`. With a repository from
+/// gh's context, pull request numbers and branches become its GitHub URLs,
+/// which forest checks out wherever the repository is: `open 12` and
+/// `review 12` the pull request and `open feat` the branch. Everything else, and everything
+/// without a repository, goes to forest as it is.
+pub fn forest_args(args: &[String], repository: Option<&(String, String)>) -> Vec<String> {
+    let Some((owner, repo)) = repository else {
+        return args.to_vec();
+    };
+    let url = |path: String| format!("https://github.com/{}/{}/{}", owner, repo, path);
+    let (Some(command), Some(target)) = (args.first(), args.get(1)) else {
+        return args.to_vec();
+    };
+    if target.starts_with('-') || crate::github::parse(target).is_some() {
+        return args.to_vec();
+    }
+    let (command, target) = match (command.as_str(), number(target)) {
+        ("open" | "review", Some(pr)) => (command.as_str(), url(format!("pull/{}", pr))),
+        ("open", None) => ("open", url(format!("tree/{}", target))),
+        _ => return args.to_vec(),
+    };
+    [command.to_string(), target]
+        .into_iter()
+        .chain(args[2..].iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn maps_gh_context_to_forest_commands() {
+        let widget = repository("acme/widget", None).unwrap();
+        assert_eq!(widget, ("acme".to_string(), "widget".to_string()));
+        assert_eq!(repository("github.com/acme/widget", None).unwrap(), widget);
+        assert!(repository("acme/widget", Some("ghe.example.com")).is_err());
+        assert!(repository("widget", None).is_err());
+
+        let mapped = |line: &str| forest_args(&args(line), Some(&widget)).join(" ");
+        assert_eq!(
+            mapped("open #12 --detach"),
+            "open https://github.com/acme/widget/pull/12 --detach"
+        );
+        assert_eq!(
+            mapped("review 12 --sandbox"),
+            "review https://github.com/acme/widget/pull/12 --sandbox"
+        );
+        assert_eq!(
+            mapped("open feat/login"),
+            "open https://github.com/acme/widget/tree/feat/login"
+        );
+        assert_eq!(mapped("open --jj @"), "open --jj @");
+        assert_eq!(mapped("kill feat/login"), "kill feat/login");
+        assert_eq!(mapped("ls"), "ls");
+        assert_eq!(forest_args(&args("open 12"), None).join(" "), "open 12");
+    }
+
+    #[test]
+    fn writes_an_executable_extension() {
+        let dir = tempdir().unwrap();
+        let ext = write(dir.path(), Path::new("/opt/forest bin/forest")).unwrap();
+        assert!(ext.ends_with("gh-forest"));
+        let script = ext.join("gh-forest");
+        let content = fs::read_to_string(&script).unwrap();
+        assert!(content.contains("exec '/opt/forest bin/forest' gh-extension run -- \"$@\""));
+        let mode = fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111);
+    }
+}
@@ -0,0 +1,1604 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::config::{Config, MissingRepo, RemoteProtocol};
+use crate::github::{self, Ref};
+use crate::{
+    compat, confirm, devcontainer, locks, review, run_command_verbose, sessions, shell, style,
+    timesheet,
+};
+
+/// The `[remotes]` table of forest.toml, for fork workflows where `upstream`
+/// is the project and `origin` the user's fork.
+#[derive(Deserialize, Default)]
+pub struct Remotes {
+    /// Remote new session branches start from, `sync` rebases onto and pull
+    /// requests are opened against
+    pub base: Option<String>,
+    /// Remote session branches are pushed to
+    pub push: Option<String>,
+}
+
+impl Remotes {
+    pub fn base(&self) -> &str {
+        self.base.as_deref().unwrap_or("origin")
+    }
+
+    pub fn push(&self) -> &str {
+        self.push.as_deref().unwrap_or("origin")
+    }
+
+    /// Whether pull requests go from a fork to another repository.
+    pub fn cross_repository(&self) -> bool {
+        self.base() != self.push()
+    }
+}
+
+/// Repository root for `open`, initialising a repository when run outside one.
+pub fn find_or_init_repo(config: &Config, verbose: bool) -> anyhow::Result<PathBuf> {
+    // Are we inside a git repository?
+    if verbose {
+        println!("Checking git repository root");
+    }
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(o) if o.status.success() => {
+            let path = str::from_utf8(&o.stdout)?.trim();
+            Ok(PathBuf::from(path))
+        }
+        _ => init_missing_repo(config, verbose),
+    }
+}
+
+/// Creates `branch` unless it exists, starting from `from`, else
+/// `default_base_branch`, else the base remote's default branch when one is
+/// configured, else HEAD; `from` and `default_base_branch` are fetched first
+/// unless `fetch` is off. Then makes sure there's an `origin` remote.
+pub fn ensure_git_setup(
+    branch: &str,
+    repo_root: &Path,
+    from: Option<&str>,
+    fetch: bool,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    // Check if branch exists
+    let branch_exists = Command::new("git")
+        .args(["show-ref", "--verify", &format!("refs/heads/{}", branch)])
+        .current_dir(repo_root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if branch_exists && is_default_branch(repo_root, config.remotes.base(), branch) {
+        style::warn(format!(
+            "{} is the default branch, and the session commits to it directly; \
+             `forest main` opens it read-only",
+            branch
+        ));
+    }
+
+    if let Some(from) = from.filter(|_| branch_exists) {
+        style::warn(format!(
+            "branch {} exists already, so --from {} changes nothing",
+            branch, from
+        ));
+    }
+
+    if !branch_exists {
+        // with a base remote configured, new work starts from its default
+        // branch rather than whatever is checked out
+        let start = match (
+            from.or(config.default_base_branch.as_deref()),
+            &config.remotes.base,
+        ) {
+            (Some(base), _) => Some(from_start(
+                repo_root,
+                config.remotes.base(),
+                base,
+                fetch,
+                verbose,
+            )?),
+            (None, Some(remote)) => Some(base_start(repo_root, remote, verbose)?),
+            (None, None) => None,
+        };
+        let _lock =
+            locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+        if verbose {
+            println!("Creating git branch {}", branch);
+        }
+        let mut cmd = Command::new("git");
+        cmd.args(["branch", branch])
+            .args(start)
+            .current_dir(repo_root);
+        let status = run_command_verbose(&mut cmd, verbose)?;
+        if !status.success() {
+            anyhow::bail!("git branch failed");
+        }
+    }
+
+    // Check remote 'origin'
+    let remote_exists = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .current_dir(repo_root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+
+    if !remote_exists {
+        if verbose {
+            println!("Creating origin remote");
+        }
+        if let Some(org) = &config.githuborg {
+            let repo_name = repo_root.file_name().unwrap_or_default().to_string_lossy();
+            let repo_spec = format!("{}/{}", org, repo_name);
+            let mut cmd = Command::new("gh");
+            cmd.args([
+                "repo",
+                "create",
+                &repo_spec,
+                "--source",
+                repo_root.to_str().unwrap(),
+                "--remote",
+                "origin",
+                "--push",
+            ]);
+            let status = run_command_verbose(&mut cmd, verbose)?;
+            if !status.success() {
+                anyhow::bail!("gh repo create failed");
+            }
+            // gh picks the protocol from its own settings
+            if let Some(protocol) = config.remote_protocol {
+                set_remote_protocol(repo_root, protocol, verbose)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Points `origin` at its GitHub repository over `protocol`.
+fn set_remote_protocol(
+    repo_root: &Path,
+    protocol: RemoteProtocol,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .current_dir(repo_root)
+        .output()?;
+    let url = String::from_utf8_lossy(&output.stdout);
+    let Some(url) = github::with_protocol(&url, protocol) else {
+        return Ok(());
+    };
+    let mut cmd = Command::new("git");
+    cmd.args(["remote", "set-url", "origin", &url])
+        .current_dir(repo_root);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("git remote set-url failed");
+    }
+    Ok(())
+}
+
+/// Makes git in the session's worktree reach GitHub over the configured
+/// protocol whatever its remotes say. Without one, remotes are rewritten to
+/// HTTPS when the container has no `ssh` to use them with. Returns the
+/// protocol the worktree was set up for, `None` when it was left alone.
+pub fn configure_remote_protocol(
+    worktree_path: &Path,
+    target: &[String],
+    protocol: Option<RemoteProtocol>,
+    verbose: bool,
+) -> anyhow::Result<Option<RemoteProtocol>> {
+    let container = |args: &[&str]| {
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(worktree_path)
+            .args(target)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        run_command_verbose(&mut cmd, verbose)
+    };
+    let protocol = match protocol {
+        Some(protocol) => protocol,
+        None if container(&["sh", "-c", "command -v ssh"])?.success() => return Ok(None),
+        None => {
+            style::note("the session's container has no ssh; reaching GitHub over HTTPS instead");
+            RemoteProtocol::Https
+        }
+    };
+    enable_worktree_config(worktree_path, target, verbose)?;
+    for (key, value) in github::rewrite_config(protocol) {
+        if verbose {
+            println!("Setting {} = {} in the session worktree", key, value);
+        }
+        if !container(&[
+            "git",
+            "-C",
+            "/code",
+            "config",
+            "--worktree",
+            "--add",
+            &key,
+            &value,
+        ])?
+        .success()
+        {
+            anyhow::bail!("git config {} failed", key);
+        }
+    }
+    Ok(Some(protocol))
+}
+
+/// The protocol an existing session worktree reaches GitHub with: the
+/// configured one, else HTTPS when `configure_remote_protocol` fell back to
+/// it for a container without `ssh`.
+pub fn worktree_protocol(
+    configured: Option<RemoteProtocol>,
+    repo_root: &Path,
+    worktree_path: &Path,
+) -> Option<RemoteProtocol> {
+    configured.or_else(|| {
+        let admin = worktree_admin_dir(repo_root, worktree_path)?;
+        let (helper, _) = github::rewrite_config(RemoteProtocol::Https).pop()?;
+        Command::new("git")
+            .arg("config")
+            .arg("--file")
+            .arg(admin.join("config.worktree"))
+            .args(["--get", &helper])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok()?
+            .success()
+            .then_some(RemoteProtocol::Https)
+    })
+}
+
+/// Turns on `extensions.worktreeConfig` in the repository, which git needs to
+/// read a worktree's own `config.worktree`. It's a lasting change to the
+/// repository, so it's only made when a session worktree gets settings of its
+/// own, and left alone when it's already on.
+fn enable_worktree_config(
+    worktree_path: &Path,
+    target: &[String],
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let git_config = |args: &[&str]| {
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(worktree_path)
+            .args(target)
+            .args(["git", "-C", "/repo", "config"])
+            .args(args)
+            .stderr(Stdio::null());
+        cmd
+    };
+    let current = git_config(&["--get", "--type=bool", "extensions.worktreeConfig"]).output()?;
+    if String::from_utf8_lossy(&current.stdout).trim() == "true" {
+        return Ok(());
+    }
+    style::note("turning on extensions.worktreeConfig in the repository for per-session settings");
+    let mut cmd = git_config(&["extensions.worktreeConfig", "true"]);
+    if !run_command_verbose(cmd.stdout(Stdio::null()), verbose)?.success() {
+        anyhow::bail!("git config extensions.worktreeConfig failed");
+    }
+    Ok(())
+}
+
+/// The `GH_TOKEN` of a session's shell when it reaches GitHub over HTTPS,
+/// `protocol` being what `configure_remote_protocol` or `worktree_protocol`
+/// said: the host's `GH_TOKEN` or `GITHUB_TOKEN`, else the GitHub CLI's.
+pub fn github_token(protocol: Option<RemoteProtocol>) -> Option<String> {
+    if protocol != Some(RemoteProtocol::Https) {
+        return None;
+    }
+    let token = std::env::var("GH_TOKEN")
+        .or_else(|_| std::env::var("GITHUB_TOKEN"))
+        .ok()
+        .or_else(|| {
+            let output = Command::new("gh")
+                .args(["auth", "token"])
+                .stderr(Stdio::null())
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .filter(|token| !token.is_empty());
+    if token.is_none() {
+        style::warn("no GitHub token for the session: set GH_TOKEN or log in with `gh auth login`");
+    }
+    token
+}
+
+pub fn init_missing_repo(config: &Config, verbose: bool) -> anyhow::Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    let init = match config.missing_repo.unwrap_or_default() {
+        MissingRepo::Init => true,
+        MissingRepo::Fail => false,
+        MissingRepo::Prompt => {
+            crate::interactive()
+                && confirm(&format!(
+                    "{} is not a git repository. Initialise one here?",
+                    cwd.display()
+                ))?
+        }
+    };
+    if !init {
+        anyhow::bail!(
+            "{} is not inside a git repository.\n\
+             forest sessions are git branches, so run it from a repository checkout, \
+             run `git init` first, or set `missing_repo = \"init\"` in forest.toml",
+            cwd.display()
+        );
+    }
+
+    if verbose {
+        println!("Initialising git repository in {}", cwd.display());
+    }
+    let mut cmd = Command::new("git");
+    cmd.arg("init");
+    let status = run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("git init failed");
+    }
+    // branches and `gh repo create --push` both need a commit to point at
+    let mut cmd = Command::new("git");
+    cmd.args(["commit", "--allow-empty", "-m", "Initial commit"]);
+    let status = run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("git commit failed");
+    }
+    Ok(cwd)
+}
+
+/// Points a session branch at a jj change and returns the session name, taken
+/// from the change's bookmark unless `name` is given. jj imports the branch as a
+/// bookmark, so the session shows up in `jj log` like any other.
+pub fn open_jj_change(
+    revision: &str,
+    name: Option<String>,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<String> {
+    let (repo_root, _) = current_repo("open --jj")?;
+    if !compat::detect(&repo_root).jj {
+        anyhow::bail!(
+            "{} is not a colocated jj repository; run `jj git init --colocate` first",
+            repo_root.display()
+        );
+    }
+    let change = compat::resolve_jj_change(&repo_root, revision)?;
+    let name = name.unwrap_or_else(|| change.session());
+    if verbose {
+        println!("jj change {} is commit {}", change.change_id, change.commit);
+    }
+
+    let branch_exists = Command::new("git")
+        .args(["show-ref", "--verify", &format!("refs/heads/{}", name)])
+        .current_dir(&repo_root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !branch_exists {
+        let _lock =
+            locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+        let mut cmd = Command::new("git");
+        cmd.args(["branch", &name, &change.commit])
+            .current_dir(&repo_root);
+        let status = run_command_verbose(&mut cmd, verbose)?;
+        if !status.success() {
+            anyhow::bail!("git branch failed");
+        }
+    }
+    Ok(name)
+}
+
+/// Moves into the checkout of the repository GitHub URL `target` names and
+/// sets up the branch for what it points at, returning the session name: the
+/// branch of a `/tree/` URL, `review/<n>` for a pull request, `issue/<n>` off
+/// the default branch for an issue, `commit/<sha>` for a commit and `dev` for
+/// the repository itself.
+pub fn open_github_url(
+    target: &github::Target,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<String> {
+    let repo_root = github_checkout(target, config, verbose)?;
+    std::env::set_current_dir(&repo_root)?;
+    let git = |args: &[&str]| -> anyhow::Result<()> {
+        let mut cmd = Command::new("git");
+        cmd.args(args).current_dir(&repo_root);
+        if !run_command_verbose(&mut cmd, verbose)?.success() {
+            anyhow::bail!("git {} failed", args.join(" "));
+        }
+        Ok(())
+    };
+    let (name, start) = match &target.reference {
+        Ref::Repo => return Ok("dev".to_string()),
+        Ref::Pull(pr) => return review::fetch(&repo_root, *pr, verbose),
+        Ref::Tree(path) => {
+            git(&["fetch", "--quiet", "origin"])?;
+            let Some(branch) = remote_branch(&repo_root, path) else {
+                anyhow::bail!("origin of {} has no branch {}", repo_root.display(), path);
+            };
+            let start = format!("origin/{}", branch);
+            (branch, start)
+        }
+        Ref::Issue(issue) => (
+            format!("issue/{}", issue),
+            default_branch(&repo_root, "origin")?,
+        ),
+        Ref::Commit(sha) => {
+            let known = Command::new("git")
+                .args(["cat-file", "-e", &format!("{}^{{commit}}", sha)])
+                .current_dir(&repo_root)
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success());
+            if !known {
+                git(&["fetch", "--quiet", "origin", sha])?;
+            }
+            (format!("commit/{}", &sha[..7]), sha.clone())
+        }
+    };
+    if !is_branch(&repo_root, &name) {
+        let _lock =
+            locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+        git(&["branch", &name, &start])?;
+    }
+    Ok(name)
+}
+
+/// A clone of `target`: the current repository or `./<repo>` when their
+/// `origin` is it, else a fresh clone into `./<repo>`.
+fn github_checkout(
+    target: &github::Target,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<PathBuf> {
+    let origin = |repo_root: &Path| {
+        Command::new("git")
+            .args(["remote", "get-url", "origin"])
+            .current_dir(repo_root)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .is_some_and(|o| target.is_remote(&String::from_utf8_lossy(&o.stdout)))
+    };
+    if let Some(repo_root) = repo_toplevel().filter(|root| origin(root)) {
+        return Ok(repo_root);
+    }
+    let dir = std::env::current_dir()?.join(&target.repo);
+    if dir.exists() {
+        if !origin(&dir) {
+            anyhow::bail!(
+                "{} exists but isn't a clone of {}/{}",
+                dir.display(),
+                target.owner,
+                target.repo
+            );
+        }
+        return Ok(dir);
+    }
+    let url = target.clone_url(config.remote_protocol.unwrap_or(RemoteProtocol::Https));
+    println!("Cloning {} into {}", url, dir.display());
+    let mut cmd = Command::new("git");
+    cmd.arg("clone").arg(&url).arg(&dir);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("git clone {} failed", url);
+    }
+    Ok(dir)
+}
+
+/// The branch of `origin` a `/tree/` path starts with. Branch names may hold
+/// slashes too, so the longest match wins.
+fn remote_branch(repo_root: &Path, path: &str) -> Option<String> {
+    let segments: Vec<&str> = path.split('/').collect();
+    (1..=segments.len()).rev().find_map(|count| {
+        let branch = segments[..count].join("/");
+        Command::new("git")
+            .args(["show-ref", "--verify", "--quiet"])
+            .arg(format!("refs/remotes/origin/{}", branch))
+            .current_dir(repo_root)
+            .status()
+            .is_ok_and(|s| s.success())
+            .then_some(branch)
+    })
+}
+
+/// Copies selected repo-local git config into the session worktree's own
+/// `config.worktree`, rewriting host paths to the container's `/repo` mount so
+/// settings like `core.hooksPath` keep working inside the container. Identity
+/// and signing settings the user's `includeIf` sections pick for the
+/// repository and session `branch` are copied too, since the conditions can't
+/// match `/repo` and the container's branch the way they do on the host.
+pub fn propagate_git_config(
+    repo_root: &Path,
+    worktree_path: &Path,
+    target: &[String],
+    keys: &[String],
+    branch: &str,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let host_root = repo_root.to_string_lossy();
+    let mut values = Vec::new();
+    for key in keys {
+        let output = Command::new("git")
+            .args(["config", "--local", "--get", key])
+            .current_dir(repo_root)
+            .stderr(Stdio::null())
+            .output()?;
+        if output.status.success() {
+            let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            values.push((key.clone(), value.replace(&*host_root, "/repo")));
+        }
+    }
+    values.extend(conditional_identity(repo_root, branch));
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    let container_git = |args: &[&str]| -> anyhow::Result<()> {
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(worktree_path)
+            .args(target)
+            .arg("git")
+            .args(args);
+        let status = run_command_verbose(&mut cmd, verbose)?;
+        if !status.success() {
+            anyhow::bail!("git {} failed", shell::join(args));
+        }
+        Ok(())
+    };
+
+    enable_worktree_config(worktree_path, target, verbose)?;
+    for (key, value) in &values {
+        if verbose {
+            println!("Setting {} = {} in the session worktree", key, value);
+        }
+        container_git(&["-C", "/code", "config", "--worktree", key, value])?;
+    }
+
+    let sparse = values
+        .iter()
+        .any(|(key, value)| key.eq_ignore_ascii_case("core.sparseCheckout") && value == "true");
+    let patterns = repo_root.join(".git").join("info").join("sparse-checkout");
+    if sparse && patterns.exists() {
+        // the worktree's admin dir is recorded as a container path in /code/.git
+        let link = fs::read_to_string(worktree_path.join(".git"))?;
+        let admin = link
+            .trim()
+            .strip_prefix("gitdir: /repo/")
+            .map(|rel| repo_root.join(rel))
+            .ok_or_else(|| anyhow::anyhow!("unexpected worktree link {}", link.trim()))?;
+        fs::create_dir_all(admin.join("info"))?;
+        fs::copy(&patterns, admin.join("info").join("sparse-checkout"))?;
+        container_git(&["-C", "/code", "sparse-checkout", "reapply"])?;
+    }
+    Ok(())
+}
+
+/// Settings taken from the files `includeIf` sections include.
+const IDENTITY_KEYS: [&str; 6] = [
+    "user.name",
+    "user.email",
+    "user.signingkey",
+    "commit.gpgsign",
+    "tag.gpgsign",
+    "gpg.format",
+];
+
+/// Identity and signing settings from the files the user's `includeIf`
+/// sections include for repository `repo_root` with `branch` checked out.
+fn conditional_identity(repo_root: &Path, branch: &str) -> Vec<(String, String)> {
+    let Ok(output) = Command::new("git")
+        .args([
+            "config",
+            "--show-origin",
+            "--get-regexp",
+            r"^includeif\..*\.path$",
+        ])
+        .current_dir(repo_root)
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return Vec::new();
+    };
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    let git_dir =
+        fs::canonicalize(repo_root.join(".git")).unwrap_or_else(|_| repo_root.join(".git"));
+    let mut values: Vec<(String, String)> = Vec::new();
+    for include in parse_includes(&String::from_utf8_lossy(&output.stdout), &home) {
+        if !include_applies(&include.condition, &git_dir, branch, &home) {
+            continue;
+        }
+        let Ok(output) = Command::new("git")
+            .args(["config", "--file"])
+            .arg(&include.path)
+            .arg("--list")
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if IDENTITY_KEYS.contains(&key) {
+                // later includes win, as they do in git
+                values.retain(|(k, _)| k != key);
+                values.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+    values
+}
+
+/// An `includeIf.<condition>.path` entry.
+#[derive(Debug, PartialEq)]
+struct Include {
+    condition: String,
+    path: PathBuf,
+}
+
+/// The entries `git config --show-origin --get-regexp` lists, with paths
+/// resolved the way git does: `~/` from `home`, relative ones from the
+/// including file's directory.
+fn parse_includes(listing: &str, home: &Path) -> Vec<Include> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let (origin, entry) = line.split_once('\t')?;
+            let (key, path) = entry.split_once(' ')?;
+            let condition = key.strip_prefix("includeif.")?.strip_suffix(".path")?;
+            let path = match path.strip_prefix("~/") {
+                Some(rest) => home.join(rest),
+                None => Path::new(origin.strip_prefix("file:")?)
+                    .parent()?
+                    .join(path),
+            };
+            Some(Include {
+                condition: condition.to_string(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Whether an `includeIf` condition holds for a repository whose git
+/// directory is `git_dir` with `branch` checked out. Conditions forest can't
+/// judge, like `hasconfig:`, don't hold.
+fn include_applies(condition: &str, git_dir: &Path, branch: &str, home: &Path) -> bool {
+    let Some((kind, pattern)) = condition.split_once(':') else {
+        return false;
+    };
+    match kind {
+        "gitdir" | "gitdir/i" => {
+            let mut pattern = match pattern.strip_prefix("~/") {
+                Some(rest) => format!("{}/{}", home.display(), rest),
+                None if pattern.starts_with('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            };
+            if pattern.ends_with('/') {
+                pattern.push_str("**");
+            }
+            let git_dir = git_dir.to_string_lossy();
+            if kind == "gitdir/i" {
+                glob_match(
+                    pattern.to_lowercase().as_bytes(),
+                    git_dir.to_lowercase().as_bytes(),
+                )
+            } else {
+                glob_match(pattern.as_bytes(), git_dir.as_bytes())
+            }
+        }
+        "onbranch" => {
+            let mut pattern = pattern.to_string();
+            if pattern.ends_with('/') {
+                pattern.push_str("**");
+            }
+            glob_match(pattern.as_bytes(), branch.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+/// Wildcard matching as git's config conditions do it: `*` and `?` stay
+/// within a path component, `**` crosses them.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+                || rest.first() == Some(&b'/') && glob_match(&rest[1..], text)
+        }
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob_match(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            text.first().is_some_and(|&c| c != b'/') && glob_match(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// git-branchless hooks run `git branchless`, so when the container doesn't
+/// have it every commit in the session would fail. Hooks are turned off for the
+/// session worktree only; branchless picks up its commits on the next run on
+/// the host.
+pub fn disable_branchless_hooks(
+    worktree_path: &Path,
+    target: &[String],
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let git = |args: &[&str]| {
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(worktree_path)
+            .args(target)
+            .arg("git")
+            .args(args)
+            .stdout(Stdio::null());
+        run_command_verbose(&mut cmd, verbose)
+    };
+    if git(&["branchless", "--help"])?.success() {
+        return Ok(());
+    }
+    println!("git-branchless isn't installed in the container, disabling its hooks in /code");
+    enable_worktree_config(worktree_path, target, verbose)?;
+    let disabled = git(&[
+        "-C",
+        "/code",
+        "config",
+        "--worktree",
+        "core.hooksPath",
+        "/dev/null",
+    ])?
+    .success();
+    if !disabled {
+        anyhow::bail!("failed to disable git-branchless hooks");
+    }
+    Ok(())
+}
+
+/// Marks the branch as a forest session in the repository's git config, so git
+/// tooling can tell session branches apart without forest:
+/// `git config --get-regexp '^branch\..*\.forestsession$'`.
+pub fn record_session_metadata(
+    repo_root: &Path,
+    branch: &str,
+    label: &str,
+    dev_env: Option<&str>,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let _lock = locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+    let get = |key: &str| {
+        Command::new("git")
+            .args(["config", "--local", "--get", key])
+            .current_dir(repo_root)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    };
+    let set = |key: &str, value: &str| -> anyhow::Result<()> {
+        if get(key).as_deref() == Some(value) {
+            return Ok(());
+        }
+        let mut cmd = Command::new("git");
+        cmd.args(["config", "--local", key, value])
+            .current_dir(repo_root);
+        let status = run_command_verbose(&mut cmd, verbose)?;
+        if !status.success() {
+            anyhow::bail!("git config {} failed", key);
+        }
+        Ok(())
+    };
+
+    let section = format!("branch.{}", branch);
+    set(&format!("{}.forestSession", section), label)?;
+    if let Some(env) = dev_env {
+        set(&format!("{}.forestDevcontainerEnv", section), env)?;
+    }
+    let created_by = format!("{}.forestCreatedBy", section);
+    if get(&created_by).is_none() {
+        let user = std::env::var("USER").unwrap_or_else(|_| String::from("unknown"));
+        set(&created_by, &user)?;
+        set(
+            &format!("{}.forestCreatedAt", section),
+            &timesheet::now().to_string(),
+        )?;
+    }
+    // leave descriptions people wrote themselves alone
+    let description = format!("{}.description", section);
+    if get(&description).is_none() {
+        set(&description, &format!("forest session {}", label))?;
+    }
+    Ok(())
+}
+
+/// What the session worktree has checked out: a branch name, or the commit
+/// when its HEAD is detached. Read from git's admin directory so the
+/// container needn't be running.
+pub fn worktree_head(repo_root: &Path, worktree_path: &Path) -> Option<String> {
+    let admin = worktree_admin_dir(repo_root, worktree_path)?;
+    let head = fs::read_to_string(admin.join("HEAD")).ok()?;
+    let head = head.trim();
+    Some(
+        head.strip_prefix("ref: refs/heads/")
+            .unwrap_or(head)
+            .to_string(),
+    )
+}
+
+/// `owner/repo` of the GitHub repository `remote` points at.
+pub fn remote_repository(repo_root: &Path, remote: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", remote])
+        .current_dir(repo_root)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    github::repository(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Fetches `remote` and returns where its default branch is, such as
+/// `upstream/main`.
+pub fn base_start(repo_root: &Path, remote: &str, verbose: bool) -> anyhow::Result<String> {
+    let mut cmd = Command::new("git");
+    cmd.args(["fetch", "--quiet", remote])
+        .current_dir(repo_root);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("git fetch {} failed", remote);
+    }
+    remote_default_branch(repo_root, remote)
+}
+
+/// Where a branch asked to start from `base`, a branch, tag or commit, starts:
+/// `<remote>/<base>` when `remote` has that branch, fetched first unless
+/// `fetch` is off so that it's as fresh as the remote's, else `base` itself.
+/// A local branch `base` with commits the remote's lacks wins, with a warning.
+/// A failed fetch only warns, for working offline.
+pub fn from_start(
+    repo_root: &Path,
+    remote: &str,
+    base: &str,
+    fetch: bool,
+    verbose: bool,
+) -> anyhow::Result<String> {
+    let git_ok = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(repo_root)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    let has_remote = git_ok(&["remote", "get-url", remote]);
+    if fetch && has_remote {
+        let mut cmd = Command::new("git");
+        cmd.args(["fetch", "--quiet", remote])
+            .current_dir(repo_root);
+        if !run_command_verbose(&mut cmd, verbose)?.success() {
+            style::warn(format!(
+                "git fetch {} failed; starting from {} as last fetched",
+                remote, base
+            ));
+        }
+    }
+    let tracking = format!("{}/{}", remote, base);
+    let tracked = format!("refs/remotes/{}", tracking);
+    if has_remote && git_ok(&["show-ref", "--verify", "--quiet", &tracked]) {
+        let local = format!("refs/heads/{}", base);
+        // a local branch with commits the remote's lacks would lose them
+        if git_ok(&["show-ref", "--verify", "--quiet", &local])
+            && !git_ok(&["merge-base", "--is-ancestor", &local, &tracked])
+        {
+            style::warn(format!(
+                "{} has commits {} doesn't; starting from the local branch",
+                base, tracking
+            ));
+            return Ok(base.to_string());
+        }
+        return Ok(tracking);
+    }
+    if git_ok(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{}^{{commit}}", base),
+    ]) {
+        return Ok(base.to_string());
+    }
+    anyhow::bail!(
+        "no branch, tag or commit {} to start from, here or on {}",
+        base,
+        remote
+    )
+}
+
+/// Name of `remote`'s default branch: the branch `<remote>/HEAD` points at,
+/// else the one recorded for the repository in `repos.json`, else the one
+/// GitHub reports, which is then recorded. Clones made with `git init` and
+/// `git remote add` have no `<remote>/HEAD`, and asking GitHub each time is slow.
+pub fn default_branch_name(repo_root: &Path, remote: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "--short"])
+        .arg(format!("refs/remotes/{}/HEAD", remote))
+        .current_dir(repo_root)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let head = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if let Some(branch) = head.strip_prefix(&format!("{}/", remote)) {
+        return Some(branch.to_string());
+    }
+    let data_dir = crate::config::data_dir().ok();
+    if let Some(branch) = data_dir
+        .as_deref()
+        .and_then(|dir| sessions::default_branch(dir, repo_root, remote))
+    {
+        return Some(branch);
+    }
+    let repository = remote_repository(repo_root, remote)?;
+    let output = Command::new("gh")
+        .args(["repo", "view", &repository, "--json", "defaultBranchRef"])
+        .args(["--jq", ".defaultBranchRef.name"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch.is_empty() {
+        return None;
+    }
+    if let Some(dir) = data_dir {
+        if let Err(e) = sessions::record_default_branch(&dir, repo_root, remote, &branch) {
+            style::warn(format!("couldn't record the default branch: {}", e));
+        }
+    }
+    Some(branch)
+}
+
+/// The remote-tracking branch of `remote`'s default branch, else
+/// `<remote>/main` or `<remote>/master`, whichever was fetched.
+pub fn remote_default_branch(repo_root: &Path, remote: &str) -> anyhow::Result<String> {
+    let head = default_branch_name(repo_root, remote)
+        .map(|branch| format!("{}/{}", remote, branch))
+        .unwrap_or_default();
+    let tracked = |branch: &str| {
+        Command::new("git")
+            .args(["show-ref", "--verify", "--quiet"])
+            .arg(format!("refs/remotes/{}", branch))
+            .current_dir(repo_root)
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    [
+        head,
+        format!("{}/main", remote),
+        format!("{}/master", remote),
+    ]
+    .into_iter()
+    .find(|branch| !branch.is_empty() && tracked(branch))
+    .ok_or_else(|| anyhow::anyhow!("can't tell the default branch of remote {}", remote))
+}
+
+/// The local branch of `remote`'s default branch, else `main` or `master`,
+/// whichever exists.
+pub fn default_branch(repo_root: &Path, remote: &str) -> anyhow::Result<String> {
+    default_branch_name(repo_root, remote)
+        .into_iter()
+        .chain(["main".to_string(), "master".to_string()])
+        .find(|branch| require_branch(branch, repo_root).is_ok())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "can't tell the default branch of {}; open it with `forest open --readonly <branch>`",
+                repo_root.display()
+            )
+        })
+}
+
+/// Whether `branch` is `remote`'s default branch, which sessions leave to
+/// pull requests.
+pub fn is_default_branch(repo_root: &Path, remote: &str, branch: &str) -> bool {
+    default_branch_name(repo_root, remote).is_some_and(|default| default == branch)
+}
+
+/// Whether `branch` is merged into `remote`'s default branch, the local one
+/// or the one last fetched, so deleting it loses nothing. `git branch
+/// --delete` only asks whether it's merged into what's checked out.
+pub fn merged_into_default(repo_root: &Path, remote: &str, branch: &str) -> bool {
+    default_branch(repo_root, remote)
+        .into_iter()
+        .chain(remote_default_branch(repo_root, remote))
+        .any(|target| {
+            Command::new("git")
+                .args(["merge-base", "--is-ancestor", branch, &target])
+                .current_dir(repo_root)
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        })
+}
+
+pub fn require_branch(branch: &str, repo_root: &Path) -> anyhow::Result<()> {
+    let exists = Command::new("git")
+        .args(["show-ref", "--verify", "--quiet"])
+        .arg(format!("refs/heads/{}", branch))
+        .current_dir(repo_root)
+        .status()
+        .is_ok_and(|s| s.success());
+    if !exists {
+        anyhow::bail!("no branch {} in {}", branch, repo_root.display());
+    }
+    Ok(())
+}
+
+/// Checks `branch` out detached into a read-only session's worktree. The
+/// container can't write `/code`, so git runs on the host and the worktree is
+/// then pointed at the container paths, as if it had been added in there.
+pub fn add_readonly_worktree(
+    repo_root: &Path,
+    worktree_path: &Path,
+    branch: &str,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let mut cmd = Command::new("git");
+    cmd.args(["worktree", "add", "--quiet", "--detach"])
+        .arg(worktree_path)
+        .arg(branch)
+        .current_dir(repo_root);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("git worktree add failed");
+    }
+    let git_file = worktree_path.join(".git");
+    let content = fs::read_to_string(&git_file)?;
+    let id = content
+        .trim()
+        .strip_prefix("gitdir: ")
+        .and_then(|dir| Path::new(dir).file_name())
+        .ok_or_else(|| anyhow::anyhow!("unexpected {}", git_file.display()))?
+        .to_string_lossy()
+        .to_string();
+    fs::write(&git_file, format!("gitdir: /repo/.git/worktrees/{}\n", id))?;
+    fs::write(
+        repo_root.join(".git/worktrees").join(&id).join("gitdir"),
+        "/code/.git\n",
+    )?;
+    Ok(())
+}
+
+/// Moves a read-only session's detached checkout up to where `branch` is now,
+/// so reopening shows the latest code. A worktree that has a branch checked
+/// out was a writable session's, and is left alone.
+pub fn refresh_readonly_worktree(
+    repo_root: &Path,
+    worktree_path: &Path,
+    branch: &str,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let Some(admin) = worktree_admin_dir(repo_root, worktree_path) else {
+        return Ok(());
+    };
+    if fs::read_to_string(admin.join("HEAD"))?.starts_with("ref: ") {
+        return Ok(());
+    }
+    let mut cmd = Command::new("git");
+    cmd.env("GIT_DIR", &admin)
+        .env("GIT_WORK_TREE", worktree_path)
+        .current_dir(worktree_path)
+        .args(["checkout", "--quiet", "--detach", branch]);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("updating the read-only checkout of {} failed", branch);
+    }
+    Ok(())
+}
+
+/// `git status --porcelain` lines of a session's worktree, run on the host
+/// through git's admin directory for it; empty without one.
+pub fn worktree_changes(repo_root: &Path, worktree_path: &Path) -> anyhow::Result<Vec<String>> {
+    let Some(admin) = worktree_admin_dir(repo_root, worktree_path) else {
+        return Ok(Vec::new());
+    };
+    let output = Command::new("git")
+        .env("GIT_DIR", &admin)
+        .env("GIT_WORK_TREE", worktree_path)
+        .current_dir(worktree_path)
+        .args(["status", "--porcelain"])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("git status failed in {}", worktree_path.display());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Commits on `branch` that no branch of `remote` has.
+pub fn unpushed_commits(repo_root: &Path, branch: &str, remote: &str) -> anyhow::Result<usize> {
+    let output = Command::new("git")
+        .args(["rev-list", "--count", branch, "--not"])
+        .arg(format!("--remotes={}", remote))
+        .current_dir(repo_root)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("git rev-list {} failed", branch);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().parse()?)
+}
+
+pub fn is_branch(repo_root: &Path, name: &str) -> bool {
+    Command::new("git")
+        .args(["show-ref", "--verify", "--quiet"])
+        .arg(format!("refs/heads/{}", name))
+        .current_dir(repo_root)
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// First git release that can write worktrees with relative paths, which older
+/// versions cannot read.
+pub const RELATIVE_WORKTREES: (u32, u32, u32) = (2, 48, 0);
+
+pub fn parse_git_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|p| p.parse::<u32>().ok());
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or(0),
+    ))
+}
+
+/// Compares host and container git versions before the container writes a
+/// worktree the host has to read. Returns extra `-c` options for the container's
+/// `git worktree add` that keep the result readable by the host.
+pub fn check_git_skew(
+    worktree_path: &Path,
+    target: &[String],
+    verbose: bool,
+) -> &'static [&'static str] {
+    let host = Command::new("git")
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|o| parse_git_version(&String::from_utf8_lossy(&o.stdout)));
+    let container = devcontainer::command("exec")
+        .arg("--workspace-folder")
+        .arg(worktree_path)
+        .args(target)
+        .arg("git")
+        .arg("--version")
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .and_then(|o| parse_git_version(&String::from_utf8_lossy(&o.stdout)));
+    let (Some(host), Some(container)) = (host, container) else {
+        if verbose {
+            println!("Could not determine host and container git versions");
+        }
+        return &[];
+    };
+    if verbose {
+        println!("Host git {:?}, container git {:?}", host, container);
+    }
+
+    if container >= RELATIVE_WORKTREES && host < RELATIVE_WORKTREES {
+        if verbose {
+            println!("Disabling relative worktree paths for the host's older git");
+        }
+        return &["-c", "worktree.useRelativePaths=false"];
+    }
+    if host >= RELATIVE_WORKTREES && container < RELATIVE_WORKTREES {
+        style::warn(format!(
+            "container git {}.{}.{} predates relative worktree paths; \
+             worktrees created by the host's git {}.{}.{} with worktree.useRelativePaths \
+             will look corrupted inside the container",
+            container.0, container.1, container.2, host.0, host.1, host.2
+        ));
+    } else if host.0 != container.0 || host.1.abs_diff(container.1) >= 10 {
+        style::warn(format!(
+            "host git {}.{}.{} and container git {}.{}.{} are far apart; \
+             shared worktree metadata may not be readable by both",
+            host.0, host.1, host.2, container.0, container.1, container.2
+        ));
+    }
+    &[]
+}
+
+/// Root of the git repository the current directory is in.
+pub fn repo_toplevel() -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(PathBuf::from(str::from_utf8(&output.stdout).ok()?.trim()))
+}
+
+/// Repository root and name for commands that act on an existing session's
+/// worktree and therefore have to run inside its repository.
+pub fn current_repo(command: &str) -> anyhow::Result<(PathBuf, String)> {
+    let Some(repo_root) = repo_toplevel() else {
+        anyhow::bail!("run `forest {}` inside the session's repository", command);
+    };
+    let repo_name = repo_root
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("failed to determine repo name"))?
+        .to_string_lossy()
+        .to_string();
+    Ok((repo_root, repo_name))
+}
+
+/// git's admin directory for a session worktree, which the worktree's .git
+/// file records as a container path.
+pub fn worktree_admin_dir(repo_root: &Path, worktree_path: &Path) -> Option<PathBuf> {
+    fs::read_to_string(worktree_path.join(".git"))
+        .ok()
+        .and_then(|link| {
+            link.trim()
+                .strip_prefix("gitdir: /repo/")
+                .map(|rel| repo_root.join(rel))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn finds_worktree_admin_dir_on_the_host() {
+        let dir = tempdir().unwrap();
+        let worktree = dir.path().join("feat");
+        fs::create_dir(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: /repo/.git/worktrees/code\n").unwrap();
+        assert_eq!(
+            worktree_admin_dir(Path::new("/src/widget"), &worktree),
+            Some(PathBuf::from("/src/widget/.git/worktrees/code"))
+        );
+        assert_eq!(
+            worktree_admin_dir(Path::new("/src/widget"), dir.path()),
+            None
+        );
+    }
+
+    #[test]
+    fn remembers_the_https_fallback_of_a_worktree() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("widget");
+        let admin = repo.join(".git/worktrees/code");
+        let worktree = dir.path().join("feat");
+        fs::create_dir_all(&admin).unwrap();
+        fs::create_dir(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: /repo/.git/worktrees/code\n").unwrap();
+        assert_eq!(worktree_protocol(None, &repo, &worktree), None);
+
+        let (helper, value) = github::rewrite_config(RemoteProtocol::Https).pop().unwrap();
+        let status = Command::new("git")
+            .arg("config")
+            .arg("--file")
+            .arg(admin.join("config.worktree"))
+            .args([&helper, &value])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            worktree_protocol(None, &repo, &worktree),
+            Some(RemoteProtocol::Https)
+        );
+        assert_eq!(
+            worktree_protocol(Some(RemoteProtocol::Ssh), &repo, &worktree),
+            Some(RemoteProtocol::Ssh)
+        );
+        assert_eq!(github_token(Some(RemoteProtocol::Ssh)), None);
+    }
+
+    #[test]
+    fn evaluates_conditional_includes() {
+        let home = Path::new("/home/me");
+        let listing = "file:/home/me/.gitconfig\tincludeif.gitdir:~/work/.path .gitconfig-work\n\
+                       file:/home/me/.gitconfig\tincludeif.onbranch:oss/.path ~/oss.inc\n\
+                       file:/home/me/.gitconfig\tinclude.path /etc/shared\n";
+        assert_eq!(
+            parse_includes(listing, home),
+            [
+                Include {
+                    condition: "gitdir:~/work/".to_string(),
+                    path: PathBuf::from("/home/me/.gitconfig-work"),
+                },
+                Include {
+                    condition: "onbranch:oss/".to_string(),
+                    path: PathBuf::from("/home/me/oss.inc"),
+                },
+            ]
+        );
+
+        let work = Path::new("/home/me/work/widget/.git");
+        let applies = |condition| include_applies(condition, work, "feat/login", home);
+        assert!(applies("gitdir:~/work/"));
+        assert!(!applies("gitdir:~/personal/"));
+        assert!(applies("gitdir:widget/.git"));
+        assert!(applies("gitdir:/home/*/work/*/.git"));
+        assert!(!applies("gitdir:/home/*/.git"));
+        assert!(applies("gitdir/i:~/WORK/"));
+        assert!(!applies("gitdir:~/WORK/"));
+        assert!(applies("onbranch:feat/"));
+        assert!(applies("onbranch:feat/*"));
+        assert!(!applies("onbranch:main"));
+        assert!(!applies("hasconfig:remote.*.url:https://example.com/**"));
+    }
+
+    #[test]
+    fn counts_unpushed_commits() {
+        let dir = tempdir().unwrap();
+        git(dir.path(), &["init", "--quiet", "--initial-branch=main"]);
+        git(
+            dir.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "one"],
+        );
+        git(
+            dir.path(),
+            &["update-ref", "refs/remotes/origin/main", "HEAD"],
+        );
+        assert_eq!(unpushed_commits(dir.path(), "main", "origin").unwrap(), 0);
+        git(
+            dir.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "two"],
+        );
+        git(
+            dir.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "three"],
+        );
+        assert_eq!(unpushed_commits(dir.path(), "main", "origin").unwrap(), 2);
+        git(
+            dir.path(),
+            &["update-ref", "refs/remotes/fork/main", "HEAD"],
+        );
+        assert_eq!(unpushed_commits(dir.path(), "main", "fork").unwrap(), 0);
+    }
+
+    #[test]
+    fn finds_remote_branches_in_tree_paths() {
+        let dir = tempdir().unwrap();
+        git(dir.path(), &["init", "--quiet"]);
+        git(
+            dir.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "init"],
+        );
+        for branch in ["feat/login", "fix"] {
+            git(
+                dir.path(),
+                &[
+                    "update-ref",
+                    &format!("refs/remotes/origin/{}", branch),
+                    "HEAD",
+                ],
+            );
+        }
+        let branch = |path| remote_branch(dir.path(), path);
+        assert_eq!(branch("feat/login/src").as_deref(), Some("feat/login"));
+        assert_eq!(branch("fix/src/lib.rs").as_deref(), Some("fix"));
+        assert_eq!(branch("feat"), None);
+    }
+
+    #[test]
+    fn starts_from_the_remotes_fresh_branch() {
+        let dir = tempdir().unwrap();
+        let upstream = dir.path().join("upstream");
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&upstream).unwrap();
+        git(&upstream, &["init", "--quiet", "--initial-branch=main"]);
+        git(
+            &upstream,
+            &["commit", "--quiet", "--allow-empty", "-m", "one"],
+        );
+        git(
+            dir.path(),
+            &["clone", "--quiet", &upstream.to_string_lossy(), "repo"],
+        );
+        git(&repo, &["tag", "v1"]);
+        git(
+            &upstream,
+            &["commit", "--quiet", "--allow-empty", "-m", "two"],
+        );
+        let head = |dir: &Path, rev: &str| {
+            let output = Command::new("git")
+                .args(["rev-parse", rev])
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+
+        assert_eq!(
+            from_start(&repo, "origin", "main", false, false).unwrap(),
+            "origin/main"
+        );
+        assert_ne!(head(&repo, "origin/main"), head(&upstream, "main"));
+        // fetching brings origin/main up to the remote's
+        assert_eq!(
+            from_start(&repo, "origin", "main", true, false).unwrap(),
+            "origin/main"
+        );
+        assert_eq!(head(&repo, "origin/main"), head(&upstream, "main"));
+        // tags and commits the remote has no branch of are taken as they are
+        assert_eq!(
+            from_start(&repo, "origin", "v1", false, false).unwrap(),
+            "v1"
+        );
+        assert!(from_start(&repo, "origin", "nope", false, false).is_err());
+        // and without the remote, nothing is fetched
+        assert_eq!(
+            from_start(&repo, "fork", "main", true, false).unwrap(),
+            "main"
+        );
+        // a local branch ahead of the remote's keeps its commits
+        git(
+            &repo,
+            &["commit", "--quiet", "--allow-empty", "-m", "local"],
+        );
+        assert_eq!(
+            from_start(&repo, "origin", "main", false, false).unwrap(),
+            "main"
+        );
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=forest",
+                "-c",
+                "user.email=forest@localhost",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn adds_readonly_worktrees_with_container_paths() {
+        let repo = tempdir().unwrap();
+        git(repo.path(), &["init", "--quiet", "-b", "trunk"]);
+        git(
+            repo.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "one"],
+        );
+        assert!(default_branch(repo.path(), "origin").is_err());
+        git(repo.path(), &["branch", "main"]);
+        assert_eq!(default_branch(repo.path(), "origin").unwrap(), "main");
+        git(
+            repo.path(),
+            &["update-ref", "refs/remotes/origin/trunk", "trunk"],
+        );
+        git(
+            repo.path(),
+            &[
+                "symbolic-ref",
+                "refs/remotes/origin/HEAD",
+                "refs/remotes/origin/trunk",
+            ],
+        );
+        assert_eq!(default_branch(repo.path(), "origin").unwrap(), "trunk");
+        assert!(is_default_branch(repo.path(), "origin", "trunk"));
+        assert!(!is_default_branch(repo.path(), "origin", "main"));
+        git(repo.path(), &["branch", "feat", "trunk"]);
+        git(repo.path(), &["checkout", "--quiet", "-b", "wip"]);
+        git(
+            repo.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "two"],
+        );
+        assert!(merged_into_default(repo.path(), "origin", "feat"));
+        assert!(!merged_into_default(repo.path(), "origin", "wip"));
+        git(repo.path(), &["checkout", "--quiet", "trunk"]);
+        assert_eq!(
+            remote_default_branch(repo.path(), "origin").unwrap(),
+            "origin/trunk"
+        );
+        assert!(remote_default_branch(repo.path(), "upstream").is_err());
+        git(
+            repo.path(),
+            &["update-ref", "refs/remotes/upstream/main", "trunk"],
+        );
+        assert_eq!(
+            remote_default_branch(repo.path(), "upstream").unwrap(),
+            "upstream/main"
+        );
+
+        let worktree = repo.path().join("wt/trunk");
+        fs::create_dir_all(&worktree).unwrap();
+        add_readonly_worktree(repo.path(), &worktree, "trunk", false).unwrap();
+        assert_eq!(
+            fs::read_to_string(worktree.join(".git")).unwrap(),
+            "gitdir: /repo/.git/worktrees/trunk\n"
+        );
+        let admin = repo.path().join(".git/worktrees/trunk");
+        assert_eq!(
+            fs::read_to_string(admin.join("gitdir")).unwrap(),
+            "/code/.git\n"
+        );
+
+        git(
+            repo.path(),
+            &["commit", "--quiet", "--allow-empty", "-m", "two"],
+        );
+        refresh_readonly_worktree(repo.path(), &worktree, "trunk", false).unwrap();
+        let tip = Command::new("git")
+            .args(["rev-parse", "trunk"])
+            .current_dir(repo.path())
+            .output()
+            .unwrap();
+        assert_eq!(
+            worktree_head(repo.path(), &worktree).unwrap(),
+            String::from_utf8_lossy(&tip.stdout).trim()
+        );
+    }
+
+    #[test]
+    fn reads_worktree_head_from_the_admin_dir() {
+        let repo = tempdir().unwrap();
+        let admin = repo.path().join(".git/worktrees/code");
+        fs::create_dir_all(&admin).unwrap();
+        let worktree = tempdir().unwrap();
+        fs::write(
+            worktree.path().join(".git"),
+            "gitdir: /repo/.git/worktrees/code\n",
+        )
+        .unwrap();
+        fs::write(admin.join("HEAD"), "ref: refs/heads/feat/y\n").unwrap();
+        assert_eq!(
+            worktree_head(repo.path(), worktree.path()).as_deref(),
+            Some("feat/y")
+        );
+        fs::write(admin.join("HEAD"), "0123abcd\n").unwrap();
+        assert_eq!(
+            worktree_head(repo.path(), worktree.path()).as_deref(),
+            Some("0123abcd")
+        );
+    }
+
+    #[test]
+    fn parses_git_versions() {
+        assert_eq!(parse_git_version("git version 2.39.2\n"), Some((2, 39, 2)));
+        assert_eq!(
+            parse_git_version("git version 2.48.1.windows.1"),
+            Some((2, 48, 1))
+        );
+        assert_eq!(parse_git_version("git version 2.50"), Some((2, 50, 0)));
+        assert_eq!(parse_git_version("bash: git: not found"), None);
+    }
+}
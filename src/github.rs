@@ -0,0 +1,252 @@
+use crate::config::RemoteProtocol;
+
+/// What a GitHub URL given to `open` points at.
+#[derive(Debug, PartialEq)]
+pub enum Ref {
+    /// The repository itself
+    Repo,
+    /// `/tree/<path>`: a branch, possibly followed by a directory; which
+    /// segments name the branch is only known once the remote is fetched
+    Tree(String),
+    Pull(u64),
+    Issue(u64),
+    Commit(String),
+}
+
+/// A parsed `https://github.com/<owner>/<repo>[/...]` URL.
+#[derive(Debug, PartialEq)]
+pub struct Target {
+    pub owner: String,
+    pub repo: String,
+    pub reference: Ref,
+}
+
+impl Target {
+    pub fn clone_url(&self, protocol: RemoteProtocol) -> String {
+        remote_url(&format!("{}/{}", self.owner, self.repo), protocol)
+    }
+
+    /// Whether git remote `url` is this repository, over HTTPS or SSH.
+    pub fn is_remote(&self, url: &str) -> bool {
+        let url = url.trim().trim_end_matches('/');
+        let url = url.strip_suffix(".git").unwrap_or(url);
+        let path = [
+            "https://github.com/",
+            "ssh://git@github.com/",
+            "git@github.com:",
+        ]
+        .iter()
+        .find_map(|prefix| url.strip_prefix(prefix));
+        path.is_some_and(|path| path.eq_ignore_ascii_case(&format!("{}/{}", self.owner, self.repo)))
+    }
+}
+
+/// URL prefixes of GitHub remotes over each protocol.
+const SSH_PREFIXES: [&str; 2] = ["git@github.com:", "ssh://git@github.com/"];
+const HTTPS_PREFIX: &str = "https://github.com/";
+
+/// Remote URL of GitHub repository `owner/repo` over `protocol`.
+pub fn remote_url(repository: &str, protocol: RemoteProtocol) -> String {
+    match protocol {
+        RemoteProtocol::Ssh => format!("{}{}.git", SSH_PREFIXES[0], repository),
+        RemoteProtocol::Https => format!("{}{}.git", HTTPS_PREFIX, repository),
+    }
+}
+
+/// GitHub remote `url` over `protocol` instead, `None` when it isn't a
+/// GitHub remote or already uses it.
+pub fn with_protocol(url: &str, protocol: RemoteProtocol) -> Option<String> {
+    let url = url.trim();
+    let repository = SSH_PREFIXES
+        .iter()
+        .chain([&HTTPS_PREFIX])
+        .find_map(|prefix| url.strip_prefix(prefix))?;
+    let repository = repository.strip_suffix(".git").unwrap_or(repository);
+    let converted = remote_url(repository, protocol);
+    (converted != url).then_some(converted)
+}
+
+/// `owner/repo` of GitHub remote `url`, over either protocol.
+pub fn repository(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/');
+    let repository = SSH_PREFIXES
+        .iter()
+        .chain([&HTTPS_PREFIX])
+        .find_map(|prefix| url.strip_prefix(prefix))?;
+    let repository = repository.strip_suffix(".git").unwrap_or(repository);
+    let (owner, repo) = repository.split_once('/')?;
+    (!owner.is_empty() && !repo.is_empty() && !repo.contains('/')).then(|| repository.to_string())
+}
+
+/// `git config` entries making git in a session reach GitHub over `protocol`
+/// whatever its remotes say. Over HTTPS the token comes from `GH_TOKEN` or
+/// `GITHUB_TOKEN` in the container.
+pub fn rewrite_config(protocol: RemoteProtocol) -> Vec<(String, String)> {
+    match protocol {
+        RemoteProtocol::Https => {
+            let mut entries: Vec<(String, String)> = SSH_PREFIXES
+                .iter()
+                .map(|prefix| {
+                    (
+                        format!("url.{}.insteadOf", HTTPS_PREFIX),
+                        prefix.to_string(),
+                    )
+                })
+                .collect();
+            entries.push((
+                "credential.https://github.com.helper".to_string(),
+                "!f() { token=${GH_TOKEN:-$GITHUB_TOKEN}; test \"$1\" = get && test -n \"$token\" \
+                 && echo username=x-access-token && echo \"password=$token\"; }; f"
+                    .to_string(),
+            ));
+            entries
+        }
+        RemoteProtocol::Ssh => vec![(
+            format!("url.{}.insteadOf", SSH_PREFIXES[0]),
+            HTTPS_PREFIX.to_string(),
+        )],
+    }
+}
+
+/// The repository and reference of a GitHub URL, `None` for anything else so
+/// callers can fall back to treating it as a session name.
+pub fn parse(url: &str) -> Option<Target> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let rest = rest
+        .strip_prefix("github.com/")
+        .or_else(|| rest.strip_prefix("www.github.com/"))?;
+    // fragments and queries (`#issuecomment-1`, `?w=1`) don't change the target
+    let rest = rest.split(['#', '?']).next().unwrap_or_default();
+    let mut parts = rest.trim_end_matches('/').splitn(4, '/');
+    let owner = parts.next().filter(|s| !s.is_empty())?.to_string();
+    let repo = parts.next().filter(|s| !s.is_empty())?;
+    let repo = repo.strip_suffix(".git").unwrap_or(repo).to_string();
+    let number = |n: Option<&str>| n?.split('/').next()?.parse::<u64>().ok();
+    let reference = match (parts.next(), parts.next()) {
+        (None, _) => Ref::Repo,
+        (Some("tree"), Some(path)) => Ref::Tree(path.to_string()),
+        (Some("pull"), n) => Ref::Pull(number(n)?),
+        (Some("issues"), n) => Ref::Issue(number(n)?),
+        (Some("commit"), Some(sha)) => {
+            let sha = sha.split('/').next().unwrap_or_default();
+            if sha.len() < 7 || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            Ref::Commit(sha.to_lowercase())
+        }
+        _ => return None,
+    };
+    Some(Target {
+        owner,
+        repo,
+        reference,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_github_urls() {
+        let target = parse("https://github.com/acme/widget").unwrap();
+        assert_eq!(
+            (target.owner.as_str(), target.repo.as_str()),
+            ("acme", "widget")
+        );
+        assert_eq!(target.reference, Ref::Repo);
+        assert_eq!(
+            target.clone_url(RemoteProtocol::Https),
+            "https://github.com/acme/widget.git"
+        );
+        assert_eq!(
+            target.clone_url(RemoteProtocol::Ssh),
+            "git@github.com:acme/widget.git"
+        );
+        assert_eq!(
+            parse("github.com/acme/widget.git/").unwrap().reference,
+            Ref::Repo
+        );
+        assert_eq!(
+            parse("https://github.com/acme/widget/tree/feat/login")
+                .unwrap()
+                .reference,
+            Ref::Tree("feat/login".to_string())
+        );
+        assert_eq!(
+            parse("https://github.com/acme/widget/pull/42/files?w=1")
+                .unwrap()
+                .reference,
+            Ref::Pull(42)
+        );
+        assert_eq!(
+            parse("https://github.com/acme/widget/issues/7#issuecomment-1")
+                .unwrap()
+                .reference,
+            Ref::Issue(7)
+        );
+        assert_eq!(
+            parse("https://github.com/acme/widget/commit/ABCDEF1234")
+                .unwrap()
+                .reference,
+            Ref::Commit("abcdef1234".to_string())
+        );
+        assert_eq!(parse("feat/login"), None);
+        assert_eq!(parse("https://gitlab.com/acme/widget"), None);
+        assert_eq!(parse("https://github.com/acme"), None);
+        assert_eq!(parse("https://github.com/acme/widget/pull/x"), None);
+        assert_eq!(parse("https://github.com/acme/widget/actions"), None);
+
+        assert!(target.is_remote("git@github.com:Acme/widget.git"));
+        assert!(target.is_remote("https://github.com/acme/widget"));
+        assert!(target.is_remote("ssh://git@github.com/acme/widget.git"));
+        assert!(!target.is_remote("https://github.com/acme/widget-fork"));
+    }
+
+    #[test]
+    fn rewrites_remote_protocols() {
+        assert_eq!(
+            with_protocol("git@github.com:acme/widget.git", RemoteProtocol::Https).as_deref(),
+            Some("https://github.com/acme/widget.git")
+        );
+        assert_eq!(
+            with_protocol("https://github.com/acme/widget\n", RemoteProtocol::Ssh).as_deref(),
+            Some("git@github.com:acme/widget.git")
+        );
+        assert_eq!(
+            with_protocol("https://github.com/acme/widget.git", RemoteProtocol::Https),
+            None
+        );
+        assert_eq!(
+            with_protocol("https://gitlab.com/acme/widget.git", RemoteProtocol::Ssh),
+            None
+        );
+        assert_eq!(
+            repository("git@github.com:acme/widget.git\n").as_deref(),
+            Some("acme/widget")
+        );
+        assert_eq!(
+            repository("https://github.com/me/widget").as_deref(),
+            Some("me/widget")
+        );
+        assert_eq!(repository("https://gitlab.com/acme/widget.git"), None);
+        let https = rewrite_config(RemoteProtocol::Https);
+        assert_eq!(
+            https[..2],
+            [
+                (
+                    "url.https://github.com/.insteadOf".to_string(),
+                    "git@github.com:".to_string()
+                ),
+                (
+                    "url.https://github.com/.insteadOf".to_string(),
+                    "ssh://git@github.com/".to_string()
+                ),
+            ]
+        );
+        assert!(https[2].1.contains("password=$token"));
+    }
+}
@@ -0,0 +1,769 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+/// Where a session with `shell_helpers` finds the helpers, and the socket
+/// forest answers them on.
+pub const MOUNT: &str = "/forest";
+
+/// Where login shells pick up the profile script.
+pub const PROFILE: &str = "/etc/profile.d/forest.sh";
+
+const SOCKET: &str = "forest.sock";
+
+/// Commands the helpers run on the host, as `forest-<command>` or
+/// `forest <command>`, `open` also as `forest open-url`.
+pub const COMMANDS: [&str; 4] = ["sync", "pr", "open", "copy"];
+
+/// Most of a `copy` request the host's clipboard gets.
+const MAX_COPY: u64 = 1 << 20;
+
+/// Where `forest paste` leaves the host's clipboard inside a session.
+pub const PASTED: &str = "/tmp/forest-clipboard";
+
+/// Clipboard programs the helpers stand in for inside the container.
+const CLIPBOARD_SHIMS: [&str; 6] = ["pbcopy", "pbpaste", "xclip", "xsel", "wl-copy", "wl-paste"];
+
+/// The `[clipboard]` table of forest.toml.
+#[derive(Deserialize, Default)]
+pub struct ClipboardConfig {
+    /// Let sessions write to the host's clipboard; unset refuses it, since
+    /// anything in the container could plant text to be pasted on the host
+    pub copy: Option<bool>,
+    /// Copy with an OSC 52 terminal sequence when forest isn't answering
+    #[serde(default)]
+    pub osc52: bool,
+}
+
+impl ClipboardConfig {
+    pub fn copy(&self) -> bool {
+        self.copy.unwrap_or(false)
+    }
+}
+
+/// Host directory mounted at `/forest` in session `label`.
+pub fn dir(data_dir: &Path, label: &str) -> PathBuf {
+    data_dir.join("helpers").join(label)
+}
+
+/// File of the token of the helpers in `dir`: beside it, so not mounted.
+fn token_path(dir: &Path) -> PathBuf {
+    let mut path = dir.as_os_str().to_owned();
+    path.push(".token");
+    PathBuf::from(path)
+}
+
+/// The token every request to the helpers in `dir` starts with, created the
+/// first time. Only the session's shells get it, as `FOREST_TOKEN`, so other
+/// users of the host can't use the socket.
+pub fn token(dir: &Path) -> anyhow::Result<String> {
+    let path = token_path(dir);
+    if let Ok(token) = fs::read_to_string(&path) {
+        return Ok(token.trim().to_string());
+    }
+    let mut bytes = [0u8; 16];
+    fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?
+        .write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// Removes the helpers of session `label` and their token.
+pub fn remove(data_dir: &Path, label: &str) -> std::io::Result<()> {
+    let dir = dir(data_dir, label);
+    match fs::remove_file(token_path(&dir)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// The profile script exporting `FOREST_*` variables and putting the helpers
+/// on PATH.
+fn profile(session: &str, repo: &str, clipboard: &ClipboardConfig) -> String {
+    format!(
+        "# written by forest for session {session}\n\
+         export FOREST_SESSION={}\n\
+         export FOREST_REPO={}\n\
+         export FOREST_SOCKET={MOUNT}/{SOCKET}\n\
+         {}\
+         export BROWSER=\"${{BROWSER:-{MOUNT}/bin/forest-open}}\"\n\
+         case \":$PATH:\" in\n  *:{MOUNT}/bin:*) ;;\n  *) export PATH=\"{MOUNT}/bin:$PATH\" ;;\nesac\n",
+        crate::shell::quote(session),
+        crate::shell::quote(repo),
+        if clipboard.osc52 {
+            "export FOREST_OSC52=1\n"
+        } else {
+            ""
+        },
+    )
+}
+
+/// `forest-call <command> [args]`: sends `FOREST_TOKEN`, the request and,
+/// for `copy`, its standard input to forest on the host with whichever of
+/// python3, socat or `nc -U` the container has, printing the output and
+/// exiting with its status.
+const CALL: &str = r#"#!/bin/sh
+# written by forest: asks forest on the host to run <command> for $FOREST_SESSION
+socket="${FOREST_SOCKET:-/forest/forest.sock}"
+if [ ! -S "$socket" ]; then
+    echo "forest isn't listening; it answers while a \`forest open\` or \`forest attach\` shell of this session runs" >&2
+    exit 1
+fi
+if [ -z "$FOREST_TOKEN" ]; then
+    echo "FOREST_TOKEN isn't set; use the helpers from a \`forest open\` or \`forest attach\` shell" >&2
+    exit 1
+fi
+connect() {
+    if command -v python3 >/dev/null 2>&1; then
+        python3 -c 'import socket, sys
+s = socket.socket(socket.AF_UNIX)
+s.connect(sys.argv[1])
+s.sendall(sys.stdin.buffer.read())
+while True:
+    data = s.recv(4096)
+    if not data:
+        break
+    sys.stdout.buffer.write(data)
+    sys.stdout.flush()' "$socket"
+    elif command -v socat >/dev/null 2>&1; then
+        socat -t 86400 - "UNIX-CONNECT:$socket"
+    elif nc -h 2>&1 | grep -q -- ' -U'; then
+        nc -U "$socket"
+    else
+        echo "forest helpers need python3, socat or nc with -U in the container" >&2
+        echo "forest-exit 127"
+    fi
+}
+request() {
+    printf 'forest-token %s\n%s\n' "$FOREST_TOKEN" "$*"
+    if [ "$1" = copy ]; then
+        cat
+    fi
+}
+request "$@" | connect | while IFS= read -r line; do
+    case "$line" in
+        "forest-exit "*) exit "${line#forest-exit }" ;;
+        *) printf '%s\n' "$line" ;;
+    esac
+done
+"#;
+
+/// `forest <command>` inside the container, for the commands the helpers run.
+const FOREST: &str = r#"#!/bin/sh
+# written by forest: the host's forest, for what it does from a session
+case "$1" in
+    open-url) shift; exec forest-call open "$@" ;;
+    sync|pr|open|copy) exec forest-call "$@" ;;
+    *)
+        echo "inside a session forest runs sync, pr, open-url and copy on the host" >&2
+        exit 2
+        ;;
+esac
+"#;
+
+/// `forest-copy`: copies standard input through forest, or with
+/// `FOREST_OSC52` and nothing answering, through the terminal.
+const COPY: &str = r#"#!/bin/sh
+# written by forest: copy standard input to the host's clipboard
+if [ -n "$FOREST_OSC52" ] && [ ! -S "${FOREST_SOCKET:-/forest/forest.sock}" ]; then
+    # OSC 52: terminals that allow it put the text on their clipboard
+    printf '\033]52;c;%s\a' "$(base64 | tr -d '\n')" > /dev/tty
+    exit
+fi
+exec forest-call copy "$@"
+"#;
+
+/// `pbcopy`, `xclip` and the like: copying goes to `forest-copy`, pasting
+/// prints what `forest paste` left.
+const CLIPBOARD: &str = r#"#!/bin/sh
+# written by forest: the host's clipboard, through forest-copy and `forest paste`
+paste=
+case "${0##*/}" in
+    pbpaste|wl-paste) paste=1 ;;
+    xclip) for arg; do case "$arg" in -o|-out) paste=1 ;; esac; done ;;
+    xsel) for arg; do case "$arg" in --output) paste=1 ;; --*) ;; -*o*) paste=1 ;; esac; done ;;
+esac
+if [ -z "$paste" ]; then
+    exec forest-copy
+fi
+if [ ! -f /tmp/forest-clipboard ]; then
+    echo "nothing to paste; run \`forest paste $FOREST_SESSION\` on the host first" >&2
+    exit 1
+fi
+exec cat /tmp/forest-clipboard
+"#;
+
+/// Writes the profile script and helpers of session `session` of repository
+/// `repo` into `dir`.
+pub fn write(
+    dir: &Path,
+    session: &str,
+    repo: &str,
+    clipboard: &ClipboardConfig,
+) -> anyhow::Result<()> {
+    let bin = dir.join("bin");
+    fs::create_dir_all(&bin)?;
+    // the container's user is someone else on the host
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o755))?;
+    fs::write(dir.join("profile.sh"), profile(session, repo, clipboard))?;
+    let mut scripts = vec![
+        ("forest-call".to_string(), CALL.to_string()),
+        ("forest".to_string(), FOREST.to_string()),
+        ("forest-copy".to_string(), COPY.to_string()),
+    ];
+    scripts.extend(CLIPBOARD_SHIMS.map(|name| (name.to_string(), CLIPBOARD.to_string())));
+    for command in COMMANDS.iter().filter(|command| **command != "copy") {
+        scripts.push((
+            format!("forest-{}", command),
+            format!(
+                "#!/bin/sh\n# written by forest: `{}` on the host\nexec forest-call {} \"$@\"\n",
+                describe(command),
+                command
+            ),
+        ));
+    }
+    for (name, content) in scripts {
+        let path = bin.join(name);
+        fs::write(&path, content)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// `--mount` values of the helpers in `dir`, both read-only.
+pub fn mounts(dir: &Path) -> [String; 2] {
+    let backend = crate::backend::current();
+    [
+        backend.readonly_bind_mount(dir, MOUNT),
+        backend.readonly_bind_mount(&dir.join("profile.sh"), PROFILE),
+    ]
+}
+
+/// What helper `forest-<command>` does on the host, for its comment.
+fn describe(command: &str) -> &'static str {
+    match command {
+        "sync" => "forest sync $FOREST_SESSION",
+        "pr" => "forest pr $FOREST_SESSION",
+        _ => "open URL in the browser",
+    }
+}
+
+/// What a request asks of the host.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    /// Run forest with these arguments
+    Forest(Vec<String>),
+    /// Open an http or https URL in the browser
+    Open(String),
+    /// Put what follows the request on the clipboard
+    Copy,
+}
+
+/// Request `line` from session `session`: one of `COMMANDS`, with forest's
+/// always for that session and `pr` taking `--draft` and `--base`. Anything
+/// else is refused, so a container can't make the host run what it likes.
+pub fn parse_request(line: &str, session: &str) -> Result<Request, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    match command {
+        "open" => {
+            return match (words.next(), words.next()) {
+                (Some(url), None)
+                    if (url.starts_with("https://") || url.starts_with("http://"))
+                        && !url.chars().any(char::is_control) =>
+                {
+                    Ok(Request::Open(url.to_string()))
+                }
+                _ => Err("forest-open takes one http or https URL".to_string()),
+            }
+        }
+        "copy" => {
+            return match words.next() {
+                None => Ok(Request::Copy),
+                Some(word) => Err(format!("forest-copy doesn't take {}", word)),
+            }
+        }
+        "sync" | "pr" => {}
+        _ => {
+            return Err(format!(
+                "forest helpers run {}, not {:?}",
+                COMMANDS.join(", "),
+                command
+            ))
+        }
+    }
+    let mut args = vec![
+        "--non-interactive".to_string(),
+        command.to_string(),
+        session.to_string(),
+    ];
+    while let Some(word) = words.next() {
+        match (command, word) {
+            ("pr", "--draft") => args.push(word.to_string()),
+            ("pr", "--base") => match words.next().filter(|b| !b.starts_with('-')) {
+                Some(base) => args.extend(["--base".to_string(), base.to_string()]),
+                None => return Err("--base needs a branch".to_string()),
+            },
+            _ => return Err(format!("forest-{} doesn't take {}", command, word)),
+        }
+    }
+    Ok(Request::Forest(args))
+}
+
+/// The host's program opening URLs.
+fn opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// The host's clipboard programs, tried in order.
+fn clipboards() -> &'static [(&'static str, &'static [&'static str])] {
+    if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    }
+}
+
+/// Answers the helpers of one session while it lives, removing the socket
+/// when dropped.
+pub struct Server {
+    /// `None` when another forest answers
+    socket: Option<PathBuf>,
+    token: String,
+}
+
+impl Server {
+    /// `FOREST_TOKEN=<token>` for the session's shells.
+    pub fn env(&self) -> String {
+        format!("FOREST_TOKEN={}", self.token)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(socket) = &self.socket {
+            let _ = fs::remove_file(socket);
+        }
+    }
+}
+
+/// What a server answers with.
+struct Context {
+    /// Copy requests may reach the host's clipboard
+    copy: bool,
+    token: String,
+    label: String,
+    session: String,
+    repo_root: PathBuf,
+    forest: PathBuf,
+}
+
+/// Starts answering requests on the socket in `dir` for session `session`,
+/// whose container has label `label`, running `forest` in `repo_root`, unless
+/// another forest already answers there. Copies reach the clipboard if
+/// `copy`.
+pub fn serve(
+    dir: &Path,
+    label: &str,
+    session: &str,
+    repo_root: &Path,
+    forest: &Path,
+    copy: bool,
+) -> anyhow::Result<Server> {
+    let token = token(dir)?;
+    let socket = dir.join(SOCKET);
+    if UnixStream::connect(&socket).is_ok() {
+        return Ok(Server {
+            socket: None,
+            token,
+        });
+    }
+    // left behind by a forest that didn't get to clean up
+    let _ = fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)?;
+    // the container's user is someone else on the host; the token keeps
+    // everyone else out
+    fs::set_permissions(&socket, fs::Permissions::from_mode(0o777))?;
+    let context = Context {
+        copy,
+        token: token.clone(),
+        label: label.to_string(),
+        session: session.to_string(),
+        repo_root: repo_root.to_path_buf(),
+        forest: forest.to_path_buf(),
+    };
+    std::thread::spawn(move || {
+        // one request at a time: they'd push or rebase the same branch
+        for stream in listener.incoming().flatten() {
+            let _ = answer(stream, &context);
+        }
+    });
+    Ok(Server {
+        socket: Some(socket),
+        token,
+    })
+}
+
+/// Reads a line of at most 4 KiB.
+fn read_line(reader: &mut BufReader<UnixStream>) -> std::io::Result<String> {
+    let mut line = String::new();
+    reader.by_ref().take(4096).read_line(&mut line)?;
+    Ok(line)
+}
+
+fn answer(mut stream: UnixStream, context: &Context) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let first = read_line(&mut reader)?;
+    let request = match first.trim_end().strip_prefix("forest-token ") {
+        Some(token) if token == context.token => {
+            parse_request(&read_line(&mut reader)?, &context.session)
+        }
+        _ => Err("forest-token is missing or wrong".to_string()),
+    };
+    let code = match request {
+        Ok(Request::Forest(args)) => {
+            let mut cmd = Command::new(&context.forest);
+            cmd.args(&args).current_dir(&context.repo_root);
+            run(&mut cmd, &stream, None)?
+        }
+        Ok(Request::Open(url)) => {
+            let url = match host_url(&url, |port| {
+                crate::runtime::published_port(&context.label, port)
+            }) {
+                Ok(url) => url,
+                Err(port) => {
+                    writeln!(
+                        stream,
+                        "port {} of the session isn't published to the host; add it to appPort in devcontainer.json",
+                        port
+                    )?;
+                    url
+                }
+            };
+            open(&url, &stream)?
+        }
+        Ok(Request::Copy) if !context.copy => {
+            writeln!(
+                stream,
+                "copying to the host's clipboard is off; see copy under [clipboard] in forest.toml"
+            )?;
+            1
+        }
+        Ok(Request::Copy) => {
+            let mut input = Vec::new();
+            reader.take(MAX_COPY).read_to_end(&mut input)?;
+            copy(&input, &stream)?
+        }
+        Err(e) => {
+            writeln!(stream, "{}", e)?;
+            2
+        }
+    };
+    writeln!(stream, "forest-exit {}", code)
+}
+
+/// `url` with the session's own address, localhost, in place of what the
+/// host reaches it at: localhost at the port `published` says. `Err` with the
+/// port when it isn't published. Other URLs are left alone.
+pub fn host_url(url: &str, published: impl Fn(u16) -> Option<u16>) -> Result<String, u16> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Ok(url.to_string());
+    };
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(end);
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !authority.ends_with(']') => match port.parse() {
+            Ok(port) => (host, port),
+            Err(_) => return Ok(url.to_string()),
+        },
+        _ if scheme == "https" => (authority, 443),
+        _ => (authority, 80),
+    };
+    let local = host.eq_ignore_ascii_case("localhost")
+        || ["127.0.0.1", "0.0.0.0", "[::1]", "[::]"].contains(&host);
+    if !local {
+        return Ok(url.to_string());
+    }
+    match published(port) {
+        Some(host_port) => Ok(format!("{}://localhost:{}{}", scheme, host_port, path)),
+        None => Err(port),
+    }
+}
+
+/// Opens `url` with the host's opener.
+fn open(url: &str, stream: &UnixStream) -> std::io::Result<i32> {
+    match run(Command::new(opener()).arg(url), stream, None) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            writeln!(&mut &*stream, "{} isn't installed on the host", opener())?;
+            Ok(127)
+        }
+        result => result,
+    }
+}
+
+/// Puts `input` on the host's clipboard with the first clipboard program
+/// there is.
+fn copy(input: &[u8], stream: &UnixStream) -> std::io::Result<i32> {
+    for (program, args) in clipboards() {
+        match run(Command::new(program).args(*args), stream, Some(input)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            result => return result,
+        }
+    }
+    let names: Vec<&str> = clipboards().iter().map(|(program, _)| *program).collect();
+    writeln!(
+        &mut &*stream,
+        "no clipboard program on the host; install {}",
+        names.join(", ")
+    )?;
+    Ok(127)
+}
+
+/// The host's programs printing the clipboard, tried in order.
+fn pastes() -> &'static [(&'static str, &'static [&'static str])] {
+    if cfg!(target_os = "macos") {
+        &[("pbpaste", &[])]
+    } else {
+        &[
+            ("wl-paste", &["--no-newline"]),
+            ("xclip", &["-selection", "clipboard", "-o"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ]
+    }
+}
+
+/// What the host's clipboard holds, from the first program there is that
+/// prints it.
+pub fn read_clipboard() -> anyhow::Result<Vec<u8>> {
+    for (program, args) in pastes() {
+        match Command::new(program)
+            .args(*args)
+            .stderr(Stdio::inherit())
+            .output()
+        {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+            Ok(output) if output.status.success() => return Ok(output.stdout),
+            Ok(_) => anyhow::bail!("{} couldn't read the clipboard", program),
+        }
+    }
+    let names: Vec<&str> = pastes().iter().map(|(program, _)| *program).collect();
+    anyhow::bail!(
+        "no clipboard program on the host; install {}",
+        names.join(", ")
+    )
+}
+
+/// Runs `cmd` with its output going to `stream` and `input`, if any, on its
+/// standard input, returning its exit code.
+fn run(cmd: &mut Command, stream: &UnixStream, input: Option<&[u8]>) -> std::io::Result<i32> {
+    cmd.stdin(if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    })
+    .stdout(Stdio::from(OwnedFd::from(stream.try_clone()?)))
+    .stderr(Stdio::from(OwnedFd::from(stream.try_clone()?)));
+    let mut child = cmd.spawn()?;
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        // a clipboard program may well not read it all
+        let _ = stdin.write_all(input);
+    }
+    Ok(child.wait()?.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Shutdown;
+    use tempfile::tempdir;
+
+    #[test]
+    fn writes_helpers_for_the_session() {
+        let dir = tempdir().unwrap();
+        let helpers = dir.path().join("forest-feat-x");
+        let clipboard = ClipboardConfig {
+            copy: None,
+            osc52: true,
+        };
+        assert!(!clipboard.copy());
+        write(&helpers, "feat/x", "widget", &clipboard).unwrap();
+        let profile = fs::read_to_string(helpers.join("profile.sh")).unwrap();
+        assert!(profile.contains("export FOREST_OSC52=1\n"));
+        assert!(profile.contains("export FOREST_SESSION=feat/x\n"));
+        assert!(profile.contains("export FOREST_SOCKET=/forest/forest.sock\n"));
+        assert!(profile.contains("export PATH=\"/forest/bin:$PATH\""));
+        assert!(profile.contains("export BROWSER=\"${BROWSER:-/forest/bin/forest-open}\""));
+        for name in [
+            "forest",
+            "forest-call",
+            "forest-sync",
+            "forest-pr",
+            "forest-open",
+            "forest-copy",
+            "pbcopy",
+            "xclip",
+            "wl-paste",
+        ] {
+            let mode = fs::metadata(helpers.join("bin").join(name))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o111, 0o111, "{}", name);
+        }
+    }
+
+    #[test]
+    fn only_runs_allowed_commands_for_the_session() {
+        let forest = |args: &[&str]| Request::Forest(args.iter().map(|a| a.to_string()).collect());
+        assert_eq!(
+            parse_request("pr --draft --base release\n", "feat/x"),
+            Ok(forest(&[
+                "--non-interactive",
+                "pr",
+                "feat/x",
+                "--draft",
+                "--base",
+                "release"
+            ]))
+        );
+        assert_eq!(
+            parse_request("sync", "feat/x"),
+            Ok(forest(&["--non-interactive", "sync", "feat/x"]))
+        );
+        assert_eq!(
+            parse_request("open https://example.com/a?b=c\n", "feat/x"),
+            Ok(Request::Open("https://example.com/a?b=c".to_string()))
+        );
+        assert_eq!(parse_request("copy\n", "feat/x"), Ok(Request::Copy));
+        assert!(parse_request("rm other", "feat/x").is_err());
+        assert!(parse_request("sync other", "feat/x").is_err());
+        assert!(parse_request("pr --base --draft", "feat/x").is_err());
+        assert!(parse_request("open file:///etc/passwd", "feat/x").is_err());
+        assert!(parse_request("open --help", "feat/x").is_err());
+        assert!(parse_request("copy now", "feat/x").is_err());
+        assert!(parse_request("", "feat/x").is_err());
+    }
+
+    #[test]
+    fn clipboard_programs_copy_through_forest() {
+        let dir = tempdir().unwrap();
+        let helpers = dir.path().join("forest-feat-x");
+        write(&helpers, "feat/x", "widget", &ClipboardConfig::default()).unwrap();
+        let stub = dir.path().join("stub");
+        fs::create_dir(&stub).unwrap();
+        fs::write(stub.join("forest-copy"), "#!/bin/sh\necho copied\n").unwrap();
+        fs::set_permissions(stub.join("forest-copy"), fs::Permissions::from_mode(0o755)).unwrap();
+        for (program, args) in [
+            ("pbcopy", &[][..]),
+            ("xclip", &["-selection", "clipboard"][..]),
+            ("xsel", &["--clipboard", "--input"][..]),
+        ] {
+            let output = Command::new(helpers.join("bin").join(program))
+                .args(args)
+                .env("PATH", format!("{}:/usr/bin:/bin", stub.display()))
+                .output()
+                .unwrap();
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                "copied\n",
+                "{}",
+                program
+            );
+        }
+    }
+
+    #[test]
+    fn points_local_urls_at_published_ports() {
+        let published = |port| (port == 3000).then_some(49152);
+        assert_eq!(
+            host_url("http://localhost:3000/callback?code=x", published),
+            Ok("http://localhost:49152/callback?code=x".to_string())
+        );
+        assert_eq!(
+            host_url("http://127.0.0.1:3000", published),
+            Ok("http://localhost:49152".to_string())
+        );
+        assert_eq!(host_url("http://[::1]:8080/", published), Err(8080));
+        assert_eq!(host_url("https://localhost/", published), Err(443));
+        assert_eq!(
+            host_url("https://example.com:3000/", published),
+            Ok("https://example.com:3000/".to_string())
+        );
+    }
+
+    #[test]
+    fn answers_requests_on_its_socket() {
+        let dir = tempdir().unwrap();
+        let forest = dir.path().join("forest");
+        fs::write(&forest, "#!/bin/sh\necho \"ran $*\"\nexit 3\n").unwrap();
+        fs::set_permissions(&forest, fs::Permissions::from_mode(0o755)).unwrap();
+        let helpers = super::dir(dir.path(), "feat-x");
+        fs::create_dir_all(&helpers).unwrap();
+        let server = serve(&helpers, "feat-x", "feat/x", dir.path(), &forest, false).unwrap();
+        let other = serve(&helpers, "feat-x", "feat/x", dir.path(), &forest, false).unwrap();
+        assert!(other.socket.is_none());
+        assert_eq!(other.env(), server.env());
+        let mode = fs::metadata(token_path(&helpers))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let token = server.token.clone();
+
+        let request = |line: &str| {
+            let mut stream = UnixStream::connect(helpers.join(SOCKET)).unwrap();
+            stream.write_all(line.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        };
+        assert_eq!(
+            request(&format!("forest-token {}\nsync\n", token)),
+            "ran --non-interactive sync feat/x\nforest-exit 3\n"
+        );
+        assert!(
+            request(&format!("forest-token {}\nkill feat/x\n", token)).ends_with("forest-exit 2\n")
+        );
+        assert_eq!(
+            request("forest-token guess\nsync\n"),
+            "forest-token is missing or wrong\nforest-exit 2\n"
+        );
+        assert_eq!(
+            request("sync\n"),
+            "forest-token is missing or wrong\nforest-exit 2\n"
+        );
+        assert!(request(&format!("forest-token {}\ncopy\ntext", token))
+            .ends_with("is off; see copy under [clipboard] in forest.toml\nforest-exit 1\n"));
+        drop(other);
+        assert!(helpers.join(SOCKET).exists());
+        drop(server);
+        assert!(!helpers.join(SOCKET).exists());
+        remove(dir.path(), "feat-x").unwrap();
+        assert!(!helpers.exists() && !token_path(&helpers).exists());
+    }
+}
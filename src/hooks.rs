@@ -0,0 +1,99 @@
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+
+/// The `[hooks]` table of forest.toml: shell commands run around `open` and
+/// `kill`. Host hooks run with `sh -c` in the repository; a failing `pre_`
+/// hook stops the command, a failing `post_` hook only warns.
+#[derive(Deserialize, Default)]
+pub struct Hooks {
+    /// On the host before `open` starts the container
+    pub pre_open: Option<String>,
+    /// On the host once the session is up, before its shell starts
+    pub post_open: Option<String>,
+    /// Inside the container in `/code` once the session is up, before its
+    /// shell starts
+    pub post_open_in_container: Option<String>,
+    /// On the host before `kill` stops or removes the container
+    pub pre_kill: Option<String>,
+    /// On the host after `kill`
+    pub post_kill: Option<String>,
+}
+
+/// The session a hook runs for, passed to it as `FOREST_*` variables.
+pub struct Context<'a> {
+    pub session: &'a str,
+    pub label: &'a str,
+    pub repo: Option<&'a Path>,
+    pub worktree: Option<&'a Path>,
+}
+
+impl Context<'_> {
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("FOREST_SESSION", self.session.to_string()),
+            ("FOREST_LABEL", self.label.to_string()),
+        ];
+        if let Some(repo) = self.repo {
+            env.push(("FOREST_REPO", repo.display().to_string()));
+        }
+        if let Some(worktree) = self.worktree {
+            env.push(("FOREST_WORKTREE", worktree.display().to_string()));
+        }
+        env
+    }
+}
+
+/// Runs hook `hook` on the host, when it's configured.
+pub fn run(
+    hook: &str,
+    command: Option<&str>,
+    context: &Context,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let Some(command) = command else {
+        return Ok(());
+    };
+    println!("Running {} hook", hook);
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]).envs(context.env());
+    if let Some(repo) = context.repo {
+        cmd.current_dir(repo);
+    }
+    let status = crate::run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("{} hook failed: {}", hook, command);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn runs_host_hooks_with_session_env() {
+        let repo = tempdir().unwrap();
+        let context = Context {
+            session: "feat/x",
+            label: "feat-x",
+            repo: Some(repo.path()),
+            worktree: None,
+        };
+        run(
+            "post_open",
+            Some("printf %s \"$FOREST_SESSION\" > hook.out"),
+            &context,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("hook.out")).unwrap(),
+            "feat/x"
+        );
+        assert!(run("pre_kill", Some("exit 3"), &context, false).is_err());
+        assert!(run("pre_kill", None, &context, false).is_ok());
+    }
+}
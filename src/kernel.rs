@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::Path;
+
+/// Distribution family, from `/etc/os-release`, picking the remediation hints.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Distro {
+    Debian,
+    Fedora,
+    Arch,
+    Suse,
+    Alpine,
+    Other,
+}
+
+impl Distro {
+    fn detect(root: &Path) -> Distro {
+        let release = fs::read_to_string(root.join("etc/os-release")).unwrap_or_default();
+        let mut ids = Vec::new();
+        for line in release.lines() {
+            if let Some(value) = line
+                .strip_prefix("ID=")
+                .or_else(|| line.strip_prefix("ID_LIKE="))
+            {
+                ids.extend(
+                    value
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .map(str::to_string),
+                );
+            }
+        }
+        for id in ids {
+            let distro = match id.as_str() {
+                "debian" | "ubuntu" => Distro::Debian,
+                "fedora" | "rhel" | "centos" => Distro::Fedora,
+                "arch" => Distro::Arch,
+                "suse" | "opensuse" => Distro::Suse,
+                "alpine" => Distro::Alpine,
+                _ => continue,
+            };
+            return distro;
+        }
+        Distro::Other
+    }
+
+    fn install(self, package: &str) -> String {
+        match self {
+            Distro::Debian => format!("sudo apt install {}", package),
+            Distro::Fedora => format!("sudo dnf install {}", package),
+            Distro::Arch => format!("sudo pacman -S {}", package),
+            Distro::Suse => format!("sudo zypper install {}", package),
+            Distro::Alpine => format!("sudo apk add {}", package),
+            Distro::Other => format!("install {} with your package manager", package),
+        }
+    }
+
+    /// How to boot with `param` on the kernel command line.
+    fn kernel_param(self, param: &str) -> String {
+        match self {
+            Distro::Fedora => format!("sudo grubby --update-kernel=ALL --args={}", param),
+            Distro::Debian => format!(
+                "add {} to GRUB_CMDLINE_LINUX in /etc/default/grub, run `sudo update-grub`",
+                param
+            ),
+            Distro::Suse => format!(
+                "add {} to GRUB_CMDLINE_LINUX_DEFAULT in /etc/default/grub, run \
+                 `sudo grub2-mkconfig -o /boot/grub2/grub.cfg`",
+                param
+            ),
+            _ => format!(
+                "add {} to the kernel command line in your bootloader",
+                param
+            ),
+        }
+    }
+}
+
+fn read(root: &Path, path: &str) -> Option<String> {
+    fs::read_to_string(root.join(path))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn is_wsl(root: &Path) -> bool {
+    read(root, "proc/version").is_some_and(|v| v.to_ascii_lowercase().contains("microsoft"))
+}
+
+fn user_namespaces(root: &Path, distro: Distro) -> Option<String> {
+    if read(root, "proc/sys/user/max_user_namespaces").as_deref() == Some("0") {
+        return Some(
+            "user namespaces are disabled (user.max_user_namespaces = 0); fix: \
+             `echo user.max_user_namespaces=15000 | sudo tee /etc/sysctl.d/userns.conf \
+             && sudo sysctl --system`"
+                .to_string(),
+        );
+    }
+    // Debian and older Ubuntu kernels gate unprivileged namespaces separately
+    if read(root, "proc/sys/kernel/unprivileged_userns_clone").as_deref() == Some("0") {
+        let hint = match distro {
+            Distro::Debian | Distro::Other => {
+                "`echo kernel.unprivileged_userns_clone=1 | sudo tee /etc/sysctl.d/userns.conf \
+                 && sudo sysctl --system`"
+            }
+            _ => "enable kernel.unprivileged_userns_clone with sysctl",
+        };
+        return Some(format!(
+            "unprivileged user namespaces are disabled (kernel.unprivileged_userns_clone = 0); \
+             fix: {}",
+            hint
+        ));
+    }
+    None
+}
+
+fn cgroups_v2(root: &Path, distro: Distro) -> Option<String> {
+    if root.join("sys/fs/cgroup/cgroup.controllers").exists() {
+        return None;
+    }
+    let hint = if is_wsl(root) {
+        "set `kernelCommandLine = cgroup_no_v1=all` under [wsl2] in %UserProfile%\\.wslconfig, \
+         run `wsl --shutdown`"
+            .to_string()
+    } else {
+        distro.kernel_param("systemd.unified_cgroup_hierarchy=1")
+    };
+    Some(format!(
+        "cgroups v2 is not mounted at /sys/fs/cgroup, so rootless containers can't set \
+         resource limits; fix: {}, then reboot",
+        hint
+    ))
+}
+
+fn overlayfs(root: &Path, distro: Distro) -> Option<String> {
+    let filesystems = read(root, "proc/filesystems").unwrap_or_default();
+    if filesystems
+        .lines()
+        .any(|l| l.split_whitespace().last() == Some("overlay"))
+    {
+        return None;
+    }
+    Some(format!(
+        "the overlay filesystem is not available; fix: `sudo modprobe overlay` and \
+         `echo overlay | sudo tee /etc/modules-load.d/overlay.conf`, or `{}` for \
+         rootless storage",
+        distro.install("fuse-overlayfs")
+    ))
+}
+
+/// Kernel features rootless podman needs that are missing under `root`
+/// (`/` outside of tests), each with a fix for the detected distribution.
+pub fn check(root: &Path) -> Vec<String> {
+    let distro = Distro::detect(root);
+    [
+        user_namespaces(root, distro),
+        cgroups_v2(root, distro),
+        overlayfs(root, distro),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Lays out the kernel interfaces of a host podman runs well on under `root`,
+/// for tests of the checks.
+pub fn healthy_root(root: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(root.join("proc/sys/user"))?;
+    fs::create_dir_all(root.join("sys/fs/cgroup"))?;
+    fs::write(root.join("proc/sys/user/max_user_namespaces"), "63000\n")?;
+    fs::write(
+        root.join("sys/fs/cgroup/cgroup.controllers"),
+        "cpu memory pids\n",
+    )?;
+    fs::write(
+        root.join("proc/filesystems"),
+        "nodev\tproc\nnodev\toverlay\n",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn passes_on_a_healthy_kernel() {
+        let root = tempdir().unwrap();
+        healthy_root(root.path()).unwrap();
+        assert!(check(root.path()).is_empty());
+    }
+
+    #[test]
+    fn hints_for_the_distribution() {
+        let root = tempdir().unwrap();
+        healthy_root(root.path()).unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/os-release"),
+            "ID=pop\nID_LIKE=\"ubuntu debian\"\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("proc/sys/user/max_user_namespaces"), "0\n").unwrap();
+        fs::remove_file(root.path().join("sys/fs/cgroup/cgroup.controllers")).unwrap();
+        fs::write(root.path().join("proc/filesystems"), "nodev\tproc\n").unwrap();
+
+        let problems = check(root.path());
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("user.max_user_namespaces"));
+        assert!(problems[1].contains("update-grub"));
+        assert!(problems[2].contains("sudo apt install fuse-overlayfs"));
+    }
+
+    #[test]
+    fn detects_distributions() {
+        let root = tempdir().unwrap();
+        assert_eq!(Distro::detect(root.path()), Distro::Other);
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/os-release"),
+            "NAME=\"Rocky Linux\"\nID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n",
+        )
+        .unwrap();
+        assert_eq!(Distro::detect(root.path()), Distro::Fedora);
+    }
+}
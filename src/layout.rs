@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::{run_command_verbose, run_output_verbose, shell};
+
+/// tmux layout of a preset's panes unless it names another, panes of equal
+/// size. `main-vertical` would give the others a column each in the 80x24 of
+/// a detached tmux session.
+pub const DEFAULT_ARRANGE: &str = "tiled";
+
+/// A `[layouts.<name>]` preset in forest.toml: sessions `forest layout apply`
+/// opens, each in a tmux window split into the same panes.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Preset {
+    /// Session names, or patterns of sessions of the current repository
+    pub sessions: Vec<String>,
+    /// Commands run in each session's container, a pane each; an empty one
+    /// is the session's shell
+    #[serde(default = "shell_only")]
+    pub panes: Vec<String>,
+    /// tmux layout the panes are arranged in, such as `main-vertical`
+    pub arrange: Option<String>,
+}
+
+fn shell_only() -> Vec<String> {
+    vec![String::new()]
+}
+
+impl Preset {
+    pub fn arrange(&self) -> &str {
+        self.arrange.as_deref().unwrap_or(DEFAULT_ARRANGE)
+    }
+}
+
+/// Preset `name` of `presets`, checked to have sessions and panes.
+pub fn find<'a>(presets: &'a BTreeMap<String, Preset>, name: &str) -> anyhow::Result<&'a Preset> {
+    let Some(preset) = presets.get(name) else {
+        if presets.is_empty() {
+            anyhow::bail!(
+                "no layout preset {}; define one under [layouts.{}] in forest.toml",
+                name,
+                name
+            );
+        }
+        let known: Vec<&str> = presets.keys().map(String::as_str).collect();
+        anyhow::bail!(
+            "no layout preset {}; forest.toml defines {}",
+            name,
+            known.join(", ")
+        );
+    };
+    if preset.sessions.is_empty() {
+        anyhow::bail!("layout preset {} lists no sessions", name);
+    }
+    if preset.panes.is_empty() {
+        anyhow::bail!("layout preset {} has no panes", name);
+    }
+    Ok(preset)
+}
+
+/// tmux session holding preset `name`'s windows. tmux would turn the `.` and
+/// `:` of a session name into `_` itself.
+pub fn tmux_session(name: &str) -> String {
+    format!("forest-{}", name.replace(['.', ':'], "_"))
+}
+
+/// Shell command a pane runs for `pane` of session `name`: `forest attach`
+/// for the shell, `forest exec` for anything else.
+pub fn pane_command(forest: &Path, name: &str, pane: &str) -> String {
+    let forest = shell::quote(&forest.to_string_lossy());
+    if pane.trim().is_empty() {
+        format!("{} attach {}", forest, shell::quote(name))
+    } else {
+        format!(
+            "{} exec {} -- sh -c {}",
+            forest,
+            shell::quote(name),
+            shell::quote(pane)
+        )
+    }
+}
+
+/// Runs tmux with `args`, returning what it printed.
+fn tmux(args: &[&str], verbose: bool) -> anyhow::Result<String> {
+    let mut cmd = Command::new("tmux");
+    cmd.args(args);
+    let output = run_output_verbose(&mut cmd, verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("tmux command not found; layouts are tmux windows")
+        } else {
+            e.into()
+        }
+    })?;
+    if !output.status.success() {
+        anyhow::bail!("tmux {} failed", args[0]);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Creates tmux session `target` for `preset`: a window per session of
+/// `names`, named after it, split into the preset's panes started in `dir`.
+/// Returns false when tmux already has the session, which is left as it is.
+pub fn build(
+    target: &str,
+    preset: &Preset,
+    names: &[String],
+    forest: &Path,
+    dir: &Path,
+    verbose: bool,
+) -> anyhow::Result<bool> {
+    let exact = format!("={}", target);
+    let exists = Command::new("tmux")
+        .args(["has-session", "-t", &exact])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if exists {
+        return Ok(false);
+    }
+    let dir = dir.to_string_lossy();
+    // windows are addressed by id: a session name can hold `.`, which tmux
+    // targets read as a pane
+    let in_session = format!("{}:", exact);
+    for (i, name) in names.iter().enumerate() {
+        let mut panes = preset
+            .panes
+            .iter()
+            .map(|pane| pane_command(forest, name, pane));
+        let first = panes.next().unwrap_or_default();
+        let mut create = if i == 0 {
+            vec!["new-session", "-d", "-s", target]
+        } else {
+            vec!["new-window", "-d", "-t", &in_session]
+        };
+        create.extend(["-P", "-F", "#{window_id}", "-n", name, "-c", &dir, &first]);
+        let window = tmux(&create, verbose)?;
+        // a pane whose command ends shows how, instead of closing
+        tmux(
+            &["set-option", "-w", "-t", &window, "remain-on-exit", "on"],
+            verbose,
+        )?;
+        for pane in panes {
+            tmux(
+                &["split-window", "-d", "-t", &window, "-c", &dir, &pane],
+                verbose,
+            )?;
+            // arranged after each split, so that the next one finds room
+            tmux(&["select-layout", "-t", &window, preset.arrange()], verbose)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Shows tmux session `target` in this terminal, switching to it from inside
+/// tmux.
+pub fn attach(target: &str, verbose: bool) -> anyhow::Result<()> {
+    let exact = format!("={}", target);
+    let action = if std::env::var_os("TMUX").is_some() {
+        "switch-client"
+    } else {
+        "attach-session"
+    };
+    let mut cmd = Command::new("tmux");
+    cmd.args([action, "-t", &exact]);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("tmux {} failed", action);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_presets_and_their_panes() {
+        let presets: BTreeMap<String, Preset> = toml::from_str(
+            r#"
+            [review]
+            sessions = ["feat/login", "feat/signup"]
+            panes = ["", "cargo watch -x test", "tail -F /tmp/app.log"]
+            arrange = "main-horizontal"
+
+            [solo]
+            sessions = ["fix"]
+
+            [empty]
+            sessions = []
+            "#,
+        )
+        .unwrap();
+        let review = find(&presets, "review").unwrap();
+        assert_eq!(review.arrange(), "main-horizontal");
+        let solo = find(&presets, "solo").unwrap();
+        assert_eq!(solo.panes, [""]);
+        assert_eq!(solo.arrange(), DEFAULT_ARRANGE);
+        assert!(find(&presets, "empty").is_err());
+        let missing = find(&presets, "nope").unwrap_err().to_string();
+        assert!(missing.contains("empty, review, solo"), "{}", missing);
+
+        let forest = Path::new("/usr/bin/forest");
+        assert_eq!(
+            pane_command(forest, "feat/login", &review.panes[0]),
+            "/usr/bin/forest attach feat/login"
+        );
+        assert_eq!(
+            pane_command(forest, "feat/login", &review.panes[1]),
+            "/usr/bin/forest exec feat/login -- sh -c 'cargo watch -x test'"
+        );
+        assert_eq!(tmux_session("v1.2"), "forest-v1_2");
+    }
+}
@@ -0,0 +1,170 @@
+//! Library side of forest, shared by the `forest` binary and its tests. The
+//! `session` module drives sessions the way the commands do; `config`, `git`
+//! and `backend` hold what they're built on.
+
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub mod act;
+pub mod backend;
+pub mod bulk;
+pub mod bundle;
+pub mod checkpoint;
+pub mod ci;
+pub mod cli;
+pub mod compat;
+pub mod completions;
+pub mod config;
+pub mod devcontainer;
+pub mod drift;
+pub mod egress;
+pub mod env;
+pub mod failpoints;
+pub mod features;
+pub mod gh_extension;
+pub mod git;
+pub mod github;
+pub mod helpers;
+pub mod hooks;
+pub mod kernel;
+pub mod layout;
+pub mod locks;
+pub mod matrix;
+pub mod mux;
+pub mod names;
+pub mod policy;
+pub mod priority;
+pub mod prune;
+pub mod registry;
+pub mod report;
+pub mod resources;
+pub mod review;
+pub mod runtime;
+pub mod scaffold;
+pub mod scan;
+pub mod secrets;
+pub mod selftest;
+pub mod session;
+pub mod sessions;
+pub mod shell;
+pub mod snapshots;
+pub mod store;
+pub mod style;
+pub mod telemetry;
+pub mod template;
+pub mod timesheet;
+pub mod trash;
+pub mod wip;
+
+pub fn run_command_verbose(
+    cmd: &mut Command,
+    verbose: bool,
+) -> std::io::Result<std::process::ExitStatus> {
+    let span = command_span(cmd, verbose);
+    let status = match failpoints::check(cmd) {
+        Some(simulated) => simulated?,
+        None => cmd.status()?,
+    };
+    span.set("process.exit.code", status.code().unwrap_or(-1));
+    Ok(status)
+}
+
+/// Like `run_command_verbose`, capturing stdout; stderr still reaches the user.
+pub fn run_output_verbose(
+    cmd: &mut Command,
+    verbose: bool,
+) -> std::io::Result<std::process::Output> {
+    let span = command_span(cmd, verbose);
+    let output = match failpoints::check(cmd) {
+        Some(simulated) => std::process::Output {
+            status: simulated?,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        },
+        None => cmd.stderr(Stdio::inherit()).output()?,
+    };
+    span.set("process.exit.code", output.status.code().unwrap_or(-1));
+    Ok(output)
+}
+
+pub fn command_span(cmd: &Command, verbose: bool) -> telemetry::Span {
+    if verbose {
+        println!("Running: {}", shell::display(cmd));
+    }
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let span = telemetry::span(&format!("exec {}", program));
+    span.set("process.executable.name", &program);
+    span.set(
+        "process.command_args",
+        secrets::redact(
+            &cmd.get_args()
+                .map(|a| a.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    );
+    span
+}
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Stops forest asking questions, for `--non-interactive`.
+pub fn set_non_interactive(on: bool) {
+    NON_INTERACTIVE.store(on, Ordering::Relaxed);
+}
+
+/// Whether forest may ask a question: stdin is a terminal and
+/// `--non-interactive` wasn't given. Without one, questions take their
+/// default answer.
+pub fn interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed) && std::io::stdin().is_terminal()
+}
+
+pub fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub fn copy_dir(src: &Path, dest: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn command_exists(cmd: &str) -> bool {
+    Command::new(cmd)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_exists_detects_commands() {
+        assert!(command_exists("true"));
+        assert!(!command_exists("definitely_not_a_command"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Path of the worktree `.git` file recorded by forest-managed worktrees, which
+/// are always created at `/code` inside the container.
+const FOREST_GITDIR: &str = "/code/.git";
+
+/// Lock files untouched for this long belong to a git process that died.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Clears locks left behind in `<git_dir>/worktrees` when a container died in the
+/// middle of a worktree operation.
+///
+/// Only worktrees forest created are touched. An explicit `locked` marker is
+/// removed for the worktree checked out on `branch`, which is about to be
+/// recreated; leftover `*.lock` files are removed once they are old enough that
+/// no live git process can own them.
+pub fn recover_stale_worktree_locks(
+    git_dir: &Path,
+    branch: &str,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(git_dir.join("worktrees")) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let admin = entry?.path();
+        let gitdir = fs::read_to_string(admin.join("gitdir")).unwrap_or_default();
+        if gitdir.trim() != FOREST_GITDIR {
+            continue;
+        }
+        let head = fs::read_to_string(admin.join("HEAD")).unwrap_or_default();
+        let ours = head.trim() == format!("ref: refs/heads/{}", branch);
+
+        let locked = admin.join("locked");
+        if ours && locked.exists() {
+            let reason = fs::read_to_string(&locked).unwrap_or_default();
+            let reason = match reason.trim() {
+                "" => "no reason given".to_string(),
+                r => r.to_string(),
+            };
+            crate::style::warn(format!(
+                "removing stale lock on worktree {} ({})",
+                admin.display(),
+                reason
+            ));
+            fs::remove_file(&locked)?;
+        }
+
+        for file in fs::read_dir(&admin)? {
+            let path = file?.path();
+            if path.extension().map(|e| e != "lock").unwrap_or(true) {
+                continue;
+            }
+            let age = fs::metadata(&path)?
+                .modified()
+                .ok()
+                .and_then(|m| SystemTime::now().duration_since(m).ok())
+                .unwrap_or_default();
+            if age < STALE_LOCK_AGE {
+                if verbose {
+                    println!("Leaving recent lock {}", path.display());
+                }
+                continue;
+            }
+            crate::style::warn(format!("removing stale lock file {}", path.display()));
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Locks git itself takes on the shared repository for ref, index and config
+/// updates.
+const GIT_LOCKS: [&str; 4] = ["index.lock", "HEAD.lock", "packed-refs.lock", "config.lock"];
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Advisory lock serialising forest's git mutations on one repository across
+/// sessions. Released when dropped.
+pub struct RepoLock {
+    path: PathBuf,
+}
+
+impl RepoLock {
+    /// Takes `<git_dir>/forest.lock`, waiting up to `timeout` for other forest
+    /// processes and for git's own locks on the repository to be released.
+    pub fn acquire(git_dir: &Path, timeout: Duration, verbose: bool) -> anyhow::Result<Self> {
+        let path = git_dir.join("forest.lock");
+        let deadline = Instant::now() + timeout;
+        let mut announced = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", std::process::id())?;
+                    let lock = RepoLock { path };
+                    wait_for_git_locks(git_dir, deadline, verbose)?;
+                    return Ok(lock);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            let holder = fs::read_to_string(&path).unwrap_or_default();
+            let holder = holder.trim();
+            if !holder.is_empty() && !process_alive(holder) {
+                crate::style::warn(format!(
+                    "removing forest lock {} left by exited process {}",
+                    path.display(),
+                    holder
+                ));
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "timed out after {}s waiting for another forest process (pid {}) \
+                     holding {}",
+                    timeout.as_secs(),
+                    holder,
+                    path.display()
+                );
+            }
+            // sessions of a bulk command wait for each other quietly
+            if !announced && holder != std::process::id().to_string() {
+                println!(
+                    "Waiting for another forest process (pid {}) working on this repository",
+                    holder
+                );
+                announced = true;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn process_alive(pid: &str) -> bool {
+    Command::new("kill")
+        .args(["-0", pid])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(true)
+}
+
+/// Waits until none of git's repository-wide lock files exist.
+fn wait_for_git_locks(git_dir: &Path, deadline: Instant, verbose: bool) -> anyhow::Result<()> {
+    for name in GIT_LOCKS {
+        let path = git_dir.join(name);
+        let mut announced = false;
+        while path.exists() {
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "timed out waiting for {}; if no git process is running in any \
+                     session, remove the file and retry",
+                    path.display()
+                );
+            }
+            if !announced && verbose {
+                println!("Waiting for git lock {}", path.display());
+            }
+            announced = true;
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn unlocks_only_the_sessions_forest_worktree() {
+        let git_dir = tempdir().unwrap();
+        let worktrees = git_dir.path().join("worktrees");
+        for (id, gitdir, branch) in [
+            ("code", FOREST_GITDIR, "feat"),
+            ("code1", FOREST_GITDIR, "other"),
+            ("mine", "/home/me/mine/.git", "feat"),
+        ] {
+            let admin = worktrees.join(id);
+            fs::create_dir_all(&admin).unwrap();
+            fs::write(admin.join("gitdir"), format!("{}\n", gitdir)).unwrap();
+            fs::write(admin.join("HEAD"), format!("ref: refs/heads/{}\n", branch)).unwrap();
+            fs::write(admin.join("locked"), "initializing").unwrap();
+            fs::write(admin.join("index.lock"), "").unwrap();
+        }
+
+        recover_stale_worktree_locks(git_dir.path(), "feat", false).unwrap();
+
+        assert!(!worktrees.join("code").join("locked").exists());
+        assert!(worktrees.join("code1").join("locked").exists());
+        assert!(worktrees.join("mine").join("locked").exists());
+        // freshly written lock files may still belong to a running git
+        assert!(worktrees.join("code").join("index.lock").exists());
+    }
+
+    #[test]
+    fn repo_lock_is_exclusive_and_released_on_drop() {
+        let git_dir = tempdir().unwrap();
+        let lock = RepoLock::acquire(git_dir.path(), Duration::from_secs(1), false).unwrap();
+        assert!(RepoLock::acquire(git_dir.path(), Duration::ZERO, false).is_err());
+        drop(lock);
+        assert!(RepoLock::acquire(git_dir.path(), Duration::ZERO, false).is_ok());
+    }
+
+    #[test]
+    fn repo_lock_times_out_on_git_lock() {
+        let git_dir = tempdir().unwrap();
+        fs::write(git_dir.path().join("index.lock"), "").unwrap();
+        let err = RepoLock::acquire(git_dir.path(), Duration::ZERO, false)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("index.lock"));
+        assert!(!git_dir.path().join("forest.lock").exists());
+    }
+}
@@ -1,276 +1,1294 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
 use std::fs;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::str;
+use std::time::Duration;
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches};
 use directories::ProjectDirs;
-use serde::Deserialize;
-use serde_json::Value;
+use forest::cli::{
+    BundleAction, Cli, Commands, GhExtensionAction, LayoutAction, MatrixAction, SnapshotAction,
+    TemplateAction, TrashAction,
+};
+use forest::config::*;
+use forest::git::*;
+use forest::names::{sanitize_podman_name, valid_podman_name};
+use forest::session::*;
+use forest::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-use std::process::Stdio;
+mod topics;
 
-fn run_command_verbose(
-    cmd: &mut Command,
-    verbose: bool,
-) -> std::io::Result<std::process::ExitStatus> {
-    if verbose {
-        println!("Running: {:?}", cmd);
+/// A command run by `forest exec` failed; forest exits with the same status
+/// instead of reporting an error of its own.
+#[derive(Debug)]
+struct CommandExit(u8);
+
+impl std::fmt::Display for CommandExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "command exited with status {}", self.0)
     }
-    cmd.status()
 }
 
-fn sanitize_podman_name(branch: &str) -> String {
-    let mut name: String = branch
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' {
-                c
-            } else {
-                '-'
+impl std::error::Error for CommandExit {}
+
+fn main() -> std::process::ExitCode {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if let Ok(dir) = data_dir() {
+            let _ = report::record(&dir, &report::Failure::new(info, true));
+        }
+        style::note(report::HINT);
+    }));
+
+    let matches = Cli::command().get_matches();
+    let result = Cli::from_arg_matches(&matches)
+        .map_err(anyhow::Error::from)
+        .and_then(|cli| {
+            let command = matches.subcommand_name().unwrap_or_default();
+            start(cli, command)
+        });
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            if let Some(exit) = e.downcast_ref::<CommandExit>() {
+                return std::process::ExitCode::from(exit.0);
             }
-        })
-        .collect();
-    if name
-        .chars()
-        .next()
-        .map(|c| !c.is_ascii_alphanumeric())
-        .unwrap_or(true)
-    {
-        name.insert(0, 's');
+            style::error(format!("{:#}", e));
+            if let Ok(dir) = data_dir() {
+                let _ = report::record(&dir, &report::Failure::new(format!("{:#}", e), false));
+                style::note(report::HINT);
+            }
+            std::process::ExitCode::FAILURE
+        }
     }
-    name
 }
 
-fn valid_podman_name(name: &str) -> bool {
-    let mut chars = name.chars();
-    match chars.next() {
-        Some(c) if c.is_ascii_alphanumeric() => {}
-        _ => return false,
+fn start(cli: Cli, command: &str) -> anyhow::Result<()> {
+    let mut config = load_config()?;
+    let plain = cli.plain
+        || config.plain.unwrap_or(false)
+        || std::env::var("TERM").is_ok_and(|term| term == "dumb");
+    style::init(cli.color, config.theme.unwrap_or_default(), plain);
+    if plain {
+        // for the tools forest runs too: devcontainer, image builds and the
+        // commands of `exec`
+        std::env::set_var("NO_COLOR", "1");
+        std::env::set_var("BUILDKIT_PROGRESS", "plain");
     }
-    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
-}
+    forest::set_non_interactive(cli.non_interactive);
+    backend::init(
+        cli.command
+            .backend()
+            .map(backend::Kind::from)
+            .or(config.backend)
+            .unwrap_or_default(),
+    );
+    // the command line comes last so its flags win over the config's
+    devcontainer::init(
+        config
+            .devcontainer_args
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .chain(cli.devcontainer_args)
+            .collect(),
+    );
+    config.features =
+        features::resolve(std::mem::take(&mut config.features), &cli.enable_features)?;
+    let verbose = cli.verbose;
 
-fn ensure_git_setup(branch: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
-    // Are we inside a git repository?
-    if verbose {
-        println!("Checking git repository root");
-    }
-    let output = Command::new("git")
-        .args(["rev-parse", "--show-toplevel"])
-        .stderr(Stdio::null())
-        .output();
-    let repo_root = match output {
-        Ok(o) if o.status.success() => {
-            let path = str::from_utf8(&o.stdout)?.trim();
-            PathBuf::from(path)
-        }
-        _ => return Ok(()),
+    if let Some(endpoint) = &config.otlp_endpoint {
+        telemetry::init(endpoint);
+    }
+    let result = {
+        let span = telemetry::span(&format!("forest {}", command));
+        let result = run(cli.command, config, verbose);
+        if let Err(e) = &result {
+            span.error(&e.to_string());
+        }
+        result
     };
+    telemetry::flush(verbose);
+    result
+}
 
-    // Check if branch exists
-    let branch_exists = Command::new("git")
-        .args(["show-ref", "--verify", &format!("refs/heads/{}", branch)])
-        .current_dir(&repo_root)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false);
-
-    if !branch_exists {
-        if verbose {
-            println!("Creating git branch {}", branch);
+fn run(command: Commands, mut config: Config, verbose: bool) -> anyhow::Result<()> {
+    match command {
+        Commands::Open {
+            names,
+            devcontainer_env,
+            jj,
+            container_image,
+            features,
+            network,
+            allow_hosts,
+            reset_overrides,
+            restore_wip,
+            readonly,
+            from,
+            no_fetch,
+            also_mount,
+            priority,
+            cpus,
+            memory,
+            detach,
+            scaffold,
+            adopt,
+            env,
+            env_file,
+            jobs,
+            ..
+        } => {
+            let names = match jj {
+                Some(change) => {
+                    features::require(&config.features, "jj")?;
+                    if names.len() > 1 {
+                        anyhow::bail!("--jj opens one session");
+                    }
+                    let name = names.into_iter().next();
+                    vec![open_jj_change(&change, name, &config, verbose)?]
+                }
+                None => {
+                    let mut opened = Vec::new();
+                    for name in names {
+                        opened.push(match github::parse(&name) {
+                            Some(target) => open_github_url(&target, &config, verbose)?,
+                            None => name,
+                        });
+                    }
+                    expand_sessions(&opened, false, &config)?
+                }
+            };
+            let changes = Overrides {
+                image: container_image,
+                features,
+                network: network.map(egress::Mode::from),
+                allow_hosts,
+                readonly,
+                // stored resolved, so later opens from elsewhere mount the same
+                mounts: also_mount
+                    .iter()
+                    .map(|spec| {
+                        let (source, target) = parse_mount(spec, &std::env::current_dir()?)?;
+                        Ok(format!("{}:{}", source.display(), target))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+                priority,
+                platform: None,
+                cpus: cpus
+                    .map(|cpus| resources::parse_cpus(&cpus.to_string()))
+                    .transpose()
+                    .map_err(anyhow::Error::msg)?,
+                memory: memory
+                    .map(|memory| resources::parse_memory(&memory))
+                    .transpose()
+                    .map_err(anyhow::Error::msg)?,
+            };
+            let options = OpenOptions {
+                reset: reset_overrides,
+                restore_wip,
+                // there's one terminal for the shells of several sessions
+                detach: detach || names.len() > 1,
+                scaffold,
+                adopt,
+                env: env::from_flags(&env, &env_file)?,
+                rebuild: None,
+                from,
+                no_fetch,
+            };
+            let open = |name: &str| {
+                open_session(
+                    name,
+                    config.devcontainer_env(devcontainer_env.as_deref()),
+                    &changes,
+                    &options,
+                    &config,
+                    verbose,
+                )
+                .map(|_| ())
+            };
+            if let [name] = &names[..] {
+                open(name)?;
+            } else {
+                set_non_interactive(true);
+                bulk::summarize("open", &names, bulk::run(&names, jobs, open))?;
+            }
         }
-        let mut cmd = Command::new("git");
-        cmd.args(["branch", branch]).current_dir(&repo_root);
-        let status = run_command_verbose(&mut cmd, verbose)?;
-        if !status.success() {
-            anyhow::bail!("git branch failed");
+        Commands::Main { devcontainer_env } => {
+            let (repo_root, _) = current_repo("main")?;
+            let changes = Overrides {
+                readonly: true,
+                ..Overrides::default()
+            };
+            open_session(
+                &default_branch(&repo_root, config.remotes.base())?,
+                config.devcontainer_env(devcontainer_env.as_deref()),
+                &changes,
+                &OpenOptions::default(),
+                &config,
+                verbose,
+            )?;
+        }
+        Commands::Attach { name } => attach_session(&name, &config, verbose)?,
+        Commands::Rebuild {
+            name,
+            no_cache,
+            detach,
+        } => {
+            let rebuild = if no_cache {
+                Rebuild::NoCache
+            } else {
+                Rebuild::Cached
+            };
+            rebuild_session(&name, rebuild, detach, &config, verbose)?
+        }
+        Commands::Paste { name } => paste_session(&name, &config, verbose)?,
+        Commands::Drift { name } => drift_session(&name, &config)?,
+        Commands::Pr { name, draft, base } => {
+            open_pull_request(&name, draft, base.as_deref(), &config, verbose)?
+        }
+        Commands::Renice { name, priority } => renice_session(&name, priority, verbose)?,
+        Commands::Kill {
+            names,
+            all,
+            jobs,
+            down,
+            purge,
+            push,
+            force,
+            stash,
+            ..
+        } => {
+            let tier = if purge {
+                KillTier::Purge
+            } else if down {
+                KillTier::Down
+            } else {
+                config.kill_tier.unwrap_or_default()
+            };
+            let options = KillOptions { push, force, stash };
+            let names = expand_sessions(&names, !all, &config)?;
+            let kill = |name: &str| kill_session(name, tier, &options, &config, verbose);
+            if let [name] = &names[..] {
+                kill(name)?;
+            } else {
+                // the answers of several sessions' questions would get mixed up
+                set_non_interactive(true);
+                bulk::summarize("kill", &names, bulk::run(&names, jobs, kill))?;
+            }
+        }
+        Commands::Rm {
+            name,
+            keep_branch,
+            keep_worktree,
+        } => remove_session(&name, keep_branch, keep_worktree, &config, verbose)?,
+        Commands::Rename { name, new_name } => rename_session(&name, &new_name, &config, verbose)?,
+        Commands::Prune { yes } => prune_sessions(yes, &config, verbose)?,
+        Commands::Ls { all, .. } => list_sessions(all, &config, verbose)?,
+        Commands::Status { name } => session_status(&name, &config)?,
+        Commands::AdoptBranch { name, restore } => adopt_branch(&name, restore, &config, verbose)?,
+        Commands::Inspect { name, json } => inspect_session(&name, json)?,
+        Commands::Migrate => migrate_sessions()?,
+        Commands::Precheck => precheck(verbose)?,
+        Commands::Time { since } => show_time(since.as_deref())?,
+        Commands::Focus { name } => focus_session(&name, &config, verbose)?,
+        Commands::Unfocus => unfocus(verbose)?,
+        Commands::Subtask { session, name } => open_subtask(&session, &name, &config, verbose)?,
+        Commands::Exec {
+            names,
+            group_output,
+            command,
+        } => {
+            let layout = if group_output {
+                mux::Layout::Grouped
+            } else {
+                mux::Layout::Interleaved
+            };
+            exec_sessions(&names, &command, layout, &config, verbose)?
+        }
+        Commands::SelfTest { stub } => selftest::run(&worktrees_dir(&config), stub, verbose)?,
+        Commands::Checkpoint { name } => {
+            features::require(&config.features, "checkpoint")?;
+            checkpoint_session(&name, verbose)?
+        }
+        Commands::Resume { name } => {
+            features::require(&config.features, "checkpoint")?;
+            resume_session(&name, verbose)?
+        }
+        Commands::Features => show_features(&config),
+        Commands::Init { template } => init_devcontainer(template, &config)?,
+        Commands::Validate { devcontainer_env } => validate_devcontainer(
+            config.devcontainer_env(devcontainer_env.as_deref()),
+            verbose,
+        )?,
+        Commands::Sanitize { names } => show_sanitized(&names),
+        Commands::Overlay { edit } => overlay_command(edit, verbose)?,
+        Commands::Sync { name } => sync_session(&name, &config, verbose)?,
+        Commands::Act { name, job } => act_session(&name, job.as_deref(), &config, verbose)?,
+        Commands::CiExec {
+            reference,
+            devcontainer_env,
+            timeout,
+            command,
+        } => ci_exec(
+            &reference,
+            &command,
+            devcontainer_env.as_deref(),
+            timeout,
+            &config,
+            verbose,
+        )?,
+        Commands::Matrix { action } => matrix_command(action, &config, verbose)?,
+        Commands::Layout { action } => layout_command(action, &config, verbose)?,
+        Commands::Help { topic } => show_help(&topic)?,
+        Commands::Man { output } => write_man_pages(&output)?,
+        Commands::Scan { target } => scan_command(&target, &config, verbose)?,
+        Commands::Review {
+            pr,
+            sandbox,
+            allow_hosts,
+            devcontainer_env,
+        } => {
+            let pr = review::parse_pr(&pr)?;
+            if sandbox {
+                open_sandbox(
+                    pr,
+                    config.devcontainer_env(devcontainer_env.as_deref()),
+                    &allow_hosts,
+                    &config,
+                    verbose,
+                )?;
+            } else {
+                let (repo_root, _) = current_repo("review")?;
+                let branch = review::fetch(&repo_root, pr, verbose)?;
+                open_session(
+                    &branch,
+                    config.devcontainer_env(devcontainer_env.as_deref()),
+                    &Overrides::default(),
+                    &OpenOptions::default(),
+                    &config,
+                    verbose,
+                )?;
+            }
+        }
+        Commands::Completions { shell, sessions } => match shell {
+            Some(shell) if !sessions => {
+                completions::generate(shell.into(), &mut Cli::command(), &mut std::io::stdout())?
+            }
+            _ => {
+                for name in completions::session_names(
+                    &data_dir()?,
+                    &worktrees_dir(&config),
+                    config.worktree_layout.unwrap_or_default(),
+                )? {
+                    println!("{}", name);
+                }
+            }
+        },
+        Commands::ReportBug { output } => report_bug(output)?,
+        Commands::GhExtension { action } => gh_extension_command(action)?,
+        Commands::Trash { action } => trash_command(action, &config)?,
+        Commands::Snapshots { action } => snapshot_command(action, &config, verbose)?,
+        Commands::Bundle { action } => bundle_command(action, &config, verbose)?,
+        Commands::Template { action } => template_command(action, &config, verbose)?,
+        Commands::New {
+            name,
+            org,
+            template,
+            session,
+        } => {
+            if org.is_some() {
+                config.githuborg = org;
+            }
+            new_project(&name, template.as_deref(), &session, &config, verbose)?
         }
     }
+    Ok(())
+}
 
-    // Check remote 'origin'
-    let remote_exists = Command::new("git")
-        .args(["remote", "get-url", "origin"])
-        .current_dir(&repo_root)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false);
-
-    if !remote_exists {
-        if verbose {
-            println!("Creating origin remote");
-        }
-        if let Some(org) = &config.githuborg {
-            let repo_name = repo_root.file_name().unwrap_or_default().to_string_lossy();
-            let repo_spec = format!("{}/{}", org, repo_name);
-            let mut cmd = Command::new("gh");
-            cmd.args([
-                "repo",
-                "create",
-                &repo_spec,
-                "--source",
-                repo_root.to_str().unwrap(),
-                "--remote",
-                "origin",
-                "--push",
-            ]);
-            let status = run_command_verbose(&mut cmd, verbose)?;
-            if !status.success() {
-                anyhow::bail!("gh repo create failed");
+fn new_project(
+    name: &str,
+    template: Option<&str>,
+    session: &str,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let project_dir = std::env::current_dir()?.join(name);
+    if project_dir.exists() {
+        anyhow::bail!("{} already exists", project_dir.display());
+    }
+    if verbose {
+        println!("Creating project directory {}", project_dir.display());
+    }
+    match template {
+        Some(dir) if Path::new(dir).is_dir() => copy_dir(Path::new(dir), &project_dir)?,
+        template => {
+            fs::create_dir_all(&project_dir)?;
+            fs::write(project_dir.join("README.md"), format!("# {}\n", name))?;
+            if let Some(template) = template {
+                let reference = config
+                    .templates
+                    .reference(template, config.githuborg.as_deref())?;
+                registry::login_for_images(
+                    &config.registries,
+                    std::slice::from_ref(&reference),
+                    verbose,
+                )?;
+                template::apply(&reference, &project_dir, verbose)?;
             }
         }
     }
+    std::env::set_current_dir(&project_dir)?;
+    // a new project is forest's to scaffold
+    if locate_devcontainer(None)?.is_none() {
+        let template = config.scaffold_template.as_deref().unwrap_or("ubuntu");
+        scaffold_devcontainer(Path::new(".devcontainer"), template)?;
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.arg("init");
+    let status = run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("git init failed");
+    }
+    let mut cmd = Command::new("git");
+    cmd.args(["add", "-A"]);
+    let status = run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("git add failed");
+    }
+    let mut cmd = Command::new("git");
+    cmd.args(["commit", "-m", "Initial commit"]);
+    let status = run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("git commit failed");
+    }
+    println!("Created project {}", project_dir.display());
+
+    // ensure_git_setup creates the GitHub repository along with the session branch
+    open_session(
+        session,
+        None,
+        &Overrides::default(),
+        &OpenOptions::default(),
+        config,
+        verbose,
+    )?;
     Ok(())
 }
 
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-    /// Print debugging information
-    #[arg(short, long)]
+/// Opens pull request `pr` in a hardened `sandbox/<pr>` session: an exported
+/// copy of its code in a container built from the checkout's trusted
+/// configuration, with no way back to the host beyond a read-only repository.
+fn open_sandbox(
+    pr: u64,
+    dev_env: Option<&str>,
+    allow_hosts: &[String],
+    config: &Config,
     verbose: bool,
-    #[command(subcommand)]
-    command: Commands,
-}
+) -> anyhow::Result<()> {
+    let (repo_root, repo_name) = current_repo("review --sandbox")?;
+    let branch = review::fetch(&repo_root, pr, verbose)?;
+    let name = format!("sandbox/{}", pr);
+    let label = session_label(&name);
+    let snapshot = worktree_path(config, &repo_name, &name);
+    review::snapshot(&repo_root, &branch, &snapshot)?;
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Open a session, creating it if it doesn't exist
-    Open {
-        name: String,
-        /// Name of a subfolder inside `.devcontainer` holding `devcontainer.json`
-        #[arg(long)]
-        devcontainer_env: Option<String>,
-    },
-    /// Kill a running session
-    Kill { name: String },
-    /// List running sessions
-    Ls,
-    /// Verify prerequisites are installed and config is valid
-    Precheck,
-}
+    // the pull request's own devcontainer.json is as untrusted as its code
+    let devcontainer_path = find_devcontainer(dev_env, false, config)?;
+    let value: Value = serde_json::from_str(&fs::read_to_string(&devcontainer_path)?)?;
+    if !devcontainer::has_container(&value) {
+        anyhow::bail!(devcontainer::NO_CONTAINER);
+    }
+    // compose files can give the container any privilege, mount or network
+    if devcontainer::is_compose(&value) {
+        anyhow::bail!("sandboxed reviews need an image or build configuration, not Docker Compose");
+    }
+    let dir = fs::canonicalize(&devcontainer_path)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let value = review::harden(
+        &devcontainer::anchor_build_paths(&value, &dir),
+        &snapshot,
+        &repo_root,
+    );
+    let network = if allow_hosts.is_empty() {
+        egress::Mode::None
+    } else {
+        egress::Mode::Allowlist
+    };
+    let value = egress::apply(&value, network, &label);
+    let value = devcontainer::with_label(&value, VERSION_LABEL, env!("CARGO_PKG_VERSION"));
+    enforce_policy(&value, &devcontainer_path)?;
+    login_registries(&value, &devcontainer_path, config, verbose)?;
 
-#[derive(Deserialize, Default)]
-struct Config {
-    githuborg: Option<String>,
-}
+    let override_path = data_dir()?
+        .join("overrides")
+        .join(format!("{}.json", label));
+    if let Some(parent) = override_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&override_path, serde_json::to_string_pretty(&value)?)?;
+    if network == egress::Mode::Allowlist {
+        egress::ensure_network(&label, verbose)?;
+        let image = config
+            .egress_proxy_image
+            .as_deref()
+            .unwrap_or(egress::DEFAULT_PROXY_IMAGE);
+        egress::start_proxy(&label, allow_hosts, image, verbose)?;
+    }
 
-fn load_config() -> Config {
-    if let Some(proj_dirs) = ProjectDirs::from("", "", "forest") {
-        let path = proj_dirs.config_dir().join("forest.toml");
-        if let Ok(content) = fs::read_to_string(path) {
-            toml::from_str(&content).unwrap_or_default()
+    let up = telemetry::span("devcontainer up");
+    let mut up_cmd = devcontainer::command("up");
+    up_cmd
+        .arg("--workspace-folder")
+        .arg(&snapshot)
+        .arg("--id-label")
+        .arg(format!("name={}", label))
+        .arg("--override-config")
+        .arg(&override_path)
+        .arg("--remove-existing-container")
+        .arg("--skip-post-attach");
+    let output = run_output_verbose(&mut up_cmd, verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
         } else {
-            Config::default()
+            e.into()
         }
-    } else {
-        Config::default()
+    })?;
+    if !output.status.success() {
+        return Err(devcontainer::failure(
+            "up",
+            &String::from_utf8_lossy(&output.stdout),
+            &devcontainer_path,
+        ));
     }
+    let container = devcontainer::parse_up(&String::from_utf8_lossy(&output.stdout));
+    drop(up);
+    println!(
+        "Started sandbox {} for pull request #{}; remove it with `forest kill {} --purge`",
+        name, pr, name
+    );
+
+    let target = devcontainer::target_args(&label, container.as_ref().map(|c| &*c.container_id));
+    let mut cmd = devcontainer::command("exec");
+    cmd.arg("--workspace-folder")
+        .arg(&snapshot)
+        .args(&target)
+        .args(shell::in_dir("/code", &["bash"]));
+    let _shell = telemetry::span("session shell");
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("devcontainer exec failed");
+    }
+    Ok(())
 }
 
-fn find_devcontainer(dev_env: Option<&str>) -> anyhow::Result<PathBuf> {
-    if let Some(env) = dev_env {
-        let candidate = Path::new(".devcontainer")
-            .join(env)
-            .join("devcontainer.json");
-        if candidate.exists() {
-            return Ok(candidate);
-        }
-        anyhow::bail!("devcontainer {} not found", env);
+fn init_devcontainer(template: Option<String>, config: &Config) -> anyhow::Result<()> {
+    if let Some(path) = locate_devcontainer(None)? {
+        anyhow::bail!("{} already exists", path.display());
     }
+    let template = template
+        .or_else(|| config.scaffold_template.clone())
+        .unwrap_or_else(|| scaffold::detect(Path::new(".")).to_string());
+    let path = scaffold_devcontainer(&scaffold_dir(config)?, &template)?;
+    println!("Wrote {} from template {}", path.display(), template);
+    Ok(())
+}
 
-    let root = Path::new(".devcontainer.json");
-    if root.exists() {
-        return Ok(root.to_path_buf());
+/// Checks what forest itself needs from `devcontainer.json`, then lets the
+/// devcontainer CLI read the configuration the way `up` will.
+fn validate_devcontainer(dev_env: Option<&str>, verbose: bool) -> anyhow::Result<()> {
+    let Some(path) = locate_devcontainer(dev_env)? else {
+        anyhow::bail!("no devcontainer.json found; `forest init` writes one");
+    };
+    let contents = fs::read_to_string(&path)?;
+    let value: Value = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    let problems = devcontainer_problems(&value, path.parent().unwrap_or(Path::new(".")));
+    if !problems.is_empty() {
+        anyhow::bail!("{}:\n  {}", path.display(), problems.join("\n  "));
     }
+    enforce_policy(&value, &path)?;
 
-    let default = Path::new(".devcontainer").join("devcontainer.json");
-    if default.exists() {
-        return Ok(default);
+    let mut cmd = devcontainer::command("read-configuration");
+    cmd.args(["--workspace-folder", "."])
+        .arg("--config")
+        .arg(&path);
+    match run_output_verbose(&mut cmd, verbose) {
+        Ok(output) if !output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let message = devcontainer::parse_error(&stdout)
+                .map(|e| e.message)
+                .unwrap_or_else(|| "devcontainer read-configuration failed".to_string());
+            anyhow::bail!("{}: {}", path.display(), message);
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            style::warn("devcontainer CLI not found, only forest's checks were run");
+        }
+        Err(e) => return Err(e.into()),
     }
+    println!("{} is valid", path.display());
+    Ok(())
+}
 
-    // Scaffold default devcontainer.json
-    fs::create_dir_all(".devcontainer")?;
-    fs::write(
-        &default,
-        "{\n  \"image\": \"docker.io/library/ubuntu:latest\"\n}\n",
-    )?;
-    Ok(default)
+fn checkpoint_session(name: &str, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let ids = runtime::containers_with_name(&podman_name, false)?;
+    let Some(id) = ids.first() else {
+        anyhow::bail!("no running container for session {}", name);
+    };
+    checkpoint::create(&data_dir()?, &podman_name, id, verbose)?;
+    println!(
+        "Checkpointed session {}; `forest resume {}` restores its processes",
+        name, name
+    );
+    Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    let config = load_config();
+fn resume_session(name: &str, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let checkpoint = checkpoint::restore(&data_dir()?, &podman_name, verbose)?;
+    let age = timesheet::now().saturating_sub(checkpoint.created_at);
+    println!(
+        "Resumed session {} from a checkpoint taken {}m ago; attach with `forest open {}`",
+        name,
+        age / 60,
+        name
+    );
+    Ok(())
+}
 
-    let verbose = cli.verbose;
+fn scan_command(target: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let ids = runtime::containers_with_name(&session_label(target), true).unwrap_or_default();
+    let image = match ids.first() {
+        Some(id) => runtime::container_image(id)?,
+        None => target.to_string(),
+    };
+    let report = scan::scan(scan::scanner(&config.scan)?, &image, verbose)?;
+    report.print();
+    let critical = report.count(scan::Severity::Critical);
+    if critical > 0 {
+        anyhow::bail!("{} has {} critical vulnerabilities", image, critical);
+    }
+    Ok(())
+}
 
-    match cli.command {
-        Commands::Open {
+/// Problems with a parsed `devcontainer.json` that would stop `open`. Relative
+/// paths are resolved against `dir`, the directory holding the file.
+fn devcontainer_problems(value: &Value, dir: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+    let build = value.get("build");
+    if !devcontainer::has_container(value) {
+        problems.push("none of `image`, `build` or `dockerComposeFile` is set".to_string());
+    }
+    if devcontainer::is_compose(value) {
+        let files = devcontainer::compose_files(value);
+        if files.is_empty() {
+            problems.push("`dockerComposeFile` names no file".to_string());
+        }
+        for file in files {
+            if !dir.join(&file).exists() {
+                problems.push(format!("dockerComposeFile {} does not exist", file));
+            }
+        }
+        if value.get("service").and_then(Value::as_str).is_none() {
+            problems.push("`dockerComposeFile` is set but `service` isn't".to_string());
+        }
+    }
+    if let Some(build) = build {
+        match build.get("dockerfile").and_then(Value::as_str) {
+            Some(dockerfile) if !dir.join(dockerfile).exists() => {
+                problems.push(format!("build.dockerfile {} does not exist", dockerfile))
+            }
+            Some(_) => {}
+            None => problems.push("`build` has no `dockerfile`".to_string()),
+        }
+    }
+    problems
+}
+
+/// Data files `forest migrate` upgrades.
+fn stores() -> [&'static store::Schema; 6] {
+    [
+        &sessions::SESSIONS,
+        &sessions::REPOS,
+        &snapshots::SNAPSHOTS,
+        &UP_CACHE,
+        &checkpoint::CHECKPOINTS,
+        &FOCUS,
+    ]
+}
+
+/// Upgrades every data file an older forest wrote, which otherwise happens
+/// the first time each is read.
+fn migrate_sessions() -> anyhow::Result<()> {
+    let dir = data_dir()?;
+    let mut migrated = 0;
+    for schema in stores() {
+        if let Some(version) = store::migrate(&dir, schema)? {
+            println!(
+                "Upgraded {} from format {} to {}, keeping {}.v{}.bak",
+                schema.file,
+                version,
+                schema.version(),
+                schema.file,
+                version
+            );
+            migrated += 1;
+        }
+    }
+    if migrated == 0 {
+        println!("All data files are up to date");
+    }
+    Ok(())
+}
+
+/// Resolves a branch divergence: by default the session adopts the branch its
+/// worktree has checked out; with `restore` the worktree switches back.
+fn adopt_branch(name: &str, restore: bool, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let data_dir = data_dir()?;
+    let registered = sessions::load(&data_dir)?.remove(&podman_name);
+    let (repo_root, _, worktree_path) =
+        session_paths(name, registered.as_ref(), "adopt-branch", config)?;
+    let expected = expected_branch(name, registered.as_ref());
+    let Some(head) = worktree_head(&repo_root, &worktree_path) else {
+        anyhow::bail!("no session worktree at {}", worktree_path.display());
+    };
+    if head == expected {
+        println!("Session {} already has {} checked out", name, expected);
+        return Ok(());
+    }
+    if restore {
+        let running = runtime::containers_with_name(&podman_name, false)?;
+        let Some(id) = running.first() else {
+            anyhow::bail!(
+                "session {} isn't running; start it with `forest open {}`",
+                name,
+                name
+            );
+        };
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(&worktree_path)
+            .args(devcontainer::target_args(&podman_name, Some(id)))
+            .args(["git", "-C", "/code", "switch", &expected]);
+        if !run_command_verbose(&mut cmd, verbose)?.success() {
+            anyhow::bail!("git switch {} failed in session {}", expected, name);
+        }
+        println!("Switched session {} back to {}", name, expected);
+        return Ok(());
+    }
+    if !is_branch(&repo_root, &head) {
+        anyhow::bail!(
+            "session {} has detached HEAD {}; check out a branch first or use --restore",
             name,
-            devcontainer_env,
-        } => open_session(&name, devcontainer_env.as_deref(), &config, verbose)?,
-        Commands::Kill { name } => kill_session(&name, verbose)?,
-        Commands::Ls => list_sessions(verbose)?,
-        Commands::Precheck => precheck(verbose)?,
+            head
+        );
     }
+    sessions::adopt(&data_dir, &podman_name, &head)?;
+    let mut cmd = Command::new("git");
+    cmd.args(["config", "--local"])
+        .arg(format!("branch.{}.forestSession", head))
+        .arg(&podman_name)
+        .current_dir(&repo_root);
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("git config branch.{}.forestSession failed", head);
+    }
+    println!("Session {} now follows branch {}", name, head);
     Ok(())
 }
 
-fn open_session(
+fn session_status(name: &str, config: &Config) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let data_dir = data_dir()?;
+    let up_cache: BTreeMap<String, UpRecord> = store::load(&data_dir, &UP_CACHE)?;
+    let registered = sessions::load(&data_dir)?.remove(&podman_name);
+    let record = up_cache.get(&podman_name);
+    let running = runtime::containers_with_name(&podman_name, false)?;
+    let all = runtime::containers_with_name(&podman_name, true)?;
+    let container = match (running.first(), all.first()) {
+        (Some(id), _) => format!("running ({})", id),
+        (None, Some(id)) => format!("stopped ({})", id),
+        (None, None) => "none".to_string(),
+    };
+    let created_by = match (record.and_then(|r| r.forest_version.clone()), all.first()) {
+        (Some(version), _) => Some(version),
+        (None, Some(id)) => runtime::container_label(id, VERSION_LABEL)?,
+        (None, None) => None,
+    };
+    println!("session    {}", name);
+    println!("label      {}", podman_name);
+    println!("container  {}", container);
+    println!(
+        "created by forest {}",
+        created_by.as_deref().unwrap_or("(unknown)")
+    );
+    // outside the repository only registered sessions' worktrees can be found
+    let paths = session_paths(name, registered.as_ref(), "status", config).ok();
+    // a running container reports on `/code` itself; otherwise the worktree's
+    // HEAD is all there is to go on
+    let git = match running.first() {
+        Some(id) => runtime::git_states(std::slice::from_ref(id))?.remove(0),
+        None => None,
+    };
+    let host_head = paths
+        .as_ref()
+        .and_then(|(repo_root, _, worktree_path)| worktree_head(repo_root, worktree_path));
+    match (&git, host_head) {
+        (Some(state), _) => {
+            println!(
+                "branch     {}",
+                state.branch.as_deref().unwrap_or("(detached)")
+            );
+            println!("changes    {} files", state.changes);
+            match &state.upstream {
+                Some(upstream) => println!(
+                    "upstream   {}: {} ahead, {} behind",
+                    upstream, state.ahead, state.behind
+                ),
+                None => println!("upstream   (none)"),
+            }
+        }
+        (None, Some(head)) => println!("branch     {}", head),
+        (None, None) => {}
+    }
+    if let Some(id) = running.first() {
+        if let Some(started) = runtime::started_at(&runtime::inspect(id)?) {
+            println!(
+                "uptime     {}",
+                duration(timesheet::now().saturating_sub(started))
+            );
+        }
+        match runtime::stats(id) {
+            Ok(stats) => {
+                println!("cpu        {}", stats.cpu);
+                println!("memory     {} ({})", stats.memory, stats.memory_percent);
+                println!("processes  {}", stats.pids);
+            }
+            Err(e) => style::warn(format!("no resource usage for {}: {}", name, e)),
+        }
+    }
+    if let Some(record) = record {
+        if let Some(image) = &record.overrides.image {
+            println!("image      {}", image);
+        }
+        for feature in &record.overrides.features {
+            println!("feature    {}", feature);
+        }
+        if record.overrides.readonly {
+            println!("mounts     read-only");
+        }
+        for mount in &record.overrides.mounts {
+            println!("mount      {}", mount);
+        }
+        if let Some(nice) = record.overrides.priority.or(config.priority) {
+            println!("priority   {}", nice);
+        }
+        if let Some(cpus) = record.overrides.cpus.or(config.resources.cpus) {
+            println!("cpus       {}", cpus);
+        }
+        if let Some(memory) = record
+            .overrides
+            .memory
+            .as_ref()
+            .or(config.resources.memory.as_ref())
+        {
+            println!("memory     {}", memory);
+        }
+        if !record.overrides.allow_hosts.is_empty() || record.overrides.network.is_some() {
+            println!(
+                "network    {} {}",
+                format!("{:?}", record.overrides.network()).to_lowercase(),
+                record.overrides.allow_hosts.join(", ")
+            );
+        }
+    }
+    if record.is_some() || !all.is_empty() {
+        let warning = match &created_by {
+            None => Some("an unknown, older forest".to_string()),
+            Some(version) if incompatible_version(version) => Some(format!("forest {}", version)),
+            Some(_) => None,
+        };
+        if let Some(creator) = warning {
+            style::warn(format!(
+                "session {} was created by {}; if it misbehaves, recreate its container \
+                 with `forest kill {} --down` and `forest open {}`",
+                name, creator, name, name
+            ));
+        }
+    }
+    if let Some((repo_root, _, worktree_path)) = &paths {
+        warn_branch_divergence(name, registered.as_ref(), repo_root, worktree_path);
+    }
+    Ok(())
+}
+
+/// `seconds` in its two largest units, like `3d 4h` or `12m 5s`.
+fn duration(seconds: u64) -> String {
+    let units = [(86400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+    let Some(first) = units.iter().position(|(size, _)| seconds >= *size) else {
+        return "0s".to_string();
+    };
+    units[first..]
+        .iter()
+        .take(2)
+        .scan(seconds, |left, (size, unit)| {
+            let count = *left / size;
+            *left %= size;
+            Some(format!("{}{}", count, unit))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Pushes session `name`'s branch to the push remote and opens a pull request
+/// for it with `gh`: against the base remote's repository when `[remotes]`
+/// makes it a fork's, else in `githuborg`'s repository when that's set.
+fn open_pull_request(
     name: &str,
-    dev_env: Option<&str>,
+    draft: bool,
+    base: Option<&str>,
     config: &Config,
     verbose: bool,
 ) -> anyhow::Result<()> {
-    ensure_git_setup(name, config, verbose)?;
+    let podman_name = session_label(name);
+    let registered = sessions::load(&data_dir()?)?.remove(&podman_name);
+    if registered.as_ref().is_some_and(|s| s.readonly) {
+        anyhow::bail!("session {} is read-only; it has no branch of its own", name);
+    }
+    let branch = expected_branch(name, registered.as_ref());
+    let (repo_root, repo_name, worktree_path) =
+        session_paths(name, registered.as_ref(), "pr", config)?;
+    push_session_branch(
+        &podman_name,
+        &branch,
+        &repo_root,
+        &worktree_path,
+        config,
+        verbose,
+    )?;
 
-    let podman_name = sanitize_podman_name(name);
-    if !valid_podman_name(&podman_name) {
-        anyhow::bail!("invalid session name: {}", name);
+    let (repository, head) = if config.remotes.cross_repository() {
+        let github = |remote: &str| {
+            remote_repository(&repo_root, remote)
+                .ok_or_else(|| anyhow::anyhow!("remote {} isn't a GitHub repository", remote))
+        };
+        let fork = github(config.remotes.push())?;
+        let owner = fork.split('/').next().unwrap_or_default();
+        (
+            Some(github(config.remotes.base())?),
+            format!("{}:{}", owner, branch),
+        )
+    } else {
+        (
+            config
+                .githuborg
+                .as_ref()
+                .map(|org| format!("{}/{}", org, repo_name)),
+            branch.clone(),
+        )
+    };
+    let mut cmd = Command::new("gh");
+    cmd.args(["pr", "create", "--fill", "--head", &head])
+        .current_dir(&repo_root);
+    if let Some(repository) = &repository {
+        cmd.arg("--repo").arg(repository);
+    }
+    if let Some(base) = base {
+        cmd.args(["--base", base]);
+    }
+    if draft {
+        cmd.arg("--draft");
     }
+    cmd.stdin(Stdio::null());
+    let output = run_output_verbose(&mut cmd, verbose)?;
+    let printed = String::from_utf8_lossy(&output.stdout);
+    if let Some(url) = last_url(&printed).filter(|_| output.status.success()) {
+        println!("{}", url);
+        return Ok(());
+    }
+    // the push may just have updated a pull request that's already open
+    let mut cmd = Command::new("gh");
+    cmd.args(["pr", "view", &head, "--json", "url", "--jq", ".url"])
+        .current_dir(&repo_root)
+        .stderr(Stdio::null());
+    if let Some(repository) = &repository {
+        cmd.arg("--repo").arg(repository);
+    }
+    let output = cmd.output()?;
+    match last_url(&String::from_utf8_lossy(&output.stdout)) {
+        Some(url) if output.status.success() => {
+            println!("Pull request already open: {}", url);
+            Ok(())
+        }
+        _ => anyhow::bail!("gh pr create failed for {}", branch),
+    }
+}
 
-    // Determine repository root and worktree path
-    let output = Command::new("git")
-        .args(["rev-parse", "--show-toplevel"])
-        .stderr(Stdio::null())
-        .output()?;
-    let repo_root = PathBuf::from(str::from_utf8(&output.stdout)?.trim());
-    let repo_name = repo_root
-        .file_name()
-        .ok_or_else(|| anyhow::anyhow!("failed to determine repo name"))?
-        .to_string_lossy();
+/// The last URL in `gh`'s output, where it prints the pull request's.
+fn last_url(output: &str) -> Option<&str> {
+    output
+        .split_whitespace()
+        .rev()
+        .find(|word| word.starts_with("https://"))
+}
 
-    let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
-    let worktree_root = Path::new(&home).join("worktrees").join(&*repo_name);
-    let worktree_path = worktree_root.join(name);
+/// Sets session `name`'s priority, changing its container in place so the
+/// change doesn't wait for the container to be recreated.
+fn renice_session(name: &str, nice: i32, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let data_dir = data_dir()?;
+    let mut up_cache: BTreeMap<String, UpRecord> = store::load(&data_dir, &UP_CACHE)?;
+    let Some(record) = up_cache.get_mut(&podman_name) else {
+        anyhow::bail!(
+            "no session {}; create it with `forest open {} --priority {}`",
+            name,
+            shell::quote(name),
+            nice
+        );
+    };
+    let containers = runtime::containers_with_name(&podman_name, true)?;
+    for id in &containers {
+        priority::update(id, nice, verbose)?;
+    }
+    record.overrides.priority = Some(nice);
+    // the container now matches the configuration with the new priority
+    record.config = record
+        .config
+        .as_ref()
+        .map(|config| priority::apply(config, Some(nice)));
+    store::save(&data_dir, &UP_CACHE, &up_cache)?;
+    if containers.is_empty() {
+        println!(
+            "Session {} will run at priority {} once its container is created",
+            name, nice
+        );
+    } else {
+        println!("Session {} runs at priority {}", name, nice);
+    }
+    Ok(())
+}
 
-    if !worktree_path.exists() {
-        if verbose {
-            println!("Creating worktree directory {}", worktree_path.display());
-        }
-        fs::create_dir_all(&worktree_path)?;
+/// Lists how session `name`'s container differs from what the repository's
+/// devcontainer.json, with the session's overrides, would create now.
+fn drift_session(name: &str, config: &Config) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let data_dir = data_dir()?;
+    let registered = sessions::load(&data_dir)?.remove(&podman_name);
+    let Some(id) = runtime::containers_with_name(&podman_name, true)?
+        .into_iter()
+        .next()
+    else {
+        anyhow::bail!("session {} has no container", name);
+    };
+    let dev_env = registered
+        .as_ref()
+        .and_then(|s| s.devcontainer_env.as_deref())
+        .or(config.devcontainer_env(None));
+    let Some(devcontainer_path) = locate_devcontainer(dev_env)? else {
+        anyhow::bail!("no devcontainer.json here; run `forest drift` in the session's repository");
+    };
+    let value: Value = serde_json::from_str(&fs::read_to_string(&devcontainer_path)?)?;
+    let value = apply_overlay(&value)?;
+    let value = priority::apply(&value, config.priority);
+    let (cpus, memory) = config.resources.limits()?;
+    let value = resources::apply(&value, cpus, memory.as_deref());
+    let value = devcontainer::with_features(&value, &config.default_features);
+    let mut up_cache: BTreeMap<String, UpRecord> = store::load(&data_dir, &UP_CACHE)?;
+    let record = up_cache.remove(&podman_name);
+    let desired = record
+        .as_ref()
+        .map_or_else(Overrides::default, |r| r.overrides.clone())
+        .apply(&value, &podman_name);
+    let recorded = record.and_then(|r| r.config);
+    let image_id = desired
+        .get("image")
+        .and_then(Value::as_str)
+        .and_then(runtime::image_id);
+    let differences = drift::differences(
+        recorded.as_ref(),
+        &desired,
+        &runtime::inspect(&id)?,
+        image_id.as_deref(),
+    );
+    if recorded.is_none() {
+        style::note(format!(
+            "forest didn't record the configuration session {} was created from, so only its \
+             image and environment were compared",
+            name
+        ));
     }
-    let devcontainer_path = find_devcontainer(dev_env)?;
+    if differences.is_empty() {
+        println!("Session {} matches {}", name, devcontainer_path.display());
+        return Ok(());
+    }
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    println!(
+        "Recreate the container to pick these up: `forest kill {} --down` and `forest open {}`",
+        shell::quote(name),
+        shell::quote(name)
+    );
+    Ok(())
+}
 
-    if verbose {
-        println!("Using devcontainer at {}", devcontainer_path.display());
+/// Everything forest recorded about session `name` and its container as the
+/// engine reports it, in one document.
+fn session_document(name: &str) -> anyhow::Result<Value> {
+    let podman_name = session_label(name);
+    let data_dir = data_dir()?;
+    let mut registered = sessions::load(&data_dir)?;
+    let mut up_cache: BTreeMap<String, UpRecord> = store::load(&data_dir, &UP_CACHE)?;
+    let mut checkpoints = checkpoint::load(&data_dir)?;
+    let container = match runtime::containers_with_name(&podman_name, true)?.first() {
+        Some(id) => Some(runtime::inspect(id)?),
+        None => None,
+    };
+    Ok(json!({
+        "session": name,
+        "label": podman_name,
+        "backend": backend::current().program(),
+        "registry": registered.remove(&podman_name),
+        "up": up_cache.remove(&podman_name),
+        "checkpoint": checkpoints.remove(&podman_name),
+        "container": container,
+    }))
+}
+
+fn inspect_session(name: &str, json: bool) -> anyhow::Result<()> {
+    let document = session_document(name)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&document)?);
+        return Ok(());
+    }
+    for line in inspect_lines(&document) {
+        println!("{}", line);
+    }
+    if !document["container"].is_null() {
+        println!("(`--json` prints the whole document)");
+    }
+    Ok(())
+}
+
+/// The parts of a `session_document` worth reading at a glance.
+fn inspect_lines(document: &Value) -> Vec<String> {
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let mut lines = Vec::new();
+    for key in ["session", "label", "backend"] {
+        lines.push(format!("{:<10} {}", key, text(&document[key])));
     }
+    for section in ["registry", "up", "checkpoint"] {
+        for (key, value) in document[section].as_object().into_iter().flatten() {
+            lines.push(format!("{:<10} {} {}", section, key, text(value)));
+        }
+    }
+    let container = &document["container"];
+    if container.is_null() {
+        lines.push(format!("{:<10} none", "container"));
+        return lines;
+    }
+    lines.push(format!(
+        "{:<10} {} {}",
+        "container",
+        text(&container["Id"]),
+        text(container.pointer("/State/Status").unwrap_or(&Value::Null))
+    ));
+    for mount in container["Mounts"].as_array().into_iter().flatten() {
+        lines.push(format!(
+            "{:<10} {} -> {}",
+            "mount",
+            text(&mount["Source"]),
+            text(&mount["Destination"])
+        ));
+    }
+    let labels = container
+        .pointer("/Config/Labels")
+        .and_then(Value::as_object);
+    for (key, value) in labels.into_iter().flatten() {
+        lines.push(format!("{:<10} {}={}", "label", key, text(value)));
+    }
+    let env = container.pointer("/Config/Env").and_then(Value::as_array);
+    for var in env.into_iter().flatten() {
+        lines.push(format!("{:<10} {}", "env", text(var)));
+    }
+    lines
+}
 
-    let contents = fs::read_to_string(&devcontainer_path)?;
-    let value: Value = serde_json::from_str(&contents)?;
-    if value.get("image").is_none() && value.get("build").is_none() {
-        anyhow::bail!("image field missing in devcontainer");
+/// Directory inside the session worktree (`/code`) holding subtask worktrees.
+const SUBTASK_DIR: &str = ".forest-subtasks";
+
+/// Creates (or reuses) a worktree for a side branch inside a running session's
+/// container and opens a shell in it. The worktree lives under `/code`, the only
+/// writable mount the container already has, and is excluded from git status.
+fn open_subtask(session: &str, name: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(session);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", session);
+    }
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        anyhow::bail!("invalid subtask name: {}", name);
+    }
+    let (repo_root, repo_name) = current_repo("subtask")?;
+    let worktree_path = worktree_path(config, &repo_name, session);
+    if !worktree_path.join(".git").exists() {
+        anyhow::bail!(
+            "session {} has no worktree at {}; run `forest open {}` first",
+            session,
+            worktree_path.display(),
+            session
+        );
     }
+    // one lookup instead of one per exec
+    let running = runtime::containers_with_name(&podman_name, false)?;
+    let Some(id) = running.first() else {
+        anyhow::bail!(
+            "session {} isn't running; start it with `forest open {}`",
+            session,
+            session
+        );
+    };
+    let target = devcontainer::target_args(&podman_name, Some(id));
+    let branch = format!("{}-{}", session, name);
+    let subtask_path = format!("/code/{}/{}", SUBTASK_DIR, name);
+
+    if !worktree_path.join(SUBTASK_DIR).join(name).exists() {
+        let git_dir = repo_root.join(".git");
+        let _lock = locks::RepoLock::acquire(&git_dir, config.lock_timeout(), verbose)?;
+        let exclude = git_dir.join("info").join("exclude");
+        let pattern = format!("/{}/", SUBTASK_DIR);
+        let excludes = fs::read_to_string(&exclude).unwrap_or_default();
+        if !excludes.lines().any(|l| l.trim() == pattern) {
+            fs::create_dir_all(git_dir.join("info"))?;
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&exclude)?;
+            writeln!(file, "{}", pattern)?;
+        }
 
-    if value.get("build").is_some() {
-        let mut cmd = Command::new("devcontainer");
-        cmd.arg("build")
-            .arg("--workspace-folder")
-            .arg(&worktree_path);
+        let git_config = check_git_skew(&worktree_path, &target, verbose);
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(&worktree_path)
+            .args(&target)
+            .arg("git")
+            .args(git_config)
+            .args(["-C", "/repo", "worktree", "add", "-B"])
+            .args([&branch, &subtask_path, session]);
         let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
                 anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
@@ -279,29 +1297,16 @@ fn open_session(
             }
         })?;
         if !status.success() {
-            anyhow::bail!("devcontainer build failed");
+            anyhow::bail!("git worktree add failed");
         }
+        println!("Created subtask {} on branch {}", name, branch);
     }
 
-    let mut cmd = Command::new("devcontainer");
-    cmd.arg("up")
-        .arg("--workspace-folder")
+    let mut cmd = devcontainer::command("exec");
+    cmd.arg("--workspace-folder")
         .arg(&worktree_path)
-        .arg("--id-label")
-        .arg(format!("name={}", podman_name))
-        .arg("--mount")
-        .arg(format!(
-            "type=bind,source={},target=/repo",
-            repo_root.display()
-        ))
-        .arg("--mount")
-        .arg(format!(
-            "type=bind,source={},target=/code",
-            worktree_path.display()
-        ))
-        // this is a bit subtle: we'll often be using the same devcontainer that vscode uses for consistency, but we don't want
-        // all the services that might attach (rust-analyzer etc).
-        .arg("--skip-post-attach");
+        .args(&target)
+        .args(shell::in_dir(&subtask_path, &["bash"]));
     let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
         if e.kind() == std::io::ErrorKind::NotFound {
             anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
@@ -309,29 +1314,93 @@ fn open_session(
             e.into()
         }
     })?;
-  
     if !status.success() {
-        anyhow::bail!("devcontainer up failed");
+        anyhow::bail!("devcontainer exec failed");
     }
-    println!("Started session {}", name);
+    Ok(())
+}
 
-    let git_file = worktree_path.join(".git");
-    let mut need_worktree = true;
-    if let Ok(content) = fs::read_to_string(&git_file) {
-        if content.contains("/repo/.git/worktrees/") {
-            need_worktree = false;
-        }
+/// Session names `specs` stand for, patterns like `feat/*` matching the
+/// current repository's sessions, only running ones with `running`.
+fn expand_sessions(
+    specs: &[String],
+    running: bool,
+    config: &Config,
+) -> anyhow::Result<Vec<String>> {
+    if !specs.iter().any(|spec| bulk::is_pattern(spec)) {
+        return Ok(specs.to_vec());
     }
-    if need_worktree {
-        let mut cmd = Command::new("devcontainer");
-        cmd.arg("exec")
-            .arg("--workspace-folder")
-            .arg(&worktree_path)
-            .arg("--id-label")
-            .arg(format!("name={}", podman_name))
-            .arg("bash")
-            .arg("-lc")
-            .arg(format!("git -C /repo worktree add -B {} /code", name));
+    let repo = current_repo("kill").ok().map(|(repo_root, _)| repo_root);
+    let worktrees = worktrees_dir(config);
+    let up: Option<BTreeSet<String>> = if running {
+        Some(
+            runtime::session_containers(&worktrees, false)?
+                .into_iter()
+                .map(|container| container.name)
+                .collect(),
+        )
+    } else {
+        None
+    };
+    let known: Vec<String> = sessions::load(&data_dir()?)?
+        .iter()
+        .filter(|(_, session)| repo.as_ref().is_none_or(|repo| session.repo == *repo))
+        .filter(|(label, _)| up.as_ref().is_none_or(|up| up.contains(*label)))
+        .map(|(_, session)| {
+            completions::session_name(
+                session,
+                &worktrees,
+                config.worktree_layout.unwrap_or_default(),
+            )
+        })
+        .collect();
+    bulk::expand(specs, &known)
+}
+
+/// Command running `command` in `/code` of a running session's container,
+/// whose exit status `exec_sessions` passes on.
+fn exec_command(name: &str, command: &[String], config: &Config) -> anyhow::Result<Command> {
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+    let (_, repo_name) = current_repo("exec")?;
+    let worktree_path = worktree_path(config, &repo_name, name);
+    let running = runtime::containers_with_name(&podman_name, false)?;
+    let Some(id) = running.first() else {
+        anyhow::bail!(
+            "session {} isn't running; start it with `forest open {}`",
+            name,
+            name
+        );
+    };
+    let mut cmd = devcontainer::command("exec");
+    cmd.arg("--workspace-folder")
+        .arg(&worktree_path)
+        .args(devcontainer::target_args(&podman_name, Some(id)))
+        .args(env::remote_env_args(&env::entries(&config.env)))
+        .args(shell::in_dir("/code", command));
+    Ok(cmd)
+}
+
+/// Runs `command` in the sessions `names`, passing the exit status on. One
+/// session gets the terminal; several run at once with their output
+/// multiplexed, and the worst exit status wins.
+fn exec_sessions(
+    names: &[String],
+    command: &[String],
+    layout: mux::Layout,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let exit = |status: Option<std::process::ExitStatus>| {
+        status
+            .and_then(|s| s.code())
+            .and_then(|c| u8::try_from(c).ok())
+            .unwrap_or(1)
+    };
+    if let [name] = names {
+        let mut cmd = exec_command(name, command, config)?;
         let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
                 anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
@@ -340,79 +1409,947 @@ fn open_session(
             }
         })?;
         if !status.success() {
-            anyhow::bail!("git worktree add failed");
+            return Err(CommandExit(exit(Some(status)).max(1)).into());
         }
+        return Ok(());
     }
+    let mut jobs = Vec::new();
+    for name in names {
+        let command = exec_command(name, command, config)?;
+        if verbose {
+            println!("Running: {}", shell::display(&command));
+        }
+        jobs.push(mux::Job {
+            name: name.clone(),
+            command,
+        });
+    }
+    let _span = telemetry::span("exec sessions");
+    let worst = mux::run(jobs, layout).into_iter().map(exit).max();
+    match worst {
+        Some(code) if code > 0 => Err(CommandExit(code).into()),
+        _ => Ok(()),
+    }
+}
 
-    let mut cmd = Command::new("devcontainer");
-    cmd.arg("exec")
-        .arg("--workspace-folder")
-        .arg(&worktree_path)
-        .arg("--id-label")
-        .arg(format!("name={}", podman_name))
-        .arg("bash")
-        .arg("-lc")
-        .arg("cd /code && exec bash");
-    let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::NotFound {
-            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
-        } else {
-            e.into()
+/// Runs `command` in an ephemeral session and prints the JSON result,
+/// exiting with the command's status.
+fn ci_exec(
+    reference: &str,
+    command: &[String],
+    dev_env: Option<&str>,
+    timeout: u64,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    set_non_interactive(true);
+    let result = forest::ci::exec(
+        reference,
+        command,
+        dev_env,
+        Duration::from_secs(timeout),
+        config,
+        verbose,
+    );
+    println!("{}", serde_json::to_string(&result)?);
+    match result.exit_code() {
+        0 => Ok(()),
+        code => Err(CommandExit(code).into()),
+    }
+}
+
+/// Runs the workflows of session `name`'s worktree with act, keeping each
+/// job's log under the data directory.
+fn act_session(
+    name: &str,
+    job: Option<&str>,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let data_dir = data_dir()?;
+    let registered = sessions::load(&data_dir)?.remove(&podman_name);
+    let (_, _, worktree_path) = session_paths(name, registered.as_ref(), "act", config)?;
+    if !worktree_path.join(".github").join("workflows").is_dir() {
+        anyhow::bail!("session {} has no .github/workflows to run", name);
+    }
+    let logs = data_dir.join("act").join(&podman_name);
+    let (success, written) = act::run(&worktree_path, job, &logs, verbose)?;
+    for path in &written {
+        style::note(format!("log {}", path.display()));
+    }
+    if !success {
+        anyhow::bail!("act failed for session {}", name);
+    }
+    Ok(())
+}
+
+fn layout_command(action: LayoutAction, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    match action {
+        LayoutAction::Apply { preset: name } => {
+            let preset = layout::find(&config.layouts, &name)?;
+            let (repo_root, _) = current_repo("layout apply")?;
+            let names = expand_sessions(&preset.sessions, false, config)?;
+            for session in &names {
+                open_session(
+                    session,
+                    config.devcontainer_env(None),
+                    &Overrides::default(),
+                    &OpenOptions {
+                        detach: true,
+                        ..OpenOptions::default()
+                    },
+                    config,
+                    verbose,
+                )?;
+            }
+            let target = layout::tmux_session(&name);
+            let forest = std::env::current_exe()?;
+            if !layout::build(&target, preset, &names, &forest, &repo_root, verbose)? {
+                style::note(format!(
+                    "tmux session {} exists already; kill it with `tmux kill-session -t {}` \
+                     to lay it out again",
+                    target, target
+                ));
+            }
+            if !interactive() {
+                println!("Attach with `tmux attach -t {}`", target);
+                return Ok(());
+            }
+            layout::attach(&target, verbose)
+        }
+        LayoutAction::List => {
+            if config.layouts.is_empty() {
+                println!("No layout presets; define them under [layouts.<name>] in forest.toml");
+                return Ok(());
+            }
+            let mut table = style::Table::new(&["preset", "sessions", "panes", "arrange"]);
+            for (name, preset) in &config.layouts {
+                let panes: Vec<&str> = preset
+                    .panes
+                    .iter()
+                    .map(|pane| {
+                        if pane.trim().is_empty() {
+                            "shell"
+                        } else {
+                            pane
+                        }
+                    })
+                    .collect();
+                table.row(vec![
+                    (name.clone(), None),
+                    (preset.sessions.join(" "), None),
+                    (panes.join(", "), Some(style::Role::Dim)),
+                    (preset.arrange().to_string(), Some(style::Role::Dim)),
+                ]);
+            }
+            table.print();
+            Ok(())
+        }
+    }
+}
+
+fn matrix_command(action: MatrixAction, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    match action {
+        MatrixAction::Open {
+            name,
+            platforms,
+            devcontainer_env,
+        } => {
+            let (repo_root, _) = current_repo("matrix open")?;
+            require_branch(&name, &repo_root)?;
+            for spec in &platforms {
+                let platform = matrix::platform(spec)?;
+                let session = matrix::sibling(&name, &platform);
+                // later opens keep the branch as it has moved on
+                if !is_branch(&repo_root, &session) {
+                    let mut cmd = Command::new("git");
+                    cmd.args(["branch", &session, &name])
+                        .current_dir(&repo_root);
+                    if !run_command_verbose(&mut cmd, verbose)?.success() {
+                        anyhow::bail!("git branch {} failed", session);
+                    }
+                }
+                open_session(
+                    &session,
+                    config.devcontainer_env(devcontainer_env.as_deref()),
+                    &Overrides {
+                        platform: Some(platform),
+                        ..Overrides::default()
+                    },
+                    &OpenOptions {
+                        detach: true,
+                        ..OpenOptions::default()
+                    },
+                    config,
+                    verbose,
+                )?;
+            }
+            Ok(())
+        }
+        MatrixAction::Exec { name, command } => {
+            let (repo_root, _) = current_repo("matrix exec")?;
+            let siblings = matrix::siblings(&data_dir()?, &repo_root, &name)?;
+            if siblings.is_empty() {
+                anyhow::bail!(
+                    "{} has no platform sessions; create them with `forest matrix open {} \
+                     --platforms amd64,arm64`",
+                    name,
+                    shell::quote(&name)
+                );
+            }
+            let mut jobs = Vec::new();
+            for (platform, session) in &siblings {
+                jobs.push(mux::Job {
+                    name: platform.clone(),
+                    command: exec_command(session, &command, config)?,
+                });
+            }
+            let _span = telemetry::span("exec matrix");
+            let statuses = mux::run(jobs, mux::Layout::Grouped);
+            let mut table = style::Table::new(&["platform", "session", "result"]);
+            let mut worst = 0;
+            for ((platform, session), status) in siblings.into_iter().zip(statuses) {
+                let code = status
+                    .and_then(|s| s.code())
+                    .and_then(|c| u8::try_from(c).ok())
+                    .unwrap_or(1);
+                worst = worst.max(code);
+                let result = match (status, code) {
+                    (None, _) => ("didn't start".to_string(), Some(style::Role::Error)),
+                    (_, 0) => ("ok".to_string(), Some(style::Role::Success)),
+                    (_, code) => (format!("exit {}", code), Some(style::Role::Error)),
+                };
+                table.row(vec![(platform, None), (session, None), result]);
+            }
+            table.print();
+            if worst > 0 {
+                return Err(CommandExit(worst).into());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn snapshot_command(action: SnapshotAction, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let data_dir = data_dir()?;
+    match action {
+        SnapshotAction::Take { every: None } => snapshot_sessions(&data_dir, config, verbose),
+        SnapshotAction::Take {
+            every: Some(minutes),
+        } => loop {
+            // one bad pass shouldn't end the loop
+            if let Err(e) = snapshot_sessions(&data_dir, config, verbose) {
+                style::warn(format!("snapshot failed: {}", e));
+            }
+            std::thread::sleep(std::time::Duration::from_secs(minutes.max(1) * 60));
+        },
+        SnapshotAction::List { name } => {
+            let snapshots = snapshots::list(&data_dir, &session_label(&name))?;
+            if snapshots.is_empty() {
+                println!("No snapshots of session {}", name);
+                return Ok(());
+            }
+            let now = timesheet::now();
+            let mut table = style::Table::new(&["id", "age", "on", "files"]);
+            for snapshot in snapshots.iter().rev() {
+                let age = now.saturating_sub(snapshot.taken_at);
+                let age = match age {
+                    0..=3599 => format!("{}m ago", age / 60),
+                    3600..=86399 => format!("{}h ago", age / 3600),
+                    _ => format!("{}d ago", age / 86400),
+                };
+                table.row(vec![
+                    (snapshot.id.to_string(), None),
+                    (age, Some(style::Role::Dim)),
+                    (
+                        snapshots::short(&snapshot.head).to_string(),
+                        Some(style::Role::Dim),
+                    ),
+                    (snapshot.files.to_string(), None),
+                ]);
+            }
+            table.print();
+            Ok(())
+        }
+        SnapshotAction::Restore { name, id } => {
+            let label = session_label(&name);
+            let registered = sessions::load(&data_dir)?.remove(&label);
+            let (repo_root, _, worktree_path) =
+                session_paths(&name, registered.as_ref(), "snapshots restore", config)?;
+            let Some(admin) = worktree_admin_dir(&repo_root, &worktree_path) else {
+                anyhow::bail!("no session worktree at {}", worktree_path.display());
+            };
+            let worktree = snapshots::Worktree {
+                admin: &admin,
+                dir: &worktree_path,
+            };
+            // so the restore itself can be undone
+            let keep = config.snapshot_keep.unwrap_or(snapshots::DEFAULT_KEEP);
+            if let Some(current) = snapshots::take(&data_dir, &label, &worktree, keep)? {
+                println!("Snapshotted the current changes as {}", current.id);
+            }
+            snapshots::restore(&data_dir, &label, id, &worktree)?;
+            println!("Restored snapshot {} into {}", id, worktree_path.display());
+            Ok(())
+        }
+    }
+}
+
+/// Snapshots every registered session whose worktree has changed since its
+/// last snapshot.
+fn snapshot_sessions(data_dir: &Path, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let keep = config.snapshot_keep.unwrap_or(snapshots::DEFAULT_KEEP);
+    for (label, session) in sessions::load(data_dir)? {
+        let Some(admin) = worktree_admin_dir(&session.repo, &session.worktree) else {
+            continue;
+        };
+        let worktree = snapshots::Worktree {
+            admin: &admin,
+            dir: &session.worktree,
+        };
+        match snapshots::take(data_dir, &label, &worktree, keep) {
+            Ok(Some(snapshot)) => println!(
+                "Snapshot {} of {}: {} files",
+                snapshot.id, session.branch, snapshot.files
+            ),
+            Ok(None) if verbose => println!("No new changes in {}", session.branch),
+            Ok(None) => {}
+            Err(e) => style::warn(format!("failed to snapshot {}: {}", session.branch, e)),
         }
-    })?;
-    if !status.success() {
-        anyhow::bail!("devcontainer exec failed");
     }
     Ok(())
 }
 
-fn kill_session(name: &str, verbose: bool) -> anyhow::Result<()> {
-    let podman_name = sanitize_podman_name(name);
-    if !valid_podman_name(&podman_name) {
-        anyhow::bail!("invalid session name: {}", name);
+fn trash_command(action: TrashAction, config: &Config) -> anyhow::Result<()> {
+    let data_dir = data_dir()?;
+    match action {
+        TrashAction::List => {
+            let items = trash::list(&data_dir)?;
+            if items.is_empty() {
+                println!("Trash is empty");
+                return Ok(());
+            }
+            let now = timesheet::now();
+            let mut table = style::Table::new(&["id", "session", "age", "worktree"]);
+            for item in items {
+                let days = now.saturating_sub(item.meta.trashed_at) / (24 * 60 * 60);
+                table.row(vec![
+                    (item.id, None),
+                    (format!("{}/{}", item.meta.repo, item.meta.session), None),
+                    (format!("{}d ago", days), Some(style::Role::Dim)),
+                    (
+                        item.meta.worktree.display().to_string(),
+                        Some(style::Role::Dim),
+                    ),
+                ]);
+            }
+            table.print();
+        }
+        TrashAction::Restore { id } => {
+            let meta = trash::restore(&data_dir, &id)?;
+            println!(
+                "Restored session {} worktree to {}",
+                meta.session,
+                meta.worktree.display()
+            );
+        }
+        TrashAction::Empty { all } => {
+            let retention = (!all).then(|| config.trash_retention_secs());
+            let removed = trash::empty(&data_dir, retention)?;
+            println!("Removed {} trash entries", removed);
+        }
     }
-    let mut cmd = Command::new("devcontainer");
-    cmd.arg("down")
-        .arg("--id-label")
-        .arg(format!("name={}", podman_name));
-    let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::NotFound {
-            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
-        } else {
-            e.into()
+    Ok(())
+}
+
+fn prune_sessions(yes: bool, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let data_dir = data_dir()?;
+    let root = worktrees_dir(config);
+    let registered = sessions::load(&data_dir)?;
+    if !root.is_dir() && !registered.is_empty() {
+        // an unmounted worktrees directory would make every session look gone
+        anyhow::bail!("{} doesn't exist; is it mounted?", root.display());
+    }
+    let containers = runtime::session_containers(&root, true)?;
+    let repos: BTreeSet<PathBuf> = registered
+        .values()
+        .map(|session| session.repo.clone())
+        .chain(repo_toplevel())
+        .collect();
+    let layout = config.worktree_layout.unwrap_or_default();
+    let orphans = prune::survey(&registered, containers, &root, &repos, layout);
+    if orphans.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+    let mut table = style::Table::new(&["orphan", "name", "why"]);
+    let dim = |text: &str| (text.to_string(), Some(style::Role::Dim));
+    for label in &orphans.records {
+        table.row(vec![
+            ("session record".to_string(), None),
+            (label.clone(), None),
+            dim("worktree deleted"),
+        ]);
+    }
+    for container in &orphans.containers {
+        table.row(vec![
+            ("container".to_string(), None),
+            (container.name.clone(), None),
+            dim("worktree deleted"),
+        ]);
+    }
+    for worktree in &orphans.worktrees {
+        table.row(vec![
+            ("worktree".to_string(), None),
+            (worktree.path.display().to_string(), None),
+            dim("no session or container"),
+        ]);
+    }
+    for admin in &orphans.admin_dirs {
+        table.row(vec![
+            ("git worktree".to_string(), None),
+            (admin.display().to_string(), None),
+            dim("worktree deleted"),
+        ]);
+    }
+    table.print();
+    if !yes {
+        style::note("run `forest prune --yes` to remove them");
+        return Ok(());
+    }
+
+    let ids: Vec<String> = orphans.containers.iter().map(|c| c.id.clone()).collect();
+    runtime::container_action("stop", &ids, verbose)?;
+    runtime::container_action("rm", &ids, verbose)?;
+    for container in &orphans.containers {
+        if let Err(e) = egress::remove(&container.name, verbose) {
+            style::warn(format!("failed to remove the egress proxy: {}", e));
         }
-    })?;
-    if !status.success() {
-        anyhow::bail!("devcontainer down failed");
     }
-    println!("Killed session {}", name);
+    for label in &orphans.records {
+        sessions::remove(&data_dir, label)?;
+        checkpoint::forget(&data_dir, label)?;
+    }
+    for worktree in &orphans.worktrees {
+        trash::put(
+            &data_dir,
+            &worktree.session,
+            &worktree.repo,
+            &worktree.path,
+            worktree.admin.as_deref(),
+        )?;
+        remove_empty_parents(&worktree.path, &root);
+    }
+    for admin in &orphans.admin_dirs {
+        // `<repo>/.git/worktrees/<id>`
+        if let Some(git_dir) = admin.parent().and_then(Path::parent) {
+            let _lock = locks::RepoLock::acquire(git_dir, config.lock_timeout(), verbose)?;
+            fs::remove_dir_all(admin)?;
+        }
+    }
+    println!(
+        "Removed {} session records, {} containers and {} git worktrees; moved {} worktrees to the trash",
+        orphans.records.len(),
+        orphans.containers.len(),
+        orphans.admin_dirs.len(),
+        orphans.worktrees.len()
+    );
     Ok(())
 }
 
-fn list_sessions(verbose: bool) -> anyhow::Result<()> {
-    let mut cmd = Command::new("devcontainer");
-    cmd.arg("list");
-    run_command_verbose(&mut cmd, verbose).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::NotFound {
-            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+fn list_sessions(all: bool, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let root = worktrees_dir(config);
+    let containers = runtime::session_containers(&root, false)?;
+    let mut stopped = sessions::load(&data_dir()?)?;
+    for container in &containers {
+        stopped.remove(&container.name);
+    }
+    // what `prune` would look at, without the running containers
+    let orphans = if all {
+        let exited: Vec<runtime::Container> = runtime::session_containers(&root, true)?
+            .into_iter()
+            .filter(|c| !containers.iter().any(|running| running.id == c.id))
+            .collect();
+        let repos: BTreeSet<PathBuf> = stopped
+            .values()
+            .map(|session| session.repo.clone())
+            .chain(repo_toplevel())
+            .collect();
+        let layout = config.worktree_layout.unwrap_or_default();
+        let mut orphans = prune::survey(&stopped, exited.clone(), &root, &repos, layout);
+        // containers stopped with their worktree still there, which the
+        // registry doesn't know, are stopped sessions too
+        orphans.containers.extend(
+            exited
+                .into_iter()
+                .filter(|c| !stopped.contains_key(&c.name) && Path::new(&c.workspace).exists()),
+        );
+        orphans
+    } else {
+        prune::Orphans::default()
+    };
+    if containers.is_empty() && stopped.is_empty() && orphans.is_empty() {
+        println!("No sessions");
+        return Ok(());
+    }
+    if verbose {
+        println!("Querying {} session containers", containers.len());
+    }
+    let ids: Vec<String> = containers.iter().map(|c| c.id.clone()).collect();
+    let states = runtime::git_states(&ids)?;
+    let mut table = style::Table::new(&["session", "branch", "changes", "workspace"]);
+    for (container, state) in containers.iter().zip(states) {
+        let (branch, changes) = match state {
+            Some(state) => (
+                (
+                    state.branch.unwrap_or_else(|| "(detached)".to_string()),
+                    None,
+                ),
+                (
+                    format!("{} changed", state.changes),
+                    Some(if state.changes == 0 {
+                        style::Role::Success
+                    } else {
+                        style::Role::Warning
+                    }),
+                ),
+            ),
+            None => (
+                ("?".to_string(), Some(style::Role::Error)),
+                ("?".to_string(), Some(style::Role::Error)),
+            ),
+        };
+        table.row(vec![
+            (container.name.clone(), None),
+            branch,
+            changes,
+            (container.workspace.clone(), Some(style::Role::Dim)),
+        ]);
+    }
+    // the registry knows these even though no container is running
+    for (label, session) in stopped {
+        let state = if orphans.records.contains(&label) {
+            ("worktree gone".to_string(), Some(style::Role::Error))
         } else {
-            e.into()
+            ("stopped".to_string(), Some(style::Role::Dim))
+        };
+        table.row(vec![
+            (label, None),
+            (session.branch, None),
+            state,
+            (
+                session.worktree.display().to_string(),
+                Some(style::Role::Dim),
+            ),
+        ]);
+    }
+    let unknown = || ("?".to_string(), Some(style::Role::Dim));
+    for container in orphans.containers {
+        let state = if Path::new(&container.workspace).exists() {
+            ("stopped".to_string(), Some(style::Role::Dim))
+        } else {
+            ("orphaned container".to_string(), Some(style::Role::Error))
+        };
+        table.row(vec![
+            (container.name, None),
+            unknown(),
+            state,
+            (container.workspace, Some(style::Role::Dim)),
+        ]);
+    }
+    for worktree in orphans.worktrees {
+        table.row(vec![
+            (worktree.session.clone(), None),
+            worktree
+                .branch()
+                .map_or_else(unknown, |branch| (branch, None)),
+            ("worktree only".to_string(), Some(style::Role::Warning)),
+            (worktree.path.display().to_string(), Some(style::Role::Dim)),
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+/// `focus.json`, present while a session is focused.
+const FOCUS: store::Schema = store::Schema {
+    file: "focus.json",
+    migrations: &[],
+};
+
+/// Containers stopped by `forest focus`, restarted by `forest unfocus`.
+#[derive(Serialize, Deserialize, Default)]
+struct FocusState {
+    session: String,
+    stopped: Vec<String>,
+}
+
+fn focus_session(name: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let dir = data_dir()?;
+    let mut state: FocusState = store::load(&dir, &FOCUS)?;
+
+    let others: Vec<runtime::Container> =
+        runtime::session_containers(&worktrees_dir(config), false)?
+            .into_iter()
+            .filter(|c| c.name != podman_name)
+            .collect();
+    let ids: Vec<String> = others.iter().map(|c| c.id.clone()).collect();
+    for container in &others {
+        println!("Stopping session {}", container.name);
+    }
+    runtime::container_action("stop", &ids, verbose)?;
+
+    state.session = name.to_string();
+    state.stopped.extend(ids);
+    state.stopped.retain(|id| !id.is_empty());
+    store::save(&dir, &FOCUS, &state)?;
+    println!("Focused on session {}", name);
+    Ok(())
+}
+
+fn unfocus(verbose: bool) -> anyhow::Result<()> {
+    let dir = data_dir()?;
+    if !FOCUS.path(&dir).exists() {
+        anyhow::bail!("no session is focused");
+    }
+    let state: FocusState = store::load(&dir, &FOCUS)?;
+    runtime::container_action("start", &state.stopped, verbose)?;
+    fs::remove_file(FOCUS.path(&dir))?;
+    println!(
+        "Restarted {} session(s) stopped while focusing on {}",
+        state.stopped.len(),
+        state.session
+    );
+    Ok(())
+}
+
+fn template_command(action: TemplateAction, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let registry = config.templates.registry();
+    match action {
+        TemplateAction::Publish { id, version } => {
+            let (repo_root, repo_name) = current_repo("template publish")?;
+            let devcontainer_dir = repo_root.join(".devcontainer");
+            if !devcontainer_dir.join("devcontainer.json").exists() {
+                anyhow::bail!(
+                    "{} has no .devcontainer/devcontainer.json",
+                    repo_root.display()
+                );
+            }
+            let id = id.unwrap_or_else(|| sanitize_podman_name(&repo_name));
+            let namespace = config.templates.namespace(config.githuborg.as_deref())?;
+            if let Some(host) = config.registries.get(registry) {
+                registry::login(registry, host, verbose)?;
+            }
+            template::publish(
+                &devcontainer_dir,
+                &id,
+                &version,
+                registry,
+                &namespace,
+                verbose,
+            )
         }
-    })?;
+        TemplateAction::Pull { template, dir } => {
+            let reference = config
+                .templates
+                .reference(&template, config.githuborg.as_deref())?;
+            registry::login_for_images(
+                &config.registries,
+                std::slice::from_ref(&reference),
+                verbose,
+            )?;
+            template::apply(&reference, &dir, verbose)
+        }
+    }
+}
+
+fn bundle_command(action: BundleAction, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    match action {
+        BundleAction::Create {
+            name,
+            devcontainer_env,
+            output,
+        } => {
+            let (repo_root, repo_name) = current_repo("bundle create")?;
+            let devcontainer_env = config.devcontainer_env(devcontainer_env.as_deref());
+            let Some(devcontainer_path) = locate_devcontainer(devcontainer_env)? else {
+                anyhow::bail!("no devcontainer.json found; run `forest validate`");
+            };
+            let value: Value = serde_json::from_str(&fs::read_to_string(&devcontainer_path)?)?;
+            enforce_policy(&value, &devcontainer_path)?;
+            login_registries(&value, &devcontainer_path, config, verbose)?;
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "{}-{}.forest.tar",
+                    repo_name,
+                    sanitize_podman_name(&name)
+                ))
+            });
+            bundle::create(
+                &repo_root,
+                &repo_name,
+                &name,
+                &devcontainer_path,
+                &output,
+                verbose,
+            )
+        }
+        BundleAction::Open { file, dir } => {
+            let unpacked = bundle::unpack(&file, &dir, verbose)?;
+            std::env::set_current_dir(&unpacked.repo_dir)?;
+            open_session(
+                &unpacked.session,
+                Some(bundle::BUNDLE_ENV),
+                &Overrides::default(),
+                &OpenOptions::default(),
+                config,
+                verbose,
+            )?;
+            Ok(())
+        }
+    }
+}
+
+fn show_features(config: &Config) {
+    let mut table = style::Table::new(&["feature", "state", "description"]);
+    for feature in features::FEATURES {
+        let enabled = config.features.get(feature.name).copied().unwrap_or(false);
+        table.row(vec![
+            (feature.name.to_string(), None),
+            if enabled {
+                ("enabled".to_string(), Some(style::Role::Success))
+            } else {
+                ("disabled".to_string(), Some(style::Role::Dim))
+            },
+            (feature.description.to_string(), None),
+        ]);
+    }
+    table.print();
+}
+
+// man pages rendered by build.rs from the command definitions and topics
+include!(concat!(env!("OUT_DIR"), "/man_pages.rs"));
+
+/// Prints the long help of a command, e.g. `trash restore`, or a topic.
+fn show_help(words: &[String]) -> anyhow::Result<()> {
+    if let [word] = words {
+        if let Some(topic) = topics::find(word) {
+            println!("{}: {}\n", topic.name, topic.summary);
+            println!("{}", topic.body);
+            return Ok(());
+        }
+    }
+    let mut cmd = Cli::command();
+    cmd.build();
+    let mut target = &mut cmd;
+    for word in words {
+        target = target.find_subcommand_mut(word).ok_or_else(|| {
+            anyhow::anyhow!(
+                "no command or topic {}; run `forest help` to list them",
+                words.join(" ")
+            )
+        })?;
+    }
+    target.print_long_help()?;
+    if words.is_empty() {
+        println!();
+        let mut table = style::Table::new(&["topic", "summary"]);
+        for topic in topics::TOPICS {
+            table.row(vec![
+                (topic.name.to_string(), None),
+                (topic.summary.to_string(), None),
+            ]);
+        }
+        table.print();
+    }
     Ok(())
 }
 
-fn command_exists(cmd: &str) -> bool {
-    Command::new(cmd)
-        .arg("--version")
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+fn report_bug(output: Option<PathBuf>) -> anyhow::Result<()> {
+    let output =
+        output.unwrap_or_else(|| PathBuf::from(format!("forest-report-{}.txt", timesheet::now())));
+    fs::write(
+        &output,
+        report::build(config_path().as_deref(), &data_dir()?),
+    )?;
+    println!(
+        "Wrote {}; review it before attaching it to a bug report",
+        output.display()
+    );
+    Ok(())
+}
+
+fn gh_extension_command(action: GhExtensionAction) -> anyhow::Result<()> {
+    let gh = |args: &[&OsStr]| -> anyhow::Result<bool> {
+        Ok(Command::new("gh")
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .map_err(|e| anyhow::anyhow!("running gh failed: {}", e))?
+            .success())
+    };
+    match action {
+        GhExtensionAction::Install => {
+            let dir = gh_extension::write(&data_dir()?, &std::env::current_exe()?)?;
+            // gh links to the directory, so the rewritten script is enough
+            // when the extension is already there
+            let listed = Command::new("gh")
+                .args(["extension", "list"])
+                .output()
+                .map_err(|e| anyhow::anyhow!("running gh failed: {}", e))?;
+            let installed = String::from_utf8_lossy(&listed.stdout)
+                .lines()
+                .any(|line| line.contains(&format!("gh-{}", gh_extension::NAME)));
+            if !installed
+                && !gh(&[
+                    OsStr::new("extension"),
+                    OsStr::new("install"),
+                    dir.as_os_str(),
+                ])?
+            {
+                anyhow::bail!("gh extension install failed");
+            }
+            println!("Installed `gh {}`", gh_extension::NAME);
+        }
+        GhExtensionAction::Uninstall => {
+            if !gh(&[
+                OsStr::new("extension"),
+                OsStr::new("remove"),
+                OsStr::new(gh_extension::NAME),
+            ])? {
+                anyhow::bail!("gh extension remove failed");
+            }
+            let dir = gh_extension::dir(&data_dir()?);
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+            println!("Removed `gh {}`", gh_extension::NAME);
+        }
+        GhExtensionAction::Run { args } => {
+            let repository = match std::env::var("GH_REPO") {
+                Ok(gh_repo) if !gh_repo.is_empty() => Some(gh_extension::repository(
+                    &gh_repo,
+                    std::env::var("GH_HOST").ok().as_deref(),
+                )?),
+                _ => None,
+            };
+            let args = gh_extension::forest_args(&args, repository.as_ref());
+            let err = Command::new(std::env::current_exe()?).args(args).exec();
+            anyhow::bail!("running forest failed: {}", err);
+        }
+    }
+    Ok(())
+}
+
+fn write_man_pages(output: &Path) -> anyhow::Result<()> {
+    for (path, page) in PAGES {
+        let path = output.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, page)?;
+    }
+    println!("Wrote {} man pages to {}", PAGES.len(), output.display());
+    Ok(())
+}
+
+/// Prints the repository's devcontainer overlay, or with `edit` opens it in
+/// `$VISUAL` or `$EDITOR`, creating it empty, and checks it parses.
+fn overlay_command(edit: bool, verbose: bool) -> anyhow::Result<()> {
+    let path = overlay_path()?;
+    if edit {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if !path.exists() {
+            fs::write(&path, "{\n}\n")?;
+        }
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path);
+        if !run_command_verbose(&mut cmd, verbose)?.success() {
+            anyhow::bail!("{} failed", editor);
+        }
+        apply_overlay(&Value::Object(Default::default()))?;
+        println!(
+            "Later opens merge {} over devcontainer.json",
+            path.display()
+        );
+        return Ok(());
+    }
+    println!("{}", path.display());
+    match fs::read_to_string(&path) {
+        Ok(contents) => print!("{}", contents),
+        Err(_) => style::note("no overlay yet; `forest overlay --edit` creates one"),
+    }
+    Ok(())
+}
+
+fn show_sanitized(names: &[String]) {
+    let mut table = style::Table::new(&["name", "label", ""]);
+    for name in names {
+        let label = sanitize_podman_name(name);
+        let note = if &label == name { "" } else { "(rewritten)" };
+        table.row(vec![
+            (name.clone(), None),
+            (label, None),
+            (note.to_string(), Some(style::Role::Warning)),
+        ]);
+    }
+    table.print();
+    for (label, originals) in forest::names::collisions(names.iter().map(String::as_str)) {
+        println!(
+            "{} {} all map to {}",
+            style::paint(style::Role::Error, "collision:"),
+            originals.join(", "),
+            label
+        );
+    }
+}
+
+fn show_time(since: Option<&str>) -> anyhow::Result<()> {
+    let since = match since {
+        Some(span) => timesheet::now().saturating_sub(timesheet::parse_span(span)?),
+        None => 0,
+    };
+    let entries = timesheet::load(&data_dir()?)?;
+    let totals = timesheet::summarize(&entries, since);
+    if totals.is_empty() {
+        println!("No session time recorded");
+        return Ok(());
+    }
+    let mut table = style::Table::new(&["session", "hours"]);
+    for ((repo, session), seconds) in &totals {
+        table.row(vec![
+            (format!("{}/{}", repo, session), None),
+            (format!("{:.2}h", *seconds as f64 / 3600.0), None),
+        ]);
+    }
+    let total: u64 = totals.values().sum();
+    table.row(vec![
+        ("total".to_string(), Some(style::Role::Header)),
+        (
+            format!("{:.2}h", total as f64 / 3600.0),
+            Some(style::Role::Header),
+        ),
+    ]);
+    table.print();
+    Ok(())
 }
 
 fn precheck(verbose: bool) -> anyhow::Result<()> {
+    precheck_in(verbose, Path::new("/"))
+}
+
+/// `precheck` against the kernel interfaces under `root`.
+fn precheck_in(verbose: bool, root: &Path) -> anyhow::Result<()> {
     let mut errors = Vec::new();
 
     for cmd in ["devcontainer", "git", "gh"] {
@@ -424,17 +2361,31 @@ fn precheck(verbose: bool) -> anyhow::Result<()> {
         }
     }
 
+    // elsewhere podman runs in a Linux VM it manages itself
+    if cfg!(target_os = "linux") && backend::kind() == backend::Kind::Podman {
+        if verbose {
+            println!("Checking kernel features");
+        }
+        errors.extend(kernel::check(root));
+    }
+
     if let Some(proj_dirs) = ProjectDirs::from("", "", "forest") {
         let path = proj_dirs.config_dir().join("forest.toml");
         if verbose {
             println!("Checking config {}", path.display());
         }
         match fs::read_to_string(&path) {
-            Ok(content) => {
-                if let Err(e) = toml::from_str::<Config>(&content) {
-                    errors.push(format!("failed to parse {}: {}", path.display(), e));
+            Ok(content) => match toml::from_str::<Config>(&content) {
+                Ok(config) => {
+                    for (host, registry) in &config.registries {
+                        if verbose {
+                            println!("Checking registry {}", host);
+                        }
+                        errors.extend(registry::check(host, registry));
+                    }
                 }
-            }
+                Err(e) => errors.push(format!("failed to parse {}: {}", path.display(), e)),
+            },
             Err(_) => errors.push(format!("config file {} not found", path.display())),
         }
     } else {
@@ -463,27 +2414,132 @@ fn precheck(verbose: bool) -> anyhow::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::Parser;
     use std::env;
     use tempfile::tempdir;
 
     #[test]
-    fn scaffold_created_when_missing() {
-        let dir = tempdir().unwrap();
-        let orig = env::current_dir().unwrap();
-        env::set_current_dir(&dir).unwrap();
+    fn exec_takes_the_command_after_dashes() {
+        let cli =
+            Cli::try_parse_from(["forest", "exec", "feat", "--", "cargo", "test", "-q"]).unwrap();
+        let Commands::Exec { names, command, .. } = cli.command else {
+            panic!("not an exec");
+        };
+        assert_eq!(names, ["feat"]);
+        assert_eq!(command, ["cargo", "test", "-q"]);
+        assert!(Cli::try_parse_from(["forest", "exec", "feat"]).is_err());
+
+        let cli = Cli::try_parse_from(["forest", "exec", "a", "b", "--group-output", "--", "ls"])
+            .unwrap();
+        let Commands::Exec {
+            names,
+            group_output,
+            ..
+        } = cli.command
+        else {
+            panic!("not an exec");
+        };
+        assert_eq!(names, ["a", "b"]);
+        assert!(group_output);
+    }
+
+    #[test]
+    fn finds_pull_request_urls() {
+        assert_eq!(
+            last_url("Creating pull request for feat into main\n\nhttps://github.com/acme/widget/pull/7\n"),
+            Some("https://github.com/acme/widget/pull/7")
+        );
+        assert_eq!(last_url("nothing\n"), None);
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(duration(0), "0s");
+        assert_eq!(duration(42), "42s");
+        assert_eq!(duration(12 * 60 + 5), "12m 5s");
+        assert_eq!(duration(3 * 86400 + 4 * 3600 + 59), "3d 4h");
+    }
 
-        let path = find_devcontainer(None).unwrap();
-        assert!(path.exists());
-        let contents = fs::read_to_string(&path).unwrap();
-        assert!(contents.contains("ubuntu"));
+    #[test]
+    fn summarizes_inspect_documents() {
+        let document = json!({
+            "session": "feat/x",
+            "label": "feat-x",
+            "backend": "docker",
+            "registry": {"branch": "feat/x", "created_at": 7},
+            "up": null,
+            "checkpoint": null,
+            "container": {
+                "Id": "abc",
+                "State": {"Status": "running"},
+                "Mounts": [{"Source": "/src/widget", "Destination": "/repo"}],
+                "Config": {"Labels": {"name": "feat-x"}, "Env": ["PATH=/bin"]},
+            },
+        });
+        let lines = inspect_lines(&document);
+        for expected in [
+            "backend    docker",
+            "registry   created_at 7",
+            "container  abc running",
+            "mount      /src/widget -> /repo",
+            "label      name=feat-x",
+            "env        PATH=/bin",
+        ] {
+            assert!(lines.iter().any(|l| l == expected), "{}", expected);
+        }
+    }
 
-        env::set_current_dir(orig).unwrap();
+    #[test]
+    fn takes_raw_devcontainer_args() {
+        let cli = Cli::try_parse_from([
+            "forest",
+            "open",
+            "feat",
+            "--devcontainer-arg",
+            "--log-level",
+            "--devcontainer-arg=trace",
+        ])
+        .unwrap();
+        assert_eq!(cli.devcontainer_args, ["--log-level", "trace"]);
     }
 
     #[test]
-    fn command_exists_detects_commands() {
-        assert!(command_exists("true"));
-        assert!(!command_exists("definitely_not_a_command"));
+    fn help_resolves_commands_and_topics() {
+        assert!(show_help(&["trash".to_string(), "restore".to_string()]).is_ok());
+        assert!(show_help(&["worktrees".to_string()]).is_ok());
+        assert!(show_help(&["profiles".to_string()]).is_err());
+        for name in [
+            "man1/forest.1",
+            "man1/forest-trash-restore.1",
+            "man7/forest-policy.7",
+        ] {
+            assert!(PAGES.iter().any(|(path, _)| *path == name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn reports_devcontainer_problems() {
+        let dir = tempdir().unwrap();
+        let image: Value = serde_json::from_str(r#"{"image": "ubuntu"}"#).unwrap();
+        assert!(devcontainer_problems(&image, dir.path()).is_empty());
+
+        let empty: Value = serde_json::from_str("{}").unwrap();
+        assert_eq!(devcontainer_problems(&empty, dir.path()).len(), 1);
+
+        let build: Value =
+            serde_json::from_str(r#"{"build": {"dockerfile": "Dockerfile"}}"#).unwrap();
+        let problems = devcontainer_problems(&build, dir.path());
+        assert!(problems[0].contains("Dockerfile does not exist"));
+        fs::write(dir.path().join("Dockerfile"), "FROM ubuntu\n").unwrap();
+        assert!(devcontainer_problems(&build, dir.path()).is_empty());
+
+        let compose: Value =
+            serde_json::from_str(r#"{"dockerComposeFile": "compose.yml", "service": "app"}"#)
+                .unwrap();
+        let problems = devcontainer_problems(&compose, dir.path());
+        assert_eq!(problems, ["dockerComposeFile compose.yml does not exist"]);
+        fs::write(dir.path().join("compose.yml"), "services: {}\n").unwrap();
+        assert!(devcontainer_problems(&compose, dir.path()).is_empty());
     }
 
     #[test]
@@ -531,7 +2587,9 @@ mod tests {
         let config_dir = home_dir.path().join("forest");
         fs::create_dir_all(&config_dir).unwrap();
         fs::write(config_dir.join("forest.toml"), "githuborg = 'foo'\n").unwrap();
+        let root = tempdir().unwrap();
+        kernel::healthy_root(root.path()).unwrap();
 
-        assert!(precheck(false).is_ok());
+        assert!(precheck_in(false, root.path()).is_ok());
     }
 }
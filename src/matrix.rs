@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::session::{UpRecord, UP_CACHE};
+use crate::{sessions, store};
+
+/// The engine's name for platform `spec`: `arm64` is `linux/arm64`, and
+/// `linux/arm/v7` stays as it is.
+pub fn platform(spec: &str) -> anyhow::Result<String> {
+    let spec = spec.trim();
+    if spec.is_empty() || spec.contains(char::is_whitespace) {
+        anyhow::bail!("invalid platform {:?}", spec);
+    }
+    Ok(if spec.contains('/') {
+        spec.to_string()
+    } else {
+        format!("linux/{}", spec)
+    })
+}
+
+/// The session of branch `name` for `platform`, such as `feat-arm64` or
+/// `feat-arm-v7`, also the name of its branch.
+pub fn sibling(name: &str, platform: &str) -> String {
+    let arch = platform.strip_prefix("linux/").unwrap_or(platform);
+    format!("{}-{}", name, arch.replace('/', "-"))
+}
+
+/// The matrix sessions of branch `name` in repository `repo`, as platform and
+/// session name ordered by platform: sessions opened for a platform that are
+/// named as its sibling of `name`.
+pub fn siblings(data_dir: &Path, repo: &Path, name: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let up_cache: BTreeMap<String, UpRecord> = store::load(data_dir, &UP_CACHE)?;
+    let mut found: Vec<(String, String)> = sessions::load(data_dir)?
+        .into_iter()
+        .filter(|(_, session)| session.repo == repo)
+        .filter_map(|(label, session)| {
+            let platform = up_cache.get(&label)?.overrides.platform.clone()?;
+            (session.branch == sibling(name, &platform)).then_some((platform, session.branch))
+        })
+        .collect();
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Overrides;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn finds_platform_sessions_of_a_branch() {
+        assert_eq!(platform("arm64").unwrap(), "linux/arm64");
+        assert_eq!(platform("linux/arm/v7").unwrap(), "linux/arm/v7");
+        assert!(platform(" ").is_err());
+        assert_eq!(sibling("feat", "linux/arm/v7"), "feat-arm-v7");
+
+        let dir = tempdir().unwrap();
+        let mut up_cache = BTreeMap::new();
+        for (branch, repo, platform) in [
+            ("feat-arm64", "/src/widget", Some("linux/arm64")),
+            ("feat-amd64", "/src/widget", Some("linux/amd64")),
+            ("feat-amd64", "/src/gadget", Some("linux/amd64")),
+            ("feat-old", "/src/widget", None),
+        ] {
+            let label = format!("{}-{}", repo.rsplit('/').next().unwrap(), branch);
+            sessions::record(
+                dir.path(),
+                &label,
+                sessions::Session {
+                    branch: branch.to_string(),
+                    repo: PathBuf::from(repo),
+                    worktree: PathBuf::from("/w").join(branch),
+                    devcontainer_env: None,
+                    created_at: 0,
+                    readonly: false,
+                },
+            )
+            .unwrap();
+            up_cache.insert(
+                label,
+                UpRecord {
+                    forest_version: None,
+                    hash: String::new(),
+                    build_hash: None,
+                    container: None,
+                    overrides: Overrides {
+                        platform: platform.map(str::to_string),
+                        ..Overrides::default()
+                    },
+                    config: None,
+                },
+            );
+        }
+        store::save(dir.path(), &UP_CACHE, &up_cache).unwrap();
+        assert_eq!(
+            siblings(dir.path(), Path::new("/src/widget"), "feat").unwrap(),
+            [
+                ("linux/amd64".to_string(), "feat-amd64".to_string()),
+                ("linux/arm64".to_string(), "feat-arm64".to_string()),
+            ]
+        );
+    }
+}
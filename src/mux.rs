@@ -0,0 +1,214 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+
+use crate::style::{self, Role};
+
+/// How the output of jobs running at once is shown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layout {
+    /// Each line as soon as it's complete, prefixed with its job's name
+    Interleaved,
+    /// Each job's output in one block once the job ends
+    Grouped,
+}
+
+/// A command run alongside others, named for its output.
+pub struct Job {
+    pub name: String,
+    pub command: Command,
+}
+
+/// Lines read from the jobs' pipes. Whole lines are written under the lock,
+/// so output from different jobs never mixes within a line.
+struct Sink<O, E> {
+    layout: Layout,
+    width: usize,
+    out: O,
+    err: E,
+    /// Held-back lines of each job with `Grouped`, and whether each went to
+    /// stderr
+    held: Vec<Vec<(bool, Vec<u8>)>>,
+}
+
+impl<O: Write, E: Write> Sink<O, E> {
+    fn prefix(&self, name: &str, stderr: bool) -> String {
+        // padding and a bar mean nothing read out loud
+        let prefix = if style::plain() {
+            format!("{}:", name)
+        } else {
+            format!("{:<width$} |", name, width = self.width)
+        };
+        if stderr {
+            style::paint_err(Role::Dim, &prefix)
+        } else {
+            style::paint(Role::Dim, &prefix)
+        }
+    }
+
+    fn write(&mut self, stderr: bool, prefix: &[u8], line: &[u8]) {
+        let out: &mut dyn Write = if stderr { &mut self.err } else { &mut self.out };
+        let _ = out.write_all(&[prefix, line, b"\n"].concat());
+    }
+
+    fn line(&mut self, job: usize, name: &str, stderr: bool, mut line: Vec<u8>) {
+        if style::plain() {
+            line = style::settled(&line).to_vec();
+        }
+        if self.layout == Layout::Grouped {
+            self.held[job].push((stderr, line));
+            return;
+        }
+        let prefix = self.prefix(name, stderr);
+        self.write(stderr, format!("{} ", prefix).as_bytes(), &line);
+    }
+
+    fn finish(&mut self, job: usize, name: &str, status: Option<ExitStatus>) {
+        if self.layout != Layout::Grouped {
+            return;
+        }
+        let outcome = match status.and_then(|s| s.code()) {
+            Some(0) => style::paint(Role::Success, "ok"),
+            Some(code) => style::paint(Role::Error, &format!("exit {}", code)),
+            None => style::paint(Role::Error, "failed"),
+        };
+        let heading = if style::plain() {
+            format!("{}:", name)
+        } else {
+            format!("== {}", name)
+        };
+        let _ = writeln!(
+            self.out,
+            "{} {}",
+            style::paint(Role::Header, &heading),
+            outcome
+        );
+        for (stderr, line) in std::mem::take(&mut self.held[job]) {
+            self.write(stderr, b"", &line);
+        }
+        let _ = self.out.flush();
+    }
+}
+
+fn forward<O: Write, E: Write>(
+    sink: &Mutex<Sink<O, E>>,
+    job: usize,
+    name: &str,
+    stderr: bool,
+    pipe: impl Read,
+) {
+    for line in BufReader::new(pipe).split(b'\n') {
+        let Ok(mut line) = line else {
+            break;
+        };
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        sink.lock().unwrap().line(job, name, stderr, line);
+    }
+}
+
+/// Runs `jobs` at once, returning each one's exit status in order, or `None`
+/// for a job that couldn't be started.
+pub fn run(jobs: Vec<Job>, layout: Layout) -> Vec<Option<ExitStatus>> {
+    run_to(jobs, layout, std::io::stdout(), std::io::stderr())
+}
+
+fn run_to<O: Write + Send, E: Write + Send>(
+    jobs: Vec<Job>,
+    layout: Layout,
+    out: O,
+    err: E,
+) -> Vec<Option<ExitStatus>> {
+    let sink = Mutex::new(Sink {
+        layout,
+        width: jobs.iter().map(|j| j.name.len()).max().unwrap_or(0),
+        out,
+        err,
+        held: vec![Vec::new(); jobs.len()],
+    });
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut job)| {
+                let sink = &sink;
+                scope.spawn(move || {
+                    let child = job
+                        .command
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped())
+                        .spawn();
+                    let mut child = match child {
+                        Ok(child) => child,
+                        Err(e) => {
+                            let message = format!("failed to start: {}", e).into_bytes();
+                            let mut sink = sink.lock().unwrap();
+                            sink.line(index, &job.name, true, message);
+                            sink.finish(index, &job.name, None);
+                            return None;
+                        }
+                    };
+                    let stdout = child.stdout.take().expect("piped stdout");
+                    let stderr = child.stderr.take().expect("piped stderr");
+                    let name = job.name.as_str();
+                    std::thread::scope(|pipes| {
+                        pipes.spawn(|| forward(sink, index, name, false, stdout));
+                        forward(sink, index, name, true, stderr);
+                    });
+                    let status = child.wait().ok();
+                    sink.lock().unwrap().finish(index, name, status);
+                    status
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or(None))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(name: &str, script: &str) -> Job {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        Job {
+            name: name.to_string(),
+            command,
+        }
+    }
+
+    fn jobs() -> Vec<Job> {
+        vec![
+            job("a", "echo one; sleep 0.3; echo two; exit 3"),
+            job("bb", "echo three >&2"),
+        ]
+    }
+
+    #[test]
+    fn prefixes_every_line() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let statuses = run_to(jobs(), Layout::Interleaved, &mut out, &mut err);
+        assert_eq!(statuses[0].and_then(|s| s.code()), Some(3));
+        assert!(statuses[1].is_some_and(|s| s.success()));
+        assert_eq!(String::from_utf8(out).unwrap(), "a  | one\na  | two\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "bb | three\n");
+    }
+
+    #[test]
+    fn groups_output_per_job() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run_to(jobs(), Layout::Grouped, &mut out, &mut err);
+        let out = String::from_utf8(out).unwrap();
+        // bb finishes first, so its block comes first
+        assert_eq!(out, "== bb ok\n== a exit 3\none\ntwo\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "three\n");
+    }
+}
@@ -0,0 +1,115 @@
+//! Mapping from session (branch) names to container name labels.
+
+use std::collections::BTreeMap;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest label forest generates, matching the hostname limit since podman
+/// derives container hostnames from it.
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Turns a branch name into a label podman accepts as a container name.
+///
+/// Names that are already valid labels are used as-is. Otherwise accented
+/// letters are transliterated to ASCII, remaining invalid characters become
+/// `-`, overly long names are truncated, and a short hash of the original name
+/// is appended so `feat/cool` and `feat.cool` don't end up sharing a container.
+/// The original name stays the source of truth for git and worktree paths.
+pub fn sanitize_podman_name(branch: &str) -> String {
+    let name = legacy_podman_name(&transliterate(branch));
+    if name == branch && name.len() <= MAX_LABEL_LEN {
+        return name;
+    }
+    let suffix = format!("-{:06x}", fnv1a(branch.as_bytes()) & 0xff_ffff);
+    let base: String = name.chars().take(MAX_LABEL_LEN - suffix.len()).collect();
+    format!("{}{}", base, suffix)
+}
+
+/// Decomposes accented characters and drops the combining marks, spelling out
+/// the few letters that don't decompose.
+fn transliterate(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.nfd() {
+        if is_combining_mark(c) {
+            continue;
+        }
+        match c {
+            'ß' => out.push_str("ss"),
+            'æ' => out.push_str("ae"),
+            'Æ' => out.push_str("AE"),
+            'œ' => out.push_str("oe"),
+            'Œ' => out.push_str("OE"),
+            'ø' => out.push('o'),
+            'Ø' => out.push('O'),
+            'ł' => out.push('l'),
+            'Ł' => out.push('L'),
+            'đ' | 'ð' => out.push('d'),
+            'Đ' | 'Ð' => out.push('D'),
+            'þ' => out.push_str("th"),
+            'Þ' => out.push_str("TH"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The label scheme used before hash suffixes, which maps distinct names like
+/// `feat/cool` and `feat cool` to the same label. Kept to find containers
+/// created by older versions of forest.
+pub fn legacy_podman_name(branch: &str) -> String {
+    let mut name: String = branch
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if name
+        .chars()
+        .next()
+        .map(|c| !c.is_ascii_alphanumeric())
+        .unwrap_or(true)
+    {
+        name.insert(0, 's');
+    }
+    name
+}
+
+/// FNV-1a, chosen because labels must stay identical across forest builds.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_extend(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+/// Continues FNV-1a hash `hash` over `bytes`.
+pub fn fnv1a_extend(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub fn valid_podman_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphanumeric() => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+/// Groups names that sanitize to the same label, keyed by that label. Only
+/// labels shared by more than one distinct name are reported.
+pub fn collisions<'a>(names: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, Vec<String>> {
+    let mut by_label: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in names {
+        let originals = by_label.entry(sanitize_podman_name(name)).or_default();
+        if !originals.iter().any(|o| o == name) {
+            originals.push(name.to_string());
+        }
+    }
+    by_label.retain(|_, originals| originals.len() > 1);
+    by_label
+}
@@ -0,0 +1,282 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Where administrators install the policy; `FOREST_POLICY` overrides it.
+pub const POLICY_PATH: &str = "/etc/forest/policy.toml";
+
+/// Organisation-wide restrictions on the sessions forest creates.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Base images sessions may use; a trailing `*` matches any suffix
+    pub allowed_images: Option<Vec<String>>,
+    /// Registries base images may come from, e.g. `registry.example.com`
+    pub allowed_registries: Option<Vec<String>>,
+    /// Reject `privileged`, `capAdd` and the matching `runArgs`
+    #[serde(default)]
+    pub forbid_privileged: bool,
+    /// Require `--memory` and `--cpus` in `runArgs`
+    #[serde(default)]
+    pub require_resource_limits: bool,
+    /// Oldest forest release allowed to create sessions
+    pub min_forest_version: Option<String>,
+}
+
+/// Loads the policy if one is installed. A policy that doesn't parse is an
+/// error rather than being ignored, so a typo can't silently lift restrictions.
+pub fn load() -> anyhow::Result<Option<(PathBuf, Policy)>> {
+    let path = std::env::var_os("FOREST_POLICY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(POLICY_PATH));
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow::anyhow!("failed to read {}: {}", path.display(), e)),
+    };
+    let policy = toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("invalid policy {}: {}", path.display(), e))?;
+    Ok(Some((path, policy)))
+}
+
+impl Policy {
+    /// Everything in a session's devcontainer configuration that the policy
+    /// forbids. `dir` holds the `devcontainer.json`, for resolving Dockerfiles.
+    pub fn violations(&self, devcontainer: &Value, dir: &Path) -> Vec<String> {
+        let mut violations = Vec::new();
+        if let Some(min) = &self.min_forest_version {
+            if parse_version(env!("CARGO_PKG_VERSION")) < parse_version(min) {
+                violations.push(format!(
+                    "forest {} is older than the required {}; upgrade forest",
+                    env!("CARGO_PKG_VERSION"),
+                    min
+                ));
+            }
+        }
+
+        for image in base_images(devcontainer, dir) {
+            if let Some(allowed) = &self.allowed_images {
+                if !allowed.iter().any(|pattern| image_matches(pattern, &image)) {
+                    violations.push(format!("image {} is not in allowed_images", image));
+                }
+            }
+            if let Some(registries) = &self.allowed_registries {
+                let registry = registry(&image);
+                if !registries.iter().any(|r| r == registry) {
+                    violations.push(format!(
+                        "image {} comes from registry {}, which is not in allowed_registries",
+                        image, registry
+                    ));
+                }
+            }
+        }
+
+        let run_args: Vec<&str> = devcontainer
+            .get("runArgs")
+            .and_then(Value::as_array)
+            .map(|args| args.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let has_arg = |names: &[&str]| {
+            run_args.iter().any(|arg| {
+                names
+                    .iter()
+                    .any(|name| arg == name || arg.starts_with(&format!("{}=", name)))
+            })
+        };
+        if self.forbid_privileged {
+            if devcontainer.get("privileged").and_then(Value::as_bool) == Some(true)
+                || has_arg(&["--privileged"])
+            {
+                violations.push("privileged containers are forbidden".to_string());
+            }
+            let cap_add = devcontainer
+                .get("capAdd")
+                .and_then(Value::as_array)
+                .is_some_and(|caps| !caps.is_empty());
+            if cap_add || has_arg(&["--cap-add"]) {
+                violations.push("adding capabilities is forbidden".to_string());
+            }
+        }
+        if self.require_resource_limits {
+            if !has_arg(&["--memory", "-m"]) {
+                violations.push("runArgs must set a memory limit (--memory)".to_string());
+            }
+            if !has_arg(&["--cpus"]) {
+                violations.push("runArgs must set a CPU limit (--cpus)".to_string());
+            }
+        }
+        violations
+    }
+}
+
+/// Images a session is built from: `image`, or the `FROM` lines of the
+/// Dockerfile, skipping references to earlier build stages.
+pub fn base_images(devcontainer: &Value, dir: &Path) -> Vec<String> {
+    if let Some(image) = devcontainer.get("image").and_then(Value::as_str) {
+        return vec![image.to_string()];
+    }
+    let Some(dockerfile) = devcontainer
+        .get("build")
+        .and_then(|b| b.get("dockerfile"))
+        .and_then(Value::as_str)
+    else {
+        return Vec::new();
+    };
+    let content = fs::read_to_string(dir.join(dockerfile)).unwrap_or_default();
+    let mut stages: Vec<String> = Vec::new();
+    let mut images = Vec::new();
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        if !words.next().is_some_and(|w| w.eq_ignore_ascii_case("FROM")) {
+            continue;
+        }
+        let mut words = words.skip_while(|w| w.starts_with("--"));
+        let Some(image) = words.next() else {
+            continue;
+        };
+        if !stages.iter().any(|s| s.eq_ignore_ascii_case(image)) {
+            images.push(image.to_string());
+        }
+        if words.next().is_some_and(|w| w.eq_ignore_ascii_case("AS")) {
+            if let Some(stage) = words.next() {
+                stages.push(stage.to_string());
+            }
+        }
+    }
+    images
+}
+
+fn image_matches(pattern: &str, image: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        return image.starts_with(prefix);
+    }
+    // a pattern without a tag allows every tag of the image
+    image == pattern
+        || image
+            .strip_prefix(pattern)
+            .is_some_and(|rest| rest.starts_with(':') || rest.starts_with('@'))
+}
+
+/// Registry host of an image reference, following docker's rule that the first
+/// path component is a host only if it looks like one.
+pub fn registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            first
+        }
+        _ => "docker.io",
+    }
+}
+
+pub fn parse_version(version: &str) -> (u64, u64, u64) {
+    let mut parts = version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|p| p.parse().unwrap_or(0));
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn json(s: &str) -> Value {
+        serde_json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn restricts_images_and_registries() {
+        let policy = Policy {
+            allowed_images: Some(vec![
+                "registry.example.com/*".to_string(),
+                "ubuntu".to_string(),
+            ]),
+            allowed_registries: Some(vec!["registry.example.com".to_string()]),
+            ..Policy::default()
+        };
+        let dir = tempdir().unwrap();
+        assert!(policy
+            .violations(
+                &json(r#"{"image": "registry.example.com/base:1"}"#),
+                dir.path()
+            )
+            .is_empty());
+        // allowed by name, but from Docker Hub
+        assert_eq!(
+            policy
+                .violations(&json(r#"{"image": "ubuntu:24.04"}"#), dir.path())
+                .len(),
+            1
+        );
+        assert_eq!(
+            policy
+                .violations(&json(r#"{"image": "ubuntu-evil"}"#), dir.path())
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn checks_dockerfile_base_images() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("Dockerfile"),
+            "FROM --platform=linux/amd64 rust:1 AS build\nFROM build\nFROM debian:12\n",
+        )
+        .unwrap();
+        let images = base_images(
+            &json(r#"{"build": {"dockerfile": "Dockerfile"}}"#),
+            dir.path(),
+        );
+        assert_eq!(images, ["rust:1", "debian:12"]);
+    }
+
+    #[test]
+    fn forbids_privileges_and_requires_limits() {
+        let policy = Policy {
+            forbid_privileged: true,
+            require_resource_limits: true,
+            ..Policy::default()
+        };
+        let dir = tempdir().unwrap();
+        let violations = policy.violations(
+            &json(r#"{"image": "x", "privileged": true, "runArgs": ["--cap-add=SYS_ADMIN", "--memory=4g"]}"#),
+            dir.path(),
+        );
+        assert_eq!(violations.len(), 3);
+        assert!(violations.iter().any(|v| v.contains("--cpus")));
+        assert!(policy
+            .violations(
+                &json(r#"{"image": "x", "runArgs": ["-m", "4g", "--cpus", "2"]}"#),
+                dir.path()
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn pins_minimum_version() {
+        let policy = Policy {
+            min_forest_version: Some("999.0.0".to_string()),
+            ..Policy::default()
+        };
+        let dir = tempdir().unwrap();
+        assert_eq!(policy.violations(&json("{}"), dir.path()).len(), 1);
+        assert!(parse_version("v1.2") < parse_version("1.10.0"));
+    }
+
+    #[test]
+    fn finds_registries() {
+        assert_eq!(registry("ubuntu"), "docker.io");
+        assert_eq!(registry("library/ubuntu"), "docker.io");
+        assert_eq!(registry("ghcr.io/org/img:1"), "ghcr.io");
+        assert_eq!(registry("localhost:5000/img"), "localhost:5000");
+    }
+}
@@ -0,0 +1,105 @@
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use crate::runtime::program;
+
+/// Priorities range like `nice`: -20 runs first, 19 last, 0 is the default.
+pub const RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+/// CPU shares for a nice value: 1024 at 0, each step worth about 25% more or
+/// less CPU, as the kernel weighs nice levels.
+pub fn cpu_shares(nice: i32) -> u64 {
+    (1024.0 * 1.25f64.powi(-nice)).round().clamp(2.0, 262144.0) as u64
+}
+
+/// Block IO weight for a nice value: 500 at 0, 1000 at -20 and 10 at 19.
+pub fn io_weight(nice: i32) -> u64 {
+    let nice = nice.clamp(*RANGE.start(), *RANGE.end());
+    if nice <= 0 {
+        (500 - 25 * nice) as u64
+    } else {
+        (500 - 490 * nice / 19) as u64
+    }
+}
+
+/// Engine arguments giving a container the priority `nice`, for `run` and
+/// `update` alike.
+pub fn args(nice: i32) -> [String; 2] {
+    [
+        format!("--cpu-shares={}", cpu_shares(nice)),
+        format!("--blkio-weight={}", io_weight(nice)),
+    ]
+}
+
+/// The configuration with its container run at priority `nice`, replacing
+/// CPU shares and IO weight it sets itself; unchanged without a priority.
+pub fn apply(value: &Value, nice: Option<i32>) -> Value {
+    let mut value = value.clone();
+    let (Some(nice), Some(object)) = (nice, value.as_object_mut()) else {
+        return value;
+    };
+    let mut run_args: Vec<Value> = Vec::new();
+    let mut skip_value = false;
+    for arg in object
+        .get("runArgs")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+    {
+        let text = arg.as_str().unwrap_or_default();
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        let flag = text.split('=').next().unwrap_or_default();
+        if ["--cpu-shares", "-c", "--blkio-weight"].contains(&flag) {
+            skip_value = !text.contains('=');
+            continue;
+        }
+        run_args.push(arg);
+    }
+    run_args.extend(args(nice).map(Value::String));
+    object.insert("runArgs".to_string(), Value::Array(run_args));
+    value
+}
+
+/// Changes the priority of running container `id` in place.
+pub fn update(id: &str, nice: i32, verbose: bool) -> anyhow::Result<()> {
+    let mut cmd = Command::new(program());
+    cmd.arg("update").args(args(nice)).arg(id);
+    if !verbose {
+        cmd.stdout(Stdio::null());
+    }
+    if !crate::run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("{} update failed", program());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_nice_values_to_weights() {
+        assert_eq!(cpu_shares(0), 1024);
+        assert_eq!(cpu_shares(1), 819);
+        assert_eq!(cpu_shares(19), 15);
+        assert_eq!(cpu_shares(-20), 88818);
+        assert_eq!(io_weight(0), 500);
+        assert_eq!(io_weight(-20), 1000);
+        assert_eq!(io_weight(19), 10);
+        assert_eq!(io_weight(10), 243);
+
+        let value: Value = serde_json::from_str(
+            r#"{"image": "x", "runArgs": ["--cpus=2", "--cpu-shares", "100", "--blkio-weight=300"]}"#,
+        )
+        .unwrap();
+        assert_eq!(apply(&value, None), value);
+        assert_eq!(
+            apply(&value, Some(10))["runArgs"],
+            serde_json::json!(["--cpus=2", "--cpu-shares=110", "--blkio-weight=243"])
+        );
+    }
+}
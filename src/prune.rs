@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::WorktreeLayout;
+use crate::runtime::Container;
+use crate::sessions::Session;
+
+/// A worktree under the worktrees directory that no session record or
+/// container claims.
+pub struct Worktree {
+    pub path: PathBuf,
+    /// Repository name, the directory the worktree sits in
+    pub repo: String,
+    /// Session name, the worktree's path below the repository's directory
+    pub session: String,
+    /// Git's admin directory for the worktree, when a known repository has it
+    pub admin: Option<PathBuf>,
+}
+
+impl Worktree {
+    /// Branch checked out in the worktree, from the `HEAD` in its admin
+    /// directory since host git can't follow the worktree's `.git`.
+    pub fn branch(&self) -> Option<String> {
+        let head = fs::read_to_string(self.admin.as_ref()?.join("HEAD")).ok()?;
+        head.trim()
+            .strip_prefix("ref: refs/heads/")
+            .map(str::to_string)
+    }
+}
+
+/// What `forest prune` finds left behind, in each direction.
+#[derive(Default)]
+pub struct Orphans {
+    /// Labels of session records whose worktree is gone
+    pub records: Vec<String>,
+    /// Containers whose worktree is gone
+    pub containers: Vec<Container>,
+    pub worktrees: Vec<Worktree>,
+    /// Entries of a repository's `.git/worktrees` whose worktree is gone
+    pub admin_dirs: Vec<PathBuf>,
+}
+
+impl Orphans {
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+            && self.containers.is_empty()
+            && self.worktrees.is_empty()
+            && self.admin_dirs.is_empty()
+    }
+}
+
+/// Session worktrees under `root`: directories holding a `.git` file, at
+/// `<repo>/<name>` or deeper for names with slashes.
+pub fn find_worktrees(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let Ok(entries) = fs::read_dir(root) else {
+        return found;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+        let path = entry.path();
+        if path.join(".git").is_file() {
+            found.push(path);
+        } else {
+            found.extend(find_worktrees(&path));
+        }
+    }
+    found.sort();
+    found
+}
+
+/// Id of the `.git/worktrees` entry a session worktree's `.git` file names.
+fn admin_id(worktree: &Path) -> Option<String> {
+    let link = fs::read_to_string(worktree.join(".git")).ok()?;
+    link.trim()
+        .strip_prefix("gitdir: /repo/.git/worktrees/")
+        .map(str::to_string)
+}
+
+/// Entries of `repo_root`'s `.git/worktrees` that are stale by `git worktree
+/// prune`'s rule, the `.git` their `gitdir` names being gone. Session entries
+/// record container paths host git never finds, so those with an id in
+/// `claimed` are kept, as are locked ones.
+pub fn stale_admin_dirs(repo_root: &Path, claimed: &BTreeSet<String>) -> Vec<PathBuf> {
+    let mut stale = Vec::new();
+    let Ok(entries) = fs::read_dir(repo_root.join(".git").join("worktrees")) else {
+        return stale;
+    };
+    for entry in entries.flatten() {
+        let admin = entry.path();
+        let id = entry.file_name().to_string_lossy().to_string();
+        if claimed.contains(&id) || admin.join("locked").exists() {
+            continue;
+        }
+        let Ok(gitdir) = fs::read_to_string(admin.join("gitdir")) else {
+            continue;
+        };
+        if !Path::new(gitdir.trim()).exists() {
+            stale.push(admin);
+        }
+    }
+    stale.sort();
+    stale
+}
+
+/// Cross-references the session records, the containers of sessions under
+/// `root` and the worktrees there, laid out as `layout` says, and in the
+/// `.git/worktrees` of `repos`.
+pub fn survey(
+    sessions: &BTreeMap<String, Session>,
+    containers: Vec<Container>,
+    root: &Path,
+    repos: &BTreeSet<PathBuf>,
+    layout: WorktreeLayout,
+) -> Orphans {
+    let mut orphans = Orphans::default();
+    let mut claimed: BTreeMap<&Path, BTreeSet<String>> = BTreeMap::new();
+    for (label, session) in sessions {
+        if !session.worktree.exists() {
+            orphans.records.push(label.clone());
+        } else if let Some(id) = admin_id(&session.worktree) {
+            claimed.entry(&session.repo).or_default().insert(id);
+        }
+    }
+    let in_use: BTreeSet<PathBuf> = sessions
+        .values()
+        .map(|session| session.worktree.clone())
+        .chain(containers.iter().map(|c| PathBuf::from(&c.workspace)))
+        .collect();
+    for path in find_worktrees(root) {
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let repo = components
+            .next()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .unwrap_or_default();
+        let session = layout.name(components.as_path());
+        if session.is_empty() {
+            continue;
+        }
+        let id = admin_id(&path);
+        // the worktree's `.git` doesn't say which repository it's in, only
+        // the repository directory's name does
+        let mut admin = None;
+        for repo_root in repos.iter().filter(|r| r.ends_with(&repo)) {
+            if let Some(id) = &id {
+                claimed.entry(repo_root).or_default().insert(id.clone());
+                let dir = repo_root.join(".git").join("worktrees").join(id);
+                if admin.is_none() && dir.exists() {
+                    admin = Some(dir);
+                }
+            }
+        }
+        if !in_use.contains(&path) {
+            orphans.worktrees.push(Worktree {
+                path,
+                repo,
+                session,
+                admin,
+            });
+        }
+    }
+    orphans.containers = containers
+        .into_iter()
+        .filter(|c| !Path::new(&c.workspace).exists())
+        .collect();
+    let none = BTreeSet::new();
+    for repo_root in repos {
+        let claimed = claimed.get(repo_root.as_path()).unwrap_or(&none);
+        orphans
+            .admin_dirs
+            .extend(stale_admin_dirs(repo_root, claimed));
+    }
+    orphans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn worktree(path: &Path, id: &str) {
+        fs::create_dir_all(path).unwrap();
+        fs::write(
+            path.join(".git"),
+            format!("gitdir: /repo/.git/worktrees/{}\n", id),
+        )
+        .unwrap();
+    }
+
+    fn admin(repo: &Path, id: &str, gitdir: &str) {
+        let dir = repo.join(".git").join("worktrees").join(id);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("gitdir"), format!("{}\n", gitdir)).unwrap();
+    }
+
+    #[test]
+    fn finds_orphans_in_each_direction() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("worktrees");
+        let repo = dir.path().join("src").join("widget");
+        let session = |name: &str| Session {
+            branch: name.to_string(),
+            repo: repo.clone(),
+            worktree: root.join("widget").join(name),
+            devcontainer_env: None,
+            created_at: 0,
+            readonly: false,
+        };
+        let container = |name: &str| Container {
+            id: format!("id-{}", name),
+            name: name.to_string(),
+            workspace: root.join("widget").join(name).display().to_string(),
+        };
+
+        // a live session, and one whose worktree was deleted
+        worktree(&root.join("widget/feat/login"), "login");
+        admin(&repo, "login", "/code/.git");
+        admin(&repo, "gone", "/code/.git");
+        // a session forest no longer knows, and one a container still runs
+        worktree(&root.join("widget/stray"), "stray");
+        admin(&repo, "stray", "/code/.git");
+        worktree(&root.join("widget/legacy"), "legacy");
+        admin(&repo, "legacy", "/code/.git");
+        // the user's own worktrees: one deleted, one locked
+        let own = dir.path().join("own");
+        fs::create_dir_all(&own).unwrap();
+        fs::write(own.join(".git"), "gitdir: elsewhere\n").unwrap();
+        admin(&repo, "own", &own.join(".git").display().to_string());
+        admin(&repo, "deleted", "/nowhere/.git");
+        admin(&repo, "usb", "/media/usb/.git");
+        fs::write(repo.join(".git/worktrees/usb/locked"), "").unwrap();
+
+        let sessions = BTreeMap::from([
+            ("feat-login".to_string(), session("feat/login")),
+            ("gone".to_string(), session("gone")),
+        ]);
+        let containers = vec![container("legacy"), container("gone")];
+        let repos = BTreeSet::from([repo.clone()]);
+        let orphans = survey(&sessions, containers, &root, &repos, WorktreeLayout::Nested);
+
+        assert_eq!(orphans.records, ["gone"]);
+        assert_eq!(
+            orphans
+                .containers
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            ["gone"]
+        );
+        assert_eq!(orphans.worktrees.len(), 1);
+        assert_eq!(orphans.worktrees[0].repo, "widget");
+        assert_eq!(orphans.worktrees[0].session, "stray");
+        assert_eq!(
+            orphans.worktrees[0].admin,
+            Some(repo.join(".git/worktrees/stray"))
+        );
+        assert_eq!(orphans.worktrees[0].branch(), None);
+        fs::write(
+            repo.join(".git/worktrees/stray/HEAD"),
+            "ref: refs/heads/stray\n",
+        )
+        .unwrap();
+        assert_eq!(orphans.worktrees[0].branch().as_deref(), Some("stray"));
+        assert_eq!(
+            orphans.admin_dirs,
+            [
+                repo.join(".git/worktrees/deleted"),
+                repo.join(".git/worktrees/gone")
+            ]
+        );
+        assert!(!orphans.is_empty());
+        assert!(survey(
+            &BTreeMap::new(),
+            Vec::new(),
+            &root.join("none"),
+            &BTreeSet::new(),
+            WorktreeLayout::Flat
+        )
+        .is_empty());
+    }
+}
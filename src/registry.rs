@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::backend;
+
+/// A private registry, set under `[registries."<host>"]` in forest.toml.
+#[derive(Deserialize, Clone)]
+pub struct Registry {
+    /// Docker credential helper holding the registry's credentials, e.g.
+    /// `secretservice`, `osxkeychain` or `pass`; forest runs
+    /// `docker-credential-<helper> get`
+    pub credential_helper: String,
+}
+
+/// Output of `docker-credential-<helper> get`.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Credentials {
+    username: String,
+    secret: String,
+}
+
+fn helper_program(registry: &Registry) -> String {
+    format!("docker-credential-{}", registry.credential_helper)
+}
+
+/// Whether the runtime already holds credentials for `host`.
+pub fn logged_in(host: &str) -> bool {
+    backend::current().logged_in(host)
+}
+
+fn credentials(host: &str, registry: &Registry) -> anyhow::Result<Credentials> {
+    let program = helper_program(registry);
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow::anyhow!("{} command not found", program)
+            } else {
+                e.into()
+            }
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(host.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} has no credentials for {}: {}",
+            program,
+            host,
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow::anyhow!("unexpected output from {}: {}", program, e))
+}
+
+/// Logs the runtime in to `host` with credentials from the helper, unless it
+/// is logged in already. The secret goes over stdin, never on a command line.
+pub fn login(host: &str, registry: &Registry, verbose: bool) -> anyhow::Result<()> {
+    if logged_in(host) {
+        if verbose {
+            println!("Already logged in to {}", host);
+        }
+        return Ok(());
+    }
+    let credentials = credentials(host, registry)?;
+    println!("Logging in to {}", host);
+    let program = backend::current().program();
+    let mut child = Command::new(program)
+        .args([
+            "login",
+            "--username",
+            &credentials.username,
+            "--password-stdin",
+            host,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(credentials.secret.as_bytes())?;
+    }
+    if !child.wait()?.success() {
+        anyhow::bail!("{} login to {} failed", program, host);
+    }
+    Ok(())
+}
+
+/// Logs in to the configured registries that `images` come from, before they
+/// are pulled or built on.
+pub fn login_for_images(
+    registries: &BTreeMap<String, Registry>,
+    images: &[String],
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let mut hosts: Vec<&str> = images.iter().map(|i| crate::policy::registry(i)).collect();
+    hosts.sort();
+    hosts.dedup();
+    for host in hosts {
+        if let Some(registry) = registries.get(host) {
+            login(host, registry, verbose)?;
+        }
+    }
+    Ok(())
+}
+
+/// Problems reaching or authenticating to a configured registry, for `precheck`.
+pub fn check(host: &str, registry: &Registry) -> Vec<String> {
+    let mut problems = Vec::new();
+    // the v2 API root answers 200 or 401 on any registry that's up
+    let status = Command::new("curl")
+        .args([
+            "--silent",
+            "--output",
+            "/dev/null",
+            "--write-out",
+            "%{http_code}",
+        ])
+        .args(["--max-time", "5"])
+        .arg(format!("https://{}/v2/", host))
+        .output();
+    match status {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).trim() != "000" => {}
+        Ok(_) => problems.push(format!("registry {} is not reachable", host)),
+        Err(_) => problems.push(format!("curl is needed to check registry {}", host)),
+    }
+    if !logged_in(host) {
+        if let Err(e) = credentials(host, registry) {
+            problems.push(format!("not logged in to {}: {}", host, e));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_helper_output() {
+        let credentials: Credentials = serde_json::from_str(
+            r#"{"ServerURL": "registry.example.com", "Username": "ci", "Secret": "s3cret"}"#,
+        )
+        .unwrap();
+        assert_eq!(credentials.username, "ci");
+        assert_eq!(credentials.secret, "s3cret");
+        let registry = Registry {
+            credential_helper: "pass".to_string(),
+        };
+        assert_eq!(helper_program(&registry), "docker-credential-pass");
+    }
+}
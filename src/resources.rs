@@ -0,0 +1,266 @@
+use std::fs;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The `[resources]` table of forest.toml: how busy the host may be before
+/// `open` starts another container, and what each container may use.
+#[derive(Deserialize, Default)]
+pub struct ResourceConfig {
+    /// Memory that must stay available, in MiB (default 1024)
+    pub min_free_memory_mb: Option<u64>,
+    /// One-minute load average per CPU above which the host is saturated
+    /// (default 1.5)
+    pub max_load_per_cpu: Option<f64>,
+    /// Offer to stop idle sessions when the host is saturated
+    #[serde(default)]
+    pub stop_idle: bool,
+    /// CPU use in percent below which a running session counts as idle
+    /// (default 1)
+    pub idle_cpu_percent: Option<f64>,
+    /// CPUs a session without `--cpus` may use, such as 2 or 1.5
+    pub cpus: Option<f64>,
+    /// Memory a session without `--memory` may use, such as `8g`
+    pub memory: Option<String>,
+}
+
+impl ResourceConfig {
+    /// The default limits, checked like `--cpus` and `--memory`.
+    pub fn limits(&self) -> anyhow::Result<(Option<f64>, Option<String>)> {
+        let invalid = |e: String| anyhow::anyhow!("[resources] in forest.toml: {}", e);
+        let cpus = match self.cpus {
+            Some(cpus) => Some(parse_cpus(&cpus.to_string()).map_err(invalid)?),
+            None => None,
+        };
+        let memory = match &self.memory {
+            Some(memory) => Some(parse_memory(memory).map_err(invalid)?),
+            None => None,
+        };
+        Ok((cpus, memory))
+    }
+
+    pub fn idle_cpu_percent(&self) -> f64 {
+        self.idle_cpu_percent.unwrap_or(1.0)
+    }
+
+    /// Why the host is too busy for another session; empty when it isn't.
+    pub fn pressure(&self, host: &Host) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let min_free = self.min_free_memory_mb.unwrap_or(1024);
+        if host.available_mb < min_free {
+            reasons.push(format!(
+                "{} MiB of {} MiB memory available, below {} MiB",
+                host.available_mb, host.total_mb, min_free
+            ));
+        }
+        let max_load = self.max_load_per_cpu.unwrap_or(1.5) * host.cpus as f64;
+        if host.load > max_load {
+            reasons.push(format!(
+                "load average {:.2} on {} CPUs, above {:.2}",
+                host.load, host.cpus, max_load
+            ));
+        }
+        reasons
+    }
+}
+
+/// What the host has left to give.
+pub struct Host {
+    pub available_mb: u64,
+    pub total_mb: u64,
+    /// One-minute load average
+    pub load: f64,
+    pub cpus: usize,
+}
+
+/// Reads the host's memory and load from `/proc`; `None` where there's no
+/// `/proc` to read, so the check is skipped.
+pub fn host() -> Option<Host> {
+    let (available_mb, total_mb) = parse_meminfo(&fs::read_to_string("/proc/meminfo").ok()?)?;
+    Some(Host {
+        available_mb,
+        total_mb,
+        load: parse_loadavg(&fs::read_to_string("/proc/loadavg").ok()?)?,
+        cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    })
+}
+
+/// Available and total memory in MiB from `/proc/meminfo`.
+fn parse_meminfo(meminfo: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+    };
+    Some((field("MemAvailable")? / 1024, field("MemTotal")? / 1024))
+}
+
+fn parse_loadavg(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+/// A CPU limit: a positive number of CPUs, fractions allowed.
+pub fn parse_cpus(spec: &str) -> Result<f64, String> {
+    match spec.trim().parse::<f64>() {
+        Ok(cpus) if cpus > 0.0 && cpus.is_finite() => Ok(cpus),
+        _ => Err(format!(
+            "invalid CPU limit {:?}; use a positive number such as 2 or 1.5",
+            spec
+        )),
+    }
+}
+
+/// A memory limit as the engines take it: a number of bytes, or of `k`, `m`
+/// or `g` units. Returned lowercase.
+pub fn parse_memory(spec: &str) -> Result<String, String> {
+    let spec = spec.trim().to_ascii_lowercase();
+    let digits = spec.trim_end_matches(['b', 'k', 'm', 'g']);
+    let unit = &spec[digits.len()..];
+    let valid = !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && digits.parse::<u64>().is_ok_and(|n| n > 0)
+        && unit.len() <= 1;
+    if !valid {
+        return Err(format!(
+            "invalid memory limit {:?}; use bytes or a k, m or g amount such as 8g",
+            spec
+        ));
+    }
+    Ok(spec)
+}
+
+/// Engine arguments capping a container at `cpus` and `memory`.
+pub fn limit_args(cpus: Option<f64>, memory: Option<&str>) -> Vec<String> {
+    cpus.map(|cpus| format!("--cpus={}", cpus))
+        .into_iter()
+        .chain(memory.map(|memory| format!("--memory={}", memory)))
+        .collect()
+}
+
+/// The configuration with its container capped at `cpus` and `memory`,
+/// replacing the limits it sets itself; unchanged without either.
+pub fn apply(value: &Value, cpus: Option<f64>, memory: Option<&str>) -> Value {
+    let mut value = value.clone();
+    let added = limit_args(cpus, memory);
+    let Some(object) = value.as_object_mut().filter(|_| !added.is_empty()) else {
+        return value;
+    };
+    let mut replaced: Vec<&str> = Vec::new();
+    if cpus.is_some() {
+        replaced.push("--cpus");
+    }
+    if memory.is_some() {
+        replaced.extend(["--memory", "-m"]);
+    }
+    let mut run_args: Vec<Value> = Vec::new();
+    let mut skip_value = false;
+    for arg in object
+        .get("runArgs")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+    {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        let text = arg.as_str().unwrap_or_default();
+        let flag = text.split('=').next().unwrap_or_default();
+        if replaced.contains(&flag) {
+            skip_value = !text.contains('=');
+            continue;
+        }
+        run_args.push(arg);
+    }
+    run_args.extend(added.into_iter().map(Value::String));
+    object.insert("runArgs".to_string(), Value::Array(run_args));
+    value
+}
+
+/// A percentage as `stats` prints it, like `12.5%`.
+pub fn parse_percent(text: &str) -> Option<f64> {
+    text.trim().trim_end_matches('%').trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_host_pressure() {
+        let meminfo =
+            "MemTotal:       16316412 kB\nMemFree:  512000 kB\nMemAvailable:     819200 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some((800, 15933)));
+        assert_eq!(parse_meminfo("MemTotal: 1 kB\n"), None);
+        assert_eq!(parse_loadavg("7.52 3.10 1.00 2/345 6789\n"), Some(7.52));
+        assert_eq!(parse_percent("0.53%"), Some(0.53));
+        assert_eq!(parse_percent("--"), None);
+
+        let host = Host {
+            available_mb: 800,
+            total_mb: 15934,
+            load: 7.52,
+            cpus: 4,
+        };
+        let config = ResourceConfig::default();
+        assert_eq!(
+            config.pressure(&host),
+            [
+                "800 MiB of 15934 MiB memory available, below 1024 MiB",
+                "load average 7.52 on 4 CPUs, above 6.00",
+            ]
+        );
+        let relaxed = ResourceConfig {
+            min_free_memory_mb: Some(512),
+            max_load_per_cpu: Some(2.0),
+            ..ResourceConfig::default()
+        };
+        assert!(relaxed.pressure(&host).is_empty());
+    }
+
+    #[test]
+    fn caps_containers() {
+        assert_eq!(parse_cpus("1.5"), Ok(1.5));
+        assert!(parse_cpus("0").is_err());
+        assert!(parse_cpus("two").is_err());
+        assert_eq!(parse_memory("8G").as_deref(), Ok("8g"));
+        assert_eq!(parse_memory("1073741824").as_deref(), Ok("1073741824"));
+        assert!(parse_memory("8gb").is_err());
+        assert!(parse_memory("g").is_err());
+        assert!(parse_memory("1.5g").is_err());
+
+        let value = serde_json::json!({
+            "image": "ubuntu",
+            "runArgs": ["--memory", "2g", "--cpus=4", "--init"]
+        });
+        assert_eq!(apply(&value, None, None), value);
+        assert_eq!(
+            apply(&value, None, Some("8g"))["runArgs"],
+            serde_json::json!(["--cpus=4", "--init", "--memory=8g"])
+        );
+        assert_eq!(
+            apply(&value, Some(2.0), Some("8g"))["runArgs"],
+            serde_json::json!(["--init", "--cpus=2", "--memory=8g"])
+        );
+
+        let config = ResourceConfig {
+            cpus: Some(2.0),
+            memory: Some("512M".to_string()),
+            ..ResourceConfig::default()
+        };
+        assert_eq!(
+            config.limits().unwrap(),
+            (Some(2.0), Some("512m".to_string()))
+        );
+        let invalid = ResourceConfig {
+            memory: Some("lots".to_string()),
+            ..ResourceConfig::default()
+        };
+        assert!(invalid.limits().is_err());
+    }
+}
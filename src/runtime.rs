@@ -0,0 +1,447 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Container runtime used for queries `devcontainer` doesn't cover.
+pub fn program() -> &'static str {
+    crate::backend::current().program()
+}
+
+/// A container created by `devcontainer up` for a forest session.
+#[derive(Clone)]
+pub struct Container {
+    pub id: String,
+    /// Sanitized session name from the `name` id label
+    pub name: String,
+    /// Workspace folder the container was created for
+    pub workspace: String,
+}
+
+/// Lists containers whose devcontainer workspace lives under `worktrees`,
+/// running ones unless `all` is set.
+pub fn session_containers(worktrees: &Path, all: bool) -> anyhow::Result<Vec<Container>> {
+    let mut cmd = Command::new(program());
+    cmd.args(["ps", "--filter", "label=name", "--format", "json"]);
+    if all {
+        cmd.arg("--all");
+    }
+    let output = cmd.stderr(Stdio::inherit()).output().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("{} command not found", program())
+        } else {
+            e.into()
+        }
+    })?;
+    if !output.status.success() {
+        anyhow::bail!("{} ps failed", program());
+    }
+    Ok(parse_ps(&String::from_utf8_lossy(&output.stdout))?
+        .into_iter()
+        .filter(|c| Path::new(&c.workspace).starts_with(worktrees))
+        .collect())
+}
+
+/// Ids of containers carrying the `name=<name>` id label, including stopped
+/// ones when `all` is set.
+pub fn containers_with_name(name: &str, all: bool) -> anyhow::Result<Vec<String>> {
+    let mut cmd = Command::new(program());
+    cmd.args(["ps", "--quiet", "--filter"])
+        .arg(format!("label=name={}", name));
+    if all {
+        cmd.arg("--all");
+    }
+    let output = cmd.stderr(Stdio::inherit()).output().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("{} command not found", program())
+        } else {
+            e.into()
+        }
+    })?;
+    if !output.status.success() {
+        anyhow::bail!("{} ps failed", program());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Image a container was created from, as the reference it was created with.
+pub fn container_image(id: &str) -> anyhow::Result<String> {
+    let output = Command::new(program())
+        .args(["inspect", "--format"])
+        .arg(crate::backend::current().image_template())
+        .arg(id)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} inspect {} failed", program(), id);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Value of label `key` on a container, `None` when it has no such label.
+pub fn container_label(id: &str, key: &str) -> anyhow::Result<Option<String>> {
+    let output = Command::new(program())
+        .args(["inspect", "--format"])
+        .arg(format!("{{{{index .Config.Labels \"{}\"}}}}", key))
+        .arg(id)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} inspect {} failed", program(), id);
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // a missing key prints as `<no value>`
+    Ok(Some(value).filter(|v| !v.is_empty() && v != "<no value>"))
+}
+
+/// Host port reaching `port` of session `label`'s running container: the one
+/// it's published on, or `port` itself on the host's network. `None` when
+/// the host can't reach it.
+pub fn published_port(label: &str, port: u16) -> Option<u16> {
+    let id = containers_with_name(label, false)
+        .ok()?
+        .into_iter()
+        .next()?;
+    let network = Command::new(program())
+        .args(["inspect", "--format", "{{.HostConfig.NetworkMode}}", &id])
+        .output()
+        .ok()?;
+    if String::from_utf8_lossy(&network.stdout).trim() == "host" {
+        return Some(port);
+    }
+    let output = Command::new(program())
+        .args(["port", &id, &format!("{}/tcp", port)])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_port(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// First host port of `port`'s output, such as `0.0.0.0:32768`.
+fn parse_port(output: &str) -> Option<u16> {
+    output
+        .lines()
+        .next()?
+        .rsplit_once(':')?
+        .1
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Id of the local image `reference` names, `None` when there's no such image.
+pub fn image_id(reference: &str) -> Option<String> {
+    let output = Command::new(program())
+        .args(["image", "inspect", "--format", "{{.Id}}", reference])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some(id).filter(|id| output.status.success() && !id.is_empty())
+}
+
+/// The engine's raw `inspect` document for container `id`.
+pub fn inspect(id: &str) -> anyhow::Result<serde_json::Value> {
+    let output = Command::new(program())
+        .arg("inspect")
+        .arg(id)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} inspect {} failed", program(), id);
+    }
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    // podman and docker both print a one-element array
+    Ok(match value {
+        serde_json::Value::Array(mut items) if items.len() == 1 => items.remove(0),
+        other => other,
+    })
+}
+
+/// Git state of a session's `/code` checkout as seen inside its container.
+pub struct GitState {
+    pub branch: Option<String>,
+    /// Branch tracked upstream, such as `origin/feat`
+    pub upstream: Option<String>,
+    /// Commits the branch has that its upstream doesn't, and the other way
+    pub ahead: usize,
+    pub behind: usize,
+    /// Modified, staged and untracked paths
+    pub changes: usize,
+}
+
+/// Queries the git state of many containers at once, so listing stays fast as
+/// the number of sessions grows. Results are in the order of `ids`; `None`
+/// means the container couldn't be queried.
+pub fn git_states(ids: &[String]) -> anyhow::Result<Vec<Option<GitState>>> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(rt.block_on(async {
+        let mut set = tokio::task::JoinSet::new();
+        for (i, id) in ids.iter().enumerate() {
+            let id = id.clone();
+            set.spawn(async move { (i, git_state(&id).await) });
+        }
+        let mut states: Vec<Option<GitState>> = ids.iter().map(|_| None).collect();
+        while let Some(joined) = set.join_next().await {
+            // a task that panicked leaves its container unqueried, not the rest
+            if let Ok((i, state)) = joined {
+                states[i] = state;
+            }
+        }
+        states
+    }))
+}
+
+async fn git_state(id: &str) -> Option<GitState> {
+    let output = tokio::process::Command::new(program())
+        .args([
+            "exec",
+            id,
+            "git",
+            "-C",
+            "/code",
+            "status",
+            "--porcelain",
+            "--branch",
+        ])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `git status --porcelain --branch`, whose header reads
+/// `## <branch>...<upstream> [ahead N, behind M]`.
+fn parse_status(status: &str) -> GitState {
+    let mut lines = status.lines();
+    let header = lines
+        .next()
+        .and_then(|header| header.strip_prefix("## "))
+        .unwrap_or("");
+    let (refs, counts) = header.split_once(" [").unwrap_or((header, ""));
+    let (branch, upstream) = refs.split_once("...").unwrap_or((refs, ""));
+    let branch = branch.split(' ').next().unwrap_or(branch);
+    let count = |key: &str| {
+        counts
+            .trim_end_matches(']')
+            .split(", ")
+            .find_map(|c| c.strip_prefix(key)?.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    GitState {
+        branch: Some(branch.to_string()).filter(|b| !b.is_empty() && b != "HEAD"),
+        upstream: Some(upstream.to_string()).filter(|u| !u.is_empty()),
+        ahead: count("ahead "),
+        behind: count("behind "),
+        changes: lines.filter(|l| !l.trim().is_empty()).count(),
+    }
+}
+
+/// Resource usage of a running container, as the engine formats it.
+pub struct Stats {
+    pub cpu: String,
+    pub memory: String,
+    pub memory_percent: String,
+    pub pids: String,
+}
+
+/// A single `stats` sample of container `id`. Both engines accept the same
+/// template, so its fields are read from one tab-separated line.
+pub fn stats(id: &str) -> anyhow::Result<Stats> {
+    let output = Command::new(program())
+        .args([
+            "stats",
+            "--no-stream",
+            "--format",
+            "{{.CPUPerc}}\t{{.MemUsage}}\t{{.MemPerc}}\t{{.PIDs}}",
+        ])
+        .arg(id)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} stats {} failed", program(), id);
+    }
+    parse_stats(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| anyhow::anyhow!("unexpected {} stats output", program()))
+}
+
+fn parse_stats(output: &str) -> Option<Stats> {
+    let line = output.lines().find(|l| !l.trim().is_empty())?;
+    let mut fields = line.split('\t').map(|f| f.trim().to_string());
+    Some(Stats {
+        cpu: fields.next()?,
+        memory: fields.next()?,
+        memory_percent: fields.next()?,
+        pids: fields.next()?,
+    })
+}
+
+/// When the container of an `inspect` document last started, in Unix time.
+pub fn started_at(inspect: &serde_json::Value) -> Option<u64> {
+    parse_timestamp(inspect.pointer("/State/StartedAt")?.as_str()?)
+}
+
+/// Parses an RFC 3339 timestamp such as `2024-05-01T10:00:00.123456789+02:00`
+/// or one ending in `Z`, ignoring fractions of a second.
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date = date.split('-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (clock, offset) = match time.find(['Z', '+', '-']) {
+        Some(at) => time.split_at(at),
+        None => return None,
+    };
+    let mut clock = clock
+        .split('.')
+        .next()?
+        .split(':')
+        .map(|p| p.parse::<i64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+    let offset = match offset {
+        "Z" => 0,
+        _ => {
+            let (sign, hm) = offset.split_at(1);
+            let (h, m) = hm.split_once(':')?;
+            let minutes = h.parse::<i64>().ok()? * 60 + m.parse::<i64>().ok()?;
+            if sign == "-" {
+                -minutes * 60
+            } else {
+                minutes * 60
+            }
+        }
+    };
+    // days since 1970-01-01 in the proleptic Gregorian calendar
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(seconds).ok()
+}
+
+fn parse_ps(json: &str) -> anyhow::Result<Vec<Container>> {
+    Ok(crate::backend::current()
+        .parse_ps(json)?
+        .into_iter()
+        .filter_map(|mut entry| {
+            Some(Container {
+                name: entry.labels.remove("name")?,
+                workspace: entry.labels.remove("devcontainer.local_folder")?,
+                id: entry.id,
+            })
+        })
+        .collect())
+}
+
+/// Runs `<runtime> <action> <ids...>` for actions like `stop` and `start`.
+pub fn container_action(action: &str, ids: &[String], verbose: bool) -> anyhow::Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let mut cmd = Command::new(program());
+    cmd.arg(action).args(ids);
+    if !verbose {
+        cmd.stdout(Stdio::null());
+    }
+    let status = crate::run_command_verbose(&mut cmd, verbose)?;
+    if !status.success() {
+        anyhow::bail!("{} {} failed", program(), action);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_git_status() {
+        let state = parse_status("## feat/x...origin/feat/x [ahead 1]\n M src/main.rs\n?? new\n");
+        assert_eq!(state.branch.as_deref(), Some("feat/x"));
+        assert_eq!(state.changes, 2);
+
+        assert_eq!(state.upstream.as_deref(), Some("origin/feat/x"));
+        assert_eq!((state.ahead, state.behind), (1, 0));
+
+        let state = parse_status("## main...origin/main [ahead 2, behind 13]\n");
+        assert_eq!((state.ahead, state.behind), (2, 13));
+
+        let state = parse_status("## new\n");
+        assert_eq!(state.branch.as_deref(), Some("new"));
+        assert_eq!(state.upstream, None);
+
+        let state = parse_status("## HEAD (no branch)\n");
+        assert_eq!(state.branch, None);
+        assert_eq!(state.changes, 0);
+    }
+
+    #[test]
+    fn parses_published_ports() {
+        assert_eq!(parse_port("0.0.0.0:32768\n[::]:32768\n"), Some(32768));
+        assert_eq!(parse_port("[::]:8080\n"), Some(8080));
+        assert_eq!(parse_port(""), None);
+    }
+
+    #[test]
+    fn parses_stats() {
+        let stats = parse_stats("0.53%\t412.3MB / 16.4GB\t2.51%\t17\n").unwrap();
+        assert_eq!(stats.cpu, "0.53%");
+        assert_eq!(stats.memory, "412.3MB / 16.4GB");
+        assert_eq!(stats.memory_percent, "2.51%");
+        assert_eq!(stats.pids, "17");
+        assert!(parse_stats("").is_none());
+    }
+
+    #[test]
+    fn parses_start_times() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp("2024-05-01T10:00:00.123456789Z"),
+            Some(1714557600)
+        );
+        assert_eq!(
+            parse_timestamp("2024-05-01T12:00:00.5+02:00"),
+            Some(1714557600)
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T23:30:00-01:00"),
+            Some(1709253000)
+        );
+        // docker's placeholder for a container that never started
+        assert_eq!(parse_timestamp("0001-01-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn parses_podman_ps_json() {
+        let json = r#"[
+            {"Id": "abc",
+             "Labels": {"name": "feat-x", "devcontainer.local_folder": "/h/worktrees/r/feat/x"}},
+            {"Id": "def", "Labels": {"name": "other"}}
+        ]"#;
+        let containers = parse_ps(json).unwrap();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].id, "abc");
+        assert_eq!(containers[0].name, "feat-x");
+        assert!(parse_ps("").unwrap().is_empty());
+    }
+}
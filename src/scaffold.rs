@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Templates forest ships, the first being the fallback for repositories it
+/// doesn't recognise.
+pub const BUILTIN: [&str; 4] = ["ubuntu", "rust", "node", "python"];
+
+/// Image of the `ubuntu` template.
+const UBUNTU_IMAGE: &str = "docker.io/library/ubuntu:latest";
+
+/// Image the language templates add their toolchain feature to.
+const BASE_IMAGE: &str = "mcr.microsoft.com/devcontainers/base:ubuntu";
+
+fn builtin(name: &str) -> Option<String> {
+    let language = |feature: &str, extensions: &[&str], post_create: &str| {
+        let value = serde_json::json!({
+            "image": BASE_IMAGE,
+            "features": { feature: {} },
+            "customizations": { "vscode": { "extensions": extensions } },
+            "postCreateCommand": post_create,
+        });
+        serde_json::to_string_pretty(&value).unwrap_or_default() + "\n"
+    };
+    Some(match name {
+        "ubuntu" => format!("{{\n  \"image\": \"{}\"\n}}\n", UBUNTU_IMAGE),
+        "rust" => language(
+            "ghcr.io/devcontainers/features/rust:1",
+            &["rust-lang.rust-analyzer"],
+            "cargo fetch",
+        ),
+        "node" => language(
+            "ghcr.io/devcontainers/features/node:1",
+            &["dbaeumer.vscode-eslint"],
+            "npm install",
+        ),
+        "python" => language(
+            "ghcr.io/devcontainers/features/python:1",
+            &["ms-python.python"],
+            "[ ! -f requirements.txt ] || pip install --user -r requirements.txt",
+        ),
+        _ => return None,
+    })
+}
+
+/// Where a template's files come from.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    Builtin(String),
+    /// A directory holding `devcontainer.json`, copied with whatever else is
+    /// in it, such as a Dockerfile
+    Dir(PathBuf),
+    /// A devcontainer.json on its own
+    File(PathBuf),
+}
+
+/// The template a repository's files suggest: `rust` with a Cargo.toml,
+/// `node` with a package.json, `python` with the usual Python project files,
+/// else `ubuntu`.
+pub fn detect(repo: &Path) -> &'static str {
+    let has = |file: &str| repo.join(file).exists();
+    if has("Cargo.toml") {
+        "rust"
+    } else if has("package.json") {
+        "node"
+    } else if has("pyproject.toml") || has("requirements.txt") || has("setup.py") {
+        "python"
+    } else {
+        BUILTIN[0]
+    }
+}
+
+/// Template `name`: a directory of that name in `templates`, which may
+/// replace a built-in, a built-in, or a path to a template directory or a
+/// devcontainer.json.
+pub fn resolve(name: &str, templates: Option<&Path>) -> anyhow::Result<Source> {
+    if let Some(dir) = templates.map(|t| t.join(name)) {
+        if dir.join("devcontainer.json").is_file() {
+            return Ok(Source::Dir(dir));
+        }
+    }
+    if BUILTIN.contains(&name) {
+        return Ok(Source::Builtin(name.to_string()));
+    }
+    let path = Path::new(name);
+    if path.join("devcontainer.json").is_file() {
+        return Ok(Source::Dir(path.to_path_buf()));
+    }
+    if path.is_file() {
+        return Ok(Source::File(path.to_path_buf()));
+    }
+    anyhow::bail!(
+        "no template {}; use one of {} or a path to a devcontainer.json or a directory holding one",
+        name,
+        available(templates).join(", ")
+    )
+}
+
+/// Names of the built-in templates and those in `templates`.
+pub fn available(templates: Option<&Path>) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN.iter().map(|s| s.to_string()).collect();
+    let user = templates
+        .and_then(|t| fs::read_dir(t).ok())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().join("devcontainer.json").is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string());
+    for name in user {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Writes template `source` into `dir`, returning its devcontainer.json.
+pub fn write(source: &Source, dir: &Path) -> anyhow::Result<PathBuf> {
+    let path = dir.join("devcontainer.json");
+    fs::create_dir_all(dir)?;
+    match source {
+        Source::Builtin(name) => {
+            let Some(contents) = builtin(name) else {
+                anyhow::bail!("no built-in template {}", name);
+            };
+            fs::write(&path, contents)?;
+        }
+        Source::Dir(template) => crate::copy_dir(template, dir)?,
+        Source::File(file) => {
+            fs::copy(file, &path)?;
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn scaffolds_from_templates() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("widget");
+        fs::create_dir(&repo).unwrap();
+        assert_eq!(detect(&repo), "ubuntu");
+        fs::write(repo.join("pyproject.toml"), "").unwrap();
+        assert_eq!(detect(&repo), "python");
+        fs::write(repo.join("Cargo.toml"), "").unwrap();
+        assert_eq!(detect(&repo), "rust");
+
+        let templates = dir.path().join("templates");
+        let custom = templates.join("rust");
+        fs::create_dir_all(&custom).unwrap();
+        fs::write(
+            custom.join("devcontainer.json"),
+            r#"{"build": {"dockerfile": "Dockerfile"}}"#,
+        )
+        .unwrap();
+        fs::write(custom.join("Dockerfile"), "FROM rust\n").unwrap();
+        assert_eq!(
+            resolve("rust", Some(&templates)).unwrap(),
+            Source::Dir(custom.clone())
+        );
+        assert_eq!(
+            resolve("rust", None).unwrap(),
+            Source::Builtin("rust".to_string())
+        );
+        assert!(resolve("cobol", Some(&templates))
+            .unwrap_err()
+            .to_string()
+            .contains("ubuntu, rust, node, python"));
+
+        let written = write(&resolve("node", None).unwrap(), &repo.join("a")).unwrap();
+        let value: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(written).unwrap()).unwrap();
+        assert_eq!(value["postCreateCommand"], "npm install");
+        assert!(value["features"]
+            .get("ghcr.io/devcontainers/features/node:1")
+            .is_some());
+
+        write(&Source::Dir(custom), &repo.join("b")).unwrap();
+        assert!(repo.join("b").join("Dockerfile").exists());
+        let file = repo.join("b").join("devcontainer.json");
+        assert_eq!(
+            resolve(&file.to_string_lossy(), None).unwrap(),
+            Source::File(file)
+        );
+        assert_eq!(
+            resolve(&repo.join("b").to_string_lossy(), None).unwrap(),
+            Source::Dir(repo.join("b"))
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::style::{Role, Table};
+
+/// Vulnerability scanner run by `forest scan`, set with `scanner` under `[scan]`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Scanner {
+    Trivy,
+    Grype,
+}
+
+impl Scanner {
+    fn program(self) -> &'static str {
+        match self {
+            Scanner::Trivy => "trivy",
+            Scanner::Grype => "grype",
+        }
+    }
+}
+
+/// What `open` does when a new session's image has critical vulnerabilities.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnCritical {
+    /// Print the report and open the session anyway
+    Warn,
+    /// Stop the container and refuse to open the session
+    Block,
+}
+
+/// The `[scan]` table of forest.toml.
+#[derive(Deserialize, Default)]
+pub struct ScanConfig {
+    /// Scanner to use instead of the first of trivy and grype that is installed
+    pub scanner: Option<Scanner>,
+    /// Scan images when `open` starts a container; unset skips the scan
+    pub on_critical: Option<OnCritical>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Critical,
+    High,
+    Medium,
+    Low,
+    Unknown,
+}
+
+impl Severity {
+    fn parse(s: &str) -> Severity {
+        match s.to_ascii_lowercase().as_str() {
+            "critical" => Severity::Critical,
+            "high" => Severity::High,
+            "medium" => Severity::Medium,
+            "low" | "negligible" => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Critical => "critical",
+            Severity::High => "high",
+            Severity::Medium => "medium",
+            Severity::Low => "low",
+            Severity::Unknown => "unknown",
+        }
+    }
+}
+
+pub struct Finding {
+    pub id: String,
+    pub severity: Severity,
+    pub package: String,
+    pub installed: String,
+    pub fixed: Option<String>,
+}
+
+pub struct Report {
+    pub image: String,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    /// One line like `critical 2, high 5, medium 12, low 30`.
+    pub fn summary(&self) -> String {
+        [
+            Severity::Critical,
+            Severity::High,
+            Severity::Medium,
+            Severity::Low,
+        ]
+        .iter()
+        .map(|s| format!("{} {}", s.label(), self.count(*s)))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    /// Prints the summary and a table of the critical and high findings.
+    pub fn print(&self) {
+        println!("{}: {}", self.image, self.summary());
+        let mut serious: Vec<&Finding> = self
+            .findings
+            .iter()
+            .filter(|f| f.severity <= Severity::High)
+            .collect();
+        if serious.is_empty() {
+            return;
+        }
+        serious.sort_by(|a, b| a.severity.cmp(&b.severity).then(a.id.cmp(&b.id)));
+        let mut table = Table::new(&["severity", "id", "package", "installed", "fixed in"]);
+        for finding in serious {
+            let role = if finding.severity == Severity::Critical {
+                Role::Error
+            } else {
+                Role::Warning
+            };
+            table.row(vec![
+                (finding.severity.label().to_string(), Some(role)),
+                (finding.id.clone(), None),
+                (finding.package.clone(), None),
+                (finding.installed.clone(), Some(Role::Dim)),
+                (finding.fixed.clone().unwrap_or_default(), None),
+            ]);
+        }
+        table.print();
+    }
+}
+
+/// The configured scanner, or the first one installed.
+pub fn scanner(config: &ScanConfig) -> anyhow::Result<Scanner> {
+    if let Some(scanner) = config.scanner {
+        return Ok(scanner);
+    }
+    [Scanner::Trivy, Scanner::Grype]
+        .into_iter()
+        .find(|s| crate::command_exists(s.program()))
+        .ok_or_else(|| anyhow::anyhow!("no vulnerability scanner found; install trivy or grype"))
+}
+
+pub fn scan(scanner: Scanner, image: &str, verbose: bool) -> anyhow::Result<Report> {
+    let mut cmd = Command::new(scanner.program());
+    match scanner {
+        Scanner::Trivy => cmd.args(["image", "--quiet", "--format", "json", image]),
+        Scanner::Grype => cmd.args([image, "--quiet", "--output", "json"]),
+    };
+    println!("Scanning {} with {}", image, scanner.program());
+    let output = crate::run_output_verbose(cmd.stdin(Stdio::null()), verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("{} command not found", scanner.program())
+        } else {
+            e.into()
+        }
+    })?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed to scan {}", scanner.program(), image);
+    }
+    let value: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow::anyhow!("unexpected {} output: {}", scanner.program(), e))?;
+    let findings = match scanner {
+        Scanner::Trivy => parse_trivy(&value),
+        Scanner::Grype => parse_grype(&value),
+    };
+    Ok(Report {
+        image: image.to_string(),
+        findings,
+    })
+}
+
+fn str_at(value: &Value, pointer: &str) -> String {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn parse_trivy(value: &Value) -> Vec<Finding> {
+    let results = value["Results"].as_array().cloned().unwrap_or_default();
+    results
+        .iter()
+        .flat_map(|r| r["Vulnerabilities"].as_array().cloned().unwrap_or_default())
+        .map(|v| Finding {
+            id: str_at(&v, "/VulnerabilityID"),
+            severity: Severity::parse(&str_at(&v, "/Severity")),
+            package: str_at(&v, "/PkgName"),
+            installed: str_at(&v, "/InstalledVersion"),
+            fixed: Some(str_at(&v, "/FixedVersion")).filter(|f| !f.is_empty()),
+        })
+        .collect()
+}
+
+fn parse_grype(value: &Value) -> Vec<Finding> {
+    let matches = value["matches"].as_array().cloned().unwrap_or_default();
+    matches
+        .iter()
+        .map(|m| Finding {
+            id: str_at(m, "/vulnerability/id"),
+            severity: Severity::parse(&str_at(m, "/vulnerability/severity")),
+            package: str_at(m, "/artifact/name"),
+            installed: str_at(m, "/artifact/version"),
+            fixed: m
+                .pointer("/vulnerability/fix/versions")
+                .and_then(Value::as_array)
+                .and_then(|versions| versions.first())
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_trivy_report() {
+        let value: Value = serde_json::from_str(
+            r#"{"Results": [
+                {"Target": "debian", "Vulnerabilities": [
+                    {"VulnerabilityID": "CVE-1", "PkgName": "openssl", "InstalledVersion": "3.0.1",
+                     "FixedVersion": "3.0.2", "Severity": "CRITICAL"},
+                    {"VulnerabilityID": "CVE-2", "PkgName": "zlib", "InstalledVersion": "1.2",
+                     "Severity": "LOW"}]},
+                {"Target": "cargo"}
+            ]}"#,
+        )
+        .unwrap();
+        let report = Report {
+            image: "x".to_string(),
+            findings: parse_trivy(&value),
+        };
+        assert_eq!(report.count(Severity::Critical), 1);
+        assert_eq!(report.findings[0].fixed.as_deref(), Some("3.0.2"));
+        assert_eq!(report.findings[1].fixed, None);
+        assert_eq!(report.summary(), "critical 1, high 0, medium 0, low 1");
+    }
+
+    #[test]
+    fn parses_grype_report() {
+        let value: Value = serde_json::from_str(
+            r#"{"matches": [
+                {"vulnerability": {"id": "CVE-3", "severity": "High", "fix": {"versions": ["2.0"]}},
+                 "artifact": {"name": "curl", "version": "1.0"}},
+                {"vulnerability": {"id": "CVE-4", "severity": "Negligible", "fix": {"versions": []}},
+                 "artifact": {"name": "tar", "version": "1.3"}}
+            ]}"#,
+        )
+        .unwrap();
+        let findings = parse_grype(&value);
+        assert_eq!(findings[0].severity, Severity::High);
+        assert_eq!(findings[0].fixed.as_deref(), Some("2.0"));
+        assert_eq!(findings[1].severity, Severity::Low);
+        assert_eq!(findings[1].fixed, None);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Where a secret's value comes from, picked by the prefix of its reference
+/// under `[secrets]`: `op://` for 1Password, `pass:` for pass and `cmd:` for
+/// any command printing it.
+pub trait Provider: Sync {
+    /// Name in errors
+    fn name(&self) -> &'static str;
+
+    /// The rest of `reference` when it's this provider's.
+    fn matches<'a>(&self, reference: &'a str) -> Option<&'a str>;
+
+    /// Command printing the secret `rest` names.
+    fn command(&self, rest: &str) -> Command;
+
+    /// The secret in the command's output.
+    fn value(&self, output: &str) -> String {
+        output.trim_end_matches(['\n', '\r']).to_string()
+    }
+}
+
+/// 1Password's CLI, `op read op://vault/item/field`.
+pub struct OnePassword;
+
+impl Provider for OnePassword {
+    fn name(&self) -> &'static str {
+        "op"
+    }
+
+    fn matches<'a>(&self, reference: &'a str) -> Option<&'a str> {
+        reference.starts_with("op://").then_some(reference)
+    }
+
+    fn command(&self, rest: &str) -> Command {
+        let mut cmd = Command::new("op");
+        cmd.args(["read", "--no-newline", rest]);
+        cmd
+    }
+}
+
+/// The standard Unix password manager, `pass show <name>`.
+pub struct Pass;
+
+impl Provider for Pass {
+    fn name(&self) -> &'static str {
+        "pass"
+    }
+
+    fn matches<'a>(&self, reference: &'a str) -> Option<&'a str> {
+        reference.strip_prefix("pass:")
+    }
+
+    fn command(&self, rest: &str) -> Command {
+        let mut cmd = Command::new("pass");
+        cmd.args(["show", rest]);
+        cmd
+    }
+
+    /// pass keeps the password on the first line, and notes after it.
+    fn value(&self, output: &str) -> String {
+        output.lines().next().unwrap_or_default().to_string()
+    }
+}
+
+/// A shell command printing the secret, such as `security
+/// find-generic-password -w -s api`.
+pub struct Shell;
+
+impl Provider for Shell {
+    fn name(&self) -> &'static str {
+        "cmd"
+    }
+
+    fn matches<'a>(&self, reference: &'a str) -> Option<&'a str> {
+        reference.strip_prefix("cmd:")
+    }
+
+    fn command(&self, rest: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", rest]);
+        cmd
+    }
+}
+
+const PROVIDERS: [&dyn Provider; 3] = [&OnePassword, &Pass, &Shell];
+
+/// The provider of `reference` and what it's given.
+pub fn provider(reference: &str) -> anyhow::Result<(&'static dyn Provider, &str)> {
+    PROVIDERS
+        .iter()
+        .find_map(|p| p.matches(reference).map(|rest| (*p, rest)))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "secret {:?} names no provider; start it with op://, pass: or cmd:",
+                reference
+            )
+        })
+}
+
+/// Values handed out so far, hidden from printed and traced command lines.
+static RESOLVED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Asks `reference`'s provider for the secret. The value stays in memory.
+pub fn resolve(reference: &str) -> anyhow::Result<String> {
+    let (provider, rest) = provider(reference)?;
+    let output = provider
+        .command(rest)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| anyhow::anyhow!("running {} failed: {}", provider.name(), e))?;
+    if !output.status.success() {
+        anyhow::bail!("{} couldn't read secret {}", provider.name(), reference);
+    }
+    let value = provider.value(&String::from_utf8_lossy(&output.stdout));
+    if value.is_empty() {
+        anyhow::bail!(
+            "{} returned an empty secret for {}",
+            provider.name(),
+            reference
+        );
+    }
+    hide(&value);
+    Ok(value)
+}
+
+/// Keeps `value` out of printed and traced command lines from now on.
+pub fn hide(value: &str) {
+    if let Ok(mut resolved) = RESOLVED.lock() {
+        resolved.push(value.to_string());
+    }
+}
+
+/// Secrets resolved for one command, by variable name, held in memory only.
+#[derive(Default)]
+pub struct Resolved(Vec<(String, String)>);
+
+impl Resolved {
+    /// Adds a secret resolved elsewhere, such as the GitHub token, ahead of
+    /// `[secrets]`, so a secret of the same name there wins.
+    pub fn add(&mut self, key: &str, value: String) {
+        hide(&value);
+        self.0.insert(0, (key.to_string(), value));
+    }
+
+    /// Passes the secrets on to a `devcontainer` command: their names as
+    /// `--remote-env KEY` and their values through its environment, keeping
+    /// them out of `ps` and of anything hashed or stored.
+    pub fn pass_to(&self, cmd: &mut Command) {
+        for (key, value) in &self.0 {
+            cmd.arg("--remote-env").arg(key).env(key, value);
+        }
+    }
+}
+
+/// The secrets under `[secrets]`, by variable name.
+pub fn resolve_all(secrets: &BTreeMap<String, String>) -> anyhow::Result<Resolved> {
+    secrets
+        .iter()
+        .map(|(key, reference)| {
+            crate::env::check_key(key)?;
+            let value = resolve(reference).map_err(|e| anyhow::anyhow!("secret {}: {}", key, e))?;
+            Ok((key.clone(), value))
+        })
+        .collect::<anyhow::Result<_>>()
+        .map(Resolved)
+}
+
+/// `text` with every secret resolved so far replaced by `***`.
+pub fn redact(text: &str) -> String {
+    let Ok(resolved) = RESOLVED.lock() else {
+        return text.to_string();
+    };
+    resolved.iter().fold(text.to_string(), |text, value| {
+        text.replace(value.as_str(), "***")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_through_providers() {
+        assert_eq!(provider("op://Private/api/key").unwrap().0.name(), "op");
+        assert_eq!(provider("pass:work/github").unwrap().1, "work/github");
+        assert!(resolve("vault:kv/api")
+            .unwrap_err()
+            .to_string()
+            .contains("op://, pass: or cmd:"));
+        assert_eq!(Pass.value("hunter2\nuser: me\n"), "hunter2");
+
+        let secrets = BTreeMap::from([
+            (
+                "API_KEY".to_string(),
+                "cmd:printf 'k3y-%s\\n' 42".to_string(),
+            ),
+            ("TOKEN".to_string(), "cmd:echo t0ken".to_string()),
+        ]);
+        let mut cmd = Command::new("devcontainer");
+        let mut resolved = resolve_all(&secrets).unwrap();
+        resolved.add("GH_TOKEN", "gh0_x".to_string());
+        resolved.pass_to(&mut cmd);
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--remote-env",
+                "GH_TOKEN",
+                "--remote-env",
+                "API_KEY",
+                "--remote-env",
+                "TOKEN"
+            ]
+        );
+        let envs: Vec<_> = cmd.get_envs().map(|(k, v)| (k, v.unwrap())).collect();
+        assert_eq!(
+            envs,
+            [
+                ("API_KEY".as_ref(), "k3y-42".as_ref()),
+                ("GH_TOKEN".as_ref(), "gh0_x".as_ref()),
+                ("TOKEN".as_ref(), "t0ken".as_ref())
+            ]
+        );
+        assert_eq!(
+            redact("--remote-env API_KEY=k3y-42 --remote-env GH_TOKEN=gh0_x TOKEN=t0ken"),
+            "--remote-env API_KEY=*** --remote-env GH_TOKEN=*** TOKEN=***"
+        );
+        assert!(resolve("cmd:false").is_err());
+        assert!(resolve("cmd:true").is_err());
+        let bad = BTreeMap::from([("1X".to_string(), "cmd:echo x".to_string())]);
+        assert!(resolve_all(&bad).is_err());
+    }
+}
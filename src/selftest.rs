@@ -0,0 +1,174 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Stand-in for the devcontainer CLI that simulates containers on the host.
+const STUB: &str = include_str!("selftest_stub.sh");
+
+const SESSION: &str = "forest-self-test";
+const MARKER: &str = "forest-self-test-ok";
+
+/// Runs open, exec and kill against a throwaway repository using the forest
+/// binary itself, reporting the first stage that fails.
+pub fn run(worktrees: &Path, stub: bool, verbose: bool) -> anyhow::Result<()> {
+    let repo_name = format!("forest-self-test-{}", std::process::id());
+    let scratch = std::env::temp_dir().join(&repo_name);
+    let result = run_stages(
+        &scratch,
+        &scratch.join(&repo_name),
+        worktrees,
+        stub,
+        verbose,
+    );
+    let _ = fs::remove_dir_all(&scratch);
+    let _ = fs::remove_dir_all(worktrees.join(&repo_name));
+    result?;
+    println!("Self-test passed");
+    Ok(())
+}
+
+fn run_stages(
+    scratch: &Path,
+    repo: &Path,
+    worktrees: &Path,
+    stub: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let mut env: Vec<(&str, PathBuf)> = Vec::new();
+
+    stage("create throwaway repository", || {
+        fs::create_dir_all(repo)?;
+        fs::write(repo.join("README.md"), "forest self-test\n")?;
+        let origin = scratch.join("origin.git");
+        git(repo, &["init", "--quiet"])?;
+        git(repo, &["add", "README.md"])?;
+        git(
+            repo,
+            &[
+                "-c",
+                "user.name=forest",
+                "-c",
+                "user.email=forest@localhost",
+                "commit",
+                "--quiet",
+                "-m",
+                "self-test",
+            ],
+        )?;
+        // a local origin keeps open from creating a GitHub repository
+        git(scratch, &["init", "--quiet", "--bare", "origin.git"])?;
+        git(
+            repo,
+            &["remote", "add", "origin", &origin.to_string_lossy()],
+        )?;
+        Ok(())
+    })?;
+
+    if stub {
+        stage("install devcontainer stub", || {
+            let bin = scratch.join("stub-bin");
+            fs::create_dir_all(&bin)?;
+            let path = bin.join("devcontainer");
+            fs::write(&path, STUB)?;
+            let status = Command::new("chmod").arg("+x").arg(&path).status()?;
+            if !status.success() {
+                anyhow::bail!("chmod failed");
+            }
+            let search = std::env::var("PATH").unwrap_or_default();
+            env.push((
+                "PATH",
+                PathBuf::from(format!("{}:{}", bin.display(), search)),
+            ));
+            env.push(("DEVCONTAINER_STATE", bin));
+            let home = scratch.join("home");
+            fs::create_dir_all(&home)?;
+            env.push(("XDG_CONFIG_HOME", home.join(".config")));
+            env.push(("XDG_DATA_HOME", home.join(".local/share")));
+            env.push(("HOME", home));
+            Ok(())
+        })?;
+    }
+
+    stage("open session and run a command", || {
+        let output = forest(
+            repo,
+            &env,
+            &["open", "--scaffold", SESSION],
+            Some(&format!("echo {}\n", MARKER)),
+            verbose,
+        )?;
+        if !String::from_utf8_lossy(&output.stdout).contains(MARKER) {
+            anyhow::bail!("command output missing from the session shell");
+        }
+        Ok(())
+    })?;
+
+    stage("kill session", || {
+        forest(repo, &env, &["kill", SESSION, "--down"], None, verbose)?;
+        Ok(())
+    })?;
+
+    if verbose && !stub {
+        println!("Worktrees were created under {}", worktrees.display());
+    }
+    Ok(())
+}
+
+fn stage(name: &str, f: impl FnOnce() -> anyhow::Result<()>) -> anyhow::Result<()> {
+    print!("{}... ", name);
+    std::io::stdout().flush()?;
+    match f() {
+        Ok(()) => {
+            println!("ok");
+            Ok(())
+        }
+        Err(e) => {
+            println!("FAILED");
+            anyhow::bail!("self-test failed at stage \"{}\": {}", name, e)
+        }
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let status = Command::new("git").args(args).current_dir(dir).status()?;
+    if !status.success() {
+        anyhow::bail!("git {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
+fn forest(
+    repo: &Path,
+    env: &[(&str, PathBuf)],
+    args: &[&str],
+    input: Option<&str>,
+    verbose: bool,
+) -> anyhow::Result<Output> {
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.current_dir(repo).envs(env.iter().map(|(k, v)| (*k, v)));
+    if verbose {
+        cmd.arg("--verbose");
+    }
+    cmd.args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+    if let Some(input) = input {
+        child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    }
+    drop(child.stdin.take());
+    let output = child.wait_with_output()?;
+    if verbose {
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+    }
+    if !output.status.success() {
+        anyhow::bail!(
+            "forest {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output)
+}
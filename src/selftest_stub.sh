@@ -0,0 +1,60 @@
+#!/bin/sh
+# Minimal stand-in for the devcontainer CLI used by `forest self-test --stub`.
+# Containers are simulated on the host: /code and /repo in exec commands are
+# rewritten to the session worktree and the mounted repository.
+cmd=$1
+shift
+workspace=""
+name=""
+repo=""
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --workspace-folder)
+      workspace=$2
+      shift 2
+      ;;
+    --id-label)
+      name=${2#name=}
+      shift 2
+      ;;
+    --docker-path)
+      shift 2
+      ;;
+    --mount)
+      case "$2" in
+        *target=/repo) repo=$(echo "$2" | sed 's#.*source=\([^,]*\),.*#\1#') ;;
+      esac
+      shift 2
+      ;;
+    --*)
+      shift
+      ;;
+    *)
+      break
+      ;;
+  esac
+done
+case "$cmd" in
+  build)
+    exit 0
+    ;;
+  up)
+    echo "$repo" > "$DEVCONTAINER_STATE/$name.repo"
+    exit 0
+    ;;
+  exec)
+    [ -f "$DEVCONTAINER_STATE/$name.repo" ] || exit 1
+    repo=$(cat "$DEVCONTAINER_STATE/$name.repo")
+    n=$#
+    for arg in "$@"; do
+      set -- "$@" "$(printf '%s' "$arg" | sed "s#/code#$workspace#g; s#/repo#$repo#g")"
+    done
+    shift "$n"
+    exec "$@"
+    ;;
+  down)
+    rm -f "$DEVCONTAINER_STATE/$name.repo"
+    exit 0
+    ;;
+esac
+exit 1
@@ -0,0 +1,2521 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{
+    data_dir, templates_dir, worktree_path, worktrees_dir, Config, KillTier, ScaffoldTarget,
+};
+use crate::git::*;
+use crate::names::{legacy_podman_name, sanitize_podman_name, valid_podman_name};
+use crate::{
+    backend, checkpoint, confirm, devcontainer, egress, env, helpers, hooks, locks, names, policy,
+    priority, prune, registry, resources, run_command_verbose, run_output_verbose, runtime,
+    scaffold, scan, secrets, sessions, shell, snapshots, store, style, telemetry, timesheet, trash,
+    wip,
+};
+
+/// Container label for a session.
+///
+/// Sessions created before labels carried hash suffixes keep their old label
+/// until their container is removed, so the label the session registry holds
+/// for `name` wins, then the legacy label when only a container with it
+/// exists.
+pub fn session_label(name: &str) -> String {
+    let label = sanitize_podman_name(name);
+    let legacy = legacy_podman_name(name);
+    if label == legacy {
+        return label;
+    }
+    let candidates = [label, legacy];
+    if let Some(registered) = data_dir()
+        .and_then(|dir| sessions::load(&dir))
+        .ok()
+        .and_then(|sessions| sessions::registered_label(&sessions, name, &candidates))
+    {
+        return registered;
+    }
+    let [label, legacy] = candidates;
+    let has = |l: &str| {
+        runtime::containers_with_name(l, true)
+            .map(|ids| !ids.is_empty())
+            .unwrap_or(false)
+    };
+    if !has(&label) && has(&legacy) {
+        return legacy;
+    }
+    label
+}
+
+/// The `devcontainer.json` a session would use, if there is one.
+pub fn locate_devcontainer(dev_env: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
+    if let Some(path) = locate_devcontainer_in(Path::new(""), dev_env)? {
+        return Ok(Some(path));
+    }
+    let overlay = overlay_dir()?.join("devcontainer.json");
+    Ok(overlay.exists().then_some(overlay))
+}
+
+/// The `devcontainer.json` checked in under `dir`, if there is one.
+pub fn locate_devcontainer_in(
+    dir: &Path,
+    dev_env: Option<&str>,
+) -> anyhow::Result<Option<PathBuf>> {
+    if let Some(env) = dev_env {
+        let candidate = dir
+            .join(".devcontainer")
+            .join(env)
+            .join("devcontainer.json");
+        if candidate.exists() {
+            return Ok(Some(candidate));
+        }
+        anyhow::bail!("devcontainer {} not found", env);
+    }
+
+    let root = dir.join(".devcontainer.json");
+    if root.exists() {
+        return Ok(Some(root));
+    }
+
+    let default = dir.join(".devcontainer").join("devcontainer.json");
+    Ok(default.exists().then_some(default))
+}
+
+/// The devcontainer.json a session would use, scaffolding a default one when
+/// there's none: straight away with `scaffold`, else only if the user agrees
+/// when asked. `scaffold` in forest.toml picks the repository or forest's
+/// overlay directory for it, and `scaffold_template` the template, which is
+/// otherwise guessed from the repository's files.
+pub fn find_devcontainer(
+    dev_env: Option<&str>,
+    scaffold: bool,
+    config: &Config,
+) -> anyhow::Result<PathBuf> {
+    if let Some(path) = locate_devcontainer(dev_env)? {
+        return Ok(path);
+    }
+    let dir = scaffold_dir(config)?;
+    let template = config
+        .scaffold_template
+        .clone()
+        .unwrap_or_else(|| scaffold::detect(Path::new(".")).to_string());
+    let agreed = scaffold
+        || (crate::interactive()
+            && confirm(&format!(
+                "No devcontainer.json found. Write one from template {} to {}?",
+                template,
+                dir.display()
+            ))?);
+    if !agreed {
+        anyhow::bail!(
+            "no devcontainer.json found; add one, or pass --scaffold to write a default \
+             one to {} (`scaffold = \"overlay\"` in forest.toml keeps it out of the repository)",
+            dir.display()
+        );
+    }
+    let path = scaffold_devcontainer(&dir, &template)?;
+    style::note(format!(
+        "wrote {} from template {}",
+        path.display(),
+        template
+    ));
+    Ok(path)
+}
+
+/// Writes the devcontainer.json of `template` into `dir`, looking for it in
+/// the `templates` directory next to forest.toml before the built-in ones.
+pub fn scaffold_devcontainer(dir: &Path, template: &str) -> anyhow::Result<PathBuf> {
+    let source = scaffold::resolve(template, templates_dir().as_deref())?;
+    scaffold::write(&source, dir)
+}
+
+/// Where `scaffold_devcontainer` writes for the repository in the current
+/// directory, as `scaffold` in forest.toml picks.
+pub fn scaffold_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    Ok(match config.scaffold.unwrap_or_default() {
+        ScaffoldTarget::Repo => PathBuf::from(".devcontainer"),
+        ScaffoldTarget::Overlay => overlay_dir()?,
+    })
+}
+
+/// The configuration with the repository's overlay, `devcontainer.overlay.json`
+/// in its overlay directory, merged over it; unchanged without one. The
+/// overlay holds personal tweaks, such as mounts, environment and features,
+/// that would otherwise be edits to the repository's devcontainer.json.
+pub fn apply_overlay(value: &Value) -> anyhow::Result<Value> {
+    let path = overlay_path()?;
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(value.clone());
+    };
+    let overlay: Value = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("invalid overlay {}: {}", path.display(), e))?;
+    Ok(devcontainer::merge_overlay(value, &overlay))
+}
+
+/// The devcontainer overlay of the repository in the current directory.
+pub fn overlay_path() -> anyhow::Result<PathBuf> {
+    Ok(overlay_dir()?.join("devcontainer.overlay.json"))
+}
+
+/// forest's own directory for the repository in the current directory,
+/// holding its overlay and a scaffolded devcontainer.json kept out of it.
+pub fn overlay_dir() -> anyhow::Result<PathBuf> {
+    let cwd = fs::canonicalize(std::env::current_dir()?)?;
+    let name = cwd.file_name().unwrap_or_default().to_string_lossy();
+    let key = config_hash(&[&cwd.to_string_lossy()]);
+    let devcontainers = data_dir()?.join("devcontainers");
+    let dir = devcontainers.join(format!("{}-{}", name, &key[..8]));
+    if !dir.exists() {
+        let legacy = legacy_config_hash(&[&cwd.to_string_lossy()]);
+        let legacy = devcontainers.join(format!("{}-{}", name, &legacy[..8]));
+        if legacy.exists() {
+            fs::rename(&legacy, &dir)?;
+        }
+    }
+    Ok(dir)
+}
+
+/// How `open` treats the session beyond its configuration.
+#[derive(Default)]
+pub struct OpenOptions {
+    /// Drop the overrides recorded by earlier opens
+    pub reset: bool,
+    /// Undo the WIP commit or stash `wip_on_detach` made
+    pub restore_wip: bool,
+    /// Stop once the session is up instead of starting its shell
+    pub detach: bool,
+    /// Write a default devcontainer.json without asking when there's none
+    pub scaffold: bool,
+    /// Take over containers using the session's label or proxy name that
+    /// forest didn't create for it
+    pub adopt: bool,
+    /// `KEY=VALUE` variables of `-e` and `--env-file`, which this open's
+    /// lifecycle commands and shell get besides `[env]`'s
+    pub env: Vec<String>,
+    /// Build the image again and recreate the container, from scratch with
+    /// `Rebuild::NoCache`
+    pub rebuild: Option<Rebuild>,
+    /// Branch, tag or commit a new session branch starts from instead of
+    /// `default_base_branch` or HEAD
+    pub from: Option<String>,
+    /// Start from `from` as last fetched instead of fetching it first
+    pub no_fetch: bool,
+}
+
+/// How `rebuild` builds a session's image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rebuild {
+    /// Reusing the engine's layer cache
+    Cached,
+    /// Without the cache, so every step runs again
+    NoCache,
+}
+
+/// A session `open` brought up.
+#[derive(Debug)]
+pub struct Opened {
+    /// Container label of the session
+    pub label: String,
+    /// The session's worktree on the host
+    pub worktree: PathBuf,
+    /// Id of the container `devcontainer up` started, when this open ran it
+    pub container_id: Option<String>,
+}
+
+/// A session of the repository in the current directory, for driving forest
+/// from Rust the way its subcommands do.
+pub struct Session<'a> {
+    pub name: &'a str,
+    pub config: &'a Config,
+    pub verbose: bool,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(name: &'a str, config: &'a Config) -> Self {
+        Session {
+            name,
+            config,
+            verbose: false,
+        }
+    }
+
+    /// Creates or resumes the session, like `forest open`.
+    pub fn open(
+        &self,
+        dev_env: Option<&str>,
+        changes: &Overrides,
+        options: &OpenOptions,
+    ) -> anyhow::Result<Opened> {
+        open_session(
+            self.name,
+            dev_env,
+            changes,
+            options,
+            self.config,
+            self.verbose,
+        )
+    }
+
+    /// Opens a shell in the running session, like `forest attach`.
+    pub fn attach(&self) -> anyhow::Result<()> {
+        attach_session(self.name, self.config, self.verbose)
+    }
+
+    /// Builds the image again and recreates the container, like
+    /// `forest rebuild`.
+    pub fn rebuild(&self, rebuild: Rebuild, detach: bool) -> anyhow::Result<()> {
+        rebuild_session(self.name, rebuild, detach, self.config, self.verbose)
+    }
+
+    /// Stops or removes the session's container, like `forest kill`.
+    pub fn kill(&self, tier: KillTier, options: &KillOptions) -> anyhow::Result<()> {
+        kill_session(self.name, tier, options, self.config, self.verbose)
+    }
+
+    /// Deletes the session, like `forest rm`.
+    pub fn remove(&self, keep_branch: bool, keep_worktree: bool) -> anyhow::Result<()> {
+        remove_session(
+            self.name,
+            keep_branch,
+            keep_worktree,
+            self.config,
+            self.verbose,
+        )
+    }
+}
+
+pub fn open_session(
+    name: &str,
+    dev_env: Option<&str>,
+    changes: &Overrides,
+    options: &OpenOptions,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<Opened> {
+    let repo_root = find_or_init_repo(config, verbose)?;
+    let tools = config.tools(&repo_root, verbose);
+
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+
+    // Determine worktree path
+    let repo_name = repo_root
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("failed to determine repo name"))?
+        .to_string_lossy();
+
+    // a session opened before `worktree_layout` changed keeps its worktree
+    let worktree_path = match sessions::load(&data_dir()?)?.remove(&podman_name) {
+        Some(session) if session.repo == repo_root && session.worktree.exists() => session.worktree,
+        _ => worktree_path(config, &repo_name, name),
+    };
+    check_nesting(&worktrees_dir(config).join(&*repo_name), &worktree_path)?;
+
+    if !worktree_path.exists() {
+        if verbose {
+            println!("Creating worktree directory {}", worktree_path.display());
+        }
+        fs::create_dir_all(&worktree_path)?;
+    }
+    let devcontainer_path = find_devcontainer(dev_env, options.scaffold, config)?;
+
+    if verbose {
+        println!("Using devcontainer at {}", devcontainer_path.display());
+    }
+    let hook_context = hooks::Context {
+        session: name,
+        label: &podman_name,
+        repo: Some(&repo_root),
+        worktree: Some(&worktree_path),
+    };
+    hooks::run(
+        "pre_open",
+        config.hooks.pre_open.as_deref(),
+        &hook_context,
+        verbose,
+    )?;
+
+    let contents = fs::read_to_string(&devcontainer_path)?;
+    let value: Value = serde_json::from_str(&contents)?;
+    let value = apply_overlay(&value)?;
+    let value = priority::apply(&value, config.priority);
+    let (cpus, memory) = config.resources.limits()?;
+    let value = resources::apply(&value, cpus, memory.as_deref());
+    let value = devcontainer::with_features(&value, &config.default_features);
+
+    let up_cache: BTreeMap<String, UpRecord> = store::load(&data_dir()?, &UP_CACHE)?;
+    let recorded = up_cache.get(&podman_name).map(|r| r.overrides.clone());
+    let overrides = if options.reset {
+        Overrides::default()
+    } else {
+        recorded.clone().unwrap_or_default()
+    }
+    .merge(changes);
+    let configured = overrides.apply(&value, &podman_name);
+    let value = devcontainer::with_label(&configured, VERSION_LABEL, env!("CARGO_PKG_VERSION"));
+    let network = overrides.network();
+    if network == egress::Mode::Allowlist && overrides.allow_hosts.is_empty() {
+        anyhow::bail!("--network allowlist needs at least one --allow-host");
+    }
+    if !devcontainer::has_container(&value) {
+        anyhow::bail!(devcontainer::NO_CONTAINER);
+    }
+    if devcontainer::is_compose(&value) {
+        check_compose_overrides(&value, &overrides)?;
+    }
+    enforce_policy(&value, &devcontainer_path)?;
+    login_registries(&value, &devcontainer_path, config, verbose)?;
+
+    let extra_mounts = config
+        .extra_mounts
+        .iter()
+        .chain(&overrides.mounts)
+        .map(|spec| parse_mount(spec, &repo_root))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mount = |source: &Path, target: &str| {
+        if overrides.readonly {
+            backend::current().readonly_bind_mount(source, target)
+        } else {
+            backend::current().bind_mount(source, target)
+        }
+    };
+    // resolved afresh on every open and only ever passed on, never stored
+    let mut secrets = secrets::resolve_all(&config.secrets)?;
+    let extra_env = &options.env;
+    let mut up_cmd = devcontainer::command("up");
+    up_cmd
+        .arg("--workspace-folder")
+        .arg(&worktree_path)
+        .arg("--id-label")
+        .arg(format!("name={}", podman_name))
+        .arg("--mount")
+        .arg(mount(&repo_root, "/repo"))
+        .arg("--mount")
+        .arg(mount(&worktree_path, "/code"))
+        .args(
+            extra_mounts
+                .iter()
+                .flat_map(|(source, target)| ["--mount".to_string(), mount(source, target)]),
+        )
+        // this is a bit subtle: we'll often be using the same devcontainer that vscode uses for consistency, but we don't want
+        // all the services that might attach (rust-analyzer etc).
+        .arg("--skip-post-attach")
+        .args(env::remote_env_args(&env::entries(&config.env)));
+    secrets.pass_to(&mut up_cmd);
+    up_cmd.args(env::remote_env_args(extra_env));
+    if config.shell_helpers.unwrap_or(false) && !overrides.readonly {
+        let dir = helpers::dir(&data_dir()?, &podman_name);
+        helpers::write(&dir, name, &repo_name, &config.clipboard)?;
+        up_cmd.args(
+            helpers::mounts(&dir)
+                .into_iter()
+                .flat_map(|mount| ["--mount".to_string(), mount]),
+        );
+    }
+    // the configuration always goes through a copy, which also carries the
+    // version label
+    let override_path = data_dir()?
+        .join("overrides")
+        .join(format!("{}.json", podman_name));
+    if let Some(parent) = override_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // the copy lives elsewhere, so relative build paths must be resolved first
+    let dir = fs::canonicalize(&devcontainer_path)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let anchored = devcontainer::anchor_build_paths(&value, &dir);
+    fs::write(&override_path, serde_json::to_string_pretty(&anchored)?)?;
+    up_cmd.arg("--override-config").arg(&override_path);
+    // what the image is built from, which the configuration only names
+    let build_files: Vec<String> = devcontainer::build_files(&anchored)
+        .iter()
+        .map(|file| fs::read_to_string(file).unwrap_or_default())
+        .collect();
+    let build_hash = config_hash(&build_files.iter().map(String::as_str).collect::<Vec<_>>());
+    let build_changed = up_cache
+        .get(&podman_name)
+        .and_then(|record| record.build_hash.as_ref())
+        .is_some_and(|hash| *hash != build_hash);
+    // `up` keeps an existing container as it is, whatever the configuration says
+    if options.rebuild.is_some() || recorded.is_some_and(|r| r != overrides) || build_changed {
+        up_cmd.arg("--remove-existing-container");
+    }
+
+    let adopted = up_cache
+        .get(&podman_name)
+        .and_then(|record| record.container.as_ref())
+        .map(|c| c.container_id.as_str());
+    check_name_collisions(name, &podman_name, &worktree_path, adopted, options.adopt)?;
+
+    // skip build and up entirely when the session's container is already running,
+    // or only stopped, from the same devcontainer.json and arguments; secrets
+    // are in the arguments by name only, their values in the environment
+    let up_args: Vec<String> = up_cmd
+        .get_args()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    let up_hash = config_hash(&[
+        &contents,
+        &value.to_string(),
+        &shell::join(&up_args),
+        &build_hash,
+    ]);
+    let running = runtime::containers_with_name(&podman_name, false).unwrap_or_default();
+    if running.is_empty() && checkpoint::load(&data_dir()?)?.contains_key(&podman_name) {
+        anyhow::bail!(
+            "session {} has a checkpoint; restore it with `forest resume {}` or discard it \
+             with `forest kill {} --down`",
+            name,
+            name,
+            name
+        );
+    }
+    let stopped = if running.is_empty() {
+        runtime::containers_with_name(&podman_name, true).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let existing = if running.is_empty() {
+        &stopped
+    } else {
+        &running
+    };
+    let cached = up_cache
+        .get(&podman_name)
+        .filter(|record| record.hash == up_hash && !existing.is_empty());
+    let reuse = cached.is_some() && options.rebuild.is_none();
+    if running.is_empty() {
+        check_host_resources(&podman_name, config, verbose)?;
+    }
+    // podman lists short ids, devcontainer reports full ones
+    let mut container = cached
+        .and_then(|record| record.container.clone())
+        .filter(|c| {
+            existing
+                .iter()
+                .any(|id| c.container_id.starts_with(id.as_str()))
+        });
+
+    // the session branch and the image are independent, so prepare them together
+    // features are built into an image too, which a rebuild refreshes
+    let needs_build = options.rebuild.is_some() || !reuse && value.get("build").is_some();
+    let prepare = telemetry::span("prepare branch and image");
+    std::thread::scope(|scope| {
+        let git = scope.spawn(|| {
+            if overrides.readonly {
+                require_branch(name, &repo_root)
+            } else {
+                ensure_git_setup(
+                    name,
+                    &repo_root,
+                    options.from.as_deref(),
+                    !options.no_fetch,
+                    config,
+                    verbose,
+                )
+            }
+        });
+        let build = if needs_build {
+            build_image(
+                &worktree_path,
+                &devcontainer_path,
+                overrides.platform.as_deref(),
+                options.rebuild == Some(Rebuild::NoCache),
+                verbose,
+            )
+        } else {
+            Ok(())
+        };
+        let git = git
+            .join()
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("git setup panicked")));
+        build.and(git)
+    })?;
+    drop(prepare);
+    if !overrides.readonly {
+        record_session_metadata(&repo_root, name, &podman_name, dev_env, config, verbose)?;
+    }
+    sessions::record(
+        &data_dir()?,
+        &podman_name,
+        sessions::Session {
+            branch: name.to_string(),
+            repo: repo_root.clone(),
+            worktree: worktree_path.clone(),
+            devcontainer_env: dev_env.map(str::to_string),
+            created_at: timesheet::now(),
+            readonly: overrides.readonly,
+        },
+    )?;
+
+    if reuse {
+        if verbose {
+            println!("Container unchanged since the last up, skipping devcontainer up");
+        }
+        if stopped.is_empty() {
+            println!("Reusing running session {}", name);
+        } else {
+            // `start` brings the container back as `kill` left it, where `up`
+            // would create it again once anything about it is in doubt
+            let _span = telemetry::span("start container");
+            runtime::container_action("start", &stopped, verbose)?;
+            println!("Resumed stopped session {}", name);
+        }
+        if network == egress::Mode::Allowlist {
+            egress::resume_proxy(&podman_name, verbose)?;
+        }
+    } else {
+        if matches!(network, egress::Mode::Internal | egress::Mode::Allowlist) {
+            egress::ensure_network(&podman_name, verbose)?;
+        }
+        if network == egress::Mode::Allowlist {
+            let image = config
+                .egress_proxy_image
+                .as_deref()
+                .unwrap_or(egress::DEFAULT_PROXY_IMAGE);
+            egress::start_proxy(&podman_name, &overrides.allow_hosts, image, verbose)?;
+        } else {
+            egress::remove_proxy(&podman_name, verbose)?;
+        }
+        let _span = telemetry::span("devcontainer up");
+        // progress goes to stderr, the result is a JSON line on stdout
+        let output = run_output_verbose(&mut up_cmd, verbose).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+            } else {
+                e.into()
+            }
+        })?;
+        if !output.status.success() {
+            return Err(devcontainer::failure(
+                "up",
+                &String::from_utf8_lossy(&output.stdout),
+                &devcontainer_path,
+            ));
+        }
+        container = devcontainer::parse_up(&String::from_utf8_lossy(&output.stdout));
+        if let (Some(c), true) = (&container, verbose) {
+            println!(
+                "Container {} (user {})",
+                c.container_id,
+                c.remote_user.as_deref().unwrap_or("default")
+            );
+        }
+        println!("Started session {}", name);
+        // sessions keep the version that created them, unknown for older ones
+        let forest_version = match up_cache.get(&podman_name) {
+            Some(record) => record.forest_version.clone(),
+            None => Some(env!("CARGO_PKG_VERSION").to_string()),
+        };
+        let record = UpRecord {
+            forest_version,
+            hash: up_hash,
+            build_hash: Some(build_hash),
+            container: container.clone(),
+            overrides: overrides.clone(),
+            config: Some(configured.clone()),
+        };
+        store::update(
+            &data_dir()?,
+            &UP_CACHE,
+            |up_cache: &mut BTreeMap<String, UpRecord>| {
+                up_cache.insert(podman_name.clone(), record);
+                Ok(())
+            },
+        )?;
+        gate_session_image(&podman_name, container.as_ref(), config, verbose)?;
+    }
+
+    let target =
+        devcontainer::target_args(&podman_name, container.as_ref().map(|c| &*c.container_id));
+
+    let git_file = worktree_path.join(".git");
+    let mut need_worktree = true;
+    // what a new worktree gets set up for; an existing one already was
+    let mut protocol = None;
+    if let Ok(content) = fs::read_to_string(&git_file) {
+        if content.contains("/repo/.git/worktrees/") {
+            need_worktree = false;
+        }
+    }
+    if need_worktree && overrides.readonly {
+        let _span = telemetry::span("create worktree");
+        let _lock =
+            locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+        add_readonly_worktree(&repo_root, &worktree_path, name, verbose)?;
+    } else if need_worktree {
+        let _span = telemetry::span("create worktree");
+        let git_dir = repo_root.join(".git");
+        let _lock = locks::RepoLock::acquire(&git_dir, config.lock_timeout(), verbose)?;
+        locks::recover_stale_worktree_locks(&git_dir, name, verbose)?;
+        let git_config = check_git_skew(&worktree_path, &target, verbose);
+        // `-B` resets an existing branch to HEAD, which would move a bookmark jj
+        // is tracking; ensure_git_setup has already created the branch
+        let add = if tools.jj {
+            vec!["/code", name]
+        } else {
+            vec!["-B", name, "/code"]
+        };
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(&worktree_path)
+            .args(&target)
+            .arg("git")
+            .args(git_config)
+            .args(["-C", "/repo", "worktree", "add"])
+            .args(add);
+        let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+            } else {
+                e.into()
+            }
+        })?;
+        if !status.success() {
+            anyhow::bail!("git worktree add failed");
+        }
+        propagate_git_config(
+            &repo_root,
+            &worktree_path,
+            &target,
+            &config.propagate_git_config(),
+            name,
+            verbose,
+        )?;
+        protocol = Some(configure_remote_protocol(
+            &worktree_path,
+            &target,
+            config.remote_protocol,
+            verbose,
+        )?);
+        if tools.branchless {
+            disable_branchless_hooks(&worktree_path, &target, verbose)?;
+        }
+    } else if overrides.readonly {
+        refresh_readonly_worktree(&repo_root, &worktree_path, name, verbose)?;
+    } else {
+        let registered = sessions::load(&data_dir()?)?.remove(&podman_name);
+        warn_branch_divergence(name, registered.as_ref(), &repo_root, &worktree_path);
+    }
+    let protocol = protocol
+        .unwrap_or_else(|| worktree_protocol(config.remote_protocol, &repo_root, &worktree_path));
+    if let Some(token) = github_token(protocol) {
+        secrets.add("GH_TOKEN", token);
+    }
+    let session_git = wip::SessionGit {
+        worktree_path: &worktree_path,
+        target: &target,
+        verbose,
+    };
+    if options.restore_wip && overrides.readonly {
+        style::warn(format!(
+            "session {} is read-only; there's no work to restore",
+            name
+        ));
+    } else if options.restore_wip && session_git.restore()? {
+        println!("Restored the work saved when session {} was left", name);
+    }
+    if let Err(e) = hooks::run(
+        "post_open",
+        config.hooks.post_open.as_deref(),
+        &hook_context,
+        verbose,
+    ) {
+        style::warn(e.to_string());
+    }
+    if let Some(command) = &config.hooks.post_open_in_container {
+        println!("Running post_open_in_container hook");
+        let mut cmd = devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(&worktree_path)
+            .args(&target);
+        // host paths mean nothing inside the container
+        for (key, value) in &hook_context.env()[..2] {
+            cmd.arg("--remote-env").arg(format!("{}={}", key, value));
+        }
+        cmd.args(env::remote_env_args(&shell_env(config, &[])));
+        secrets.pass_to(&mut cmd);
+        cmd.args(env::remote_env_args(extra_env));
+        cmd.args(shell::in_dir("/code", &["sh", "-c", command]));
+        if !run_command_verbose(&mut cmd, verbose)?.success() {
+            style::warn(format!("post_open_in_container hook failed: {}", command));
+        }
+    }
+
+    if options.detach {
+        // the name alone on the last line, for scripts
+        println!("{}", name);
+    } else {
+        let wip = config.wip_on_detach.filter(|_| !overrides.readonly);
+        let mut env = shell_env(config, extra_env);
+        let helpers = serve_helpers(&podman_name, name, &repo_root, config);
+        env.extend(helpers.as_ref().map(helpers::Server::env));
+        session_shell(name, &repo_name, &session_git, wip, &env, &secrets)?;
+    }
+    Ok(Opened {
+        label: podman_name,
+        worktree: worktree_path,
+        container_id: container.map(|c| c.container_id),
+    })
+}
+
+/// Runs the interactive shell of a running session in `/code`, recording the
+/// time spent in it and saving uncommitted work as `wip` asks once it exits.
+/// `env` holds `KEY=value` variables the shell gets besides the container's own
+/// and `secrets`, which `env` wins over.
+pub fn session_shell(
+    name: &str,
+    repo_name: &str,
+    session_git: &wip::SessionGit,
+    wip: Option<wip::Mode>,
+    env: &[String],
+    secrets: &secrets::Resolved,
+) -> anyhow::Result<()> {
+    let mut cmd = devcontainer::command("exec");
+    cmd.arg("--workspace-folder")
+        .arg(session_git.worktree_path)
+        .args(session_git.target);
+    secrets.pass_to(&mut cmd);
+    cmd.args(env::remote_env_args(env));
+    cmd.args(shell::in_dir("/code", &["bash"]));
+    let shell = telemetry::span("session shell");
+    let start = timesheet::now();
+    let status = run_command_verbose(&mut cmd, session_git.verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+        } else {
+            e.into()
+        }
+    })?;
+    let entry = timesheet::Entry {
+        session: name.to_string(),
+        repo: repo_name.to_string(),
+        start,
+        end: timesheet::now(),
+    };
+    if let Err(e) = data_dir().and_then(|dir| timesheet::record(&dir, &entry)) {
+        style::warn(format!("failed to record session time: {}", e));
+    }
+    drop(shell);
+    if let Some(mode) = wip {
+        save_wip(name, session_git, mode);
+    }
+    if !status.success() {
+        anyhow::bail!("devcontainer exec failed");
+    }
+    Ok(())
+}
+
+/// Opens a shell in session `name`'s running container without any of
+/// `open`'s provisioning: no build, `up`, git or worktree setup.
+pub fn attach_session(name: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+    let data_dir = data_dir()?;
+    let registered = sessions::load(&data_dir)?.remove(&podman_name);
+    let running = runtime::containers_with_name(&podman_name, false)?;
+    let Some(id) = running.first() else {
+        if registered.is_some() || !runtime::containers_with_name(&podman_name, true)?.is_empty() {
+            anyhow::bail!(
+                "session {} isn't running; start it with `forest open {}`",
+                name,
+                shell::quote(name)
+            );
+        }
+        anyhow::bail!(
+            "no session {}; create it with `forest open {}`",
+            name,
+            shell::quote(name)
+        );
+    };
+    let (repo_root, repo_name, worktree_path) =
+        session_paths(name, registered.as_ref(), "attach", config)?;
+    let mut up_cache: BTreeMap<String, UpRecord> = store::load(&data_dir, &UP_CACHE)?;
+    let record = up_cache.remove(&podman_name);
+    // the container id devcontainer reported is the full form of podman's
+    let id = record
+        .as_ref()
+        .and_then(|r| r.container.as_ref())
+        .map(|c| c.container_id.clone())
+        .filter(|full| full.starts_with(id.as_str()))
+        .unwrap_or_else(|| id.clone());
+    let readonly = record.is_some_and(|r| r.overrides.readonly);
+    let target = devcontainer::target_args(&podman_name, Some(&id));
+    let session_git = wip::SessionGit {
+        worktree_path: &worktree_path,
+        target: &target,
+        verbose,
+    };
+    let wip = config.wip_on_detach.filter(|_| !readonly);
+    let mut secrets = secrets::resolve_all(&config.secrets)?;
+    let protocol = worktree_protocol(config.remote_protocol, &repo_root, &worktree_path);
+    if let Some(token) = github_token(protocol) {
+        secrets.add("GH_TOKEN", token);
+    }
+    let mut env = shell_env(config, &[]);
+    let helpers = serve_helpers(&podman_name, name, &repo_root, config);
+    env.extend(helpers.as_ref().map(helpers::Server::env));
+    session_shell(name, &repo_name, &session_git, wip, &env, &secrets)
+}
+
+/// Builds session `name`'s image again and recreates its container from it,
+/// keeping the worktree and the overrides earlier opens recorded, then opens
+/// its shell unless `detach`.
+pub fn rebuild_session(
+    name: &str,
+    rebuild: Rebuild,
+    detach: bool,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+    let registered = sessions::load(&data_dir()?)?.remove(&podman_name);
+    let (repo_root, _, worktree_path) =
+        session_paths(name, registered.as_ref(), "rebuild", config)?;
+    if !worktree_path.exists() {
+        anyhow::bail!(
+            "no session {}; create it with `forest open {}`",
+            name,
+            shell::quote(name)
+        );
+    }
+    std::env::set_current_dir(&repo_root)?;
+    let options = OpenOptions {
+        detach,
+        rebuild: Some(rebuild),
+        ..OpenOptions::default()
+    };
+    let dev_env = registered
+        .as_ref()
+        .and_then(|session| session.devcontainer_env.as_deref());
+    open_session(
+        name,
+        config.devcontainer_env(dev_env),
+        &Overrides::default(),
+        &options,
+        config,
+        verbose,
+    )?;
+    Ok(())
+}
+
+/// Puts what the host's clipboard holds into running session `name`, at
+/// `helpers::PASTED`, where the helpers' `pbpaste`, `xclip -o` and the like
+/// print it.
+pub fn paste_session(name: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+    let registered = sessions::load(&data_dir()?)?.remove(&podman_name);
+    let running = runtime::containers_with_name(&podman_name, false)?;
+    let Some(id) = running.first() else {
+        anyhow::bail!(
+            "session {} isn't running; start it with `forest open {}`",
+            name,
+            shell::quote(name)
+        );
+    };
+    let (_, _, worktree_path) = session_paths(name, registered.as_ref(), "paste", config)?;
+    let content = helpers::read_clipboard()?;
+    let mut cmd = devcontainer::command("exec");
+    cmd.arg("--workspace-folder")
+        .arg(&worktree_path)
+        .args(devcontainer::target_args(&podman_name, Some(id)))
+        .args([
+            "sh",
+            "-c",
+            &format!("umask 077 && cat > {}", helpers::PASTED),
+        ])
+        .stdin(Stdio::piped());
+    if verbose {
+        println!("Running: {}", shell::display(&cmd));
+    }
+    let mut child = cmd.spawn().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+        } else {
+            e.into()
+        }
+    })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&content)?;
+    }
+    if !child.wait()?.success() {
+        anyhow::bail!("devcontainer exec failed");
+    }
+    println!(
+        "Pasted {} bytes into session {}; pbpaste there prints them",
+        content.len(),
+        name
+    );
+    Ok(())
+}
+
+/// With `shell_helpers`, answers the helpers of session `label` until the
+/// returned server is dropped, once its shell exits; the shell needs the
+/// server's `env`. Containers created without the helpers have nothing to
+/// answer.
+fn serve_helpers(
+    label: &str,
+    name: &str,
+    repo_root: &Path,
+    config: &Config,
+) -> Option<helpers::Server> {
+    if !config.shell_helpers.unwrap_or(false) {
+        return None;
+    }
+    let dir = helpers::dir(&data_dir().ok()?, label);
+    if !dir.exists() {
+        return None;
+    }
+    let served = std::env::current_exe()
+        .map_err(anyhow::Error::from)
+        .and_then(|forest| {
+            helpers::serve(
+                &dir,
+                label,
+                name,
+                repo_root,
+                &forest,
+                config.clipboard.copy(),
+            )
+        });
+    match served {
+        Ok(server) => {
+            secrets::hide(&server.env());
+            Some(server)
+        }
+        Err(e) => {
+            style::warn(format!(
+                "the session's shell helpers won't be answered: {}",
+                e
+            ));
+            None
+        }
+    }
+}
+
+/// Variables of a session's shells and commands: `[env]` and then `extra`,
+/// such as `-e` flags, later ones winning. The GitHub token goes with the
+/// secrets, whose values stay off command lines.
+pub fn shell_env(config: &Config, extra: &[String]) -> Vec<String> {
+    env::entries(&config.env)
+        .into_iter()
+        .chain(extra.iter().cloned())
+        .collect()
+}
+
+/// Warns before another container starts on a host already short of memory
+/// or CPU, and with `stop_idle` offers to stop sessions that are doing
+/// nothing.
+pub fn check_host_resources(label: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let Some(host) = resources::host() else {
+        return Ok(());
+    };
+    let reasons = config.resources.pressure(&host);
+    if reasons.is_empty() {
+        return Ok(());
+    }
+    style::warn(format!(
+        "the host is saturated ({}); another session may make it thrash",
+        reasons.join(", ")
+    ));
+    if !config.resources.stop_idle {
+        return Ok(());
+    }
+    let threshold = config.resources.idle_cpu_percent();
+    let idle: Vec<runtime::Container> = runtime::session_containers(&worktrees_dir(config), false)?
+        .into_iter()
+        .filter(|c| c.name != label)
+        .filter(|c| {
+            runtime::stats(&c.id)
+                .ok()
+                .and_then(|stats| resources::parse_percent(&stats.cpu))
+                .is_some_and(|cpu| cpu < threshold)
+        })
+        .collect();
+    if idle.is_empty() {
+        style::note("no idle sessions to stop");
+        return Ok(());
+    }
+    let names: Vec<&str> = idle.iter().map(|c| c.name.as_str()).collect();
+    let names = names.join(", ");
+    if !crate::interactive() || !confirm(&format!("Stop idle sessions {}?", names))? {
+        return Ok(());
+    }
+    let ids: Vec<String> = idle.iter().map(|c| c.id.clone()).collect();
+    runtime::container_action("stop", &ids, verbose)?;
+    println!("Stopped idle sessions {}", names);
+    Ok(())
+}
+
+/// Saves a session's uncommitted work as `wip_on_detach` asks. Failing to
+/// doesn't stop the session being left, so it only warns.
+pub fn save_wip(name: &str, session_git: &wip::SessionGit, mode: wip::Mode) {
+    match session_git.save(mode) {
+        Ok(true) => println!(
+            "Saved uncommitted work in session {} as a {}; `forest open {} --restore-wip` \
+             puts it back",
+            name,
+            match mode {
+                wip::Mode::Commit => "WIP commit",
+                wip::Mode::Stash => "stash entry",
+            },
+            shell::quote(name)
+        ),
+        Ok(false) => {}
+        Err(e) => style::warn(format!(
+            "failed to save uncommitted work in session {}: {}",
+            name, e
+        )),
+    }
+}
+
+/// Refuses configurations the administrator's policy forbids, listing every
+/// violation at once.
+pub fn enforce_policy(value: &Value, devcontainer_path: &Path) -> anyhow::Result<()> {
+    let Some((policy_path, policy)) = policy::load()? else {
+        return Ok(());
+    };
+    let dir = devcontainer_path.parent().unwrap_or(Path::new("."));
+    let violations = policy.violations(value, dir);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "{} violates the policy in {}:\n  {}",
+        devcontainer_path.display(),
+        policy_path.display(),
+        violations.join("\n  ")
+    )
+}
+
+/// Scans a freshly started session's image when `[scan]` sets `on_critical`,
+/// stopping the container if critical vulnerabilities should block it.
+pub fn gate_session_image(
+    podman_name: &str,
+    container: Option<&devcontainer::UpResult>,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let Some(on_critical) = config.scan.on_critical else {
+        return Ok(());
+    };
+    let _span = telemetry::span("scan image");
+    let id = match container {
+        Some(c) => c.container_id.clone(),
+        None => runtime::containers_with_name(podman_name, false)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no running container for {}", podman_name))?,
+    };
+    let image = runtime::container_image(&id)?;
+    let report = scan::scan(scan::scanner(&config.scan)?, &image, verbose)?;
+    let critical = report.count(scan::Severity::Critical);
+    if critical == 0 {
+        if verbose {
+            println!("{}: {}", image, report.summary());
+        }
+        return Ok(());
+    }
+    report.print();
+    match on_critical {
+        scan::OnCritical::Warn => {
+            style::warn(format!(
+                "{} has {} critical vulnerabilities",
+                image, critical
+            ));
+            Ok(())
+        }
+        scan::OnCritical::Block => {
+            runtime::container_action("stop", &[id], verbose)?;
+            anyhow::bail!(
+                "{} has {} critical vulnerabilities; update the image or set \
+                 `on_critical = \"warn\"` under [scan] in forest.toml",
+                image,
+                critical
+            )
+        }
+    }
+}
+
+pub fn login_registries(
+    value: &Value,
+    devcontainer_path: &Path,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    if config.registries.is_empty() {
+        return Ok(());
+    }
+    let dir = devcontainer_path.parent().unwrap_or(Path::new("."));
+    registry::login_for_images(
+        &config.registries,
+        &policy::base_images(value, dir),
+        verbose,
+    )
+}
+
+pub fn build_image(
+    worktree_path: &Path,
+    config_path: &Path,
+    platform: Option<&str>,
+    no_cache: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let mut cmd = devcontainer::command("build");
+    cmd.arg("--workspace-folder").arg(worktree_path);
+    if let Some(platform) = platform {
+        cmd.arg("--platform").arg(platform);
+    }
+    if no_cache {
+        cmd.arg("--no-cache");
+    }
+    let output = run_output_verbose(&mut cmd, verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+        } else {
+            e.into()
+        }
+    })?;
+    if !output.status.success() {
+        return Err(devcontainer::failure(
+            "build",
+            &String::from_utf8_lossy(&output.stdout),
+            config_path,
+        ));
+    }
+    Ok(())
+}
+
+/// Fails when a container `open` would attach to or remove isn't this
+/// session's: one carrying its label that wasn't created for its worktree, or
+/// one holding its egress proxy's name. `adopted` is the container an earlier
+/// open used; `adopt` takes the others over instead.
+fn check_name_collisions(
+    name: &str,
+    podman_name: &str,
+    worktree_path: &Path,
+    adopted: Option<&str>,
+    adopt: bool,
+) -> anyhow::Result<()> {
+    // like the running check below, an engine that can't list containers has
+    // none to collide with
+    for id in runtime::containers_with_name(podman_name, true).unwrap_or_default() {
+        // podman lists short ids, devcontainer reports full ones
+        if adopted.is_some_and(|c| c.starts_with(id.as_str())) {
+            continue;
+        }
+        let Some(owner) = foreign_owner(&runtime::inspect(&id)?, worktree_path) else {
+            continue;
+        };
+        if adopt {
+            style::note(format!("adopting container {} ({})", id, owner));
+            continue;
+        }
+        anyhow::bail!(
+            "container {} carries session {}'s label name={} but belongs to {}; \
+             remove it, open the session under another name, or pass --adopt to \
+             take it over",
+            id,
+            name,
+            podman_name,
+            owner
+        );
+    }
+    if let Some(proxy) = egress::foreign_proxy(podman_name)? {
+        if adopt {
+            style::note(format!("replacing container {}", proxy));
+        } else {
+            anyhow::bail!(
+                "container {} has the name of session {}'s egress proxy, which open \
+                 replaces; rename it, or pass --adopt to let it be removed",
+                proxy,
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Who container `inspected`, found under a session's label, belongs to when
+/// it wasn't created for the session's worktree `worktree_path`.
+fn foreign_owner(inspected: &Value, worktree_path: &Path) -> Option<String> {
+    let labels = &inspected["Config"]["Labels"];
+    let folder = labels["devcontainer.local_folder"].as_str();
+    if folder.is_some_and(|folder| Path::new(folder) == worktree_path) {
+        return None;
+    }
+    Some(match (folder, labels[VERSION_LABEL].is_string()) {
+        (Some(folder), true) => format!("the forest session of {}", folder),
+        (Some(folder), false) => format!("the devcontainer of {}", folder),
+        (None, _) => "a container forest didn't create".to_string(),
+    })
+}
+
+/// Container label holding the forest version that created a session.
+pub const VERSION_LABEL: &str = "forest.version";
+
+/// `up-cache.json`: the last `UpRecord` of each session, by label.
+pub const UP_CACHE: store::Schema = store::Schema {
+    file: "up-cache.json",
+    migrations: &[up_cache_v2],
+};
+
+/// Version 2 dropped the per-record formats that preceded file schemas.
+pub fn up_cache_v2(mut records: Value) -> anyhow::Result<Value> {
+    if let Some(records) = records.as_object_mut() {
+        for record in records.values_mut().filter_map(Value::as_object_mut) {
+            record.remove("format");
+        }
+    }
+    Ok(records)
+}
+
+/// Last `devcontainer up` of a session, kept in `up-cache.json`.
+#[derive(Serialize, Deserialize)]
+pub struct UpRecord {
+    /// forest release that created the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forest_version: Option<String>,
+    /// Hash of the devcontainer.json, `up` arguments and build files
+    pub hash: String,
+    /// Hash of the Dockerfile or compose files alone, to recreate the
+    /// container once they change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_hash: Option<String>,
+    pub container: Option<devcontainer::UpResult>,
+    #[serde(default, skip_serializing_if = "Overrides::is_empty")]
+    pub overrides: Overrides,
+    /// Configuration the container was created from, overrides applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<Value>,
+}
+
+/// Whether a session created by forest `version` may not work with this one:
+/// it's from an older major release, or an older minor one before 1.0.
+pub fn incompatible_version(version: &str) -> bool {
+    let (major, minor, _) = policy::parse_version(version);
+    let (current_major, current_minor, _) = policy::parse_version(env!("CARGO_PKG_VERSION"));
+    major < current_major || (current_major == 0 && major == 0 && minor < current_minor)
+}
+
+/// The branch session `name` is expected to have checked out.
+pub fn expected_branch(name: &str, registered: Option<&sessions::Session>) -> String {
+    registered.map_or_else(|| name.to_string(), |s| s.branch.clone())
+}
+
+/// Warns when someone checked out another branch in the session worktree,
+/// which forest would otherwise keep treating as the session's branch.
+pub fn warn_branch_divergence(
+    name: &str,
+    registered: Option<&sessions::Session>,
+    repo_root: &Path,
+    worktree_path: &Path,
+) {
+    let expected = expected_branch(name, registered);
+    let Some(head) = worktree_head(repo_root, worktree_path) else {
+        return;
+    };
+    if head != expected {
+        style::warn(format!(
+            "session {} has {} checked out instead of {}; run `forest adopt-branch {}` to make \
+             the session follow it or `forest adopt-branch {} --restore` to switch back",
+            name,
+            head,
+            expected,
+            shell::quote(name),
+            shell::quote(name)
+        ));
+    }
+}
+
+/// Changes to a session's devcontainer configuration given to `open` instead
+/// of committed to the repository. They're kept with the session's `UpRecord`,
+/// so later opens and rebuilds of the session keep them.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Overrides {
+    /// Image used instead of the configuration's `image` or `build`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Devcontainer features added to the configuration's, with default options
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<egress::Mode>,
+    /// Hosts an allowlisted session may reach
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_hosts: Vec<String>,
+    /// Worktree and repository mounted read-only, on a detached checkout
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub readonly: bool,
+    /// Host directories mounted besides /repo and /code, as `path:target`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<String>,
+    /// Scheduling priority, a nice value from -20 to 19
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Platform the container runs as, such as `linux/arm64`, for matrix sessions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    /// CPUs the container may use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    /// Memory the container may use, such as `8g`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        self.image.is_none()
+            && self.features.is_empty()
+            && self.network.is_none()
+            && self.allow_hosts.is_empty()
+            && !self.readonly
+            && self.mounts.is_empty()
+            && self.priority.is_none()
+            && self.platform.is_none()
+            && self.cpus.is_none()
+            && self.memory.is_none()
+    }
+
+    /// Allowed hosts imply an allowlist unless a mode was chosen explicitly.
+    pub fn network(&self) -> egress::Mode {
+        self.network.unwrap_or(if self.allow_hosts.is_empty() {
+            egress::Mode::Open
+        } else {
+            egress::Mode::Allowlist
+        })
+    }
+
+    /// These overrides updated with the ones set in `changes`.
+    pub fn merge(self, changes: &Overrides) -> Overrides {
+        let union = |mut items: Vec<String>, more: &[String]| {
+            for item in more {
+                if !items.contains(item) {
+                    items.push(item.clone());
+                }
+            }
+            items
+        };
+        Overrides {
+            image: changes.image.clone().or(self.image),
+            features: union(self.features, &changes.features),
+            network: changes.network.or(self.network),
+            allow_hosts: union(self.allow_hosts, &changes.allow_hosts),
+            readonly: changes.readonly || self.readonly,
+            mounts: union(self.mounts, &changes.mounts),
+            priority: changes.priority.or(self.priority),
+            platform: changes.platform.clone().or(self.platform),
+            cpus: changes.cpus.or(self.cpus),
+            memory: changes.memory.clone().or(self.memory),
+        }
+    }
+
+    /// The configuration of the session labelled `label` with these overrides.
+    pub fn apply(&self, value: &Value, label: &str) -> Value {
+        let value = match &self.image {
+            Some(image) => devcontainer::with_image(value, image),
+            None => value.clone(),
+        };
+        let value = devcontainer::with_features(&value, &self.features);
+        let value = priority::apply(&value, self.priority);
+        let value = resources::apply(&value, self.cpus, self.memory.as_deref());
+        let value = match &self.platform {
+            Some(platform) => devcontainer::with_platform(&value, platform),
+            None => value,
+        };
+        egress::apply(&value, self.network(), label)
+    }
+}
+
+/// Fails for overrides a Docker Compose configuration can't take: its
+/// container comes from a compose service, so there's no image to replace, and
+/// the compose files own its networks. Mounts and the session label still
+/// reach it through `devcontainer up`.
+fn check_compose_overrides(value: &Value, overrides: &Overrides) -> anyhow::Result<()> {
+    if value.get("service").is_none() {
+        anyhow::bail!("devcontainer.json sets dockerComposeFile but no service to open");
+    }
+    if overrides.image.is_some() {
+        anyhow::bail!(
+            "--container-image can't replace a Docker Compose service; \
+             set the image in the compose file, or drop it with --reset-overrides"
+        );
+    }
+    if overrides.network() != egress::Mode::Open {
+        anyhow::bail!(
+            "--network needs an image or build configuration; a Docker Compose one \
+             sets its networks in its compose files"
+        );
+    }
+    if overrides.platform.is_some() {
+        anyhow::bail!(
+            "matrix sessions need an image or build configuration; set a Docker \
+             Compose service's platform in its compose file"
+        );
+    }
+    Ok(())
+}
+
+/// Where an `--also-mount` or `extra_mounts` entry, `path[:target]`, goes: a
+/// host directory, `~/` or relative to `base`, and an absolute container path,
+/// `/mounts/<directory name>` unless given.
+pub fn parse_mount(spec: &str, base: &Path) -> anyhow::Result<(PathBuf, String)> {
+    let (path, target) = match spec.rsplit_once(':') {
+        Some((path, target)) if target.starts_with('/') => (path, Some(target)),
+        _ => (spec, None),
+    };
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => Path::new(&std::env::var("HOME").unwrap_or_default()).join(rest),
+        None => base.join(path),
+    };
+    let source = fs::canonicalize(&path)
+        .map_err(|e| anyhow::anyhow!("can't mount {}: {}", path.display(), e))?;
+    let target = match target {
+        Some(target) => target.trim_end_matches('/').to_string(),
+        None => format!(
+            "/mounts/{}",
+            source.file_name().unwrap_or_default().to_string_lossy()
+        ),
+    };
+    if target.is_empty()
+        || ["/repo", "/code"]
+            .iter()
+            .any(|t| Path::new(&target).starts_with(t))
+    {
+        anyhow::bail!(
+            "can't mount {} at {}: forest uses it",
+            source.display(),
+            target
+        );
+    }
+    Ok((source, target))
+}
+
+/// Fingerprint of the inputs to `devcontainer up`, the same across forest
+/// builds and Rust releases so cached records stay valid.
+pub fn config_hash(parts: &[&str]) -> String {
+    let hash = parts.iter().fold(names::fnv1a(&[]), |hash, part| {
+        // the length keeps ["ab", "c"] apart from ["a", "bc"]
+        let hash = names::fnv1a_extend(hash, &(part.len() as u64).to_le_bytes());
+        names::fnv1a_extend(hash, part.as_bytes())
+    });
+    format!("{:016x}", hash)
+}
+
+/// The key `overlay_dir` used before `config_hash` was stable, from Rust's
+/// `DefaultHasher`; only for finding directories made then.
+fn legacy_config_hash(parts: &[&str]) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    parts.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// What `kill` does besides stopping or removing the container.
+#[derive(Default)]
+pub struct KillOptions {
+    /// Push the session branch's unpushed commits first
+    pub push: bool,
+    /// Go ahead even though the worktree has uncommitted changes
+    pub force: bool,
+    /// Stash the worktree's uncommitted changes first
+    pub stash: bool,
+}
+
+pub fn kill_session(
+    name: &str,
+    tier: KillTier,
+    options: &KillOptions,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+    let hooked = config.hooks.pre_kill.is_some() || config.hooks.post_kill.is_some();
+    let push = options.push || config.push_on_kill;
+    // stopping leaves the container and worktree as they are
+    let guarded = push || options.stash || tier != KillTier::Stop;
+    let registered = if hooked || guarded {
+        sessions::load(&data_dir()?)?.remove(&podman_name)
+    } else {
+        None
+    };
+    let paths = if hooked || guarded {
+        session_paths(name, registered.as_ref(), "kill", config).ok()
+    } else {
+        None
+    };
+    // a read-only session has no changes or commits of its own
+    if guarded && !registered.as_ref().is_some_and(|s| s.readonly) {
+        if let Some((repo_root, _, worktree_path)) = &paths {
+            guard_changes(name, repo_root, worktree_path, options, config, verbose)?;
+        }
+        if push {
+            let Some((repo_root, _, worktree_path)) = &paths else {
+                anyhow::bail!("can't find session {}'s worktree to push from", name);
+            };
+            push_before_kill(
+                &podman_name,
+                &expected_branch(name, registered.as_ref()),
+                repo_root,
+                worktree_path,
+                config,
+                verbose,
+            )?;
+        }
+    }
+    let hook_context = hooks::Context {
+        session: name,
+        label: &podman_name,
+        repo: paths.as_ref().map(|(repo_root, _, _)| repo_root.as_path()),
+        worktree: paths.as_ref().map(|(_, _, worktree)| worktree.as_path()),
+    };
+    hooks::run(
+        "pre_kill",
+        config.hooks.pre_kill.as_deref(),
+        &hook_context,
+        verbose,
+    )?;
+    kill_tier(name, &podman_name, tier, config, verbose)?;
+    if let Err(e) = hooks::run(
+        "post_kill",
+        config.hooks.post_kill.as_deref(),
+        &hook_context,
+        verbose,
+    ) {
+        style::warn(e.to_string());
+    }
+    Ok(())
+}
+
+/// Lets `kill` go ahead over uncommitted changes in the session's worktree
+/// only when they're stashed first, `force` is given or the user agrees when
+/// asked.
+fn guard_changes(
+    name: &str,
+    repo_root: &Path,
+    worktree_path: &Path,
+    options: &KillOptions,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let changes = worktree_changes(repo_root, worktree_path)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    if options.stash {
+        return stash_changes(name, repo_root, worktree_path, config, verbose);
+    }
+    let message = format!("session {} has {} uncommitted changes", name, changes.len());
+    if options.force {
+        style::warn(message);
+        return Ok(());
+    }
+    if crate::interactive() && confirm(&format!("{}. Kill it anyway?", message))? {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "{}; commit them, kill with --stash to stash them first, or with --force \
+         to leave them in the worktree",
+        message
+    )
+}
+
+/// Stashes the worktree's changes, untracked files included, where every
+/// worktree of the repository can `git stash pop` them.
+fn stash_changes(
+    name: &str,
+    repo_root: &Path,
+    worktree_path: &Path,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let Some(admin) = worktree_admin_dir(repo_root, worktree_path) else {
+        anyhow::bail!(
+            "{} isn't a worktree of {}",
+            worktree_path.display(),
+            repo_root.display()
+        );
+    };
+    let message = format!("forest kill {}", name);
+    // the stash is the repository's, shared by every worktree
+    let _lock = locks::RepoLock::acquire(&repo_root.join(".git"), config.lock_timeout(), verbose)?;
+    let mut cmd = Command::new("git");
+    cmd.env("GIT_DIR", &admin)
+        .env("GIT_WORK_TREE", worktree_path)
+        .current_dir(worktree_path)
+        .args([
+            "stash",
+            "push",
+            "--include-untracked",
+            "--message",
+            &message,
+        ]);
+    if !verbose {
+        cmd.stdout(Stdio::null());
+    }
+    if !run_command_verbose(&mut cmd, verbose)?.success() {
+        anyhow::bail!("failed to stash the changes of session {}", name);
+    }
+    println!("Stashed the changes of session {} as \"{}\"", name, message);
+    Ok(())
+}
+
+/// Pushes the session branch's unpushed commits before the session is killed.
+fn push_before_kill(
+    podman_name: &str,
+    branch: &str,
+    repo_root: &Path,
+    worktree_path: &Path,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let unpushed = unpushed_commits(repo_root, branch, config.remotes.push())?;
+    if unpushed == 0 {
+        if verbose {
+            println!("{} has nothing to push", branch);
+        }
+        return Ok(());
+    }
+    push_session_branch(
+        podman_name,
+        branch,
+        repo_root,
+        worktree_path,
+        config,
+        verbose,
+    )
+}
+
+/// Pushes session branch `branch` to the push remote, `origin` unless
+/// `[remotes]` says otherwise. The remote's default branch is refused.
+pub fn push_session_branch(
+    podman_name: &str,
+    branch: &str,
+    repo_root: &Path,
+    worktree_path: &Path,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let remote = config.remotes.push();
+    if is_default_branch(repo_root, remote, branch) {
+        anyhow::bail!(
+            "won't push {} to {}: it's the default branch, which takes changes \
+             through pull requests",
+            branch,
+            remote
+        );
+    }
+    println!("Pushing {}", branch);
+    let push = ["push", "--set-upstream", remote, branch];
+    let status = session_git(
+        podman_name,
+        repo_root,
+        worktree_path,
+        &push,
+        config,
+        verbose,
+    )?;
+    if !status.success() {
+        anyhow::bail!("failed to push {} to {}", branch, remote);
+    }
+    Ok(())
+}
+
+/// Runs git on a session's worktree, from inside the session's container
+/// when it's running, since that holds the credentials the session works
+/// with, and from the host otherwise.
+fn session_git(
+    podman_name: &str,
+    repo_root: &Path,
+    worktree_path: &Path,
+    args: &[&str],
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<std::process::ExitStatus> {
+    let mut cmd = match runtime::containers_with_name(podman_name, false)?.first() {
+        Some(id) => {
+            let mut cmd = devcontainer::command("exec");
+            cmd.arg("--workspace-folder")
+                .arg(worktree_path)
+                .args(devcontainer::target_args(podman_name, Some(id)));
+            let protocol = worktree_protocol(config.remote_protocol, repo_root, worktree_path);
+            let mut token = secrets::Resolved::default();
+            if let Some(value) = github_token(protocol) {
+                token.add("GH_TOKEN", value);
+            }
+            token.pass_to(&mut cmd);
+            cmd.args(["git", "-C", "/code"]);
+            cmd
+        }
+        None => {
+            let mut cmd = Command::new("git");
+            match worktree_admin_dir(repo_root, worktree_path) {
+                Some(admin) => cmd
+                    .env("GIT_DIR", admin)
+                    .env("GIT_WORK_TREE", worktree_path)
+                    .current_dir(worktree_path),
+                None => cmd.current_dir(repo_root),
+            };
+            cmd
+        }
+    };
+    cmd.args(args);
+    Ok(run_command_verbose(&mut cmd, verbose)?)
+}
+
+/// Fetches the base remote and rebases session `name`'s branch onto its
+/// default branch. A conflicting rebase is left for the session to finish.
+pub fn sync_session(name: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    let registered = sessions::load(&data_dir()?)?.remove(&podman_name);
+    if registered.as_ref().is_some_and(|s| s.readonly) {
+        anyhow::bail!(
+            "session {} is read-only; reopening it checks out the branch's latest commit",
+            name
+        );
+    }
+    let (repo_root, _, worktree_path) = session_paths(name, registered.as_ref(), "sync", config)?;
+    if !worktree_path.exists() {
+        anyhow::bail!("no worktree for session {}; open it first", name);
+    }
+    let remote = config.remotes.base();
+    let fetch = ["fetch", "--quiet", remote];
+    if !session_git(
+        &podman_name,
+        &repo_root,
+        &worktree_path,
+        &fetch,
+        config,
+        verbose,
+    )?
+    .success()
+    {
+        anyhow::bail!("git fetch {} failed", remote);
+    }
+    let onto = remote_default_branch(&repo_root, remote)?;
+    println!(
+        "Rebasing {} onto {}",
+        expected_branch(name, registered.as_ref()),
+        onto
+    );
+    let rebase = ["rebase", onto.as_str()];
+    if !session_git(
+        &podman_name,
+        &repo_root,
+        &worktree_path,
+        &rebase,
+        config,
+        verbose,
+    )?
+    .success()
+    {
+        anyhow::bail!(
+            "rebasing onto {} stopped; resolve it in the session and run `git rebase --continue`, or `git rebase --abort`",
+            onto
+        );
+    }
+    Ok(())
+}
+
+/// Stops or removes the session's container, and its worktree with `Purge`.
+pub fn kill_tier(
+    name: &str,
+    podman_name: &str,
+    tier: KillTier,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    if tier == KillTier::Stop {
+        let _span = telemetry::span("stop container");
+        let ids = runtime::containers_with_name(podman_name, false)?;
+        if ids.is_empty() {
+            if sessions::load(&data_dir()?)?.contains_key(podman_name) {
+                println!("Session {} is already stopped", name);
+                return Ok(());
+            }
+            anyhow::bail!("no running container for session {}", name);
+        }
+        if let Some(mode) = config.wip_on_detach {
+            let registered = sessions::load(&data_dir()?)?.remove(podman_name);
+            match session_paths(name, registered.as_ref(), "kill", config) {
+                Ok((_, _, worktree_path)) => {
+                    let target = devcontainer::target_args(podman_name, Some(&ids[0]));
+                    let session_git = wip::SessionGit {
+                        worktree_path: &worktree_path,
+                        target: &target,
+                        verbose,
+                    };
+                    save_wip(name, &session_git, mode);
+                }
+                Err(e) => style::warn(format!("not saving uncommitted work: {}", e)),
+            }
+        }
+        runtime::container_action("stop", &ids, verbose)?;
+        if let Err(e) = egress::stop_proxy(podman_name, verbose) {
+            style::warn(format!("failed to stop the egress proxy: {}", e));
+        }
+        println!("Stopped session {}", name);
+        return Ok(());
+    }
+    let down = telemetry::span("devcontainer down");
+    let mut cmd = devcontainer::command("down");
+    cmd.arg("--id-label").arg(format!("name={}", podman_name));
+    let status = run_command_verbose(&mut cmd, verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+        } else {
+            e.into()
+        }
+    })?;
+    if !status.success() {
+        anyhow::bail!("devcontainer down failed");
+    }
+    drop(down);
+    let data_dir = data_dir()?;
+    let registered = sessions::load(&data_dir)?.remove(podman_name);
+    sessions::remove(&data_dir, podman_name)?;
+    checkpoint::forget(&data_dir, podman_name)?;
+    if let Err(e) = helpers::remove(&data_dir, podman_name) {
+        style::warn(format!("failed to remove the shell helpers: {}", e));
+    }
+    if let Err(e) = egress::remove(podman_name, verbose) {
+        style::warn(format!("failed to remove the egress proxy: {}", e));
+    }
+    println!("Killed session {}", name);
+    if tier == KillTier::Purge {
+        let _span = telemetry::span("purge worktree");
+        purge_worktree(name, registered.as_ref(), config, verbose)?;
+    }
+    Ok(())
+}
+
+/// Repository root, repository name and worktree of session `name`.
+/// Registered sessions are found from anywhere; others from inside their repo.
+pub fn session_paths(
+    name: &str,
+    registered: Option<&sessions::Session>,
+    command: &str,
+    config: &Config,
+) -> anyhow::Result<(PathBuf, String, PathBuf)> {
+    match registered {
+        Some(session) => {
+            let repo_name = session
+                .repo
+                .file_name()
+                .ok_or_else(|| anyhow::anyhow!("failed to determine repo name"))?
+                .to_string_lossy()
+                .to_string();
+            Ok((session.repo.clone(), repo_name, session.worktree.clone()))
+        }
+        None => {
+            let (repo_root, repo_name) = current_repo(command)?;
+            let worktree_path = worktree_path(config, &repo_name, name);
+            Ok((repo_root, repo_name, worktree_path))
+        }
+    }
+}
+
+/// Fails when `worktree`, below repository directory `repo_dir`, would sit
+/// inside another session's worktree or hold some, as the nested layout has
+/// `feat` and `feat/cool` do.
+fn check_nesting(repo_dir: &Path, worktree: &Path) -> anyhow::Result<()> {
+    let outer = worktree
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(repo_dir) && *dir != repo_dir)
+        .find(|dir| dir.join(".git").exists());
+    if let Some(outer) = outer {
+        anyhow::bail!(
+            "{} would be inside the worktree {}; pick another session name or set \
+             worktree_layout = \"flat\"",
+            worktree.display(),
+            outer.display()
+        );
+    }
+    if !worktree.join(".git").exists() {
+        if let Some(inner) = prune::find_worktrees(worktree).first() {
+            anyhow::bail!(
+                "{} holds the worktree {}; pick another session name or set \
+                 worktree_layout = \"flat\"",
+                worktree.display(),
+                inner.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Removes the directories between `worktree` and `stop` that its removal
+/// left empty, such as `feat` once `feat/cool` is gone.
+pub fn remove_empty_parents(worktree: &Path, stop: &Path) {
+    for dir in worktree.ancestors().skip(1) {
+        if !dir.starts_with(stop) || dir == stop || fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Moves the session's worktree and git's admin directory for it into the trash.
+pub fn purge_worktree(
+    name: &str,
+    registered: Option<&sessions::Session>,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let (repo_root, repo_name, worktree_path) =
+        session_paths(name, registered, "kill --purge", config)?;
+    if !worktree_path.exists() {
+        println!("No worktree at {}", worktree_path.display());
+        return Ok(());
+    }
+
+    let admin = worktree_admin_dir(&repo_root, &worktree_path);
+    let git_dir = repo_root.join(".git");
+    let _lock = locks::RepoLock::acquire(&git_dir, config.lock_timeout(), verbose)?;
+    let data_dir = data_dir()?;
+    let id = trash::put(
+        &data_dir,
+        name,
+        &repo_name,
+        &worktree_path,
+        admin.as_deref(),
+    )?;
+    remove_empty_parents(&worktree_path, &worktrees_dir(config));
+    println!(
+        "Moved {} to the trash as {} (restore with `forest trash restore {}`)",
+        worktree_path.display(),
+        id,
+        id
+    );
+    let expired = trash::empty(&data_dir, Some(config.trash_retention_secs()))?;
+    if verbose && expired > 0 {
+        println!("Emptied {} expired trash entries", expired);
+    }
+    Ok(())
+}
+
+/// The steps of `rename_session` that can be taken back, in order.
+#[derive(Clone, Copy, Debug)]
+enum RenameStep {
+    Stop,
+    Branch,
+    Worktree,
+    Record,
+    UpRecord,
+    Snapshots,
+}
+
+const RENAME_STEPS: [RenameStep; 6] = [
+    RenameStep::Stop,
+    RenameStep::Branch,
+    RenameStep::Worktree,
+    RenameStep::Record,
+    RenameStep::UpRecord,
+    RenameStep::Snapshots,
+];
+
+/// What `rename_session` works with.
+struct Rename<'a> {
+    new: &'a str,
+    label: String,
+    new_label: String,
+    session: sessions::Session,
+    worktree: PathBuf,
+    /// Containers of the session that were running
+    running: Vec<String>,
+    data_dir: PathBuf,
+    config: &'a Config,
+    verbose: bool,
+}
+
+impl Rename<'_> {
+    fn move_branch(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        let repo = &self.session.repo;
+        let _lock =
+            locks::RepoLock::acquire(&repo.join(".git"), self.config.lock_timeout(), self.verbose)?;
+        let mut cmd = Command::new("git");
+        cmd.args(["branch", "--move", from, to]).current_dir(repo);
+        if !run_command_verbose(&mut cmd, self.verbose)?.success() {
+            anyhow::bail!("git branch --move {} {} failed", from, to);
+        }
+        Ok(())
+    }
+
+    fn move_worktree(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from, to).map_err(|e| {
+            anyhow::anyhow!("can't move {} to {}: {}", from.display(), to.display(), e)
+        })
+    }
+
+    fn move_up_record(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        store::update(
+            &self.data_dir,
+            &UP_CACHE,
+            |up_cache: &mut BTreeMap<String, UpRecord>| {
+                if let Some(record) = up_cache.remove(from) {
+                    up_cache.insert(to.to_string(), record);
+                }
+                Ok(())
+            },
+        )
+    }
+
+    fn apply(&self, step: RenameStep) -> anyhow::Result<()> {
+        match step {
+            RenameStep::Stop => runtime::container_action("stop", &self.running, self.verbose),
+            RenameStep::Branch => self.move_branch(&self.session.branch, self.new),
+            RenameStep::Worktree => self.move_worktree(&self.session.worktree, &self.worktree),
+            RenameStep::Record => {
+                let renamed = sessions::Session {
+                    branch: self.new.to_string(),
+                    worktree: self.worktree.clone(),
+                    ..self.session.clone()
+                };
+                sessions::rename(&self.data_dir, &self.label, &self.new_label, renamed)
+            }
+            RenameStep::UpRecord => self.move_up_record(&self.label, &self.new_label),
+            RenameStep::Snapshots => {
+                snapshots::rename(&self.data_dir, &self.label, &self.new_label)
+            }
+        }
+    }
+
+    fn undo(&self, step: RenameStep) -> anyhow::Result<()> {
+        match step {
+            RenameStep::Stop => runtime::container_action("start", &self.running, self.verbose),
+            RenameStep::Branch => self.move_branch(self.new, &self.session.branch),
+            RenameStep::Worktree => self.move_worktree(&self.worktree, &self.session.worktree),
+            RenameStep::Record => sessions::rename(
+                &self.data_dir,
+                &self.new_label,
+                &self.label,
+                self.session.clone(),
+            ),
+            RenameStep::UpRecord => self.move_up_record(&self.new_label, &self.label),
+            RenameStep::Snapshots => {
+                snapshots::rename(&self.data_dir, &self.new_label, &self.label)
+            }
+        }
+    }
+}
+
+/// Renames session `old` to `new`: its branch, its worktree directory, its
+/// records and its container, whose label and mounts are fixed when it's
+/// created, so it's removed and, when it was running, created again. A
+/// failure before the container goes undoes what was done. Git's record of
+/// the worktree names container paths, which don't change.
+pub fn rename_session(old: &str, new: &str, config: &Config, verbose: bool) -> anyhow::Result<()> {
+    let label = session_label(old);
+    let new_label = session_label(new);
+    for (name, label) in [(old, &label), (new, &new_label)] {
+        if !valid_podman_name(label) {
+            anyhow::bail!("invalid session name: {}", name);
+        }
+    }
+    let data_dir = data_dir()?;
+    let registry = sessions::load(&data_dir)?;
+    let Some(session) = registry.get(&label).cloned() else {
+        anyhow::bail!(
+            "no record of session {}; only sessions `open` created can be renamed",
+            old
+        );
+    };
+    if session.readonly {
+        anyhow::bail!(
+            "session {} is read-only; its branch isn't its own to rename",
+            old
+        );
+    }
+    if registry.contains_key(&new_label)
+        || !runtime::containers_with_name(&new_label, true)?.is_empty()
+    {
+        anyhow::bail!("there's already a session {}", new);
+    }
+    let repo_name = session
+        .repo
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("failed to determine repo name"))?
+        .to_string_lossy()
+        .to_string();
+    let worktree = worktree_path(config, &repo_name, new);
+    if worktree.exists() {
+        anyhow::bail!("{} already exists", worktree.display());
+    }
+    check_nesting(&worktrees_dir(config).join(&repo_name), &worktree)?;
+    if is_branch(&session.repo, new) {
+        anyhow::bail!(
+            "there's already a branch {} in {}",
+            new,
+            session.repo.display()
+        );
+    }
+    let containers = runtime::containers_with_name(&label, true)?;
+    let rename = Rename {
+        new,
+        label,
+        new_label,
+        running: runtime::containers_with_name(&session_label(old), false)?,
+        session,
+        worktree,
+        data_dir,
+        config,
+        verbose,
+    };
+
+    for (done, step) in RENAME_STEPS.iter().enumerate() {
+        if let Err(e) = rename.apply(*step) {
+            for step in RENAME_STEPS[..done].iter().rev() {
+                if let Err(undo_error) = rename.undo(*step) {
+                    style::warn(format!("undoing the rename failed: {}", undo_error));
+                }
+            }
+            return Err(e.context(format!("renaming session {} to {}", old, new)));
+        }
+    }
+
+    // past here there's no going back
+    runtime::container_action("rm", &containers, verbose)?;
+    egress::remove(&rename.label, verbose)?;
+    checkpoint::forget(&rename.data_dir, &rename.label)?;
+    let _ = helpers::remove(&rename.data_dir, &rename.label);
+    remove_empty_parents(&rename.session.worktree, &worktrees_dir(config));
+    println!("Renamed session {} to {}", old, new);
+    if !rename.running.is_empty() {
+        std::env::set_current_dir(&rename.session.repo)?;
+        let options = OpenOptions {
+            detach: true,
+            ..OpenOptions::default()
+        };
+        let dev_env = rename.session.devcontainer_env.as_deref();
+        if let Err(e) = open_session(
+            new,
+            dev_env,
+            &Overrides::default(),
+            &options,
+            config,
+            verbose,
+        ) {
+            style::warn(format!(
+                "starting {} again failed: {}; start it with `forest open {}`",
+                new,
+                e,
+                shell::quote(new)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Deletes session `name` for good: its container, its worktree and git's
+/// record of it, and its branch. Host git can't `worktree remove` a worktree
+/// it knows by its container path, so the directories are removed directly.
+pub fn remove_session(
+    name: &str,
+    keep_branch: bool,
+    keep_worktree: bool,
+    config: &Config,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let podman_name = session_label(name);
+    if !valid_podman_name(&podman_name) {
+        anyhow::bail!("invalid session name: {}", name);
+    }
+    let data_dir = data_dir()?;
+    let registered = sessions::load(&data_dir)?.remove(&podman_name);
+    let (repo_root, _, worktree_path) = session_paths(name, registered.as_ref(), "rm", config)?;
+
+    if runtime::containers_with_name(&podman_name, true)?.is_empty() {
+        sessions::remove(&data_dir, &podman_name)?;
+        checkpoint::forget(&data_dir, &podman_name)?;
+    } else {
+        // the worktree goes too, as `rm` means it to
+        kill_session(
+            name,
+            KillTier::Down,
+            &KillOptions {
+                force: true,
+                ..KillOptions::default()
+            },
+            config,
+            verbose,
+        )?;
+    }
+
+    let git_dir = repo_root.join(".git");
+    let _lock = locks::RepoLock::acquire(&git_dir, config.lock_timeout(), verbose)?;
+    if !keep_worktree && worktree_path.exists() {
+        if let Some(admin) = worktree_admin_dir(&repo_root, &worktree_path) {
+            fs::remove_dir_all(admin)?;
+        }
+        fs::remove_dir_all(&worktree_path)?;
+        remove_empty_parents(&worktree_path, &worktrees_dir(config));
+        println!("Removed worktree {}", worktree_path.display());
+    }
+    // a read-only session only borrowed its branch
+    if !keep_branch && !registered.is_some_and(|s| s.readonly) {
+        let mut cmd = Command::new("git");
+        cmd.args(["branch", "--delete", "--quiet", name])
+            .current_dir(&repo_root)
+            .stderr(Stdio::null());
+        let deleted = run_command_verbose(&mut cmd, verbose)?.success() || {
+            // merged into the default branch, though not into what's checked out
+            let merged = merged_into_default(&repo_root, config.remotes.base(), name);
+            let mut cmd = Command::new("git");
+            cmd.args(["branch", "--delete", "--force", "--quiet", name])
+                .current_dir(&repo_root)
+                .stderr(Stdio::null());
+            merged && run_command_verbose(&mut cmd, verbose)?.success()
+        };
+        if deleted {
+            println!("Deleted branch {}", name);
+        } else {
+            style::warn(format!(
+                "kept branch {}: it isn't merged or is still checked out; \
+                 delete it with `git branch -D {}`",
+                name,
+                shell::quote(name)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use tempfile::tempdir;
+
+    #[test]
+    fn scaffold_created_when_missing() {
+        let dir = tempdir().unwrap();
+        let orig = env::current_dir().unwrap();
+        env::set_current_dir(&dir).unwrap();
+
+        crate::set_non_interactive(true);
+        let refused = find_devcontainer(None, false, &Config::default()).unwrap_err();
+        assert!(refused.to_string().contains("--scaffold"));
+        assert!(!dir.path().join(".devcontainer").exists());
+        let path = find_devcontainer(None, true, &Config::default()).unwrap();
+        assert!(path.exists());
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("ubuntu"));
+
+        env::set_current_dir(orig).unwrap();
+    }
+
+    #[test]
+    fn guards_uncommitted_changes_on_kill() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("widget");
+        let worktree = dir.path().join("feat");
+        let git = |args: &[&str]| {
+            assert!(Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .stdout(Stdio::null())
+                .status()
+                .unwrap()
+                .success());
+        };
+        fs::create_dir(&repo).unwrap();
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        git(&["config", "user.name", "forest"]);
+        git(&["config", "user.email", "forest@localhost"]);
+        git(&["commit", "--quiet", "--allow-empty", "-m", "init"]);
+        git(&[
+            "worktree",
+            "add",
+            "--quiet",
+            "-b",
+            "feat",
+            worktree.to_str().unwrap(),
+        ]);
+        // the worktree as a session's container added it
+        fs::write(worktree.join(".git"), "gitdir: /repo/.git/worktrees/feat\n").unwrap();
+        fs::write(worktree.join("notes"), "draft").unwrap();
+
+        crate::set_non_interactive(true);
+        let refused = guard_changes(
+            "feat",
+            &repo,
+            &worktree,
+            &KillOptions::default(),
+            &Config::default(),
+            false,
+        )
+        .unwrap_err();
+        assert!(refused.to_string().contains("1 uncommitted changes"));
+        let force = KillOptions {
+            force: true,
+            ..KillOptions::default()
+        };
+        guard_changes("feat", &repo, &worktree, &force, &Config::default(), false).unwrap();
+        assert!(worktree.join("notes").exists());
+        let stash = KillOptions {
+            stash: true,
+            ..KillOptions::default()
+        };
+        guard_changes("feat", &repo, &worktree, &stash, &Config::default(), false).unwrap();
+        assert!(!worktree.join("notes").exists());
+        assert!(worktree_changes(&repo, &worktree).unwrap().is_empty());
+    }
+
+    #[test]
+    fn tells_whose_container_a_label_is_on() {
+        let worktree = Path::new("/w/widget/feat");
+        let container = |labels: Value| serde_json::json!({"Config": {"Labels": labels}});
+        let own = container(serde_json::json!({"devcontainer.local_folder": "/w/widget/feat"}));
+        assert_eq!(foreign_owner(&own, worktree), None);
+        let other = container(serde_json::json!({
+            "devcontainer.local_folder": "/w/gadget/feat",
+            "forest.version": "0.1.0",
+        }));
+        assert_eq!(
+            foreign_owner(&other, worktree).as_deref(),
+            Some("the forest session of /w/gadget/feat")
+        );
+        let foreign = container(serde_json::json!({"name": "feat"}));
+        assert_eq!(
+            foreign_owner(&foreign, worktree).as_deref(),
+            Some("a container forest didn't create")
+        );
+    }
+
+    #[test]
+    fn parses_extra_mounts() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("lib")).unwrap();
+        let lib = fs::canonicalize(dir.path().join("lib")).unwrap();
+        assert_eq!(
+            parse_mount("lib", dir.path()).unwrap(),
+            (lib.clone(), "/mounts/lib".to_string())
+        );
+        let absolute = format!("{}:/work/lib/", lib.display());
+        assert_eq!(
+            parse_mount(&absolute, Path::new("/elsewhere")).unwrap(),
+            (lib, "/work/lib".to_string())
+        );
+        assert!(parse_mount("lib:/code/lib", dir.path()).is_err());
+        assert!(parse_mount("lib:/", dir.path()).is_err());
+        assert!(parse_mount("missing", dir.path()).is_err());
+    }
+
+    #[test]
+    fn keeps_worktree_hierarchies_apart() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("worktrees");
+        let repo_dir = root.join("widget");
+        let deep = repo_dir.join("feat/ui/deep/x");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join(".git"), "gitdir: /repo/.git/worktrees/x\n").unwrap();
+        check_nesting(&repo_dir, &deep).unwrap();
+        check_nesting(&repo_dir, &repo_dir.join("feat/ui/other")).unwrap();
+        // `feat/ui` would hold `feat/ui/deep/x`, and `feat/ui/deep/x/y` sit in it
+        assert!(check_nesting(&repo_dir, &repo_dir.join("feat/ui")).is_err());
+        assert!(check_nesting(&repo_dir, &deep.join("y")).is_err());
+        let flat = repo_dir.join(crate::config::WorktreeLayout::Flat.dir("feat/ui"));
+        check_nesting(&repo_dir, &flat).unwrap();
+
+        let sibling = repo_dir.join("feat/api");
+        fs::create_dir_all(&sibling).unwrap();
+        fs::remove_dir_all(&deep).unwrap();
+        remove_empty_parents(&deep, &root);
+        assert!(!repo_dir.join("feat/ui").exists());
+        assert!(sibling.exists());
+        fs::remove_dir_all(&sibling).unwrap();
+        remove_empty_parents(&sibling, &root);
+        assert!(!repo_dir.exists());
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn hashes_configurations_stably() {
+        // a fixed value, so cached records survive new forest and Rust releases
+        assert_eq!(config_hash(&["{}", "up"]), "9952845eb863f9ac");
+        assert_ne!(config_hash(&["ab", "c"]), config_hash(&["a", "bc"]));
+    }
+
+    #[test]
+    fn upgrades_up_cache_records() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("up-cache.json"),
+            r#"{"feat": {"format": 2, "forest_version": "0.1.0", "hash": "abc", "container": null}}"#,
+        )
+        .unwrap();
+        let records: BTreeMap<String, UpRecord> = store::load(dir.path(), &UP_CACHE).unwrap();
+        assert_eq!(records["feat"].forest_version.as_deref(), Some("0.1.0"));
+        let stored = fs::read_to_string(dir.path().join("up-cache.json")).unwrap();
+        assert!(!stored.contains("\"format\""));
+        assert!(dir.path().join("up-cache.json.v1.bak").exists());
+        assert!(incompatible_version("0.0.1"));
+        assert!(!incompatible_version(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn overrides_persist_until_replaced() {
+        let recorded = Overrides {
+            image: Some("rust:nightly".to_string()),
+            features: vec!["ghcr.io/devcontainers/features/go:1".to_string()],
+            ..Overrides::default()
+        };
+        // a later open without flags keeps the recorded image
+        let kept = recorded.clone().merge(&Overrides::default());
+        assert!(kept == recorded);
+        let value: Value =
+            serde_json::from_str(r#"{"build": {"dockerfile": "Dockerfile"}, "remoteUser": "dev"}"#)
+                .unwrap();
+        let applied = kept.apply(&value, "s");
+        assert_eq!(applied["image"], "rust:nightly");
+        assert!(applied.get("build").is_none());
+        assert_eq!(applied["remoteUser"], "dev");
+
+        assert_eq!(
+            applied["features"]["ghcr.io/devcontainers/features/go:1"],
+            serde_json::json!({})
+        );
+
+        let replaced = kept.merge(&Overrides {
+            image: Some("rust:1.80".to_string()),
+            features: vec![
+                "ghcr.io/devcontainers/features/go:1".to_string(),
+                "ghcr.io/devcontainers/features/node:1".to_string(),
+            ],
+            ..Overrides::default()
+        });
+        assert_eq!(replaced.image.as_deref(), Some("rust:1.80"));
+        assert_eq!(replaced.features.len(), 2);
+        assert!(Overrides::default().apply(&value, "s") == value);
+
+        let restricted = replaced.merge(&Overrides {
+            allow_hosts: vec!["crates.io".to_string()],
+            ..Overrides::default()
+        });
+        assert_eq!(restricted.network(), egress::Mode::Allowlist);
+        assert_eq!(restricted.image.as_deref(), Some("rust:1.80"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::WorktreeLayout;
+use crate::store::{self, Schema};
+
+/// `sessions.json`: every session `open` created and `kill --down` or
+/// `--purge` hasn't removed, by label.
+pub const SESSIONS: Schema = Schema {
+    file: "sessions.json",
+    migrations: &[],
+};
+
+/// `repos.json`: what forest learned about the repositories it opens
+/// sessions of, by root.
+pub const REPOS: Schema = Schema {
+    file: "repos.json",
+    migrations: &[],
+};
+
+/// What forest knows about a repository that's slow to find out.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub struct Repo {
+    /// Default branch of each remote, as its host reported it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default_branches: BTreeMap<String, String>,
+}
+
+/// What forest knows about a session without asking the container runtime.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Session {
+    /// Branch the session works on: its name, unless it adopted another
+    pub branch: String,
+    /// Root of the repository the session's worktree belongs to
+    pub repo: PathBuf,
+    pub worktree: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub devcontainer_env: Option<String>,
+    pub created_at: u64,
+    /// Opened with `--readonly`: the branch is checked out detached and
+    /// belongs to someone else, so removing the session leaves it alone
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub readonly: bool,
+}
+
+pub fn load(data_dir: &Path) -> anyhow::Result<BTreeMap<String, Session>> {
+    store::load(data_dir, &SESSIONS)
+}
+
+/// Records session `label`, keeping the creation time of an earlier record
+/// and the branch it adopted.
+pub fn record(data_dir: &Path, label: &str, mut session: Session) -> anyhow::Result<()> {
+    store::update(
+        data_dir,
+        &SESSIONS,
+        |sessions: &mut BTreeMap<String, Session>| {
+            if let Some(existing) = sessions.get(label) {
+                session.created_at = existing.created_at;
+                session.branch = existing.branch.clone();
+            }
+            sessions.insert(label.to_string(), session);
+            Ok(())
+        },
+    )
+}
+
+/// Makes session `label` follow `branch` instead of the one it was opened on.
+pub fn adopt(data_dir: &Path, label: &str, branch: &str) -> anyhow::Result<()> {
+    store::update(
+        data_dir,
+        &SESSIONS,
+        |sessions: &mut BTreeMap<String, Session>| {
+            let Some(session) = sessions.get_mut(label) else {
+                anyhow::bail!("no record of session {}; open it first", label);
+            };
+            session.branch = branch.to_string();
+            Ok(())
+        },
+    )
+}
+
+/// Moves session `label`'s record to `new_label`, as `session`, in one write.
+pub fn rename(
+    data_dir: &Path,
+    label: &str,
+    new_label: &str,
+    session: Session,
+) -> anyhow::Result<()> {
+    store::update(
+        data_dir,
+        &SESSIONS,
+        |sessions: &mut BTreeMap<String, Session>| {
+            if sessions.contains_key(new_label) {
+                anyhow::bail!("there's already a session {}", new_label);
+            }
+            sessions.remove(label);
+            sessions.insert(new_label.to_string(), session);
+            Ok(())
+        },
+    )
+}
+
+/// The label of session `name` among `candidates`, its current label first
+/// and then the ones older versions of forest gave it, by the records in
+/// `sessions`. A candidate only counts when its record is `name`'s, since old
+/// labels were shared by distinct names.
+pub fn registered_label(
+    sessions: &BTreeMap<String, Session>,
+    name: &str,
+    candidates: &[String],
+) -> Option<String> {
+    candidates
+        .iter()
+        .find(|label| {
+            sessions
+                .get(label.as_str())
+                .is_some_and(|session| session.is(name))
+        })
+        .cloned()
+}
+
+impl Session {
+    /// Whether this is the record of session `name`: it works on that branch
+    /// or its worktree is `name`'s, in either worktree layout.
+    pub fn is(&self, name: &str) -> bool {
+        self.branch == name
+            || [WorktreeLayout::Nested, WorktreeLayout::Flat]
+                .iter()
+                .any(|layout| self.worktree.ends_with(layout.dir(name)))
+    }
+}
+
+/// The default branch recorded for `remote` of `repo_root`.
+pub fn default_branch(data_dir: &Path, repo_root: &Path, remote: &str) -> Option<String> {
+    let repos: BTreeMap<PathBuf, Repo> = store::load(data_dir, &REPOS).ok()?;
+    repos.get(repo_root)?.default_branches.get(remote).cloned()
+}
+
+pub fn record_default_branch(
+    data_dir: &Path,
+    repo_root: &Path,
+    remote: &str,
+    branch: &str,
+) -> anyhow::Result<()> {
+    if default_branch(data_dir, repo_root, remote).as_deref() == Some(branch) {
+        return Ok(());
+    }
+    store::update(data_dir, &REPOS, |repos: &mut BTreeMap<PathBuf, Repo>| {
+        repos
+            .entry(repo_root.to_path_buf())
+            .or_default()
+            .default_branches
+            .insert(remote.to_string(), branch.to_string());
+        Ok(())
+    })
+}
+
+pub fn remove(data_dir: &Path, label: &str) -> anyhow::Result<()> {
+    if !load(data_dir)?.contains_key(label) {
+        return Ok(());
+    }
+    store::update(
+        data_dir,
+        &SESSIONS,
+        |sessions: &mut BTreeMap<String, Session>| {
+            sessions.remove(label);
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn keeps_creation_time() {
+        let dir = tempdir().unwrap();
+        let session = |created_at| Session {
+            branch: "feat/x".to_string(),
+            repo: PathBuf::from("/src/widget"),
+            worktree: PathBuf::from("/w/widget/feat/x"),
+            devcontainer_env: None,
+            created_at,
+            readonly: false,
+        };
+        record(dir.path(), "feat-x", session(1)).unwrap();
+        record(dir.path(), "feat-x", session(2)).unwrap();
+        assert_eq!(load(dir.path()).unwrap()["feat-x"].created_at, 1);
+        adopt(dir.path(), "feat-x", "feat/y").unwrap();
+        record(dir.path(), "feat-x", session(3)).unwrap();
+        assert_eq!(load(dir.path()).unwrap()["feat-x"].branch, "feat/y");
+        assert!(adopt(dir.path(), "other", "feat/y").is_err());
+        rename(dir.path(), "feat-x", "feat-z", session(4)).unwrap();
+        assert!(!load(dir.path()).unwrap().contains_key("feat-x"));
+        assert!(rename(dir.path(), "feat-x", "feat-z", session(5)).is_err());
+        rename(dir.path(), "feat-z", "feat-x", session(1)).unwrap();
+        remove(dir.path(), "feat-x").unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_labels_of_older_sessions() {
+        let session = |branch: &str, worktree: &str| Session {
+            branch: branch.to_string(),
+            repo: PathBuf::from("/src/widget"),
+            worktree: PathBuf::from(worktree),
+            devcontainer_env: None,
+            created_at: 1,
+            readonly: false,
+        };
+        let sessions = BTreeMap::from([
+            (
+                "feat-cool".to_string(),
+                session("feat/cool", "/w/widget/feat/cool"),
+            ),
+            (
+                "adopted".to_string(),
+                session("main", "/w/widget/feat%2Fui"),
+            ),
+        ]);
+        let candidates = ["feat-cool-1a2b3c".to_string(), "feat-cool".to_string()];
+        assert_eq!(
+            registered_label(&sessions, "feat/cool", &candidates).as_deref(),
+            Some("feat-cool")
+        );
+        assert_eq!(registered_label(&sessions, "feat cool", &candidates), None);
+        assert_eq!(
+            registered_label(&sessions, "feat/ui", &["adopted".to_string()]).as_deref(),
+            Some("adopted")
+        );
+    }
+
+    #[test]
+    fn caches_default_branches_per_repo() {
+        let dir = tempdir().unwrap();
+        let widget = Path::new("/src/widget");
+        assert_eq!(default_branch(dir.path(), widget, "origin"), None);
+        record_default_branch(dir.path(), widget, "origin", "trunk").unwrap();
+        record_default_branch(dir.path(), widget, "upstream", "main").unwrap();
+        assert_eq!(
+            default_branch(dir.path(), widget, "origin").as_deref(),
+            Some("trunk")
+        );
+        assert_eq!(
+            default_branch(dir.path(), widget, "upstream").as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            default_branch(dir.path(), Path::new("/src/gadget"), "origin"),
+            None
+        );
+    }
+}
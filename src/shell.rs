@@ -0,0 +1,84 @@
+use std::process::Command;
+
+/// Quotes `arg` for a POSIX shell, leaving words that need no quoting as
+/// they are.
+pub fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:@,+%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// A command line a POSIX shell splits back into `args`.
+pub fn join<S: AsRef<str>>(args: &[S]) -> String {
+    args.iter()
+        .map(|arg| quote(arg.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `cmd` as a command line, for showing what's being run.
+pub fn display(cmd: &Command) -> String {
+    let args: Vec<String> = std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    crate::secrets::redact(&join(&args))
+}
+
+/// Arguments running `args` in `dir` inside a container. A login shell sets
+/// up the container user's PATH; `dir` and `args` reach it as positional
+/// parameters instead of being spliced into its script.
+pub fn in_dir<S: AsRef<str>>(dir: &str, args: &[S]) -> Vec<String> {
+    [
+        "bash",
+        "-lc",
+        r#"cd -- "$1" && shift && exec "$@""#,
+        "forest",
+        dir,
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .chain(args.iter().map(|arg| arg.as_ref().to_string()))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_only_what_needs_it() {
+        assert_eq!(quote("feat/x-1"), "feat/x-1");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(join(&["git", "commit", "-m", "a b"]), "git commit -m 'a b'");
+    }
+
+    #[test]
+    fn runs_arguments_in_a_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("it's a dir");
+        std::fs::create_dir(&dir).unwrap();
+        let args = in_dir(
+            dir.to_str().unwrap(),
+            &["sh", "-c", r#"pwd; echo "$1""#, "-", "a;b $x"],
+        );
+        // bash is missing from some minimal build images
+        let Ok(output) = Command::new(&args[0])
+            .args(&args[1..])
+            .env("HOME", root.path())
+            .output()
+        else {
+            return;
+        };
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{}\na;b $x\n", dir.display())
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::{self, Schema};
+use crate::timesheet::now;
+
+/// `snapshots.json`: the snapshots kept of each session, oldest first, by
+/// label. The patches themselves live in `snapshots/<label>/<id>.patch`.
+pub const SNAPSHOTS: Schema = Schema {
+    file: "snapshots.json",
+    migrations: &[],
+};
+
+/// Snapshots kept per session when `snapshot_keep` isn't set.
+pub const DEFAULT_KEEP: usize = 20;
+
+/// Uncommitted changes of a session worktree at one point in time.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    /// Numbered per session, counting up
+    pub id: u32,
+    pub taken_at: u64,
+    /// Commit the changes were made on
+    pub head: String,
+    /// Files the patch touches
+    pub files: usize,
+}
+
+/// A session worktree seen from the host. git there can't follow the
+/// worktree's `.git` file, which names a container path, so it's given git's
+/// admin directory for the worktree directly.
+pub struct Worktree<'a> {
+    pub admin: &'a Path,
+    pub dir: &'a Path,
+}
+
+impl Worktree<'_> {
+    fn git(&self) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.env("GIT_DIR", self.admin)
+            .env("GIT_WORK_TREE", self.dir)
+            .current_dir(self.dir)
+            .stderr(Stdio::null());
+        cmd
+    }
+
+    fn output(&self, cmd: &mut Command) -> anyhow::Result<Vec<u8>> {
+        let output = cmd.output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed in {}",
+                crate::shell::display(cmd),
+                self.dir.display()
+            );
+        }
+        Ok(output.stdout)
+    }
+
+    fn head(&self) -> anyhow::Result<String> {
+        let out = self.output(self.git().args(["rev-parse", "HEAD"]))?;
+        Ok(String::from_utf8_lossy(&out).trim().to_string())
+    }
+
+    /// Every change against HEAD, untracked files included, as a binary
+    /// patch. Files are staged into a scratch index so the worktree's own
+    /// index is left alone.
+    fn diff(&self, scratch_index: &Path) -> anyhow::Result<Vec<u8>> {
+        let index = self.admin.join("index");
+        if index.exists() {
+            fs::copy(&index, scratch_index)?;
+        }
+        let staged = self.output(
+            self.git()
+                .env("GIT_INDEX_FILE", scratch_index)
+                .args(["add", "--all"]),
+        );
+        let diff = staged.and_then(|_| {
+            self.output(
+                self.git()
+                    .env("GIT_INDEX_FILE", scratch_index)
+                    .args(["diff", "--cached", "--binary", "HEAD"]),
+            )
+        });
+        let _ = fs::remove_file(scratch_index);
+        diff
+    }
+}
+
+fn session_dir(data_dir: &Path, label: &str) -> PathBuf {
+    data_dir.join("snapshots").join(label)
+}
+
+fn patch_path(data_dir: &Path, label: &str, id: u32) -> PathBuf {
+    session_dir(data_dir, label).join(format!("{}.patch", id))
+}
+
+/// Moves the snapshots of session `label` to `new_label`.
+pub fn rename(data_dir: &Path, label: &str, new_label: &str) -> anyhow::Result<()> {
+    if list(data_dir, label)?.is_empty() {
+        return Ok(());
+    }
+    store::update(
+        data_dir,
+        &SNAPSHOTS,
+        |all: &mut BTreeMap<String, Vec<Snapshot>>| {
+            let snapshots = all.remove(label).unwrap_or_default();
+            let dir = session_dir(data_dir, label);
+            if dir.exists() {
+                fs::rename(&dir, session_dir(data_dir, new_label))?;
+            }
+            all.insert(new_label.to_string(), snapshots);
+            Ok(())
+        },
+    )
+}
+
+pub fn list(data_dir: &Path, label: &str) -> anyhow::Result<Vec<Snapshot>> {
+    let mut all: BTreeMap<String, Vec<Snapshot>> = store::load(data_dir, &SNAPSHOTS)?;
+    Ok(all.remove(label).unwrap_or_default())
+}
+
+/// Snapshots the worktree's uncommitted changes, unless there are none or
+/// they're the same as in the latest snapshot. Only the newest `keep`
+/// snapshots are kept.
+pub fn take(
+    data_dir: &Path,
+    label: &str,
+    worktree: &Worktree,
+    keep: usize,
+) -> anyhow::Result<Option<Snapshot>> {
+    let dir = session_dir(data_dir, label);
+    fs::create_dir_all(&dir)?;
+    let patch = worktree.diff(&dir.join("index.tmp"))?;
+    if patch.is_empty() {
+        return Ok(None);
+    }
+    let mut all: BTreeMap<String, Vec<Snapshot>> = store::load(data_dir, &SNAPSHOTS)?;
+    let snapshots = all.entry(label.to_string()).or_default();
+    if let Some(last) = snapshots.last() {
+        if fs::read(patch_path(data_dir, label, last.id)).ok().as_ref() == Some(&patch) {
+            return Ok(None);
+        }
+    }
+    let snapshot = Snapshot {
+        id: snapshots.last().map_or(1, |s| s.id + 1),
+        taken_at: now(),
+        head: worktree.head()?,
+        files: String::from_utf8_lossy(&patch)
+            .lines()
+            .filter(|l| l.starts_with("diff --git "))
+            .count(),
+    };
+    fs::write(patch_path(data_dir, label, snapshot.id), &patch)?;
+    snapshots.push(snapshot.clone());
+    let expired = snapshots.len().saturating_sub(keep.max(1));
+    for old in snapshots.drain(..expired) {
+        let _ = fs::remove_file(patch_path(data_dir, label, old.id));
+    }
+    store::save(data_dir, &SNAPSHOTS, &all)?;
+    Ok(Some(snapshot))
+}
+
+/// Applies snapshot `id` to the worktree. The changes land on top of
+/// whatever the worktree holds now, so it's best restored into a clean one.
+pub fn restore(data_dir: &Path, label: &str, id: u32, worktree: &Worktree) -> anyhow::Result<()> {
+    let snapshot = list(data_dir, label)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| anyhow::anyhow!("no snapshot {} of session {}", id, label))?;
+    let head = worktree.head()?;
+    if head != snapshot.head {
+        crate::style::warn(format!(
+            "snapshot {} was taken on {}, the worktree is now on {}",
+            id,
+            short(&snapshot.head),
+            short(&head)
+        ));
+    }
+    let patch = patch_path(data_dir, label, id);
+    let status = worktree
+        .git()
+        .args(["apply", "--binary"])
+        .arg(&patch)
+        .stderr(Stdio::inherit())
+        .status()?;
+    if !status.success() {
+        anyhow::bail!(
+            "snapshot {} doesn't apply to the worktree as it is; the patch is {}",
+            id,
+            patch.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(10)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=forest",
+                "-c",
+                "user.email=forest@localhost",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn snapshots_and_restores_worktree_changes() {
+        let repo = tempdir().unwrap();
+        git(repo.path(), &["init", "--quiet"]);
+        fs::write(repo.path().join("a.txt"), "one\n").unwrap();
+        git(repo.path(), &["add", "a.txt"]);
+        git(repo.path(), &["commit", "--quiet", "-m", "init"]);
+        let dir = repo.path().join("wt");
+        git(
+            repo.path(),
+            &["worktree", "add", "--quiet", "-b", "feat", "wt"],
+        );
+        let admin = repo.path().join(".git/worktrees/wt");
+        let worktree = Worktree {
+            admin: &admin,
+            dir: &dir,
+        };
+        let data = tempdir().unwrap();
+
+        assert!(take(data.path(), "feat", &worktree, 2).unwrap().is_none());
+        fs::write(dir.join("a.txt"), "two\n").unwrap();
+        fs::write(dir.join("new.txt"), "new\n").unwrap();
+        let first = take(data.path(), "feat", &worktree, 2).unwrap().unwrap();
+        assert_eq!((first.id, first.files), (1, 2));
+        assert!(take(data.path(), "feat", &worktree, 2).unwrap().is_none());
+
+        // the accident the snapshot insures against
+        git(&dir, &["reset", "--quiet", "--hard"]);
+        fs::remove_file(dir.join("new.txt")).unwrap();
+        restore(data.path(), "feat", 1, &worktree).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "two\n");
+        assert_eq!(fs::read_to_string(dir.join("new.txt")).unwrap(), "new\n");
+
+        for content in ["three\n", "four\n"] {
+            fs::write(dir.join("a.txt"), content).unwrap();
+            take(data.path(), "feat", &worktree, 2).unwrap().unwrap();
+        }
+        let ids: Vec<u32> = list(data.path(), "feat")
+            .unwrap()
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, [2, 3]);
+        assert!(!patch_path(data.path(), "feat", 1).exists());
+
+        rename(data.path(), "feat", "feat-renamed").unwrap();
+        assert!(list(data.path(), "feat").unwrap().is_empty());
+        assert_eq!(list(data.path(), "feat-renamed").unwrap().len(), 2);
+        assert!(patch_path(data.path(), "feat-renamed", 3).exists());
+    }
+}
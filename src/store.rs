@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Upgrades a file's data from one schema version to the next.
+pub type Migration = fn(Value) -> anyhow::Result<Value>;
+
+/// A JSON file in forest's data directory and how its format evolved. On
+/// disk it's `{"schema": <version>, "data": ...}`; files written before
+/// formats were versioned hold just the data and count as version 1.
+pub struct Schema {
+    pub file: &'static str,
+    /// `migrations[i]` upgrades version `i + 1` to `i + 2`, so the current
+    /// version is one past the last migration
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+
+    pub fn path(&self, data_dir: &Path) -> PathBuf {
+        data_dir.join(self.file)
+    }
+}
+
+fn split(value: Value) -> (u32, Value) {
+    if let Value::Object(object) = &value {
+        if let (2, Some(version), Some(data)) = (
+            object.len(),
+            object.get("schema").and_then(Value::as_u64),
+            object.get("data"),
+        ) {
+            return (version as u32, data.clone());
+        }
+    }
+    (1, value)
+}
+
+/// The file's data at the current version, and the version it was stored at.
+fn read(data_dir: &Path, schema: &Schema) -> anyhow::Result<Option<(Value, u32)>> {
+    let path = schema.path(data_dir);
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let value: Value = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    let (version, mut data) = split(value);
+    if version > schema.version() {
+        anyhow::bail!(
+            "{} was written by a newer forest (format {}, this one reads up to {}); upgrade forest",
+            path.display(),
+            version,
+            schema.version()
+        );
+    }
+    if version < schema.version() {
+        // keep what the older forest wrote, in case the upgrade loses something
+        let backup = data_dir.join(format!("{}.v{}.bak", schema.file, version));
+        fs::write(&backup, &contents)?;
+        for migration in &schema.migrations[version as usize - 1..] {
+            data = migration(data)
+                .map_err(|e| anyhow::anyhow!("upgrading {}: {}", path.display(), e))?;
+        }
+        write(data_dir, schema, &data)?;
+    }
+    Ok(Some((data, version)))
+}
+
+fn write(data_dir: &Path, schema: &Schema, data: &Value) -> anyhow::Result<()> {
+    fs::create_dir_all(data_dir)?;
+    let path = schema.path(data_dir);
+    let envelope = json!({"schema": schema.version(), "data": data});
+    // a crash mid-write must not leave a truncated file behind
+    let temp = data_dir.join(format!("{}.tmp", schema.file));
+    fs::write(&temp, serde_json::to_string_pretty(&envelope)?)?;
+    fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+/// Reads the file, upgrading it in place first when an older forest wrote
+/// it. A missing file reads as the default.
+pub fn load<T: DeserializeOwned + Default>(data_dir: &Path, schema: &Schema) -> anyhow::Result<T> {
+    match read(data_dir, schema)? {
+        Some((data, _)) => serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("{}: {}", schema.path(data_dir).display(), e)),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn save<T: Serialize>(data_dir: &Path, schema: &Schema, data: &T) -> anyhow::Result<()> {
+    write(data_dir, schema, &serde_json::to_value(data)?)
+}
+
+/// Held while a file is loaded, changed and saved, as bulk commands change
+/// the same files from several threads.
+static UPDATES: Mutex<()> = Mutex::new(());
+
+/// Loads the file, lets `change` modify the data and saves it, with no
+/// other update from this process in between. Nothing is saved when
+/// `change` fails.
+pub fn update<T, R>(
+    data_dir: &Path,
+    schema: &Schema,
+    change: impl FnOnce(&mut T) -> anyhow::Result<R>,
+) -> anyhow::Result<R>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    let _guard = UPDATES.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut data = load(data_dir, schema)?;
+    let result = change(&mut data)?;
+    save(data_dir, schema, &data)?;
+    Ok(result)
+}
+
+/// Upgrades the file if an older forest wrote it, returning the version it
+/// was at.
+pub fn migrate(data_dir: &Path, schema: &Schema) -> anyhow::Result<Option<u32>> {
+    Ok(read(data_dir, schema)?
+        .map(|(_, version)| version)
+        .filter(|version| *version < schema.version()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    fn rename_count(mut value: Value) -> anyhow::Result<Value> {
+        if let Some(object) = value.as_object_mut() {
+            let count = object.remove("count").unwrap_or(Value::from(0));
+            object.insert("total".to_string(), count);
+        }
+        Ok(value)
+    }
+
+    const COUNTER: Schema = Schema {
+        file: "counter.json",
+        migrations: &[rename_count],
+    };
+
+    #[test]
+    fn upgrades_unversioned_files_with_backup() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("counter.json"), r#"{"count": 3}"#).unwrap();
+
+        let data: BTreeMap<String, u32> = load(dir.path(), &COUNTER).unwrap();
+        assert_eq!(data["total"], 3);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("counter.json.v1.bak")).unwrap(),
+            r#"{"count": 3}"#
+        );
+        assert_eq!(migrate(dir.path(), &COUNTER).unwrap(), None);
+
+        save(dir.path(), &COUNTER, &data).unwrap();
+        let stored: Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join("counter.json")).unwrap())
+                .unwrap();
+        assert_eq!(stored["schema"], 2);
+    }
+
+    #[test]
+    fn refuses_files_from_newer_versions() {
+        let dir = tempdir().unwrap();
+        let newer = r#"{"schema": 9, "data": {}}"#;
+        fs::write(dir.path().join("counter.json"), newer).unwrap();
+        assert!(load::<BTreeMap<String, u32>>(dir.path(), &COUNTER).is_err());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("counter.json")).unwrap(),
+            newer
+        );
+        let missing: BTreeMap<String, u32> = load(&dir.path().join("none"), &COUNTER).unwrap();
+        assert!(missing.is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::cli::ColorChoice;
+
+/// Palette for styled output, set with `theme` in forest.toml.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Standard terminal colors
+    #[default]
+    Default,
+    /// Bold, bright colors for dark or low-contrast terminals
+    Bright,
+    /// No colors, only bold and dim text
+    Mono,
+}
+
+/// What a piece of output means, which decides how it's painted.
+#[derive(Clone, Copy)]
+pub enum Role {
+    Error,
+    Warning,
+    Success,
+    /// Secondary details like paths and ages
+    Dim,
+    Header,
+}
+
+struct Style {
+    stdout: bool,
+    stderr: bool,
+    theme: Theme,
+    /// Line-by-line output for screen readers and dumb terminals
+    plain: bool,
+}
+
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+fn style() -> &'static Style {
+    STYLE.get_or_init(|| Style {
+        stdout: false,
+        stderr: false,
+        theme: Theme::Default,
+        plain: false,
+    })
+}
+
+/// Sets up output for the run; `plain` output has no colors, whatever
+/// `choice` says.
+pub fn init(choice: ColorChoice, theme: Theme, plain: bool) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = |terminal: bool| match choice {
+        _ if plain => false,
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal && !no_color,
+    };
+    let _ = STYLE.set(Style {
+        stdout: enabled(std::io::stdout().is_terminal()),
+        stderr: enabled(std::io::stderr().is_terminal()),
+        theme,
+        plain,
+    });
+}
+
+/// Whether output is plain, for `--plain`, `plain` in forest.toml or a
+/// `TERM=dumb` terminal.
+pub fn plain() -> bool {
+    style().plain
+}
+
+/// What a terminal would end up showing of `line` from a program redrawing
+/// it with carriage returns, like a progress bar: the text after the last.
+pub fn settled(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    match line.iter().rposition(|&b| b == b'\r') {
+        Some(i) => &line[i + 1..],
+        None => line,
+    }
+}
+
+/// SGR parameters for a role in a theme.
+fn sgr(theme: Theme, role: Role) -> &'static str {
+    match (theme, role) {
+        (Theme::Default, Role::Error) => "31",
+        (Theme::Default, Role::Warning) => "33",
+        (Theme::Default, Role::Success) => "32",
+        (Theme::Bright, Role::Error) => "1;91",
+        (Theme::Bright, Role::Warning) => "1;93",
+        (Theme::Bright, Role::Success) => "1;92",
+        (Theme::Mono, Role::Error | Role::Warning) => "1",
+        (Theme::Mono, Role::Success) => "",
+        (_, Role::Dim) => "2",
+        (_, Role::Header) => "1",
+    }
+}
+
+fn paint_if(enabled: bool, role: Role, text: &str) -> String {
+    let code = sgr(style().theme, role);
+    if !enabled || code.is_empty() || text.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// Styles text printed to stdout.
+pub fn paint(role: Role, text: &str) -> String {
+    paint_if(style().stdout, role, text)
+}
+
+/// Styles text printed to stderr.
+pub fn paint_err(role: Role, text: &str) -> String {
+    paint_if(style().stderr, role, text)
+}
+
+pub fn warn(message: impl Display) {
+    eprintln!("{} {}", paint_err(Role::Warning, "warning:"), message);
+}
+
+pub fn note(message: impl Display) {
+    eprintln!("{} {}", paint_err(Role::Dim, "note:"), message);
+}
+
+pub fn error(message: impl Display) {
+    eprintln!("{} {}", paint_err(Role::Error, "error:"), message);
+}
+
+/// Rows printed as aligned columns under a header on a terminal, and as plain
+/// tab-separated lines when piped, so scripts keep working. Plain output
+/// names each cell on a terminal instead of lining cells up.
+pub struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<(String, Option<Role>)>>,
+}
+
+impl Table {
+    pub fn new(header: &[&'static str]) -> Self {
+        Table {
+            header: header.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<(String, Option<Role>)>) {
+        self.rows.push(cells);
+    }
+
+    pub fn print(&self) {
+        let terminal = std::io::stdout().is_terminal();
+        if terminal && plain() {
+            print!("{}", self.render_plain());
+        } else {
+            print!("{}", self.render(terminal));
+        }
+    }
+
+    /// A line per row, each non-empty cell after its column's name.
+    fn render_plain(&self) -> String {
+        let mut out = String::new();
+        for row in &self.rows {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&self.header)
+                .filter(|((text, _), _)| !text.is_empty())
+                .map(|((text, _), header)| format!("{} {}", header, text))
+                .collect();
+            out.push_str(&cells.join(", "));
+            out.push('\n');
+        }
+        out
+    }
+
+    fn render(&self, aligned: bool) -> String {
+        let paint_cell = |text: &str, role: Option<Role>| match role {
+            Some(role) => paint(role, text),
+            None => text.to_string(),
+        };
+        let mut out = String::new();
+        if !aligned {
+            for row in &self.rows {
+                let cells: Vec<String> = row.iter().map(|(t, r)| paint_cell(t, *r)).collect();
+                out.push_str(&cells.join("\t"));
+                out.push('\n');
+            }
+            return out;
+        }
+
+        let mut widths: Vec<usize> = self.header.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, (text, _)) in row.iter().enumerate() {
+                if i >= widths.len() {
+                    widths.push(0);
+                }
+                widths[i] = widths[i].max(text.chars().count());
+            }
+        }
+        let line = |cells: Vec<(String, String)>| {
+            let last = cells.len().saturating_sub(1);
+            let mut line = String::new();
+            for (i, (plain, painted)) in cells.into_iter().enumerate() {
+                line.push_str(&painted);
+                if i < last {
+                    // pad by the unstyled width, escape codes take no columns
+                    let pad = widths[i] - plain.chars().count() + 2;
+                    line.push_str(&" ".repeat(pad));
+                }
+            }
+            line.truncate(line.trim_end().len());
+            line.push('\n');
+            line
+        };
+        out.push_str(&line(
+            self.header
+                .iter()
+                .map(|h| (h.to_uppercase(), paint(Role::Header, &h.to_uppercase())))
+                .collect(),
+        ));
+        for row in &self.rows {
+            out.push_str(&line(
+                row.iter()
+                    .map(|(t, r)| (t.clone(), paint_cell(t, *r)))
+                    .collect(),
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["name", "state"]);
+        table.row(vec![
+            ("feat-x".to_string(), None),
+            ("running".to_string(), Some(Role::Success)),
+        ]);
+        table.row(vec![("a".to_string(), None), ("stopped".to_string(), None)]);
+        table
+    }
+
+    #[test]
+    fn aligns_columns_for_terminals() {
+        assert_eq!(
+            table().render(true),
+            "NAME    STATE\nfeat-x  running\na       stopped\n"
+        );
+    }
+
+    #[test]
+    fn tab_separates_when_piped() {
+        assert_eq!(table().render(false), "feat-x\trunning\na\tstopped\n");
+    }
+
+    #[test]
+    fn names_cells_when_plain() {
+        let mut table = table();
+        table.row(vec![("b".to_string(), None), (String::new(), None)]);
+        assert_eq!(
+            table.render_plain(),
+            "name feat-x, state running\nname a, state stopped\nname b\n"
+        );
+    }
+
+    #[test]
+    fn keeps_what_carriage_returns_leave() {
+        assert_eq!(settled(b"10%\r50%\r100% done"), b"100% done");
+        assert_eq!(settled(b"building\r"), b"building");
+        assert_eq!(settled(b"plain"), b"plain");
+    }
+
+    #[test]
+    fn mono_theme_has_no_colors() {
+        assert_eq!(sgr(Theme::Mono, Role::Success), "");
+        assert_eq!(sgr(Theme::Mono, Role::Error), "1");
+    }
+}
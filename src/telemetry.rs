@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+/// A finished or running span, exported as OTLP JSON.
+struct SpanData {
+    id: u64,
+    parent: Option<u64>,
+    name: String,
+    start: u128,
+    end: u128,
+    attributes: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+struct Tracer {
+    endpoint: String,
+    trace_id: u128,
+    /// First span opened, which parents spans started on other threads
+    root: Option<u64>,
+    spans: Vec<SpanData>,
+}
+
+/// Set by `init` when an OTLP endpoint is configured; spans are no-ops otherwise.
+static TRACER: Mutex<Option<Tracer>> = Mutex::new(None);
+
+thread_local! {
+    /// Open spans on this thread, innermost last
+    static STACK: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+fn with_tracer<R>(f: impl FnOnce(&mut Tracer) -> R) -> Option<R> {
+    let mut tracer = TRACER.lock().ok()?;
+    tracer.as_mut().map(f)
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(now_nanos());
+    hasher.finish()
+}
+
+/// Starts collecting spans for export to `endpoint`, an OTLP/HTTP collector
+/// such as `http://localhost:4318`.
+pub fn init(endpoint: &str) {
+    if let Ok(mut tracer) = TRACER.lock() {
+        *tracer = Some(Tracer {
+            endpoint: endpoint.to_string(),
+            trace_id: (random_u64() as u128) << 64 | random_u64() as u128,
+            root: None,
+            spans: Vec::new(),
+        });
+    }
+}
+
+/// A span that ends when dropped.
+pub struct Span {
+    id: Option<u64>,
+}
+
+pub fn span(name: &str) -> Span {
+    let id = with_tracer(|tracer| {
+        let id = random_u64();
+        let parent = STACK.with(|stack| stack.borrow().last().copied());
+        let parent = parent.or(tracer.root);
+        tracer.root.get_or_insert(id);
+        tracer.spans.push(SpanData {
+            id,
+            parent,
+            name: name.to_string(),
+            start: now_nanos(),
+            end: 0,
+            attributes: Vec::new(),
+            error: None,
+        });
+        id
+    });
+    if let Some(id) = id {
+        STACK.with(|stack| stack.borrow_mut().push(id));
+    }
+    Span { id }
+}
+
+impl Span {
+    fn update(&self, f: impl FnOnce(&mut SpanData)) {
+        let Some(id) = self.id else {
+            return;
+        };
+        with_tracer(|tracer| {
+            if let Some(span) = tracer.spans.iter_mut().find(|s| s.id == id) {
+                f(span);
+            }
+        });
+    }
+
+    pub fn set(&self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+        self.update(|span| span.attributes.push((key.to_string(), value)));
+    }
+
+    /// Marks the span as failed.
+    pub fn error(&self, message: &str) {
+        self.update(|span| span.error = Some(message.to_string()));
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        self.update(|span| span.end = now_nanos());
+        STACK.with(|stack| stack.borrow_mut().retain(|s| *s != id));
+    }
+}
+
+/// Sends the collected spans to the collector. Export problems only warn:
+/// tracing must never fail a forest command.
+pub fn flush(verbose: bool) {
+    let Some(tracer) = TRACER.lock().ok().and_then(|mut t| t.take()) else {
+        return;
+    };
+    if tracer.spans.is_empty() {
+        return;
+    }
+    let url = if tracer.endpoint.ends_with("/v1/traces") {
+        tracer.endpoint.clone()
+    } else {
+        format!("{}/v1/traces", tracer.endpoint.trim_end_matches('/'))
+    };
+    let body = payload(tracer.trace_id, &tracer.spans).to_string();
+    if verbose {
+        println!("Exporting {} spans to {}", tracer.spans.len(), url);
+    }
+    let child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "5"])
+        .args([
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ])
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn();
+    let result = child.and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body.as_bytes())?;
+        }
+        child.wait()
+    });
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(_) => crate::style::warn(format!("failed to export traces to {}", url)),
+        Err(e) => crate::style::warn(format!("failed to run curl to export traces: {}", e)),
+    }
+}
+
+/// OTLP/HTTP JSON request body for one trace.
+fn payload(trace_id: u128, spans: &[SpanData]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let mut value = json!({
+                "traceId": format!("{:032x}", trace_id),
+                "spanId": format!("{:016x}", span.id),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": span.start.to_string(),
+                "endTimeUnixNano": span.end.max(span.start).to_string(),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(k, v)| json!({"key": k, "value": {"stringValue": v}}))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(parent) = span.parent {
+                value["parentSpanId"] = json!(format!("{:016x}", parent));
+            }
+            if let Some(message) = &span.error {
+                value["status"] = json!({"code": 2, "message": message});
+            }
+            value
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    {"key": "service.name", "value": {"stringValue": "forest"}},
+                    {"key": "service.version", "value": {"stringValue": env!("CARGO_PKG_VERSION")}},
+                ]
+            },
+            "scopeSpans": [{
+                "scope": {"name": "forest"},
+                "spans": spans,
+            }]
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_otlp_payload() {
+        let spans = [
+            SpanData {
+                id: 1,
+                parent: None,
+                name: "forest open".to_string(),
+                start: 10,
+                end: 50,
+                attributes: Vec::new(),
+                error: Some("devcontainer up failed".to_string()),
+            },
+            SpanData {
+                id: 2,
+                parent: Some(1),
+                name: "exec git".to_string(),
+                start: 20,
+                end: 30,
+                attributes: vec![("process.exit.code".to_string(), "0".to_string())],
+                error: None,
+            },
+        ];
+        let body = payload(0xabc, &spans);
+        let spans = &body["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], format!("{:032x}", 0xabc));
+        assert_eq!(spans[0]["status"]["code"], 2);
+        assert!(spans[0].get("parentSpanId").is_none());
+        assert_eq!(spans[1]["parentSpanId"], "0000000000000001");
+        assert_eq!(spans[1]["startTimeUnixNano"], "20");
+        assert_eq!(spans[1]["attributes"][0]["value"]["stringValue"], "0");
+    }
+}
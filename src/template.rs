@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_REGISTRY: &str = "ghcr.io";
+
+/// The `[templates]` table of forest.toml.
+#[derive(Deserialize, Default)]
+pub struct TemplateConfig {
+    /// OCI registry templates are published to and pulled from
+    pub registry: Option<String>,
+    /// Namespace in the registry, `<githuborg>/devcontainer-templates` by default
+    pub namespace: Option<String>,
+}
+
+impl TemplateConfig {
+    pub fn registry(&self) -> &str {
+        self.registry.as_deref().unwrap_or(DEFAULT_REGISTRY)
+    }
+
+    pub fn namespace(&self, org: Option<&str>) -> anyhow::Result<String> {
+        if let Some(namespace) = &self.namespace {
+            return Ok(namespace.clone());
+        }
+        let org = org.ok_or_else(|| {
+            anyhow::anyhow!("set `githuborg` or `namespace` under [templates] in forest.toml")
+        })?;
+        Ok(format!("{}/devcontainer-templates", org))
+    }
+
+    /// The OCI reference of `template`: a bare id is looked up in the org's
+    /// namespace, anything with a `/` is used as it is.
+    pub fn reference(&self, template: &str, org: Option<&str>) -> anyhow::Result<String> {
+        if template.contains('/') {
+            return Ok(template.to_string());
+        }
+        Ok(format!(
+            "{}/{}/{}",
+            self.registry(),
+            self.namespace(org)?,
+            template
+        ))
+    }
+}
+
+/// Lays out `devcontainer_dir` as template `id` under `dir/src`, the layout
+/// `devcontainer templates publish` reads.
+fn stage(devcontainer_dir: &Path, dir: &Path, id: &str, version: &str) -> anyhow::Result<()> {
+    let template_dir = dir.join("src").join(id);
+    crate::copy_dir(devcontainer_dir, &template_dir.join(".devcontainer"))?;
+    let metadata = json!({
+        "id": id,
+        "version": version,
+        "name": id,
+        "description": format!("Development environment of {}, published by forest", id),
+        "options": {},
+    });
+    fs::write(
+        template_dir.join("devcontainer-template.json"),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+    Ok(())
+}
+
+fn devcontainer(cmd: &mut Command, verbose: bool) -> anyhow::Result<bool> {
+    let status = crate::run_command_verbose(cmd, verbose).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("devcontainer command not found. Please install @devcontainers/cli")
+        } else {
+            e.into()
+        }
+    })?;
+    Ok(status.success())
+}
+
+/// Publishes `devcontainer_dir` as template `id` at `version` to
+/// `registry/namespace/id`.
+pub fn publish(
+    devcontainer_dir: &Path,
+    id: &str,
+    version: &str,
+    registry: &str,
+    namespace: &str,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let staging = std::env::temp_dir().join(format!("forest-template-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&staging);
+    stage(devcontainer_dir, &staging, id, version)?;
+    println!(
+        "Publishing template {}/{}/{}:{}",
+        registry, namespace, id, version
+    );
+    let mut cmd = Command::new("devcontainer");
+    cmd.args(["templates", "publish", "--registry", registry])
+        .args(["--namespace", namespace])
+        .arg(staging.join("src"));
+    let published = devcontainer(&mut cmd, verbose);
+    let _ = fs::remove_dir_all(&staging);
+    if !published? {
+        anyhow::bail!("devcontainer templates publish failed");
+    }
+    Ok(())
+}
+
+/// Applies the template at `reference` to `dir`, adding its `.devcontainer`.
+pub fn apply(reference: &str, dir: &Path, verbose: bool) -> anyhow::Result<()> {
+    println!("Applying template {}", reference);
+    let mut cmd = Command::new("devcontainer");
+    cmd.args(["templates", "apply", "--workspace-folder"])
+        .arg(dir)
+        .args(["--template-id", reference, "--template-args", "{}"]);
+    if !devcontainer(&mut cmd, verbose)? {
+        anyhow::bail!("devcontainer templates apply failed for {}", reference);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn resolves_template_references() {
+        let config = TemplateConfig::default();
+        assert_eq!(
+            config.reference("rust", Some("acme")).unwrap(),
+            "ghcr.io/acme/devcontainer-templates/rust"
+        );
+        assert_eq!(
+            config
+                .reference("registry.example.com/t/go:2", None)
+                .unwrap(),
+            "registry.example.com/t/go:2"
+        );
+        assert!(config.reference("rust", None).is_err());
+    }
+
+    #[test]
+    fn stages_publish_layout() {
+        let repo = tempdir().unwrap();
+        let devcontainer_dir = repo.path().join(".devcontainer");
+        fs::create_dir_all(&devcontainer_dir).unwrap();
+        fs::write(devcontainer_dir.join("devcontainer.json"), "{}").unwrap();
+        let staging = tempdir().unwrap();
+        stage(&devcontainer_dir, staging.path(), "widget", "1.2.0").unwrap();
+
+        let template_dir = staging.path().join("src/widget");
+        assert!(template_dir
+            .join(".devcontainer/devcontainer.json")
+            .exists());
+        let metadata: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(template_dir.join("devcontainer-template.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(metadata["id"], "widget");
+        assert_eq!(metadata["version"], "1.2.0");
+    }
+}
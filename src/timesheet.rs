@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// One attached period: from entering the session shell until leaving it.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub session: String,
+    pub repo: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn log_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("time.jsonl")
+}
+
+pub fn record(data_dir: &Path, entry: &Entry) -> anyhow::Result<()> {
+    fs::create_dir_all(data_dir)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(data_dir))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+pub fn load(data_dir: &Path) -> anyhow::Result<Vec<Entry>> {
+    let content = match fs::read_to_string(log_path(data_dir)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    // a line cut short by a crash shouldn't hide the rest of the timesheet
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Parses spans like `30m`, `12h`, `3d` or `1w` into seconds.
+pub fn parse_span(span: &str) -> anyhow::Result<u64> {
+    let span = span.trim();
+    let unit = span
+        .chars()
+        .last()
+        .ok_or_else(|| anyhow::anyhow!("empty time span"))?;
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("unknown time unit in {:?}, use s, m, h, d or w", span),
+    };
+    let count: u64 = span[..span.len() - 1]
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid time span {:?}", span))?;
+    Ok(count * multiplier)
+}
+
+/// Sums attached seconds per (repo, session), clipping periods to `since`.
+pub fn summarize(entries: &[Entry], since: u64) -> BTreeMap<(String, String), u64> {
+    let mut totals = BTreeMap::new();
+    for entry in entries {
+        if entry.end <= since {
+            continue;
+        }
+        let start = entry.start.max(since);
+        *totals
+            .entry((entry.repo.clone(), entry.session.clone()))
+            .or_insert(0) += entry.end.saturating_sub(start);
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(session: &str, start: u64, end: u64) -> Entry {
+        Entry {
+            session: session.to_string(),
+            repo: "repo".to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn parses_spans() {
+        assert_eq!(parse_span("1w").unwrap(), 604800);
+        assert_eq!(parse_span("90m").unwrap(), 5400);
+        assert!(parse_span("3y").is_err());
+        assert!(parse_span("h").is_err());
+    }
+
+    #[test]
+    fn summary_clips_to_since() {
+        let entries = [entry("a", 0, 100), entry("a", 150, 200), entry("b", 10, 20)];
+        let totals = summarize(&entries, 50);
+        assert_eq!(totals[&("repo".to_string(), "a".to_string())], 100);
+        assert!(!totals.contains_key(&("repo".to_string(), "b".to_string())));
+    }
+}
@@ -0,0 +1,147 @@
+//! Conceptual help shown by `forest help <topic>` and rendered into section 7
+//! man pages by `build.rs`, so it can't depend on the rest of the crate.
+
+pub struct Topic {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub body: &'static str,
+}
+
+pub const TOPICS: &[Topic] = &[
+    Topic {
+        name: "worktrees",
+        summary: "how sessions map to branches, worktrees and containers",
+        body: "\
+Every session is a git branch with its own worktree under ~/worktrees/<repo>/,
+or under `worktree_root` when it's set, and its own devcontainer built from
+that worktree's `.devcontainer`.
+
+`forest open <name>` creates the branch if it doesn't exist, starts the
+container with the repository mounted at /repo and the worktree at /code, and
+adds the worktree from inside the container so git's bookkeeping uses
+container paths. Commits made in a session are visible from every other
+worktree of the repository straight away.
+
+A container already carrying the session's label that wasn't created for its
+worktree, or holding the name of its egress proxy, makes `open` stop rather
+than attach to or remove it; `--adopt` takes it over.
+
+`forest main` opens the default branch read-only instead: /repo and /code are
+mounted read-only and the worktree checks the branch out detached, so no
+session branch is created. `forest open <branch> --readonly` does the same for
+any existing branch.
+
+Session names may contain `/`, e.g. `feat/login`; the container label uses a
+sanitized form, shown by `forest sanitize`.
+
+`forest kill <name>` stops the container and keeps the worktree. `--down`
+removes the container, and `--purge` also moves the worktree into the forest
+trash, from where `forest trash restore` brings it back until
+`trash_retention_days` have passed. `kill_tier` in forest.toml changes what a
+plain `kill` does. `--push`, or `push_on_kill = true`, pushes the session
+branch's unpushed commits to origin first. Uncommitted changes in the worktree
+make `--down`, `--purge` and `--push` ask before going on, or fail without a
+terminal; `--stash` stashes them first and `--force` leaves them be.
+
+`forest subtask <session> <name>` adds a worktree on the branch
+`<session>-<name>` under /code inside an already running session's container,
+for side quests that don't need a container of their own.",
+    },
+    Topic {
+        name: "configuration",
+        summary: "forest.toml settings",
+        body: "\
+forest reads `forest.toml` from the platform config directory, e.g.
+~/.config/forest/forest.toml on Linux, then `.forest.toml` at the root of
+the current repository, whose settings win (except registries,
+otlp_endpoint, extra_mounts, remote_protocol, devcontainer_args,
+worktree_root, hooks and secrets, which only the user's file sets). Every
+setting is optional:
+
+  githuborg             organisation `forest new` creates repositories under
+  worktree_root         directory holding worktrees instead of ~/worktrees
+  worktree_layout       nested (feat/cool as directories) or flat (feat%2Fcool)
+  network_worktrees     warn, local or allow when ~/worktrees is on NFS
+  missing_repo          prompt, init or fail when `open` runs outside git
+  lock_timeout          seconds to wait for other sessions' git operations
+  propagate_git_config  repository git config keys copied into worktrees
+  trash_retention_days  days purged worktrees stay in the trash
+  kill_tier             stop, down or purge for a plain `kill`
+  tool_compat           adapt to jj and git-branchless (default false)
+  otlp_endpoint         OTLP/HTTP collector receiving traces
+  theme                 default, bright or mono
+  plain                 line-by-line output for screen readers, like --plain
+  backend               podman (default) or docker
+  devcontainer_args     raw arguments for devcontainer build, up and exec
+  devcontainer_env      default for --devcontainer-env
+  default_base_branch   default for --from: where new session branches start
+  extra_mounts          path[:target] directories mounted into every session
+  priority              nice value (-20 to 19) of sessions without --priority
+  default_features      devcontainer features added to every session
+  push_on_kill          push unpushed session commits before `kill`
+  scaffold              repo or overlay, where `open --scaffold` writes
+  scaffold_template     template to scaffold from instead of a guessed one
+  remote_protocol       ssh or https for GitHub remotes, in and out of sessions
+  snapshot_keep         snapshots of uncommitted changes kept per session
+  wip_on_detach         commit or stash uncommitted work when leaving a session
+  shell_helpers         host actions (sync, pr, open, copy) from session shells
+  egress_proxy_image    image running the proxy of `--network allowlist`
+  [features]            experimental features, see `forest help features`
+  [registries.\"<host>\"] credential_helper for a private registry
+  [scan]                scanner and on_critical for image scanning
+  [env]                 environment variables of every session's commands
+  [secrets]             variables read with op://, pass: or cmd: when opening
+  [remotes]             base and push remotes for fork workflows
+  [templates]           registry and namespace of devcontainer templates
+  [resources]           min_free_memory_mb, max_load_per_cpu, stop_idle and
+                        idle_cpu_percent checked before `open` starts a container;
+                        cpus and memory capping every session's container
+  [clipboard]           copy = true lets sessions write the host's clipboard;
+                        osc52 copies through the terminal when forest isn't
+                        answering
+  [layouts.<name>]      sessions, panes and arrange of `forest layout apply`
+  [hooks]               pre_open, post_open, post_open_in_container, pre_kill
+                        and post_kill commands
+
+A `devcontainer.json` is looked up in `.devcontainer/`, or in
+`.devcontainer/<env>/` with `--devcontainer-env <env>`. `forest validate`
+checks the one a session would use.",
+    },
+    Topic {
+        name: "policy",
+        summary: "administrator restrictions on sessions",
+        body: "\
+Administrators can restrict the sessions forest creates with a policy file at
+/etc/forest/policy.toml, or the path in `FOREST_POLICY`:
+
+  allowed_images           base images sessions may use; a trailing `*`
+                           matches any suffix
+  allowed_registries       registries base images may come from
+  forbid_privileged        reject privileged containers and added capabilities
+  require_resource_limits  require `--memory` and `--cpus` in runArgs
+  min_forest_version       oldest forest release allowed to create sessions
+
+Base images are read from `image`, or from the FROM lines of the Dockerfile.
+`forest open` refuses to create a session that violates the policy, and
+`forest validate` lists every violation. A policy that fails to parse is an
+error, so a typo can't lift restrictions.",
+    },
+    Topic {
+        name: "features",
+        summary: "enabling experimental features",
+        body: "\
+Experimental features are off until enabled, either for one run with
+`--enable-feature <name>` or permanently in forest.toml:
+
+  [features]
+  jj = true
+
+`forest features` lists them and whether they're enabled. Unknown names on
+the command line are errors; in forest.toml they only warn, so one config can
+be shared across forest versions.",
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|t| t.name == name)
+}
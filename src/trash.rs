@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::names::sanitize_podman_name;
+use crate::timesheet::now;
+
+/// Where a trashed worktree came from, stored as `meta.json` next to it.
+#[derive(Serialize, Deserialize)]
+pub struct Meta {
+    pub session: String,
+    pub repo: String,
+    /// Worktree directory the files were moved out of
+    pub worktree: PathBuf,
+    /// Git's admin directory for the worktree under `.git/worktrees`, if any
+    pub admin: Option<PathBuf>,
+    pub trashed_at: u64,
+}
+
+pub struct Item {
+    pub id: String,
+    pub meta: Meta,
+}
+
+fn trash_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("trash")
+}
+
+/// Moves a purged worktree (and its git admin directory) into the trash.
+pub fn put(
+    data_dir: &Path,
+    session: &str,
+    repo: &str,
+    worktree: &Path,
+    admin: Option<&Path>,
+) -> anyhow::Result<String> {
+    let trashed_at = now();
+    let id = format!("{}-{}", trashed_at, sanitize_podman_name(session));
+    let item = trash_dir(data_dir).join(&id);
+    fs::create_dir_all(&item)?;
+    move_dir(worktree, &item.join("worktree"))?;
+    let admin = admin.filter(|a| a.exists());
+    if let Some(admin) = admin {
+        move_dir(admin, &item.join("admin"))?;
+    }
+    let meta = Meta {
+        session: session.to_string(),
+        repo: repo.to_string(),
+        worktree: worktree.to_path_buf(),
+        admin: admin.map(Path::to_path_buf),
+        trashed_at,
+    };
+    fs::write(item.join("meta.json"), serde_json::to_string_pretty(&meta)?)?;
+    Ok(id)
+}
+
+pub fn list(data_dir: &Path) -> anyhow::Result<Vec<Item>> {
+    let entries = match fs::read_dir(trash_dir(data_dir)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut items = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let Ok(content) = fs::read_to_string(entry.path().join("meta.json")) else {
+            continue;
+        };
+        items.push(Item {
+            id: entry.file_name().to_string_lossy().to_string(),
+            meta: serde_json::from_str(&content)?,
+        });
+    }
+    items.sort_by_key(|item| item.meta.trashed_at);
+    Ok(items)
+}
+
+/// Moves a trashed worktree back to where it was purged from.
+pub fn restore(data_dir: &Path, id: &str) -> anyhow::Result<Meta> {
+    let item = trash_dir(data_dir).join(id);
+    let content = fs::read_to_string(item.join("meta.json"))
+        .map_err(|_| anyhow::anyhow!("no trash entry {}", id))?;
+    let meta: Meta = serde_json::from_str(&content)?;
+    if meta.worktree.exists() {
+        anyhow::bail!(
+            "{} already exists; move it aside before restoring",
+            meta.worktree.display()
+        );
+    }
+    if let Some(parent) = meta.worktree.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_dir(&item.join("worktree"), &meta.worktree)?;
+    if let Some(admin) = &meta.admin {
+        if !admin.exists() && item.join("admin").exists() {
+            move_dir(&item.join("admin"), admin)?;
+        }
+    }
+    fs::remove_dir_all(&item)?;
+    Ok(meta)
+}
+
+/// Deletes trash entries older than `retention_secs`, or all of them when `None`.
+pub fn empty(data_dir: &Path, retention_secs: Option<u64>) -> anyhow::Result<usize> {
+    let cutoff = retention_secs.map(|r| now().saturating_sub(r));
+    let mut removed = 0;
+    for item in list(data_dir)? {
+        if cutoff.map(|c| item.meta.trashed_at < c).unwrap_or(true) {
+            fs::remove_dir_all(trash_dir(data_dir).join(&item.id))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn move_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // rename can't cross filesystems, e.g. for worktrees kept on local disk
+    let status = Command::new("mv").arg(from).arg(to).status()?;
+    if !status.success() {
+        anyhow::bail!("failed to move {} to {}", from.display(), to.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn put_and_restore_round_trip() {
+        let data = tempdir().unwrap();
+        let work = tempdir().unwrap();
+        let worktree = work.path().join("repo").join("feat");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(worktree.join("notes.txt"), "keep me").unwrap();
+        let admin = work.path().join("admin");
+        fs::create_dir_all(&admin).unwrap();
+
+        let id = put(data.path(), "feat", "repo", &worktree, Some(&admin)).unwrap();
+        assert!(!worktree.exists());
+        assert!(!admin.exists());
+        assert_eq!(list(data.path()).unwrap().len(), 1);
+
+        restore(data.path(), &id).unwrap();
+        assert_eq!(
+            fs::read_to_string(worktree.join("notes.txt")).unwrap(),
+            "keep me"
+        );
+        assert!(admin.exists());
+        assert!(list(data.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn empty_respects_retention() {
+        let data = tempdir().unwrap();
+        let work = tempdir().unwrap();
+        fs::create_dir_all(work.path().join("wt")).unwrap();
+        put(data.path(), "s", "r", &work.path().join("wt"), None).unwrap();
+
+        assert_eq!(empty(data.path(), Some(3600)).unwrap(), 0);
+        assert_eq!(empty(data.path(), None).unwrap(), 1);
+    }
+}
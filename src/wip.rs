@@ -0,0 +1,103 @@
+use std::path::Path;
+use std::process::{Output, Stdio};
+
+use serde::Deserialize;
+
+/// How `wip_on_detach` saves uncommitted work in `/code` when a session's
+/// shell exits or the session is stopped.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// A commit on the session's branch, hooks skipped
+    Commit,
+    /// A stash entry, untracked files included
+    Stash,
+}
+
+/// Message of the commits and stash entries forest makes, and how it
+/// recognises them again.
+pub const MESSAGE: &str = "WIP: saved by forest";
+
+/// git in `/code` of a running session's container.
+pub struct SessionGit<'a> {
+    pub worktree_path: &'a Path,
+    pub target: &'a [String],
+    pub verbose: bool,
+}
+
+impl SessionGit<'_> {
+    fn git(&self, args: &[&str]) -> anyhow::Result<Output> {
+        let mut cmd = crate::devcontainer::command("exec");
+        cmd.arg("--workspace-folder")
+            .arg(self.worktree_path)
+            .args(self.target)
+            .args(["git", "-C", "/code"])
+            .args(args)
+            .stdin(Stdio::null());
+        let output = crate::run_output_verbose(&mut cmd, self.verbose)?;
+        if !output.status.success() {
+            anyhow::bail!("git {} failed", crate::shell::join(args));
+        }
+        Ok(output)
+    }
+
+    fn stdout(&self, args: &[&str]) -> anyhow::Result<String> {
+        Ok(String::from_utf8_lossy(&self.git(args)?.stdout).to_string())
+    }
+
+    /// Saves uncommitted work, returning whether there was any.
+    pub fn save(&self, mode: Mode) -> anyhow::Result<bool> {
+        if self.stdout(&["status", "--porcelain"])?.trim().is_empty() {
+            return Ok(false);
+        }
+        match mode {
+            Mode::Commit => {
+                self.git(&["add", "--all"])?;
+                self.git(&["commit", "--quiet", "--no-verify", "-m", MESSAGE])?;
+            }
+            Mode::Stash => {
+                self.git(&["stash", "push", "--include-untracked", "-m", MESSAGE])?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Puts work `save` set aside back into the working tree, returning
+    /// whether there was any. Only the latest WIP commit or stash entry
+    /// counts, so nothing else is undone.
+    pub fn restore(&self) -> anyhow::Result<bool> {
+        let subject = self.stdout(&["log", "-1", "--format=%s"])?;
+        if subject.trim() == MESSAGE {
+            // a mixed reset leaves the committed changes unstaged again
+            self.git(&["reset", "--quiet", "HEAD~1"])?;
+            return Ok(true);
+        }
+        let stash = self.stdout(&["stash", "list", "-1", "--format=%s"])?;
+        if is_wip_stash(&stash) {
+            self.git(&["stash", "pop", "--quiet"])?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+/// Whether a stash subject, `On <branch>: <message>`, is one of forest's.
+fn is_wip_stash(subject: &str) -> bool {
+    subject
+        .trim()
+        .split_once(": ")
+        .is_some_and(|(_, message)| message == MESSAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_wip_stashes() {
+        assert!(is_wip_stash("On feat/x: WIP: saved by forest\n"));
+        assert!(!is_wip_stash("On feat/x: WIP: saved by hand"));
+        assert!(!is_wip_stash("WIP on feat/x: 0123abc init"));
+        assert!(!is_wip_stash(""));
+    }
+}
@@ -0,0 +1,74 @@
+use forest::names::{
+    collisions, legacy_podman_name, sanitize_podman_name, valid_podman_name, MAX_LABEL_LEN,
+};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn sanitized_names_are_valid(name in any::<String>()) {
+        prop_assert!(valid_podman_name(&sanitize_podman_name(&name)));
+    }
+
+    #[test]
+    fn sanitized_names_fit_the_label_limit(name in any::<String>()) {
+        prop_assert!(sanitize_podman_name(&name).len() <= MAX_LABEL_LEN);
+    }
+
+    #[test]
+    fn sanitizing_is_idempotent(name in any::<String>()) {
+        let once = sanitize_podman_name(&name);
+        prop_assert_eq!(sanitize_podman_name(&once), once);
+    }
+
+    #[test]
+    fn valid_names_are_unchanged(name in "[a-zA-Z0-9][a-zA-Z0-9_.-]{0,62}") {
+        prop_assert_eq!(sanitize_podman_name(&name), name);
+    }
+
+    #[test]
+    fn colliding_names_are_reported(a in "[a-z/.]{1,8}", b in "[a-z/.]{1,8}") {
+        let report = collisions([a.as_str(), b.as_str()]);
+        let collide = a != b && sanitize_podman_name(&a) == sanitize_podman_name(&b);
+        prop_assert_eq!(report.contains_key(&sanitize_podman_name(&a)), collide);
+    }
+
+    #[test]
+    fn distinct_names_get_distinct_labels(a in "[a-z/. ]{1,8}", b in "[a-z/. ]{1,8}") {
+        prop_assume!(a != b);
+        prop_assert_ne!(sanitize_podman_name(&a), sanitize_podman_name(&b));
+    }
+}
+
+#[test]
+fn rewritten_names_get_hash_suffix() {
+    let label = sanitize_podman_name("feat/cool");
+    assert!(label.starts_with("feat-cool-"));
+    assert_eq!(label.len(), "feat-cool-".len() + 6);
+    assert_ne!(label, sanitize_podman_name("feat.cool"));
+    assert_eq!(sanitize_podman_name("feat-cool"), "feat-cool");
+}
+
+#[test]
+fn unicode_names_are_transliterated() {
+    let label = sanitize_podman_name("café/Straße");
+    assert!(label.starts_with("cafe-Strasse-"));
+    assert_ne!(label, sanitize_podman_name("cafe/Strasse"));
+    assert!(sanitize_podman_name("修复").starts_with("s---"));
+}
+
+#[test]
+fn long_names_are_truncated_with_hash() {
+    let long = "feature-".repeat(20);
+    let label = sanitize_podman_name(&long);
+    assert_eq!(label.len(), MAX_LABEL_LEN);
+    assert_ne!(label, sanitize_podman_name(&"feature-".repeat(21)));
+}
+
+#[test]
+fn legacy_labels_collide() {
+    assert_eq!(
+        legacy_podman_name("feat/cool"),
+        legacy_podman_name("feat cool")
+    );
+    assert!(collisions(["feat/cool", "feat cool", "other"]).is_empty());
+}
@@ -68,17 +68,28 @@ case "$cmd" in
           workspace=$2
           shift 2
           ;;
+        --docker-path)
+          shift 2
+          ;;
         *)
           break
           ;;
       esac
     done
-    cmd="$*"
-    if [ -z "$cmd" ]; then
-      cmd=$(cat)
-    fi
     cd "$workspace"
-    sh -c "$cmd"
+    if [ "$#" -eq 0 ]; then
+      sh -c "$(cat)"
+      exit 0
+    fi
+    n=$#
+    for arg in "$@"; do
+      case "$arg" in
+        /code*) arg="$workspace${arg#/code}" ;;
+      esac
+      set -- "$@" "$arg"
+    done
+    shift "$n"
+    "$@"
     exit 0
     ;;
   down)
@@ -155,7 +166,7 @@ fn new_session_branch_inside_container() {
     cmd.env("HOME", &home_dir);
     cmd.env("WORKTREE_PATH", &worktree_path);
     cmd.env("DEVCONTAINER_STATE", podman_dir.path());
-    cmd.arg("open").arg("new-branch");
+    cmd.args(["open", "--scaffold", "new-branch"]);
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
 
@@ -176,6 +187,16 @@ fn new_session_branch_inside_container() {
         .unwrap();
     assert!(branch.status.success());
     assert_eq!(String::from_utf8_lossy(&branch.stdout).trim(), "main");
+
+    let session = Command::new("git")
+        .args(["config", "branch.new-branch.forestSession"])
+        .current_dir(&repo_dir)
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&session.stdout).trim(),
+        "new-branch"
+    );
 }
 
 #[test]
@@ -241,7 +262,7 @@ fn mounts_repo_and_worktree() {
     cmd.env("HOME", &home_dir);
     cmd.env("WORKTREE_PATH", &worktree_path);
     cmd.env("DEVCONTAINER_STATE", podman_dir.path());
-    cmd.arg("open").arg("new-branch");
+    cmd.args(["open", "--scaffold", "new-branch"]);
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
 
@@ -397,7 +418,7 @@ fn podman_name_sanitizes_branch() {
     cmd.env("HOME", &home_dir);
     cmd.env("WORKTREE_PATH", &worktree_path);
     cmd.env("DEVCONTAINER_STATE", podman_dir.path());
-    cmd.arg("open").arg("feat/cool");
+    cmd.args(["open", "--scaffold", "feat/cool"]);
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
 
@@ -411,5 +432,758 @@ fn podman_name_sanitizes_branch() {
     let out = String::from_utf8_lossy(&output.stdout);
     assert!(out.contains("feat/cool"));
 
-    assert!(podman_dir.path().join("feat-cool.workspace").exists());
+    let label = forest::names::sanitize_podman_name("feat/cool");
+    assert!(podman_dir
+        .path()
+        .join(format!("{}.workspace", label))
+        .exists());
+}
+
+#[test]
+fn open_outside_repo_fails_with_guidance() {
+    let dir = tempdir().unwrap();
+    let home_dir = dir.path().join("home");
+    fs::create_dir(&home_dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&dir)
+        .env("HOME", &home_dir)
+        .env("XDG_CONFIG_HOME", &home_dir)
+        .env("GIT_CEILING_DIRECTORIES", dir.path())
+        .arg("open")
+        .arg("new-branch")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(err.contains("not inside a git repository"));
+    assert!(!dir.path().join(".git").exists());
+}
+
+#[test]
+fn new_creates_project_and_opens_session() {
+    let dir = tempdir().unwrap();
+    let home_dir = dir.path().join("home");
+    fs::create_dir(&home_dir).unwrap();
+
+    let podman_dir = tempdir().unwrap();
+    let podman_path = podman_dir.path().join("devcontainer");
+    fs::write(&podman_path, STUB_SCRIPT).unwrap();
+    assert!(Command::new("chmod")
+        .arg("+x")
+        .arg(&podman_path)
+        .status()
+        .unwrap()
+        .success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&dir)
+        .env(
+            "PATH",
+            format!(
+                "{}:{}",
+                podman_dir.path().display(),
+                std::env::var("PATH").unwrap()
+            ),
+        )
+        .env("HOME", &home_dir)
+        .env("XDG_CONFIG_HOME", &home_dir)
+        .env("DEVCONTAINER_STATE", podman_dir.path())
+        .arg("new")
+        .arg("proj")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("Started session dev"));
+
+    let project = dir.path().join("proj");
+    assert!(project
+        .join(".devcontainer")
+        .join("devcontainer.json")
+        .exists());
+    let branches = Command::new("git")
+        .args(["branch", "--list", "dev"])
+        .current_dir(&project)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&branches.stdout).contains("dev"));
+    let files = Command::new("git")
+        .args(["ls-files"])
+        .current_dir(&project)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&files.stdout).contains(".devcontainer/devcontainer.json"));
+}
+
+#[test]
+fn self_test_passes_with_stub() {
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .args(["self-test", "--stub"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("Self-test passed"));
+}
+
+#[test]
+fn validate_points_at_broken_devcontainer() {
+    let repo_dir = tempdir().unwrap();
+    let config_dir = repo_dir.path().join(".devcontainer");
+    fs::create_dir(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("devcontainer.json"),
+        r#"{"build": {"dockerfile": "Dockerfile"}}"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&repo_dir)
+        .env("HOME", repo_dir.path())
+        .arg("validate")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(err.contains(".devcontainer/devcontainer.json"));
+    assert!(err.contains("build.dockerfile Dockerfile does not exist"));
+}
+
+#[test]
+fn policy_violations_block_sessions() {
+    let repo_dir = tempdir().unwrap();
+    fs::write(
+        repo_dir.path().join(".devcontainer.json"),
+        r#"{"image": "docker.io/library/ubuntu:latest", "privileged": true}"#,
+    )
+    .unwrap();
+    let policy = repo_dir.path().join("policy.toml");
+    fs::write(
+        &policy,
+        "allowed_registries = [\"registry.example.com\"]\nforbid_privileged = true\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&repo_dir)
+        .env("HOME", repo_dir.path())
+        .env("FOREST_POLICY", &policy)
+        .arg("validate")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(err.contains("not in allowed_registries"));
+    assert!(err.contains("privileged containers are forbidden"));
+}
+
+#[test]
+fn failpoints_fail_chosen_steps() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let home_dir = repo_dir.path().join("home");
+    fs::create_dir(&home_dir).unwrap();
+    let podman_dir = tempdir().unwrap();
+    let podman_path = podman_dir.path().join("devcontainer");
+    fs::write(&podman_path, STUB_SCRIPT).unwrap();
+    assert!(Command::new("chmod")
+        .arg("+x")
+        .arg(&podman_path)
+        .status()
+        .unwrap()
+        .success());
+
+    for (failpoints, error) in [
+        ("up", "devcontainer up failed"),
+        ("build,up=timeout", "up timed out"),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+            .current_dir(&repo_dir)
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    podman_dir.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            )
+            .env("HOME", &home_dir)
+            .env("DEVCONTAINER_STATE", podman_dir.path())
+            .env("FOREST_TEST_FAILPOINTS", failpoints)
+            .args(["open", "--scaffold", "doomed"])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{}", stderr);
+        assert!(!podman_dir.path().join("doomed.workspace").exists());
+    }
+}
+
+#[test]
+fn detached_open_returns_without_a_shell() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let home_dir = repo_dir.path().join("home");
+    fs::create_dir(&home_dir).unwrap();
+    let podman_dir = tempdir().unwrap();
+    let podman_path = podman_dir.path().join("devcontainer");
+    fs::write(&podman_path, STUB_SCRIPT).unwrap();
+    assert!(Command::new("chmod")
+        .arg("+x")
+        .arg(&podman_path)
+        .status()
+        .unwrap()
+        .success());
+
+    // stdin stays open: a shell would wait on it forever
+    let mut child = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&repo_dir)
+        .env(
+            "PATH",
+            format!(
+                "{}:{}",
+                podman_dir.path().display(),
+                std::env::var("PATH").unwrap()
+            ),
+        )
+        .env("HOME", &home_dir)
+        .env("DEVCONTAINER_STATE", podman_dir.path())
+        .args(["open", "--scaffold", "--detach", "bg"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdin = child.stdin.take();
+    let output = child.wait_with_output().unwrap();
+    drop(stdin);
+    assert!(output.status.success());
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(out.lines().last(), Some("bg"));
+    assert!(podman_dir.path().join("bg.workspace").exists());
+}
+
+#[test]
+fn open_starts_a_stopped_container_instead_of_recreating_it() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let home_dir = tempdir().unwrap();
+    let podman_dir = tempdir().unwrap();
+    for (name, script) in [
+        ("devcontainer", STUB_SCRIPT),
+        // lists the session's container, stopped, once `stopped` exists
+        (
+            "podman",
+            r#"#!/bin/sh
+case "$1" in
+  ps)
+    case "$*" in
+      *--quiet*--all*) [ ! -f "$DEVCONTAINER_STATE/stopped" ] || echo c0ffee ;;
+    esac
+    ;;
+  inspect)
+    printf '[{"Config":{"Labels":{"devcontainer.local_folder":"%s"}}}]' \
+      "$(cat "$DEVCONTAINER_STATE/stopped")"
+    ;;
+  start) echo "$2" > "$DEVCONTAINER_STATE/started" ;;
+  *) exit 1 ;;
+esac
+"#,
+        ),
+    ] {
+        let path = podman_dir.path().join(name);
+        fs::write(&path, script).unwrap();
+        assert!(Command::new("chmod")
+            .arg("+x")
+            .arg(&path)
+            .status()
+            .unwrap()
+            .success());
+    }
+    let forest = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_forest"))
+            .current_dir(&repo_dir)
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    podman_dir.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            )
+            .env("HOME", home_dir.path())
+            .env("DEVCONTAINER_STATE", podman_dir.path())
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    let output = forest(&["open", "--scaffold", "--detach", "bg"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let up = podman_dir.path().join("bg.workspace");
+    let worktree = fs::read_to_string(&up).unwrap();
+    fs::write(podman_dir.path().join("stopped"), worktree.trim()).unwrap();
+    fs::remove_file(&up).unwrap();
+
+    let output = forest(&["open", "--scaffold", "--detach", "bg"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("Resumed stopped session bg"), "{}", out);
+    assert_eq!(
+        fs::read_to_string(podman_dir.path().join("started")).unwrap(),
+        "c0ffee\n"
+    );
+    assert!(!up.exists());
+}
+
+#[test]
+fn layout_apply_opens_sessions_into_tmux_windows() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let devcontainer = repo_dir.path().join(".devcontainer");
+    fs::create_dir(&devcontainer).unwrap();
+    fs::write(
+        devcontainer.join("devcontainer.json"),
+        r#"{"image": "alpine"}"#,
+    )
+    .unwrap();
+
+    let home_dir = tempdir().unwrap();
+    let config_dir = home_dir.path().join(".config");
+    fs::create_dir_all(config_dir.join("forest")).unwrap();
+    fs::write(
+        config_dir.join("forest").join("forest.toml"),
+        "[layouts.review]\nsessions = [\"feat/a\", \"fix\"]\n\
+         panes = [\"\", \"cargo watch -x test\"]\n",
+    )
+    .unwrap();
+    let podman_dir = tempdir().unwrap();
+    for (name, script) in [
+        ("devcontainer", STUB_SCRIPT),
+        ("podman", "#!/bin/sh\n[ \"$1\" = ps ]\n"),
+        // no session yet; windows get ids as tmux would print them
+        (
+            "tmux",
+            "#!/bin/sh\necho \"$*\" >> \"$DEVCONTAINER_STATE/tmux.log\"\n\
+             [ \"$1\" = has-session ] && exit 1\necho @1\n",
+        ),
+    ] {
+        let path = podman_dir.path().join(name);
+        fs::write(&path, script).unwrap();
+        assert!(Command::new("chmod")
+            .arg("+x")
+            .arg(&path)
+            .status()
+            .unwrap()
+            .success());
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&repo_dir)
+        .env(
+            "PATH",
+            format!(
+                "{}:{}",
+                podman_dir.path().display(),
+                std::env::var("PATH").unwrap()
+            ),
+        )
+        .env("HOME", home_dir.path())
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .env("DEVCONTAINER_STATE", podman_dir.path())
+        .args(["layout", "apply", "review"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("tmux attach -t forest-review"), "{}", out);
+    for name in ["feat-a", "fix"] {
+        assert!(fs::read_dir(podman_dir.path()).unwrap().any(|entry| {
+            let file = entry.unwrap().file_name().to_string_lossy().to_string();
+            file.starts_with(name) && file.ends_with(".workspace")
+        }));
+    }
+    let calls = fs::read_to_string(podman_dir.path().join("tmux.log")).unwrap();
+    let calls: Vec<&str> = calls.lines().collect();
+    assert!(
+        calls[1].starts_with("new-session -d -s forest-review"),
+        "{:?}",
+        calls
+    );
+    assert!(calls[1].contains("-n feat/a"), "{:?}", calls);
+    assert!(calls[1].ends_with(" attach feat/a"), "{:?}", calls);
+    assert_eq!(calls[2], "set-option -w -t @1 remain-on-exit on");
+    assert!(calls[3].starts_with("split-window -d -t @1"), "{:?}", calls);
+    assert!(
+        calls[3].ends_with(" exec feat/a -- sh -c 'cargo watch -x test'"),
+        "{:?}",
+        calls
+    );
+    assert_eq!(calls[4], "select-layout -t @1 tiled");
+    assert!(
+        calls[5].starts_with("new-window -d -t =forest-review:"),
+        "{:?}",
+        calls
+    );
+    assert!(calls[5].contains("-n fix"), "{:?}", calls);
+}
+
+#[test]
+fn open_from_starts_the_session_branch_at_a_ref() {
+    let repo_dir = tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&repo_dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    git(&["init", "-b", "main"]);
+    git(&["commit", "--allow-empty", "-m", "one"]);
+    git(&["tag", "v1"]);
+    git(&["commit", "--allow-empty", "-m", "two"]);
+
+    let home_dir = tempdir().unwrap();
+    let podman_dir = tempdir().unwrap();
+    let podman_path = podman_dir.path().join("devcontainer");
+    fs::write(&podman_path, STUB_SCRIPT).unwrap();
+    assert!(Command::new("chmod")
+        .arg("+x")
+        .arg(&podman_path)
+        .status()
+        .unwrap()
+        .success());
+    let output = Command::new(env!("CARGO_BIN_EXE_forest"))
+        .current_dir(&repo_dir)
+        .env(
+            "PATH",
+            format!(
+                "{}:{}",
+                podman_dir.path().display(),
+                std::env::var("PATH").unwrap()
+            ),
+        )
+        .env("HOME", home_dir.path())
+        .env("DEVCONTAINER_STATE", podman_dir.path())
+        .args(["open", "--scaffold", "--detach", "--from", "v1", "hotfix"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(git(&["rev-parse", "hotfix"]), git(&["rev-parse", "v1"]));
+    assert_ne!(git(&["rev-parse", "hotfix"]), git(&["rev-parse", "main"]));
+}
+
+#[test]
+fn rename_moves_branch_and_worktree() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let home_dir = tempdir().unwrap();
+    let podman_dir = tempdir().unwrap();
+    for (name, script) in [
+        ("devcontainer", STUB_SCRIPT),
+        // no containers, and none to inspect
+        ("podman", "#!/bin/sh\n[ \"$1\" = ps ]\n"),
+    ] {
+        let path = podman_dir.path().join(name);
+        fs::write(&path, script).unwrap();
+        assert!(Command::new("chmod")
+            .arg("+x")
+            .arg(&path)
+            .status()
+            .unwrap()
+            .success());
+    }
+    let forest = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_forest"))
+            .current_dir(&repo_dir)
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    podman_dir.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            )
+            .env("HOME", home_dir.path())
+            .env("DEVCONTAINER_STATE", podman_dir.path())
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    let is_branch = |name: &str| {
+        Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("refs/heads/{}", name))
+            .current_dir(&repo_dir)
+            .status()
+            .unwrap()
+            .success()
+    };
+    let repo_name = repo_dir.path().file_name().unwrap().to_owned();
+    let worktrees = home_dir.path().join("worktrees").join(repo_name);
+
+    assert!(forest(&["open", "--scaffold", "--detach", "feat/old"])
+        .status
+        .success());
+    let output = forest(&["rename", "feat/old", "feat/new"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(is_branch("feat/new") && !is_branch("feat/old"));
+    assert!(worktrees.join("feat/new").is_dir());
+    assert!(!worktrees.join("feat").join("old").exists());
+
+    // the worktree can't move, so the branch is moved back
+    fs::remove_dir_all(worktrees.join("feat/new")).unwrap();
+    assert!(!forest(&["rename", "feat/new", "feat/other"])
+        .status
+        .success());
+    assert!(is_branch("feat/new") && !is_branch("feat/other"));
+}
+
+#[test]
+fn rebuild_builds_again_and_recreates_the_container() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let home_dir = tempdir().unwrap();
+    let podman_dir = tempdir().unwrap();
+    let podman_path = podman_dir.path().join("devcontainer");
+    fs::write(&podman_path, STUB_SCRIPT).unwrap();
+    assert!(Command::new("chmod")
+        .arg("+x")
+        .arg(&podman_path)
+        .status()
+        .unwrap()
+        .success());
+    let forest = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_forest"))
+            .current_dir(&repo_dir)
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    podman_dir.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            )
+            .env("HOME", home_dir.path())
+            .env("DEVCONTAINER_STATE", podman_dir.path())
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    assert!(forest(&["open", "--scaffold", "--detach", "img"])
+        .status
+        .success());
+    let build = podman_dir.path().join("img.build");
+    assert!(!build.exists());
+    let output = forest(&["--verbose", "rebuild", "img", "--no-cache", "--detach"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(build.exists());
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("--no-cache"), "{}", out);
+    assert!(out.contains("--remove-existing-container"), "{}", out);
+    assert_eq!(out.lines().last(), Some("img"));
+
+    assert!(!forest(&["rebuild", "missing", "--detach"]).status.success());
+}
+
+#[test]
+fn kills_sessions_matching_a_pattern_at_once() {
+    let repo_dir = tempdir().unwrap();
+    assert!(Command::new("git")
+        .args(["init", "-b", "main"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .args(["commit", "--allow-empty", "-m", "init"])
+        .current_dir(&repo_dir)
+        .status()
+        .unwrap()
+        .success());
+
+    let home_dir = tempdir().unwrap();
+    let podman_dir = tempdir().unwrap();
+    for (name, script) in [
+        ("devcontainer", STUB_SCRIPT),
+        // no containers, and none to inspect
+        ("podman", "#!/bin/sh\n[ \"$1\" = ps ]\n"),
+    ] {
+        let path = podman_dir.path().join(name);
+        fs::write(&path, script).unwrap();
+        assert!(Command::new("chmod")
+            .arg("+x")
+            .arg(&path)
+            .status()
+            .unwrap()
+            .success());
+    }
+    let forest = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_forest"))
+            .current_dir(&repo_dir)
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    podman_dir.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            )
+            .env("HOME", home_dir.path())
+            .env("DEVCONTAINER_STATE", podman_dir.path())
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    // several sessions open detached, with no shell to wait for
+    let output = forest(&["open", "--scaffold", "feat/a", "feat/b", "fix"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for name in ["feat-a", "feat-b", "fix"] {
+        assert!(fs::read_dir(podman_dir.path()).unwrap().any(|entry| {
+            let file = entry.unwrap().file_name().to_string_lossy().to_string();
+            file.starts_with(name) && file.ends_with(".workspace")
+        }));
+    }
+
+    // the stub runs no containers, so only --all finds the sessions
+    assert!(!forest(&["kill", "feat/*"]).status.success());
+    let output = forest(&["kill", "feat/*", "--all", "--down", "--jobs", "2"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("Killed session feat/a"), "{}", out);
+    assert!(out.contains("Killed session feat/b"), "{}", out);
+    assert!(!out.contains("Killed session fix"), "{}", out);
+
+    // their worktrees stay behind, which only `ls --all` lists; the stub
+    // leaves git's side of them to the container
+    let worktree = home_dir
+        .path()
+        .join("worktrees")
+        .join(repo_dir.path().file_name().unwrap())
+        .join("feat/a");
+    fs::write(worktree.join(".git"), "gitdir: /repo/.git/worktrees/a\n").unwrap();
+    let out = String::from_utf8_lossy(&forest(&["ls"]).stdout).to_string();
+    assert!(out.contains("fix"), "{}", out);
+    assert!(!out.contains("feat/a"), "{}", out);
+    let output = forest(&["ls", "--all"]);
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(out.contains("worktree only"), "{}", out);
+    assert!(out.contains("feat/a"), "{}", out);
 }